@@ -0,0 +1,54 @@
+//! A hyper "hello world" served through `compat::StreamWrapper`, over one
+//! end of a socket pair while the other end sends the request.
+//!
+//! Run with `cargo run --example hyper_hello --features compat`.
+
+use std::convert::Infallible;
+use std::os::fd::{FromRawFd, OwnedFd};
+
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Request, Response};
+use hyper_util::rt::TokioIo;
+use Loop::compat::StreamWrapper;
+use Loop::prelude::*;
+
+async fn hello(_: Request<hyper::body::Incoming>) -> Result<Response<String>, Infallible> {
+    Ok(Response::new("Hello, world!\n".to_string()))
+}
+
+fn main() {
+    let mut fds = [0; 2];
+    let flags = libc::SOCK_STREAM | libc::SOCK_CLOEXEC;
+    assert_eq!(
+        unsafe { libc::socketpair(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()) },
+        0
+    );
+    let (server, client) = unsafe {
+        (
+            File::from(OwnedFd::from_raw_fd(fds[0])),
+            File::from(OwnedFd::from_raw_fd(fds[1])),
+        )
+    };
+
+    let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+    let response = rt.block_on(async move {
+        let serve = spawn(async move {
+            let io = TokioIo::new(StreamWrapper::new(server));
+            http1::Builder::new()
+                .serve_connection(io, service_fn(hello))
+                .await
+        });
+
+        let mut client = client;
+        let request = "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        client.write_all(request).await.0.unwrap();
+        let (n, response) = client.read_to_end(Vec::new()).await;
+        n.unwrap();
+        serve.await.unwrap();
+        String::from_utf8(response).unwrap()
+    });
+    print!("{response}");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with("Hello, world!\n"));
+}
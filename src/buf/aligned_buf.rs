@@ -0,0 +1,188 @@
+use super::{IoBuf, IoBufMut};
+use std::alloc::{self, Layout};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+/// Block size most devices use, the default alignment.
+pub const DEFAULT_ALIGN: usize = 4096;
+
+/// A buffer at a fixed alignment with its capacity rounded up to it, as direct
+/// io with `O_DIRECT` needs.
+///
+/// Like a `Vec<u8>` with a fixed capacity: the initialized bytes are its
+/// length, reads fill the whole capacity.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+impl AlignedBuf {
+    /// An empty buffer for at least `capacity` bytes, aligned to 4096.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_alignment(capacity, DEFAULT_ALIGN)
+    }
+
+    /// An empty buffer for at least `capacity` bytes, aligned to `align`.
+    ///
+    /// # Panics
+    /// If `align` is not a power of two, or the rounded capacity overflows.
+    pub fn with_alignment(capacity: usize, align: usize) -> Self {
+        assert!(
+            align.is_power_of_two(),
+            "alignment {align} is not a power of two"
+        );
+        // Never empty, allocations of no bytes are not allowed.
+        let size = capacity.max(1).next_multiple_of(align);
+        let layout = Layout::from_size_align(size, align).expect("capacity overflows");
+        let ptr = NonNull::new(unsafe { alloc::alloc(layout) })
+            .unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        AlignedBuf {
+            ptr,
+            len: 0,
+            layout,
+        }
+    }
+
+    /// A buffer of `len` zeroes, aligned and rounded to `align`.
+    pub fn zeroed(len: usize, align: usize) -> Self {
+        let mut buf = Self::with_alignment(len, align);
+        unsafe { buf.ptr.as_ptr().write_bytes(0, len) };
+        buf.len = len;
+        buf
+    }
+
+    /// The number of bytes the buffer holds, a multiple of the alignment.
+    pub fn capacity(&self) -> usize {
+        self.layout.size()
+    }
+
+    /// The alignment of the first byte.
+    pub fn alignment(&self) -> usize {
+        self.layout.align()
+    }
+
+    /// Append `data` to the initialized bytes.
+    ///
+    /// # Panics
+    /// If the bytes do not fit into the capacity.
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        assert!(
+            data.len() <= self.capacity() - self.len,
+            "{} bytes do not fit after {} of {}",
+            data.len(),
+            self.len,
+            self.capacity()
+        );
+        unsafe {
+            let end = self.ptr.as_ptr().add(self.len);
+            end.copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+        self.len += data.len();
+    }
+
+    /// Shorten the initialized bytes to `len`, longer lengths do nothing.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Forget the initialized bytes, keeping the allocation.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.len)
+            .field("capacity", &self.capacity())
+            .field("alignment", &self.alignment())
+            .finish()
+    }
+}
+
+unsafe impl IoBuf for AlignedBuf {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len
+    }
+}
+
+unsafe impl IoBufMut for AlignedBuf {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.capacity()
+    }
+
+    #[inline]
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.len = pos;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligned_and_rounded() {
+        for (capacity, align, rounded) in [(0, 512, 512), (1, 4096, 4096), (8193, 4096, 12288)] {
+            let mut buf = AlignedBuf::with_alignment(capacity, align);
+            assert_eq!(buf.capacity(), rounded);
+            assert_eq!(buf.bytes_total(), rounded);
+            assert_eq!(buf.read_ptr() as usize % align, 0);
+            assert!(buf.is_empty());
+        }
+
+        let mut buf = AlignedBuf::new(10);
+        buf.extend_from_slice(b"abc");
+        buf.extend_from_slice(b"de");
+        assert_eq!(&*buf, b"abcde");
+        assert_eq!(buf.bytes_init(), 5);
+        buf.truncate(2);
+        assert_eq!(&*buf, b"ab");
+
+        let buf = AlignedBuf::zeroed(5000, 4096);
+        assert_eq!(buf.len(), 5000);
+        assert_eq!(buf.capacity(), 8192);
+        assert!(buf.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "alignment 3000 is not a power of two")]
+    fn rejects_odd_alignment() {
+        AlignedBuf::with_alignment(1, 3000);
+    }
+}
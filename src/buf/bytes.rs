@@ -0,0 +1,210 @@
+//! Buffers from the `bytes` crate.
+//!
+//! `Bytes` never moves its data. `BytesMut` only reallocates through `&mut`
+//! methods like `reserve`, which nobody can call while an op owns it.
+
+use super::{IoBuf, IoBufMut, IoBufMutExt};
+use bytes::{Bytes, BytesMut};
+
+unsafe impl IoBuf for Bytes {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for BytesMut {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBufMut for BytesMut {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.capacity()
+    }
+
+    #[inline]
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.set_len(pos);
+    }
+}
+
+impl IoBufMutExt for BytesMut {
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        BytesMut::reserve(self, additional);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::OpenOptions;
+    use crate::runtime::builder::RuntimeBuilder;
+    use crate::utils::testing::fixed_runtime;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+    fn pipe() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    fn proc_path(fd: &OwnedFd) -> String {
+        format!("/proc/self/fd/{}", fd.as_raw_fd())
+    }
+
+    #[test]
+    fn pointers_survive_moves() {
+        let mut buf = BytesMut::with_capacity(64);
+        buf.extend_from_slice(b"abc");
+        let ptr = buf.write_ptr();
+        let mut moved = Box::new(buf);
+        assert_eq!(moved.write_ptr(), ptr);
+        assert_eq!(moved.bytes_total(), 64);
+
+        let frozen = moved.freeze();
+        let ptr = frozen.read_ptr();
+        let clone = frozen.clone();
+        let moved = Box::new(frozen);
+        assert_eq!(moved.read_ptr(), ptr);
+        assert_eq!(clone.read_ptr(), ptr);
+    }
+
+    #[test]
+    fn round_trip() {
+        let Some(mut rt) = fixed_runtime(2) else {
+            return;
+        };
+        let (rx, tx) = pipe();
+        rt.block_on(async {
+            let rx = OpenOptions::new()
+                .read(true)
+                .open_direct(proc_path(&rx))
+                .await
+                .unwrap();
+            let tx = OpenOptions::new()
+                .write(true)
+                .open_direct(proc_path(&tx))
+                .await
+                .unwrap();
+
+            let (res, _) = tx.write_all_at(BytesMut::from(&b"ping"[..]), 0).await;
+            res.unwrap();
+            let (n, buf) = rx.read_at(BytesMut::with_capacity(8192), 0).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(&buf[..], b"ping");
+
+            // Echo the frozen bytes back without copying them.
+            let frozen = buf.freeze();
+            let (res, sent) = tx.write_all_at(frozen.clone(), 0).await;
+            res.unwrap();
+            assert_eq!(sent.as_ptr(), frozen.as_ptr());
+            let (res, echo) = rx.read_exact_at(BytesMut::with_capacity(4), 0).await;
+            res.unwrap();
+            assert_eq!(echo, frozen);
+        });
+    }
+
+    #[test]
+    fn write_split_halves() {
+        let Some(mut rt) = fixed_runtime(2) else {
+            return;
+        };
+        let (rx, tx) = pipe();
+        rt.block_on(async {
+            let rx = OpenOptions::new()
+                .read(true)
+                .open_direct(proc_path(&rx))
+                .await
+                .unwrap();
+            let tx = OpenOptions::new()
+                .write(true)
+                .open_direct(proc_path(&tx))
+                .await
+                .unwrap();
+
+            let mut head = BytesMut::from(&b"header:body"[..]);
+            let body = head.split_off(7);
+            let (res, _) = tx.write_all_at(body, 0).await;
+            res.unwrap();
+            let (res, _) = tx.write_all_at(head, 0).await;
+            res.unwrap();
+
+            let (res, buf) = rx.read_exact_at(BytesMut::with_capacity(11), 0).await;
+            res.unwrap();
+            assert_eq!(&buf[..], b"bodyheader:");
+        });
+    }
+
+    #[test]
+    fn read_to_end_grows() {
+        let path = std::env::temp_dir().join(format!("loop-bytes-grow-{}", std::process::id()));
+        let expected: Vec<u8> = (0..100_000u32).map(|i| i as u8).collect();
+        std::fs::write(&path, &expected).unwrap();
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            let (n, buf) = file.read_to_end_at(BytesMut::new(), 0).await;
+            assert_eq!(n.unwrap(), expected.len());
+            assert_eq!(&buf[..], &expected[..]);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn regular_file_takes_any_buffer() {
+        let path = std::env::temp_dir().join(format!("loop-bytes-file-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .await
+                .unwrap();
+            let (res, _) = file.write_all_at(Bytes::from_static(b"bytes "), 0).await;
+            res.unwrap();
+            let (res, _) = file.write_all_at(b"and vec".to_vec(), 6).await;
+            res.unwrap();
+
+            let (n, vec) = file.read_at(Vec::with_capacity(5), 0).await;
+            assert_eq!(n.unwrap(), 5);
+            assert_eq!(vec, b"bytes");
+            let (n, bytes) = file.read_at(BytesMut::with_capacity(7), 6).await;
+            assert_eq!(n.unwrap(), 7);
+            assert_eq!(&bytes[..], b"and vec");
+            let (n, boxed) = file.read_at(vec![0; 3].into_boxed_slice(), 10).await;
+            assert_eq!(n.unwrap(), 3);
+            assert_eq!(&boxed[..], b"vec");
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
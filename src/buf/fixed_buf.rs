@@ -0,0 +1,126 @@
+use super::{AlignedBuf, IoBuf, IoBufMut};
+use crate::driver;
+use std::fmt;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+
+/// A buffer checked out of the ones registered with the ring, see
+/// [`RuntimeBuilder::with_registered_buffers`](crate::runtime::RuntimeBuilder::with_registered_buffers).
+///
+/// [`File::read_fixed_at`](crate::fs::File::read_fixed_at) and
+/// [`File::write_fixed_at`](crate::fs::File::write_fixed_at) use it by index,
+/// skipping the page pinning other ops do each time. It works with every
+/// other op too. Dropping it checks it back in, empty.
+pub struct FixedBuf {
+    buf: ManuallyDrop<AlignedBuf>,
+    index: u16,
+    driver: driver::Inner,
+}
+
+impl FixedBuf {
+    /// Check out a free buffer of the smallest registered size that holds
+    /// `len` bytes, waiting until one is checked in when all are taken.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if the runtime has no
+    /// registered buffers, or none of them is that large.
+    pub async fn checkout(len: usize) -> io::Result<FixedBuf> {
+        let driver = driver::CURRENT.with(|inner| inner.clone());
+        let (index, buf) = std::future::poll_fn(|cx| driver.poll_take_buf(len, cx)).await?;
+        Ok(FixedBuf {
+            buf: ManuallyDrop::new(buf),
+            index,
+            driver,
+        })
+    }
+
+    /// The index of the buffer in the registered table.
+    pub fn buf_index(&self) -> u16 {
+        self.index
+    }
+
+    /// The number of bytes the buffer holds.
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    /// Append `data` to the initialized bytes.
+    ///
+    /// # Panics
+    /// If the bytes do not fit into the capacity.
+    pub fn extend_from_slice(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Shorten the initialized bytes to `len`, longer lengths do nothing.
+    pub fn truncate(&mut self, len: usize) {
+        self.buf.truncate(len);
+    }
+
+    /// Forget the initialized bytes.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+}
+
+impl Drop for FixedBuf {
+    fn drop(&mut self) {
+        // # Safety
+        // Taken once, the field is not used after.
+        let buf = unsafe { ManuallyDrop::take(&mut self.buf) };
+        self.driver.release_buf(self.index, buf);
+    }
+}
+
+impl Deref for FixedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for FixedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl fmt::Debug for FixedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FixedBuf")
+            .field("index", &self.index)
+            .field("len", &self.buf.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+unsafe impl IoBuf for FixedBuf {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.buf.read_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.buf.bytes_init()
+    }
+}
+
+unsafe impl IoBufMut for FixedBuf {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        self.buf.write_ptr()
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.buf.bytes_total()
+    }
+
+    #[inline]
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.buf.set_init(pos);
+    }
+}
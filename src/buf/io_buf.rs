@@ -0,0 +1,149 @@
+use super::Slice;
+use std::ops::RangeBounds;
+use std::rc::Rc;
+
+/// An owned buffer the kernel reads from, like the source of a write.
+///
+/// # Safety
+/// The pointer returned by [`read_ptr`](IoBuf::read_ptr) must point to
+/// [`bytes_init`](IoBuf::bytes_init) initialized bytes, and it must stay
+/// valid and unchanged when the value is moved. The ops own the buffer while
+/// the kernel uses it and only move it, so neither may change until the op
+/// hands the buffer back. Heap and static memory satisfy this, inline storage
+/// like `[u8; N]` does not.
+pub unsafe trait IoBuf: Unpin + 'static {
+    /// Pointer to the first byte.
+    fn read_ptr(&self) -> *const u8;
+
+    /// Number of initialized bytes, which is what a write sends.
+    fn bytes_init(&self) -> usize;
+
+    /// Take the bytes in `range` of the buffer, keeping the whole buffer.
+    ///
+    /// # Panics
+    /// If the range is out of the initialized bytes.
+    fn slice(self, range: impl RangeBounds<usize>) -> Slice<Self>
+    where
+        Self: Sized,
+    {
+        Slice::new(self, range)
+    }
+}
+
+unsafe impl IoBuf for Vec<u8> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for Box<[u8]> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for &'static [u8] {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for &'static str {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+// Shared read-only storage, the op keeps its clone alive until the kernel is
+// done with it.
+unsafe impl IoBuf for Rc<[u8]> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for Rc<Vec<u8>> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+unsafe impl IoBuf for String {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        self.as_ptr()
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes<T: IoBuf>(buf: &T) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) }
+    }
+
+    #[test]
+    fn pointer_survives_moves() {
+        let buf = Vec::from(&b"hello"[..]);
+        let ptr = buf.read_ptr();
+        let moved = Box::new(buf);
+        assert_eq!(moved.read_ptr(), ptr);
+        assert_eq!(bytes(&*moved), b"hello");
+    }
+
+    #[test]
+    fn impls() {
+        let mut vec = Vec::with_capacity(16);
+        vec.extend_from_slice(b"abc");
+        assert_eq!(bytes(&vec), b"abc");
+        assert_eq!(bytes(&Box::<[u8]>::from(&b"box"[..])), b"box");
+        assert_eq!(bytes(&&b"static"[..]), b"static");
+        assert_eq!(bytes(&"str"), b"str");
+        assert_eq!(bytes(&String::from("string")), b"string");
+        let shared: Rc<[u8]> = Rc::from(&b"rc"[..]);
+        assert_eq!(bytes(&shared.clone()), b"rc");
+        assert_eq!(bytes(&Rc::new(b"rc vec".to_vec())), b"rc vec");
+    }
+}
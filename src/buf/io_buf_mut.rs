@@ -0,0 +1,121 @@
+use super::{IoBuf, SliceMut};
+use std::ops::RangeBounds;
+
+/// An owned buffer the kernel writes into, like the destination of a read.
+///
+/// The bytes handed to the kernel may be uninitialized, nobody zeroes them
+/// first. A read op calls [`set_init`](IoBufMut::set_init) exactly once,
+/// with the count from the completion, and only then are the bytes read
+/// through the buffer's own api.
+///
+/// # Safety
+/// The pointer returned by [`write_ptr`](IoBufMut::write_ptr) must point to
+/// [`bytes_total`](IoBufMut::bytes_total) writable bytes, and it must stay
+/// valid and unchanged when the value is moved, see [`IoBuf`](super::IoBuf).
+/// After [`set_init(n)`](IoBufMut::set_init) the first `n` bytes must count
+/// as initialized.
+pub unsafe trait IoBufMut: Unpin + 'static {
+    /// Pointer to the first byte.
+    fn write_ptr(&mut self) -> *mut u8;
+
+    /// Number of bytes the kernel may write, initialized or not. For growable
+    /// buffers this is the capacity, not the length.
+    fn bytes_total(&mut self) -> usize;
+
+    /// Mark the first `pos` bytes as initialized after the kernel filled
+    /// them.
+    ///
+    /// # Safety
+    /// The first `pos` bytes must have been initialized, and `pos` must not
+    /// exceed [`bytes_total`](IoBufMut::bytes_total).
+    unsafe fn set_init(&mut self, pos: usize);
+
+    /// Take the bytes in `range` of the buffer for the kernel to fill,
+    /// keeping the whole buffer.
+    ///
+    /// # Panics
+    /// If the range is out of [`bytes_total`](IoBufMut::bytes_total), or
+    /// starts past the initialized bytes.
+    fn slice_mut(self, range: impl RangeBounds<usize>) -> SliceMut<Self>
+    where
+        Self: IoBuf + Sized,
+    {
+        SliceMut::new(self, range)
+    }
+}
+
+/// A buffer that can grow between reads.
+pub trait IoBufMutExt: IoBuf + IoBufMut {
+    /// Make room for at least `additional` bytes past the initialized ones.
+    /// The initialized bytes are kept, though they may move.
+    fn reserve(&mut self, additional: usize);
+}
+
+// The whole capacity goes to the kernel as is, and the length becomes what
+// was read.
+unsafe impl IoBufMut for Vec<u8> {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.capacity()
+    }
+
+    #[inline]
+    unsafe fn set_init(&mut self, pos: usize) {
+        self.set_len(pos);
+    }
+}
+
+impl IoBufMutExt for Vec<u8> {
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional);
+    }
+}
+
+unsafe impl IoBufMut for Box<[u8]> {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        self.as_mut_ptr()
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.len()
+    }
+
+    // Every byte is initialized already.
+    #[inline]
+    unsafe fn set_init(&mut self, _pos: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vec_grows_to_init() {
+        let mut vec = Vec::with_capacity(8);
+        assert_eq!(vec.bytes_total(), 8);
+        unsafe {
+            vec.write_ptr().copy_from(b"abc".as_ptr(), 3);
+            vec.set_init(3);
+        }
+        assert_eq!(vec, b"abc");
+    }
+
+    #[test]
+    fn boxed_slice_keeps_len() {
+        let mut buf = Box::<[u8]>::from(&[0u8; 4][..]);
+        assert_eq!(buf.bytes_total(), 4);
+        unsafe {
+            buf.write_ptr().write(7);
+            buf.set_init(1);
+        }
+        assert_eq!(&*buf, &[7, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,37 @@
+/// Owned buffers the kernel reads from in order, like the source of a
+/// `writev`.
+///
+/// # Safety
+/// The pointer returned by [`read_iovecs`](IoVecBuf::read_iovecs) must point
+/// to as many iovecs as it says, each covering initialized bytes. Both the
+/// iovec array and the bytes must stay valid and unchanged when the value is
+/// moved, like for [`IoBuf`](super::IoBuf), so the array has to live on the
+/// heap, not inline in the value.
+pub unsafe trait IoVecBuf: Unpin + 'static {
+    /// Pointer to the first iovec, and the number of iovecs.
+    fn read_iovecs(&mut self) -> (*const libc::iovec, usize);
+}
+
+/// Owned buffers the kernel writes into in order, like the destination of a
+/// `readv`.
+///
+/// A read op calls [`set_init`](IoVecBufMut::set_init) exactly once, with
+/// the count from the completion.
+///
+/// # Safety
+/// The pointer returned by [`write_iovecs`](IoVecBufMut::write_iovecs) must
+/// point to as many iovecs as it says, each covering writable bytes, with the
+/// same stability as for [`IoVecBuf`]. After [`set_init(n)`](IoVecBufMut::set_init)
+/// the first `n` bytes across the iovecs must count as initialized.
+pub unsafe trait IoVecBufMut: Unpin + 'static {
+    /// Pointer to the first iovec, and the number of iovecs.
+    fn write_iovecs(&mut self) -> (*mut libc::iovec, usize);
+
+    /// Mark the first `pos` bytes, across the iovecs in order, as
+    /// initialized after the kernel filled them.
+    ///
+    /// # Safety
+    /// The first `pos` bytes must have been initialized, and `pos` must not
+    /// exceed what the iovecs cover.
+    unsafe fn set_init(&mut self, pos: usize);
+}
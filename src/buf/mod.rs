@@ -0,0 +1,73 @@
+//! Owned buffers for io.
+//!
+//! The kernel reads and writes buffers after the submitting call returned, so
+//! ops take buffers by value and hand them back with the result. The traits
+//! here describe what a buffer type has to guarantee for that.
+
+mod aligned_buf;
+#[cfg(feature = "bytes")]
+mod bytes;
+mod fixed_buf;
+mod io_buf;
+mod io_buf_mut;
+mod io_vec_buf;
+mod slice;
+mod small_io_vec;
+mod vec_buf;
+
+pub use crate::driver::buf_group::{BorrowedBuf, BufRing};
+pub use aligned_buf::AlignedBuf;
+pub use fixed_buf::FixedBuf;
+pub use io_buf::IoBuf;
+pub use io_buf_mut::{IoBufMut, IoBufMutExt};
+pub use io_vec_buf::{IoVecBuf, IoVecBufMut};
+pub use slice::{Slice, SliceMut};
+pub use small_io_vec::SmallIoVec;
+pub use vec_buf::VecBuf;
+
+use std::future::Future;
+use std::io;
+
+/// Result of an op that owned a buffer, returned together with the buffer.
+pub type BufResult<T, B> = (io::Result<T>, B);
+
+// Capacity given to a buffer with none to spare on the first read.
+const MIN_READ: usize = 32;
+// Growth stops doubling here, a huge buffer grows by this much at a time.
+const MAX_READ_GROWTH: usize = 8 << 20;
+
+/// Make room in a full `buf` for the next read: double it, from at least
+/// [`MIN_READ`] and by at most [`MAX_READ_GROWTH`] bytes.
+pub(crate) fn reserve_for_read<T: IoBufMutExt>(buf: &mut T) {
+    let init = buf.bytes_init();
+    if init == buf.bytes_total() {
+        buf.reserve(init.clamp(MIN_READ, MAX_READ_GROWTH));
+    }
+}
+
+/// Read until `read` returns 0, growing `buf` whenever it is full, and return
+/// the number of bytes added.
+///
+/// `read` gets the spare part of the buffer and the number of bytes read so
+/// far, which positional reads add to their offset.
+pub(crate) async fn read_to_end<T, F, Fut>(mut buf: T, mut read: F) -> BufResult<usize, T>
+where
+    T: IoBufMutExt,
+    F: FnMut(SliceMut<T>, usize) -> Fut,
+    Fut: Future<Output = BufResult<usize, SliceMut<T>>>,
+{
+    let start = buf.bytes_init();
+    loop {
+        // Doubling keeps the number of reads logarithmic in the total.
+        reserve_for_read(&mut buf);
+        let init = buf.bytes_init();
+        let (res, slice) = read(buf.slice_mut(init..), init - start).await;
+        buf = slice.into_inner();
+        match res {
+            Ok(0) => return (Ok(buf.bytes_init() - start), buf),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return (Err(e), buf),
+        }
+    }
+}
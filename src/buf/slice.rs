@@ -0,0 +1,217 @@
+use super::{IoBuf, IoBufMut};
+use std::ops::{Bound, RangeBounds};
+
+/// A range of an owned buffer, reported to ops in place of the whole buffer.
+///
+/// The slice keeps ownership of the full buffer, [`into_inner`] gives it
+/// back.
+///
+/// [`into_inner`]: Slice::into_inner
+pub struct Slice<T> {
+    buf: T,
+    begin: usize,
+    end: usize,
+}
+
+/// A range of an owned buffer the kernel writes into.
+///
+/// Bytes written past the initialized part of the inner buffer extend it.
+pub struct SliceMut<T> {
+    buf: T,
+    begin: usize,
+    end: usize,
+}
+
+// Resolve `range` against `len`, panicking like slice indexing does.
+fn bounds(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let begin = match range.start_bound() {
+        Bound::Included(&n) => n,
+        Bound::Excluded(&n) => n.checked_add(1).expect("slice start overflows usize"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&n) => n.checked_add(1).expect("slice end overflows usize"),
+        Bound::Excluded(&n) => n,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        begin <= end,
+        "slice index starts at {begin} but ends at {end}"
+    );
+    assert!(
+        end <= len,
+        "range end index {end} out of range for buffer of length {len}"
+    );
+    (begin, end)
+}
+
+impl<T: IoBuf> Slice<T> {
+    pub(super) fn new(buf: T, range: impl RangeBounds<usize>) -> Self {
+        let (begin, end) = bounds(range, buf.bytes_init());
+        Slice { buf, begin, end }
+    }
+}
+
+impl<T> Slice<T> {
+    /// Offset of the slice in the inner buffer.
+    pub fn begin(&self) -> usize {
+        self.begin
+    }
+
+    /// End of the slice in the inner buffer.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.buf
+    }
+
+    /// Give back the whole buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+}
+
+unsafe impl<T: IoBuf> IoBuf for Slice<T> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        // In bounds, `new` checked the range.
+        unsafe { self.buf.read_ptr().add(self.begin) }
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.end - self.begin
+    }
+}
+
+impl<T: IoBuf + IoBufMut> SliceMut<T> {
+    pub(super) fn new(mut buf: T, range: impl RangeBounds<usize>) -> Self {
+        let (begin, end) = bounds(range, buf.bytes_total());
+        // Bytes before the slice have to be initialized, or filling the
+        // slice would leave a hole in the buffer.
+        assert!(
+            begin <= buf.bytes_init(),
+            "slice starts at {begin} past the {} initialized bytes",
+            buf.bytes_init()
+        );
+        SliceMut { buf, begin, end }
+    }
+}
+
+impl<T> SliceMut<T> {
+    /// Offset of the slice in the inner buffer.
+    pub fn begin(&self) -> usize {
+        self.begin
+    }
+
+    /// End of the slice in the inner buffer.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.buf
+    }
+
+    /// Give back the whole buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+}
+
+// The initialized part of the slice.
+unsafe impl<T: IoBuf + IoBufMut> IoBuf for SliceMut<T> {
+    #[inline]
+    fn read_ptr(&self) -> *const u8 {
+        // In bounds, `new` checked the range.
+        unsafe { self.buf.read_ptr().add(self.begin) }
+    }
+
+    #[inline]
+    fn bytes_init(&self) -> usize {
+        self.buf.bytes_init().min(self.end) - self.begin
+    }
+}
+
+unsafe impl<T: IoBuf + IoBufMut> IoBufMut for SliceMut<T> {
+    #[inline]
+    fn write_ptr(&mut self) -> *mut u8 {
+        // In bounds, `new` checked the range.
+        unsafe { self.buf.write_ptr().add(self.begin) }
+    }
+
+    #[inline]
+    fn bytes_total(&mut self) -> usize {
+        self.end - self.begin
+    }
+
+    #[inline]
+    unsafe fn set_init(&mut self, pos: usize) {
+        let init = self.begin + pos;
+        if init > self.buf.bytes_init() {
+            self.buf.set_init(init);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_reports_range() {
+        let buf = Vec::from(&b"hello world"[..]);
+        let ptr = buf.read_ptr();
+        let slice = buf.slice(6..);
+        assert_eq!(slice.read_ptr(), unsafe { ptr.add(6) });
+        assert_eq!(slice.bytes_init(), 5);
+        assert_eq!(slice.into_inner(), b"hello world");
+
+        let empty = b"abc".as_slice().slice(3..3);
+        assert_eq!(empty.bytes_init(), 0);
+        assert_eq!(b"abc".as_slice().slice(..=1).bytes_init(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "range end index 4 out of range for buffer of length 3")]
+    fn slice_past_end() {
+        b"abc".as_slice().slice(1..4);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice index starts at 2 but ends at 1")]
+    fn slice_backwards() {
+        #[allow(clippy::reversed_empty_ranges)]
+        b"abc".as_slice().slice(2..1);
+    }
+
+    #[test]
+    fn slice_mut_extends_inner() {
+        let mut buf = Vec::with_capacity(8);
+        buf.extend_from_slice(b"ab");
+        let mut slice = buf.slice_mut(2..6);
+        assert_eq!(slice.bytes_total(), 4);
+        assert_eq!(slice.bytes_init(), 0);
+        unsafe {
+            slice.write_ptr().copy_from(b"cd".as_ptr(), 2);
+            slice.set_init(2);
+        }
+        assert_eq!(slice.bytes_init(), 2);
+        assert_eq!(slice.into_inner(), b"abcd");
+
+        // Filling already initialized bytes keeps the length.
+        let mut slice = Vec::from(&b"abcd"[..]).slice_mut(0..1);
+        unsafe { slice.set_init(1) };
+        assert_eq!(slice.into_inner(), b"abcd");
+    }
+
+    #[test]
+    #[should_panic(expected = "slice starts at 3 past the 2 initialized bytes")]
+    fn slice_mut_leaves_hole() {
+        let mut buf = Vec::with_capacity(8);
+        buf.extend_from_slice(b"ab");
+        buf.slice_mut(3..);
+    }
+}
@@ -0,0 +1,144 @@
+use super::{IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use std::fmt;
+
+/// Up to `N` owned buffers for vectored io, with a boxed iovec array of
+/// fixed size.
+///
+/// Unlike [`VecBuf`](super::VecBuf) it holds any [`IoBuf`], like chunks from
+/// a pool, and reads fill the whole of [`bytes_total`](IoBufMut::bytes_total)
+/// of each buffer in order, marking the bytes read as initialized as a
+/// single buffer read would.
+pub struct SmallIoVec<T, const N: usize> {
+    bufs: Vec<T>,
+    iovecs: Box<[libc::iovec; N]>,
+}
+
+impl<T, const N: usize> SmallIoVec<T, N> {
+    pub fn new() -> Self {
+        let empty = libc::iovec {
+            iov_base: std::ptr::null_mut(),
+            iov_len: 0,
+        };
+        SmallIoVec {
+            bufs: Vec::with_capacity(N),
+            iovecs: Box::new([empty; N]),
+        }
+    }
+
+    /// Append `buf`, handing it back when all `N` places are taken.
+    pub fn push(&mut self, buf: T) -> Result<(), T> {
+        if self.bufs.len() == N {
+            return Err(buf);
+        }
+        self.bufs.push(buf);
+        Ok(())
+    }
+
+    /// The number of buffers.
+    pub fn len(&self) -> usize {
+        self.bufs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bufs.is_empty()
+    }
+
+    /// The buffers, in order.
+    pub fn bufs(&self) -> &[T] {
+        &self.bufs
+    }
+
+    /// Give back the buffers.
+    pub fn into_inner(self) -> Vec<T> {
+        self.bufs
+    }
+}
+
+impl<T, const N: usize> Default for SmallIoVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for SmallIoVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SmallIoVec")
+            .field("bufs", &self.bufs.len())
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+// The iovecs are written afresh before each op, the buffers may have changed
+// through the owner in between.
+unsafe impl<T: IoBuf, const N: usize> IoVecBuf for SmallIoVec<T, N> {
+    fn read_iovecs(&mut self) -> (*const libc::iovec, usize) {
+        for (iov, buf) in self.iovecs.iter_mut().zip(&self.bufs) {
+            iov.iov_base = buf.read_ptr().cast_mut().cast();
+            iov.iov_len = buf.bytes_init();
+        }
+        (self.iovecs.as_ptr(), self.bufs.len())
+    }
+}
+
+unsafe impl<T: IoBufMut, const N: usize> IoVecBufMut for SmallIoVec<T, N> {
+    fn write_iovecs(&mut self) -> (*mut libc::iovec, usize) {
+        for (iov, buf) in self.iovecs.iter_mut().zip(&mut self.bufs) {
+            iov.iov_base = buf.write_ptr().cast();
+            iov.iov_len = buf.bytes_total();
+        }
+        (self.iovecs.as_mut_ptr(), self.bufs.len())
+    }
+
+    unsafe fn set_init(&mut self, mut pos: usize) {
+        // Buffers the read did not reach keep what they had.
+        for buf in &mut self.bufs {
+            if pos == 0 {
+                return;
+            }
+            let filled = pos.min(buf.bytes_total());
+            buf.set_init(filled);
+            pos -= filled;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iovecs_cover_the_buffers() {
+        let mut bufs = SmallIoVec::<Vec<u8>, 3>::new();
+        bufs.push(b"ab".to_vec()).unwrap();
+        bufs.push(Vec::with_capacity(4)).unwrap();
+        bufs.push(b"c".to_vec()).unwrap();
+        assert_eq!(bufs.push(Vec::new()), Err(Vec::new()));
+
+        let (ptr, count) = bufs.read_iovecs();
+        let iovecs = unsafe { std::slice::from_raw_parts(ptr, count) };
+        let lens: Vec<_> = iovecs.iter().map(|iov| iov.iov_len).collect();
+        assert_eq!(lens, [2, 0, 1]);
+        // The array stays put when moved.
+        let mut moved = Box::new(bufs);
+        assert_eq!(moved.read_iovecs().0, ptr);
+
+        let (ptr, count) = moved.write_iovecs();
+        let iovecs = unsafe { std::slice::from_raw_parts(ptr, count) };
+        assert!(iovecs[1].iov_len >= 4);
+        unsafe {
+            let data = b"xyzw";
+            iovecs[0].iov_base.cast::<u8>().copy_from(data.as_ptr(), 2);
+            iovecs[1]
+                .iov_base
+                .cast::<u8>()
+                .copy_from(data[2..].as_ptr(), 2);
+            moved.set_init(iovecs[0].iov_len + 2);
+        }
+        let bufs = moved.into_inner();
+        assert_eq!(bufs[0][..2], *b"xy");
+        assert_eq!(bufs[1][..2], *b"zw");
+        // Not reached by the read.
+        assert_eq!(bufs[2], b"c");
+    }
+}
@@ -0,0 +1,209 @@
+use super::{IoVecBuf, IoVecBufMut};
+use std::fmt;
+
+// Linux rejects more iovecs than this in one call (UIO_MAXIOV).
+const MAX_IOVECS: usize = 1024;
+
+/// Owned buffers for vectored io, with the iovec array the kernel reads.
+///
+/// Writes send the initialized bytes of every buffer, reads fill them, so
+/// size the buffers before reading. After a partial transfer
+/// [`advance`](VecBuf::advance) moves past the bytes done, and the next op
+/// resumes from there. Both the buffers and the iovec array live on the
+/// heap, so their addresses stay put while the `VecBuf` is moved into and
+/// out of ops.
+pub struct VecBuf {
+    bufs: Vec<Vec<u8>>,
+    // Built on first use, `front` is the first iovec not fully consumed.
+    iovecs: Vec<libc::iovec>,
+    front: usize,
+    remaining: usize,
+}
+
+impl VecBuf {
+    pub fn new(bufs: Vec<Vec<u8>>) -> Self {
+        let remaining = bufs.iter().map(Vec::len).sum();
+        VecBuf {
+            bufs,
+            iovecs: Vec::new(),
+            front: 0,
+            remaining,
+        }
+    }
+
+    /// Bytes left to transfer.
+    pub fn len(&self) -> usize {
+        self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// Mark `n` more bytes as transferred.
+    ///
+    /// # Panics
+    /// If `n` is more than [`len`](VecBuf::len).
+    pub fn advance(&mut self, mut n: usize) {
+        assert!(
+            n <= self.remaining,
+            "advance by {n} past the {} remaining bytes",
+            self.remaining
+        );
+        self.build();
+        self.remaining -= n;
+        while n > 0 {
+            let iov = &mut self.iovecs[self.front];
+            if n < iov.iov_len {
+                iov.iov_base = unsafe { iov.iov_base.cast::<u8>().add(n) }.cast();
+                iov.iov_len -= n;
+                return;
+            }
+            n -= iov.iov_len;
+            self.front += 1;
+        }
+    }
+
+    /// Give back the buffers, whatever was advanced past.
+    pub fn into_inner(self) -> Vec<Vec<u8>> {
+        self.bufs
+    }
+
+    /// Pointer and count of the iovecs still to transfer, for the sqe.
+    pub(crate) fn iovecs(&mut self) -> (*const libc::iovec, u32) {
+        self.build();
+        let rest = &self.iovecs[self.front..];
+        (rest.as_ptr(), rest.len().min(MAX_IOVECS) as u32)
+    }
+
+    fn build(&mut self) {
+        if self.iovecs.len() == self.bufs.len() {
+            return;
+        }
+        self.iovecs = self
+            .bufs
+            .iter_mut()
+            .map(|buf| libc::iovec {
+                iov_base: buf.as_mut_ptr().cast(),
+                iov_len: buf.len(),
+            })
+            .collect();
+    }
+}
+
+impl From<Vec<Vec<u8>>> for VecBuf {
+    fn from(bufs: Vec<Vec<u8>>) -> Self {
+        VecBuf::new(bufs)
+    }
+}
+
+unsafe impl IoVecBuf for VecBuf {
+    fn read_iovecs(&mut self) -> (*const libc::iovec, usize) {
+        let (ptr, count) = self.iovecs();
+        (ptr, count as usize)
+    }
+}
+
+unsafe impl IoVecBufMut for VecBuf {
+    fn write_iovecs(&mut self) -> (*mut libc::iovec, usize) {
+        let (ptr, count) = self.iovecs();
+        (ptr.cast_mut(), count as usize)
+    }
+
+    // The buffers are read into up to their length, which is initialized
+    // already.
+    unsafe fn set_init(&mut self, _pos: usize) {}
+}
+
+impl fmt::Debug for VecBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VecBuf")
+            .field("bufs", &self.bufs.len())
+            .field("remaining", &self.remaining)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the remaining iovecs cover, in order.
+    fn pending(buf: &mut VecBuf) -> Vec<u8> {
+        let (ptr, count) = buf.iovecs();
+        let iovecs = unsafe { std::slice::from_raw_parts(ptr, count as usize) };
+        iovecs
+            .iter()
+            .flat_map(|iov| unsafe {
+                std::slice::from_raw_parts(iov.iov_base.cast::<u8>(), iov.iov_len)
+            })
+            .copied()
+            .collect()
+    }
+
+    fn abc() -> VecBuf {
+        VecBuf::from(vec![
+            b"abc".to_vec(),
+            Vec::new(),
+            b"de".to_vec(),
+            b"fgh".to_vec(),
+        ])
+    }
+
+    #[test]
+    fn advance_within_buffer() {
+        let mut buf = abc();
+        assert_eq!(buf.len(), 8);
+        buf.advance(1);
+        assert_eq!(pending(&mut buf), b"bcdefgh");
+        buf.advance(1);
+        assert_eq!(pending(&mut buf), b"cdefgh");
+        assert_eq!(buf.len(), 6);
+    }
+
+    #[test]
+    fn advance_onto_boundary() {
+        let mut buf = abc();
+        buf.advance(3);
+        assert_eq!(pending(&mut buf), b"defgh");
+        buf.advance(2);
+        assert_eq!(pending(&mut buf), b"fgh");
+        assert_eq!(buf.iovecs().1, 1);
+        buf.advance(3);
+        assert!(buf.is_empty());
+        assert_eq!(buf.iovecs().1, 0);
+    }
+
+    #[test]
+    fn advance_across_boundaries() {
+        let mut buf = abc();
+        // Stops one short of the boundary, then crosses it.
+        buf.advance(4);
+        assert_eq!(pending(&mut buf), b"efgh");
+        buf.advance(2);
+        assert_eq!(pending(&mut buf), b"gh");
+        assert_eq!(buf.into_inner()[3], b"fgh");
+    }
+
+    #[test]
+    fn pointers_survive_moves() {
+        let mut buf = abc();
+        let (ptr, _) = buf.iovecs();
+        let mut moved = Box::new(buf);
+        assert_eq!(moved.iovecs().0, ptr);
+    }
+
+    #[test]
+    fn caps_iovec_count() {
+        let mut buf = VecBuf::new(vec![vec![0; 1]; MAX_IOVECS + 1]);
+        assert_eq!(buf.iovecs().1 as usize, MAX_IOVECS);
+        buf.advance(2);
+        assert_eq!(buf.iovecs().1 as usize, MAX_IOVECS - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "advance by 9 past the 8 remaining bytes")]
+    fn advance_past_end() {
+        abc().advance(9);
+    }
+}
@@ -0,0 +1,117 @@
+use super::state::{ReadState, WriteState};
+use crate::io::{AsyncReadRent, AsyncWriteRent};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// [`futures_io::AsyncRead`] over an [`AsyncReadRent`], from
+/// [`CompatExt::compat`].
+pub struct Compat<R> {
+    read: ReadState<R>,
+}
+
+/// [`futures_io::AsyncWrite`] over an [`AsyncWriteRent`], from
+/// [`CompatExt::compat_write`]. Closing it shuts the writer down.
+pub struct CompatWrite<W> {
+    write: WriteState<W>,
+}
+
+/// Adapters to the `futures-io` traits, implemented for every type.
+///
+/// Use [`StreamWrapper`](super::StreamWrapper) for a stream that is read
+/// and written at the same time.
+pub trait CompatExt: Sized + 'static {
+    fn compat(self) -> Compat<Self>
+    where
+        Self: AsyncReadRent,
+    {
+        Compat {
+            read: ReadState::new(self),
+        }
+    }
+
+    fn compat_write(self) -> CompatWrite<Self>
+    where
+        Self: AsyncWriteRent,
+    {
+        CompatWrite {
+            write: WriteState::new(self),
+        }
+    }
+}
+
+impl<T: 'static> CompatExt for T {}
+
+impl<R: AsyncReadRent + 'static> Compat<R> {
+    /// The reader, `None` while a read is in flight. Bytes read ahead and
+    /// not handed out yet are dropped.
+    pub fn into_inner(self) -> Option<R> {
+        self.read.into_inner()
+    }
+}
+
+impl<W: AsyncWriteRent + 'static> CompatWrite<W> {
+    /// The writer, once everything written was flushed. `None` while a
+    /// write, flush or close is in flight.
+    pub fn into_inner(self) -> Option<W> {
+        self.write.into_inner()
+    }
+}
+
+impl<R: AsyncReadRent + 'static> futures_io::AsyncRead for Compat<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().read.poll_read(cx, buf)
+    }
+}
+
+impl<W: AsyncWriteRent + 'static> futures_io::AsyncWrite for CompatWrite<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use async_compression::futures::bufread::GzipDecoder;
+    use async_compression::futures::write::GzipEncoder;
+    use futures_util::io::{AsyncReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn gzip_round_trip() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let data: Vec<u8> = (0..200_000u32).map(|i| (i % 7 * i % 13) as u8).collect();
+            let mut encoder = GzipEncoder::new(Vec::new().compat_write());
+            encoder.write_all(&data).await.unwrap();
+            encoder.close().await.unwrap();
+            let compressed = encoder.into_inner().into_inner().unwrap();
+            assert!(compressed.len() < data.len() / 4);
+
+            // Reads come from a slice, which must outlive the boxed ops.
+            let compressed: &'static [u8] = compressed.leak();
+            let mut decoder = GzipDecoder::new(BufReader::new(compressed.compat()));
+            let mut out = Vec::new();
+            decoder.read_to_end(&mut out).await.unwrap();
+            assert_eq!(out, data);
+        });
+    }
+}
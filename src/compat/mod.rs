@@ -0,0 +1,14 @@
+//! Poll based io traits over the rent-style streams of this crate, for code
+//! written against them.
+//!
+//! The adapters copy between the caller's slices and buffers of their own,
+//! and drive the owned-buffer ops underneath as the caller polls.
+
+#[cfg(feature = "futures-compat")]
+mod futures_compat;
+mod state;
+mod stream_wrapper;
+
+#[cfg(feature = "futures-compat")]
+pub use futures_compat::{Compat, CompatExt, CompatWrite};
+pub use stream_wrapper::StreamWrapper;
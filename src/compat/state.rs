@@ -0,0 +1,227 @@
+//! The buffered state machines behind the poll based adapters.
+//!
+//! Each side owns its half of the stream and one buffer. A pending op is a
+//! boxed future that owns both and hands them back, so polling may stop at
+//! any point without losing bytes.
+
+use crate::io::{AsyncReadRent, AsyncWriteRent, AsyncWriteRentExt};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+const BUF_SIZE: usize = 8 * 1024;
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+pub(crate) struct ReadState<R> {
+    state: Reading<R>,
+    // Read and not handed out yet, from `pos` on.
+    pos: usize,
+}
+
+enum Reading<R> {
+    Idle(R, Vec<u8>),
+    Busy(BoxFuture<(R, io::Result<usize>, Vec<u8>)>),
+    // Only while switching between the two.
+    Empty,
+}
+
+// The reader is only ever moved, into and out of the boxed op, never pinned.
+impl<R> Unpin for ReadState<R> {}
+
+impl<R: AsyncReadRent + 'static> ReadState<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        ReadState {
+            state: Reading::Idle(reader, Vec::with_capacity(BUF_SIZE)),
+            pos: 0,
+        }
+    }
+
+    /// Copy buffered bytes into `dst`, reading more first if there are none.
+    /// 0 at the end of the stream.
+    pub(crate) fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        dst: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match std::mem::replace(&mut self.state, Reading::Empty) {
+                Reading::Idle(reader, buf) if self.pos < buf.len() => {
+                    let n = (buf.len() - self.pos).min(dst.len());
+                    dst[..n].copy_from_slice(&buf[self.pos..self.pos + n]);
+                    self.pos += n;
+                    self.state = Reading::Idle(reader, buf);
+                    return Poll::Ready(Ok(n));
+                }
+                Reading::Idle(reader, buf) if dst.is_empty() => {
+                    self.state = Reading::Idle(reader, buf);
+                    return Poll::Ready(Ok(0));
+                }
+                Reading::Idle(mut reader, mut buf) => {
+                    buf.clear();
+                    self.pos = 0;
+                    self.state = Reading::Busy(Box::pin(async move {
+                        let (res, buf) = reader.read(buf).await;
+                        (reader, res, buf)
+                    }));
+                }
+                Reading::Busy(mut fut) => match fut.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        self.state = Reading::Busy(fut);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((reader, res, buf)) => {
+                        self.state = Reading::Idle(reader, buf);
+                        if let Err(e) = res {
+                            return Poll::Ready(Err(e));
+                        }
+                        // An empty buffer now is the end of the stream.
+                        if self.pos == self.buffered_len() {
+                            return Poll::Ready(Ok(0));
+                        }
+                    }
+                },
+                Reading::Empty => unreachable!("read state is left empty"),
+            }
+        }
+    }
+
+    /// The reader, `None` while a read is in flight. Bytes read and not
+    /// handed out yet are dropped.
+    pub(crate) fn into_inner(self) -> Option<R> {
+        match self.state {
+            Reading::Idle(reader, _) => Some(reader),
+            _ => None,
+        }
+    }
+
+    fn buffered_len(&self) -> usize {
+        match &self.state {
+            Reading::Idle(_, buf) => buf.len(),
+            _ => 0,
+        }
+    }
+}
+
+pub(crate) struct WriteState<W> {
+    state: Writing<W>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WriteOp {
+    Write,
+    Flush,
+    Shutdown,
+}
+
+enum Writing<W> {
+    Idle(W, Vec<u8>),
+    Busy(WriteOp, BoxFuture<(W, io::Result<()>, Vec<u8>)>),
+    Empty,
+}
+
+impl<W> Unpin for WriteState<W> {}
+
+impl<W: AsyncWriteRent + 'static> WriteState<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        WriteState {
+            state: Writing::Idle(writer, Vec::with_capacity(BUF_SIZE)),
+        }
+    }
+
+    /// Take up to a buffer of `src` to write out, once the write before it
+    /// finished. Its error, if any, is returned here. Nothing is written
+    /// before the next call or a flush.
+    pub(crate) fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        src: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_idle(cx))?;
+        if src.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let Writing::Idle(mut writer, mut buf) = std::mem::replace(&mut self.state, Writing::Empty)
+        else {
+            unreachable!("writer is idle");
+        };
+        buf.clear();
+        let n = src.len().min(BUF_SIZE);
+        buf.extend_from_slice(&src[..n]);
+        self.state = Writing::Busy(
+            WriteOp::Write,
+            Box::pin(async move {
+                let (res, buf) = writer.write_all(buf).await;
+                (writer, res, buf)
+            }),
+        );
+        // Taken, the write starts with the next poll of the writer.
+        Poll::Ready(Ok(n))
+    }
+
+    /// Finish the pending write, then flush the writer.
+    pub(crate) fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_op(cx, WriteOp::Flush)
+    }
+
+    /// Finish the pending write, then shut the writer down.
+    pub(crate) fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_op(cx, WriteOp::Shutdown)
+    }
+
+    fn poll_op(&mut self, cx: &mut Context<'_>, op: WriteOp) -> Poll<io::Result<()>> {
+        loop {
+            if let Writing::Busy(pending, _) = &self.state {
+                let done = *pending == op;
+                ready!(self.poll_idle(cx))?;
+                if done {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            let Writing::Idle(mut writer, buf) = std::mem::replace(&mut self.state, Writing::Empty)
+            else {
+                unreachable!("writer is idle");
+            };
+            self.state = Writing::Busy(
+                op,
+                Box::pin(async move {
+                    let res = match op {
+                        WriteOp::Shutdown => writer.shutdown().await,
+                        _ => writer.flush().await,
+                    };
+                    (writer, res, buf)
+                }),
+            );
+        }
+    }
+
+    /// The writer, `None` while a write, flush or shutdown is in flight.
+    pub(crate) fn into_inner(self) -> Option<W> {
+        match self.state {
+            Writing::Idle(writer, _) => Some(writer),
+            _ => None,
+        }
+    }
+
+    // Drive the pending op to its end.
+    fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match std::mem::replace(&mut self.state, Writing::Empty) {
+            Writing::Idle(writer, buf) => {
+                self.state = Writing::Idle(writer, buf);
+                Poll::Ready(Ok(()))
+            }
+            Writing::Busy(op, mut fut) => match fut.as_mut().poll(cx) {
+                Poll::Pending => {
+                    self.state = Writing::Busy(op, fut);
+                    Poll::Pending
+                }
+                Poll::Ready((writer, res, buf)) => {
+                    self.state = Writing::Idle(writer, buf);
+                    Poll::Ready(res)
+                }
+            },
+            Writing::Empty => unreachable!("write state is left empty"),
+        }
+    }
+}
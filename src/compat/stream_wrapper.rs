@@ -0,0 +1,172 @@
+use super::state::{ReadState, WriteState};
+use crate::io::{OwnedReadHalf, OwnedWriteHalf, Split};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Poll based reads and writes over a [`Split`] stream, for libraries like
+/// hyper: the tokio traits with the `compat` feature, the `futures-io` ones
+/// with `futures-compat`.
+///
+/// Reads and writes go through buffers of 8KiB. A read that returns
+/// `Pending` keeps what the op fills for the next poll, and bytes taken by
+/// `poll_write` are written out before a flush or shutdown completes.
+/// Dropping the wrapper drops the ops in flight.
+pub struct StreamWrapper<T: Split> {
+    read: ReadState<OwnedReadHalf<T>>,
+    write: WriteState<OwnedWriteHalf<T>>,
+}
+
+impl<T: Split + 'static> StreamWrapper<T> {
+    pub fn new(stream: T) -> Self {
+        let (read, write) = stream.split();
+        StreamWrapper {
+            read: ReadState::new(read),
+            write: WriteState::new(write),
+        }
+    }
+
+    /// The stream, `None` while a read or write is in flight. Bytes read
+    /// ahead and not handed out yet are dropped.
+    pub fn into_inner(self) -> Option<T> {
+        let read = self.read.into_inner()?;
+        let write = self.write.into_inner()?;
+        read.reunite(write).ok()
+    }
+}
+
+#[cfg(feature = "compat")]
+impl<T: Split + 'static> tokio::io::AsyncRead for StreamWrapper<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = std::task::ready!(self.get_mut().read.poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "compat")]
+impl<T: Split + 'static> tokio::io::AsyncWrite for StreamWrapper<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "futures-compat")]
+impl<T: Split + 'static> futures_io::AsyncRead for StreamWrapper<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().read.poll_read(cx, buf)
+    }
+}
+
+#[cfg(feature = "futures-compat")]
+impl<T: Split + 'static> futures_io::AsyncWrite for StreamWrapper<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().write.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().write.poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(test, feature = "compat"))]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::runtime::RuntimeBuilder;
+    use std::future::Future;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::pin::pin;
+    use std::task::Waker;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn socketpair() -> (File, File) {
+        let mut fds = [0; 2];
+        let flags = libc::SOCK_STREAM | libc::SOCK_CLOEXEC;
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()) },
+            0
+        );
+        unsafe {
+            (
+                File::from(OwnedFd::from_raw_fd(fds[0])),
+                File::from(OwnedFd::from_raw_fd(fds[1])),
+            )
+        }
+    }
+
+    #[test]
+    fn pending_reads_keep_their_bytes() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, mut b) = socketpair();
+            let mut a = StreamWrapper::new(a);
+            let mut out = [0; 4];
+            {
+                let mut read = pin!(a.read(&mut out));
+                let mut cx = Context::from_waker(Waker::noop());
+                assert!(read.as_mut().poll(&mut cx).is_pending());
+            }
+            // The op of the dropped read fills the wrapper's buffer.
+            b.write_all("hello").await.0.unwrap();
+            b.metadata().await.unwrap();
+            assert_eq!(a.read(&mut out).await.unwrap(), 4);
+            assert_eq!(&out, b"hell");
+            assert_eq!(a.read(&mut out).await.unwrap(), 1);
+            assert_eq!(out[0], b'o');
+        });
+    }
+
+    #[test]
+    fn shutdown_flushes_first() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, mut b) = socketpair();
+            let mut a = StreamWrapper::new(a);
+            let data: Vec<u8> = (0..20_000u32).map(|i| i as u8).collect();
+            a.write_all(&data).await.unwrap();
+            a.shutdown().await.unwrap();
+            let (n, buf) = b.read_to_end(Vec::new()).await;
+            assert_eq!(n.unwrap(), data.len());
+            assert_eq!(buf, data);
+
+            // And the other way, to the end of the stream.
+            b.write_all("bye").await.0.unwrap();
+            drop(b);
+            let mut all = String::new();
+            a.read_to_string(&mut all).await.unwrap();
+            assert_eq!(all, "bye");
+            assert!(a.into_inner().is_some());
+        });
+    }
+}
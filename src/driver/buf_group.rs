@@ -0,0 +1,716 @@
+//! Provided buffer groups.
+//!
+//! A group is a set of equally sized buffers handed to the kernel up front.
+//! Ops submitted with `IOSQE_BUFFER_SELECT` let the kernel pick one of them
+//! when data arrives, so idle reads do not pin a buffer each.
+
+use std::{
+    alloc::{self, Layout},
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    io,
+    ops::Deref,
+    rc::Rc,
+    sync::atomic::{AtomicU16, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use io_uring::{opcode, squeue::Entry, types::BufRingEntry};
+
+use crate::driver::{
+    self,
+    op::{CompletionMeta, Mappable},
+};
+
+/// A group of kernel-selectable buffers.
+///
+/// Cloning is cheap, all clones refer to the same group. The group is torn
+/// down once the last clone and the last [`BorrowedBuf`] taken from it are gone.
+#[derive(Clone)]
+pub struct BufGroup {
+    inner: Rc<GroupInner>,
+}
+
+struct GroupInner {
+    bgid: u16,
+    count: u16,
+    buf_len: usize,
+    // Backing memory of all buffers, buffer `bid` starts at `bid * buf_len`.
+    storage: Option<Box<[u8]>>,
+    provider: Provider,
+    outstanding: Cell<usize>,
+    // Bumped on every buffer given back, tells whether one came back since
+    // an op was submitted.
+    recycled: Cell<u64>,
+    waiters: RefCell<Vec<Waker>>,
+    driver: driver::Inner,
+}
+
+// How the buffers are handed to the kernel.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// A ring if the kernel has them, provide buffers otherwise.
+    Any,
+    Ring,
+    #[cfg(test)]
+    Legacy,
+}
+
+enum Provider {
+    /// Ring mapped buffers(5.19+), recycled without a syscall.
+    Ring {
+        entries: *mut BufRingEntry,
+        layout: Layout,
+        tail: Cell<u16>,
+    },
+    /// Buffers provided with `IORING_OP_PROVIDE_BUFFERS`.
+    Legacy,
+}
+
+/// The group had no buffer left when the kernel tried to select one.
+///
+/// This is recoverable: drop some [`BorrowedBuf`]s to give their buffers back
+/// and retry the op. It is carried as the payload of an [`io::Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhausted {
+    /// Id of the exhausted group.
+    pub bgid: u16,
+}
+
+impl Exhausted {
+    /// Get the payload if the error was caused by an exhausted group.
+    pub fn from_io_error(err: &io::Error) -> Option<&Exhausted> {
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no buffer left in group {}", self.bgid)
+    }
+}
+
+impl std::error::Error for Exhausted {}
+
+/// A ring of provided buffers(5.19+) the kernel picks from when data
+/// arrives, so pending reads hold no buffer of their own.
+///
+/// Reads with the ring, like [`File::read_at_with_ring`](crate::fs::File::read_at_with_ring),
+/// return a [`BorrowedBuf`] that goes back into the ring on drop. When every
+/// buffer is borrowed they wait for one to come back rather than fail.
+///
+/// Cloning is cheap, all clones refer to the same ring.
+#[derive(Clone)]
+pub struct BufRing {
+    group: BufGroup,
+}
+
+impl BufRing {
+    /// Register a ring of `entries` buffers of `buf_len` bytes each under
+    /// group id `bgid`. `entries` must be a power of two no larger than
+    /// 32768, and `bgid` not used by another ring of the runtime.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] on kernels without buffer
+    /// rings. Must be called inside a runtime.
+    pub fn new(bgid: u16, entries: u16, buf_len: usize) -> io::Result<BufRing> {
+        if !entries.is_power_of_two() || entries > 1 << 15 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{entries} ring entries are not a power of two up to 32768"),
+            ));
+        }
+        let group = BufGroup::with_mode(bgid, entries, buf_len, Mode::Ring)?;
+        Ok(BufRing { group })
+    }
+
+    /// Id of the buffer group.
+    pub fn bgid(&self) -> u16 {
+        self.group.bgid()
+    }
+
+    /// Size of each buffer.
+    pub fn buf_len(&self) -> usize {
+        self.group.buf_len()
+    }
+
+    /// Number of buffers in the ring.
+    pub fn entries(&self) -> u16 {
+        self.group.count()
+    }
+
+    /// Number of buffers currently borrowed.
+    pub fn outstanding(&self) -> usize {
+        self.group.outstanding()
+    }
+
+    pub(crate) fn group(&self) -> &BufGroup {
+        &self.group
+    }
+}
+
+impl fmt::Debug for BufRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufRing")
+            .field("bgid", &self.bgid())
+            .field("entries", &self.entries())
+            .field("buf_len", &self.buf_len())
+            .finish()
+    }
+}
+
+impl BufGroup {
+    /// Create a group of `count` buffers of `buf_len` bytes each and provide
+    /// them to the kernel under `bgid`.
+    ///
+    /// Uses a ring mapped buffer group when the kernel supports it and falls
+    /// back to `IORING_OP_PROVIDE_BUFFERS` otherwise. With the ring, `count`
+    /// must be a power of two no larger than 32768.
+    ///
+    /// Must be called inside a runtime.
+    pub fn new(bgid: u16, count: u16, buf_len: usize) -> io::Result<BufGroup> {
+        Self::with_mode(bgid, count, buf_len, Mode::Any)
+    }
+
+    fn with_mode(bgid: u16, count: u16, buf_len: usize, mode: Mode) -> io::Result<BufGroup> {
+        if count == 0 || buf_len == 0 || buf_len > i32::MAX as usize {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        let driver = driver::CURRENT.with(|inner| inner.clone());
+        let storage = vec![0; count as usize * buf_len].into_boxed_slice();
+
+        let registered = match mode {
+            Mode::Any | Mode::Ring => Self::register_ring(&driver, bgid, count),
+            #[cfg(test)]
+            Mode::Legacy => Err(io::ErrorKind::InvalidInput.into()),
+        };
+        let provider = match registered {
+            Ok(provider) => provider,
+            // Kernels before 5.19 do not know the register opcode.
+            Err(e) if mode == Mode::Ring && e.raw_os_error() == Some(libc::EINVAL) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "provided buffer rings are not supported by the kernel(5.19+)",
+                ));
+            }
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                if !driver.is_supported(opcode::ProvideBuffers::CODE) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "kernel supports neither buffer rings nor provide buffers",
+                    ));
+                }
+                Provider::Legacy
+            }
+            Err(e) => return Err(e),
+        };
+
+        let group = BufGroup {
+            inner: Rc::new(GroupInner {
+                bgid,
+                count,
+                buf_len,
+                storage: Some(storage),
+                provider,
+                outstanding: Cell::new(0),
+                recycled: Cell::new(0),
+                waiters: RefCell::new(Vec::new()),
+                driver,
+            }),
+        };
+        match group.inner.provider {
+            Provider::Ring { .. } => {
+                for bid in 0..count {
+                    group.inner.push_ring(bid);
+                }
+            }
+            Provider::Legacy => group.inner.provide(0, count)?,
+        }
+        Ok(group)
+    }
+
+    fn register_ring(driver: &driver::Inner, bgid: u16, count: u16) -> io::Result<Provider> {
+        if !count.is_power_of_two() || count > 1 << 15 {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        let layout = Layout::from_size_align(
+            count as usize * std::mem::size_of::<BufRingEntry>(),
+            page_size(),
+        )
+        .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        // The tail lives in the first entry and must start zeroed.
+        let entries = unsafe { alloc::alloc_zeroed(layout) } as *mut BufRingEntry;
+        if entries.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        if let Err(e) = unsafe { driver.register_buf_ring(entries as u64, count, bgid) } {
+            unsafe { alloc::dealloc(entries as *mut u8, layout) };
+            return Err(e);
+        }
+        Ok(Provider::Ring {
+            entries,
+            layout,
+            tail: Cell::new(0),
+        })
+    }
+
+    /// Id of the group.
+    pub fn bgid(&self) -> u16 {
+        self.inner.bgid
+    }
+
+    /// Size of each buffer.
+    pub fn buf_len(&self) -> usize {
+        self.inner.buf_len
+    }
+
+    /// Number of buffers in the group.
+    pub fn count(&self) -> u16 {
+        self.inner.count
+    }
+
+    /// Number of buffers currently held by [`BorrowedBuf`]s and not available
+    /// to the kernel.
+    pub fn outstanding(&self) -> usize {
+        self.inner.outstanding.get()
+    }
+
+    /// Run the buffer select op `submit` makes until the kernel finds a
+    /// buffer, waiting for one to be given back whenever the group ran dry.
+    pub(crate) async fn select<F, Fut>(&self, mut submit: F) -> io::Result<BorrowedBuf>
+    where
+        F: FnMut() -> io::Result<Fut>,
+        Fut: Future<Output = io::Result<BorrowedBuf>>,
+    {
+        loop {
+            let since = self.inner.recycled.get();
+            match submit()?.await {
+                Err(e) if Exhausted::from_io_error(&e).is_some() => {
+                    std::future::poll_fn(|cx| self.poll_recycled(since, cx)).await
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Ready once the kernel has a buffer to select, at least one is not
+    /// borrowed.
+    pub(crate) fn poll_available(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.outstanding() < self.count() as usize {
+            return Poll::Ready(());
+        }
+        self.inner.waiters.borrow_mut().push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    // Ready once a buffer was given back after `since`.
+    fn poll_recycled(&self, since: u64, cx: &mut Context<'_>) -> Poll<()> {
+        if self.inner.recycled.get() != since {
+            return Poll::Ready(());
+        }
+        self.inner.waiters.borrow_mut().push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Take the buffer the kernel selected for a completed op.
+    pub(crate) fn take(&self, meta: CompletionMeta) -> io::Result<BorrowedBuf> {
+        match selected(meta, self.inner.bgid)? {
+            Some((bid, len)) => {
+                debug_assert!(bid < self.inner.count && len <= self.inner.buf_len);
+                self.inner.outstanding.set(self.inner.outstanding.get() + 1);
+                Ok(BorrowedBuf {
+                    group: self.clone(),
+                    bid: Some(bid),
+                    start: 0,
+                    len,
+                })
+            }
+            None => Ok(BorrowedBuf {
+                group: self.clone(),
+                bid: None,
+                start: 0,
+                len: 0,
+            }),
+        }
+    }
+}
+
+/// Extract `(buffer id, length)` from a completion of a buffer select op.
+///
+/// An op that completes with no data may not consume a buffer, `None` is
+/// returned then.
+fn selected(meta: CompletionMeta, bgid: u16) -> io::Result<Option<(u16, usize)>> {
+    match meta.result {
+        Ok(n) => {
+            let len = n.into_inner() as usize;
+            match meta.flags.buffer_id() {
+                Some(bid) => Ok(Some((bid, len))),
+                None if len == 0 => Ok(None),
+                None => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "completion carries data but no buffer id",
+                )),
+            }
+        }
+        Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+            Err(io::Error::other(Exhausted { bgid }))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl GroupInner {
+    fn buf_ptr(&self, bid: u16) -> *mut u8 {
+        let storage = unsafe { self.storage.as_ref().unwrap_unchecked() };
+        unsafe { (storage.as_ptr() as *mut u8).add(bid as usize * self.buf_len) }
+    }
+
+    fn push_ring(&self, bid: u16) {
+        if let Provider::Ring { entries, tail, .. } = &self.provider {
+            let mask = self.count - 1;
+            unsafe {
+                let entry = &mut *entries.add((tail.get() & mask) as usize);
+                entry.set_addr(self.buf_ptr(bid) as u64);
+                entry.set_len(self.buf_len as u32);
+                entry.set_bid(bid);
+                tail.set(tail.get().wrapping_add(1));
+                // Publish the entry to the kernel.
+                let shared = &*(BufRingEntry::tail(*entries) as *const AtomicU16);
+                shared.store(tail.get(), Ordering::Release);
+            }
+        }
+    }
+
+    fn provide(self: &Rc<Self>, bid: u16, nbufs: u16) -> io::Result<()> {
+        let op = self.driver.submit_with(ProvideBuffers {
+            group: self.clone(),
+            bid,
+            nbufs,
+        });
+        let op = op.map_err(|(e, _)| e)?;
+        // Nobody waits for the result, the lifecycle is reclaimed on completion.
+        drop(op);
+        Ok(())
+    }
+
+    fn recycle(self: &Rc<Self>, bid: u16) {
+        self.outstanding.set(self.outstanding.get() - 1);
+        match self.provider {
+            Provider::Ring { .. } => self.push_ring(bid),
+            Provider::Legacy => {
+                if let Err(e) = self.provide(bid, 1) {
+                    log::warn!("failed to provide buffer {bid} to group {}: {e}", self.bgid);
+                }
+            }
+        }
+        self.recycled.set(self.recycled.get() + 1);
+        for waker in self.waiters.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for GroupInner {
+    fn drop(&mut self) {
+        match self.provider {
+            Provider::Ring {
+                entries, layout, ..
+            } => {
+                let _ = self.driver.unregister_buf_ring(self.bgid);
+                unsafe { alloc::dealloc(entries as *mut u8, layout) };
+            }
+            Provider::Legacy => {
+                // The kernel may still hold buffers of the group, keep the
+                // storage alive until they are removed.
+                let remove = RemoveBuffers {
+                    bgid: self.bgid,
+                    nbufs: self.count,
+                    storage: self.storage.take(),
+                };
+                match self.driver.submit_with(remove) {
+                    Ok(op) => drop(op),
+                    Err((e, remove)) => {
+                        log::warn!("failed to remove buffer group {}: {e}", self.bgid);
+                        std::mem::forget(remove.storage);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A buffer selected by the kernel. It goes back to its group on drop.
+pub struct BorrowedBuf {
+    group: BufGroup,
+    // None when the op completed without consuming a buffer.
+    bid: Option<u16>,
+    // The bytes handed out, `len` of them from `start` on.
+    start: usize,
+    len: usize,
+}
+
+impl BorrowedBuf {
+    /// Id of the buffer within its group.
+    pub fn bid(&self) -> Option<u16> {
+        self.bid
+    }
+
+    /// The group this buffer belongs to.
+    pub fn group(&self) -> &BufGroup {
+        &self.group
+    }
+
+    /// Hand out only the bytes in `range` of the ones handed out now, like
+    /// the payload after a header the kernel wrote.
+    pub(crate) fn narrow(&mut self, range: std::ops::Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len);
+        self.start += range.start;
+        self.len = range.len();
+    }
+}
+
+impl Deref for BorrowedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.bid {
+            Some(bid) => unsafe {
+                std::slice::from_raw_parts(self.group.inner.buf_ptr(bid).add(self.start), self.len)
+            },
+            None => &[],
+        }
+    }
+}
+
+impl AsRef<[u8]> for BorrowedBuf {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for BorrowedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BorrowedBuf")
+            .field("bgid", &self.group.inner.bgid)
+            .field("bid", &self.bid)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl Drop for BorrowedBuf {
+    fn drop(&mut self) {
+        if let Some(bid) = self.bid {
+            self.group.inner.recycle(bid);
+        }
+    }
+}
+
+struct ProvideBuffers {
+    group: Rc<GroupInner>,
+    bid: u16,
+    nbufs: u16,
+}
+
+impl Mappable for ProvideBuffers {
+    const SKIP_CANCEL: bool = true;
+
+    fn uring_op(&mut self) -> Entry {
+        let group = &self.group;
+        opcode::ProvideBuffers::new(
+            group.buf_ptr(self.bid),
+            group.buf_len as i32,
+            self.nbufs,
+            group.bgid,
+            self.bid,
+        )
+        .build()
+    }
+}
+
+struct RemoveBuffers {
+    bgid: u16,
+    nbufs: u16,
+    #[allow(unused)]
+    storage: Option<Box<[u8]>>,
+}
+
+impl Mappable for RemoveBuffers {
+    const SKIP_CANCEL: bool = true;
+
+    fn uring_op(&mut self) -> Entry {
+        opcode::RemoveBuffers::new(self.nbufs, self.bgid).build()
+    }
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::{
+        op::{CqeFlags, MaybeFd, Op},
+        IoUringDriver,
+    };
+    use crate::runtime::builder::RuntimeBuilder;
+    use crate::utils::testing::supported;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::os::fd::AsRawFd;
+
+    fn meta(result: io::Result<u32>, flags: u32) -> CompletionMeta {
+        CompletionMeta {
+            result: MaybeFd::new_non_fd_result(result),
+            flags: CqeFlags::from_bits(flags),
+            big_cqe: [0; 2],
+        }
+    }
+
+    #[test]
+    fn selected_buffer_id() {
+        // IORING_CQE_F_BUFFER with the buffer id in the upper 16 bits.
+        let flags = (7 << 16) | 1;
+        assert_eq!(selected(meta(Ok(42), flags), 1).unwrap(), Some((7, 42)));
+    }
+
+    #[test]
+    fn selected_nothing_on_eof() {
+        assert_eq!(selected(meta(Ok(0), 0), 1).unwrap(), None);
+        assert!(selected(meta(Ok(3), 0), 1).is_err());
+    }
+
+    #[test]
+    fn selected_exhausted() {
+        let err =
+            selected(meta(Err(io::Error::from_raw_os_error(libc::ENOBUFS)), 0), 3).unwrap_err();
+        assert_eq!(Exhausted::from_io_error(&err), Some(&Exhausted { bgid: 3 }));
+
+        let err = selected(meta(Err(io::Error::from_raw_os_error(libc::EBADF)), 0), 3).unwrap_err();
+        assert_eq!(Exhausted::from_io_error(&err), None);
+        assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+    }
+
+    #[test]
+    fn grouped_recv_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let fd = server.as_raw_fd();
+
+        // The way receives take their buffer from the group.
+        async fn recv(fd: i32, group: &BufGroup) -> io::Result<BorrowedBuf> {
+            group.take(Op::recv_from_group(fd, group).unwrap().await.meta)
+        }
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let group = BufGroup::new(7, 2, 64).unwrap();
+            assert_eq!(group.outstanding(), 0);
+
+            client.write_all(b"hello").unwrap();
+            let first = recv(fd, &group).await.unwrap();
+            assert_eq!(&*first, b"hello");
+            assert_eq!(group.outstanding(), 1);
+
+            client.write_all(b"world").unwrap();
+            let second = recv(fd, &group).await.unwrap();
+            assert_eq!(&*second, b"world");
+            assert_eq!(group.outstanding(), 2);
+
+            // Every buffer is held now.
+            client.write_all(b"again").unwrap();
+            let err = recv(fd, &group).await.unwrap_err();
+            assert_eq!(Exhausted::from_io_error(&err), Some(&Exhausted { bgid: 7 }));
+
+            drop(first);
+            assert_eq!(group.outstanding(), 1);
+            let third = recv(fd, &group).await.unwrap();
+            assert_eq!(&*third, b"again");
+
+            drop((second, third));
+            drop(client);
+            let eof = recv(fd, &group).await.unwrap();
+            assert!(eof.is_empty());
+        });
+    }
+
+    #[test]
+    fn ring_read_waits_for_recycle() {
+        use crate::fs::File;
+        use std::cell::Cell;
+
+        let path = std::env::temp_dir().join(format!("loop-buf-ring-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let err = BufRing::new(5, 3, 4).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            let Some(ring) = supported(BufRing::new(5, 1, 4)) else {
+                return;
+            };
+            let file = Rc::new(File::open(&path).await.unwrap());
+            let first = file.read_with_ring(&ring).await.unwrap();
+            assert_eq!(&*first, b"0123");
+            assert_eq!(ring.outstanding(), 1);
+
+            // The ring is empty, the read waits instead of failing.
+            let done = Rc::new(Cell::new(false));
+            let waiter = crate::runtime::spawn({
+                let (file, ring, done) = (file.clone(), ring.clone(), done.clone());
+                async move {
+                    let buf = file.read_at_with_ring(&ring, 6).await;
+                    done.set(true);
+                    buf.map(|buf| buf.to_vec())
+                }
+            });
+            file.metadata().await.unwrap();
+            assert!(!done.get());
+            drop(first);
+            assert_eq!(waiter.await.unwrap(), b"6789");
+            assert_eq!(ring.outstanding(), 0);
+
+            // Continues at the file position, empty at the end.
+            assert_eq!(&*file.read_with_ring(&ring).await.unwrap(), b"4567");
+            assert!(file.read_at_with_ring(&ring, 10).await.unwrap().is_empty());
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn grouped_read_legacy() {
+        let path = std::env::temp_dir().join(format!("loop-buf-group-{}", std::process::id()));
+        std::fs::write(&path, b"0123456789").unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let fd = file.as_raw_fd();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let group = BufGroup::with_mode(3, 1, 4, Mode::Legacy).unwrap();
+            let buf = Op::read_from_group(fd, 2, &group)
+                .unwrap()
+                .result()
+                .await
+                .unwrap();
+            assert_eq!(&*buf, b"2345");
+
+            let err = Op::read_from_group(fd, 0, &group)
+                .unwrap()
+                .result()
+                .await
+                .unwrap_err();
+            assert!(Exhausted::from_io_error(&err).is_some());
+
+            // Re-providing is queued ahead of the next read.
+            drop(buf);
+            let buf = Op::read_from_group(fd, 6, &group)
+                .unwrap()
+                .result()
+                .await
+                .unwrap();
+            assert_eq!(&*buf, b"6789");
+        });
+    }
+}
@@ -1,8 +1,11 @@
-use std::io;
-use io_uring::{opcode, types};
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
 use io_uring::squeue::Entry;
+use io_uring::{opcode, types};
 use libc::c_int;
-use crate::driver::op::{Op, Mappable};
+use std::io;
 
 pub(crate) struct Close {
     fd: c_int,
@@ -14,11 +17,94 @@ impl Op<Close> {
     }
 }
 
+impl Close {
+    /// Close `fd` without waiting for the result, a failure is only logged.
+    ///
+    /// Fails if there is no driver on the current thread.
+    pub(crate) fn detached(fd: c_int) -> io::Result<()> {
+        if !driver::CURRENT.is_set() {
+            return Err(io::ErrorKind::Other.into());
+        }
+        let entry = opcode::Close::new(types::Fd(fd)).build();
+        // # Safety
+        // Close references no memory.
+        driver::CURRENT.with(|inner| unsafe { inner.submit_detached(entry) })
+    }
+}
+
 impl Mappable for Close {
     const SKIP_CANCEL: bool = true;
 
     fn uring_op(&mut self) -> Entry {
         opcode::Close::new(types::Fd(self.fd)).build()
     }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::builder::RuntimeBuilder;
+    use std::os::fd::IntoRawFd;
+
+    const CYCLES: u64 = 10_000;
+
+    async fn open_null() -> c_int {
+        let op = Op::openat(
+            libc::AT_FDCWD,
+            "/dev/null",
+            libc::O_RDONLY | libc::O_CLOEXEC,
+            0,
+        );
+        let fd = op.unwrap().await.meta.result.unwrap().into_owned().unwrap();
+        // Closed by the op under test.
+        fd.into_raw_fd()
+    }
+
+    // Returns the number of CQEs reaped during the churn.
+    fn churn(detached: bool) -> u64 {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let before = rt.driver.metrics().cqes;
+        rt.block_on(async {
+            for _ in 0..CYCLES {
+                let fd = open_null().await;
+                match detached {
+                    true => Close::detached(fd).unwrap(),
+                    false => Op::close(fd).unwrap().await.meta.result.map(drop).unwrap(),
+                }
+            }
+            // Reap whatever the last close produced.
+            let fd = open_null().await;
+            unsafe { libc::close(fd) };
+        });
+        rt.driver.metrics().cqes - before
+    }
+
+    #[test]
+    fn detached_close_skips_success_cqe() {
+        let rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let skip_success = rt.driver.inner.skip_success();
+        drop(rt);
 
-}
\ No newline at end of file
+        let tracked = churn(false);
+        let detached = churn(true);
+        assert_eq!(tracked, 2 * CYCLES + 1);
+        if skip_success {
+            assert_eq!(detached, CYCLES + 1);
+        } else {
+            assert_eq!(detached, tracked);
+        }
+    }
+
+    #[test]
+    fn detached_close_failure_is_reported() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            Close::detached(-1).unwrap();
+            let fd = open_null().await;
+            Close::detached(fd).unwrap();
+            let fd = open_null().await;
+            unsafe { libc::close(fd) };
+        });
+        assert_eq!(rt.driver.metrics().detached_failures, 1);
+    }
+}
@@ -0,0 +1,161 @@
+use crate::buf::{BufResult, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+
+/// Read at `offset` from a direct descriptor.
+pub(crate) struct ReadDirect<T> {
+    slot: u32,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Write at `offset` to a direct descriptor.
+pub(crate) struct WriteDirect<T> {
+    slot: u32,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Vectored read at `offset` from a direct descriptor.
+pub(crate) struct ReadvDirect<T> {
+    slot: u32,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Vectored write at `offset` to a direct descriptor.
+pub(crate) struct WritevDirect<T> {
+    slot: u32,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Close a direct descriptor, leaving its slot empty.
+pub(crate) struct CloseDirect {
+    slot: u32,
+}
+
+impl<T: IoBufMut> Op<ReadDirect<T>> {
+    pub(crate) fn read_direct(
+        slot: u32,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<ReadDirect<T>>, (io::Error, ReadDirect<T>)> {
+        Op::submit_or_return(ReadDirect { slot, offset, buf })
+    }
+
+    /// Wait for the read and mark what the kernel filled as initialized.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes.
+            unsafe { buf.set_init(n) };
+        }
+        (n, buf)
+    }
+}
+
+impl<T: IoBuf> Op<WriteDirect<T>> {
+    pub(crate) fn write_direct(
+        slot: u32,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<WriteDirect<T>>, (io::Error, WriteDirect<T>)> {
+        Op::submit_or_return(WriteDirect { slot, offset, buf })
+    }
+}
+
+impl<T: IoVecBufMut> Op<ReadvDirect<T>> {
+    pub(crate) fn readv_direct(
+        slot: u32,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<ReadvDirect<T>>, (io::Error, ReadvDirect<T>)> {
+        Op::submit_or_return(ReadvDirect { slot, offset, buf })
+    }
+
+    /// Wait for the read and mark what the kernel filled as initialized.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes.
+            unsafe { buf.set_init(n) };
+        }
+        (n, buf)
+    }
+}
+
+impl<T: IoVecBuf> Op<WritevDirect<T>> {
+    pub(crate) fn writev_direct(
+        slot: u32,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<WritevDirect<T>>, (io::Error, WritevDirect<T>)> {
+        Op::submit_or_return(WritevDirect { slot, offset, buf })
+    }
+
+    /// Wait for the write, returning the number of bytes written.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl Op<CloseDirect> {
+    pub(crate) fn close_direct(slot: u32) -> io::Result<Op<CloseDirect>> {
+        Op::submit_with(CloseDirect { slot })
+    }
+}
+
+impl<T: IoBufMut> Mappable for ReadDirect<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let len = self.buf.bytes_total() as u32;
+        opcode::Read::new(types::Fixed(self.slot), self.buf.write_ptr(), len)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl<T: IoBuf> Mappable for WriteDirect<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Write::new(
+            types::Fixed(self.slot),
+            self.buf.read_ptr(),
+            self.buf.bytes_init() as u32,
+        )
+        .offset(self.offset)
+        .build()
+    }
+}
+
+impl<T: IoVecBufMut> Mappable for ReadvDirect<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (iovecs, count) = self.buf.write_iovecs();
+        opcode::Readv::new(types::Fixed(self.slot), iovecs.cast_const(), count as u32)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl<T: IoVecBuf> Mappable for WritevDirect<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (iovecs, count) = self.buf.read_iovecs();
+        opcode::Writev::new(types::Fixed(self.slot), iovecs, count as u32)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl Mappable for CloseDirect {
+    const SKIP_CANCEL: bool = true;
+
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Close::new(types::Fixed(self.slot)).build()
+    }
+}
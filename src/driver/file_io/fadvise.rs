@@ -0,0 +1,50 @@
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Declare an access pattern for `len` bytes at `offset`, as
+/// `posix_fadvise(2)` with one of the `POSIX_FADV_*` values.
+pub(crate) struct Fadvise {
+    fd: RawFd,
+    offset: u64,
+    len: libc::off_t,
+    advice: i32,
+}
+
+impl Op<Fadvise> {
+    pub(crate) fn fadvise(
+        fd: RawFd,
+        offset: u64,
+        len: libc::off_t,
+        advice: i32,
+    ) -> io::Result<Op<Fadvise>> {
+        Op::submit_with(Fadvise {
+            fd,
+            offset,
+            len,
+            advice,
+        })
+    }
+}
+
+impl Fadvise {
+    /// Whether the ring of the current thread can advise, otherwise it
+    /// takes the syscall. The probe runs once when the runtime is built.
+    pub(crate) fn is_supported() -> bool {
+        driver::CURRENT
+            .try_with(|inner| inner.is_supported(opcode::Fadvise::CODE))
+            .unwrap_or(false)
+    }
+}
+
+impl Mappable for Fadvise {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Fadvise::new(types::Fd(self.fd), self.len, self.advice)
+            .offset(self.offset)
+            .build()
+    }
+}
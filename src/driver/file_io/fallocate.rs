@@ -0,0 +1,38 @@
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Allocate or deallocate `len` bytes at `offset`, as `fallocate(2)` with
+/// `mode`.
+pub(crate) struct Fallocate {
+    fd: RawFd,
+    offset: u64,
+    len: u64,
+    mode: i32,
+}
+
+impl Op<Fallocate> {
+    pub(crate) fn fallocate(
+        fd: RawFd,
+        offset: u64,
+        len: u64,
+        mode: i32,
+    ) -> io::Result<Op<Fallocate>> {
+        Op::submit_with(Fallocate {
+            fd,
+            offset,
+            len,
+            mode,
+        })
+    }
+}
+
+impl Mappable for Fallocate {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Fallocate::new(types::Fd(self.fd), self.len)
+            .offset(self.offset)
+            .mode(self.mode)
+            .build()
+    }
+}
@@ -0,0 +1,26 @@
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Flush a descriptor to storage, only its data when `data_only` is set.
+pub(crate) struct Fsync {
+    fd: RawFd,
+    data_only: bool,
+}
+
+impl Op<Fsync> {
+    pub(crate) fn fsync(fd: RawFd, data_only: bool) -> io::Result<Op<Fsync>> {
+        Op::submit_with(Fsync { fd, data_only })
+    }
+}
+
+impl Mappable for Fsync {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let flags = match self.data_only {
+            true => types::FsyncFlags::DATASYNC,
+            false => types::FsyncFlags::empty(),
+        };
+        opcode::Fsync::new(types::Fd(self.fd)).flags(flags).build()
+    }
+}
@@ -0,0 +1,73 @@
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
+use io_uring::{opcode, squeue};
+use std::io;
+use std::os::fd::RawFd;
+
+// Available since 6.9, io-uring 0.6 has no builder for it.
+const IORING_OP_FTRUNCATE: u8 = 55;
+
+// The leading fields of an SQE, the layout is fixed by the kernel ABI.
+#[repr(C)]
+struct SqeHead {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+}
+
+/// Truncate or extend a descriptor to `len` bytes.
+pub(crate) struct Ftruncate {
+    fd: RawFd,
+    len: u64,
+}
+
+impl Op<Ftruncate> {
+    pub(crate) fn ftruncate(fd: RawFd, len: u64) -> io::Result<Op<Ftruncate>> {
+        Op::submit_with(Ftruncate { fd, len })
+    }
+}
+
+impl Ftruncate {
+    /// Whether the ring of the current thread can truncate, otherwise it
+    /// takes the syscall. The probe runs once when the runtime is built.
+    pub(crate) fn is_supported() -> bool {
+        driver::CURRENT
+            .try_with(|inner| inner.is_supported(IORING_OP_FTRUNCATE))
+            .unwrap_or(false)
+    }
+}
+
+impl Mappable for Ftruncate {
+    fn uring_op(&mut self) -> squeue::Entry {
+        // A nop is zeroed but for its opcode, the kernel reads the
+        // descriptor from `fd` and the length from `off`.
+        let mut entry = opcode::Nop::new().build();
+        // # Safety
+        // `Entry` is `repr(C)` around the kernel SQE.
+        let head = unsafe { &mut *(&mut entry as *mut squeue::Entry).cast::<SqeHead>() };
+        head.opcode = IORING_OP_FTRUNCATE;
+        head.fd = self.fd;
+        head.off = self.len;
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqe_layout() {
+        let mut entry = Ftruncate { fd: 7, len: 4096 }.uring_op();
+        let bytes = unsafe { &*(&mut entry as *mut squeue::Entry).cast::<[u8; 64]>() };
+        assert_eq!(bytes[0], IORING_OP_FTRUNCATE);
+        assert_eq!(bytes[4..8], 7i32.to_ne_bytes());
+        assert_eq!(bytes[8..16], 4096u64.to_ne_bytes());
+        // Flags, buffer address, length and user data stay zero.
+        assert!(bytes[1..4].iter().chain(&bytes[16..]).all(|b| *b == 0));
+    }
+}
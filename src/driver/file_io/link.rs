@@ -0,0 +1,60 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
+use std::ffi::CString;
+use std::io;
+use std::path::Path;
+
+/// Create a symlink at `link` pointing to `target`.
+pub(crate) struct SymlinkAt {
+    target: CString,
+    link: CString,
+}
+
+/// Create a hard link at `link` to the file at `original`.
+pub(crate) struct LinkAt {
+    original: CString,
+    link: CString,
+}
+
+impl Op<SymlinkAt> {
+    pub(crate) fn symlink(target: &Path, link: &Path) -> io::Result<Op<SymlinkAt>> {
+        Op::submit_with(SymlinkAt {
+            target: cstr(target)?,
+            link: cstr(link)?,
+        })
+    }
+}
+
+impl Op<LinkAt> {
+    pub(crate) fn link(original: &Path, link: &Path) -> io::Result<Op<LinkAt>> {
+        Op::submit_with(LinkAt {
+            original: cstr(original)?,
+            link: cstr(link)?,
+        })
+    }
+}
+
+impl Mappable for SymlinkAt {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::SymlinkAt::new(
+            types::Fd(libc::AT_FDCWD),
+            self.target.as_ptr(),
+            self.link.as_ptr(),
+        )
+        .build()
+    }
+}
+
+impl Mappable for LinkAt {
+    fn uring_op(&mut self) -> squeue::Entry {
+        // Like link(2), a symlink as `original` is linked itself.
+        opcode::LinkAt::new(
+            types::Fd(libc::AT_FDCWD),
+            self.original.as_ptr(),
+            types::Fd(libc::AT_FDCWD),
+            self.link.as_ptr(),
+        )
+        .build()
+    }
+}
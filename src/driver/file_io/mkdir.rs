@@ -0,0 +1,29 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
+use std::ffi::CString;
+use std::io;
+use std::path::Path;
+
+/// Create a directory, `mode` is masked by the umask.
+pub(crate) struct MkDirAt {
+    path: CString,
+    mode: libc::mode_t,
+}
+
+impl Op<MkDirAt> {
+    pub(crate) fn mkdir(path: &Path, mode: libc::mode_t) -> io::Result<Op<MkDirAt>> {
+        Op::submit_with(MkDirAt {
+            path: cstr(path)?,
+            mode,
+        })
+    }
+}
+
+impl Mappable for MkDirAt {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::MkDirAt::new(types::Fd(libc::AT_FDCWD), self.path.as_ptr())
+            .mode(self.mode)
+            .build()
+    }
+}
@@ -1,2 +1,21 @@
+mod close;
+mod direct;
+mod fadvise;
+mod fallocate;
+mod fsync;
+mod ftruncate;
+mod link;
+mod mkdir;
 mod openat;
-mod close;
\ No newline at end of file
+mod read;
+mod rename;
+mod splice;
+mod statx;
+mod unlink;
+mod write;
+
+pub(crate) use close::Close;
+pub(crate) use fadvise::Fadvise;
+pub(crate) use ftruncate::Ftruncate;
+pub(crate) use openat::OpenAt;
+pub(crate) use statx::STATX_DIOALIGN;
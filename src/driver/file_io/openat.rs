@@ -1,15 +1,20 @@
+use crate::driver;
+use crate::driver::fixed_files::FixedSlot;
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
 use std::ffi::CString;
 use std::io;
 use std::path::Path;
-use io_uring::{opcode, types};
-use crate::driver::op::{Op, Mappable};
-use crate::driver::util::cstr;
 
 pub(crate) struct OpenAt {
     pub(crate) fd: i32,
     pub(crate) path: CString,
     pub(crate) flags: i32,
     pub(crate) mode: libc::mode_t,
+    // Set for openat2 with `RESOLVE_*` flags, boxed so the kernel reads it
+    // at a stable address while the op moves.
+    how: Option<Box<types::OpenHow>>,
 }
 
 impl Op<OpenAt> {
@@ -19,25 +24,105 @@ impl Op<OpenAt> {
         flags: i32,
         mode: libc::mode_t,
     ) -> io::Result<Op<OpenAt>> {
-        let path = cstr(path.as_ref())?;
-        let open_at = OpenAt {
-            fd: dir_fd,
-            path,
+        Op::submit_with(OpenAt::new(dir_fd, path.as_ref(), flags, mode, 0)?)
+    }
+
+    /// Open with openat2(5.6+), `resolve` takes the `RESOLVE_*` flags.
+    pub(crate) fn openat2<P: AsRef<Path>>(
+        dir_fd: i32,
+        path: P,
+        flags: i32,
+        mode: libc::mode_t,
+        resolve: u64,
+    ) -> io::Result<Op<OpenAt>> {
+        Op::submit_with(OpenAt::new(dir_fd, path.as_ref(), flags, mode, resolve)?)
+    }
+}
+
+impl OpenAt {
+    fn new(
+        fd: i32,
+        path: &Path,
+        flags: i32,
+        mode: libc::mode_t,
+        resolve: u64,
+    ) -> io::Result<OpenAt> {
+        let how = (resolve != 0).then(|| {
+            // openat2 rejects a mode when it would not create the file.
+            let mode = match flags & (libc::O_CREAT | libc::O_TMPFILE) {
+                0 => 0,
+                _ => u64::from(mode),
+            };
+            let how = types::OpenHow::new()
+                .flags(flags as u32 as u64)
+                .mode(mode)
+                .resolve(resolve);
+            Box::new(how)
+        });
+        Ok(OpenAt {
+            fd,
+            path: cstr(path)?,
             flags,
             mode,
-        };
+            how,
+        })
+    }
+
+    /// Whether the ring of the current thread has openat2, needed for
+    /// `RESOLVE_*` flags. The probe runs once when the runtime is built.
+    pub(crate) fn is_openat2_supported() -> bool {
+        driver::CURRENT
+            .try_with(|inner| inner.is_supported(opcode::OpenAt2::CODE))
+            .unwrap_or(false)
+    }
 
-        Op::submit_with(open_at)
+    fn entry(&self, slot: Option<types::DestinationSlot>) -> squeue::Entry {
+        let (fd, path) = (types::Fd(self.fd), self.path.as_c_str().as_ptr());
+        match &self.how {
+            Some(how) => opcode::OpenAt2::new(fd, path, &**how)
+                .file_index(slot)
+                .build(),
+            None => opcode::OpenAt::new(fd, path)
+                .flags(self.flags)
+                .mode(self.mode)
+                .file_index(slot)
+                .build(),
+        }
     }
 }
 
 impl Mappable for OpenAt {
     const RET_IS_FD: bool = true;
-    fn uring_op(&mut self) -> io_uring::squeue::Entry {
-        opcode::OpenAt::new(types::Fd(self.fd), self.path.as_c_str().as_ptr())
-            .flags(self.flags)
-            .mode(self.mode)
-            .build()
+    fn uring_op(&mut self) -> squeue::Entry {
+        self.entry(None)
+    }
+}
+
+/// OpenAt completing into a slot of the registered file table.
+pub(crate) struct OpenDirect {
+    pub(crate) open: OpenAt,
+    // Held by the op so the slot is only reused after the kernel is done.
+    pub(crate) slot: FixedSlot,
+}
+
+impl Op<OpenDirect> {
+    pub(crate) fn openat_direct<P: AsRef<Path>>(
+        dir_fd: i32,
+        path: P,
+        flags: i32,
+        mode: libc::mode_t,
+        resolve: u64,
+        slot: FixedSlot,
+    ) -> io::Result<Op<OpenDirect>> {
+        let open = OpenAt::new(dir_fd, path.as_ref(), flags, mode, resolve)?;
+        Op::submit_with(OpenDirect { open, slot })
     }
 }
 
+impl Mappable for OpenDirect {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let slot = types::DestinationSlot::try_from_slot_target(self.slot.index())
+            .expect("file table slots are small");
+        self.open.entry(Some(slot))
+    }
+}
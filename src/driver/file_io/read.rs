@@ -0,0 +1,162 @@
+use crate::buf::{BufResult, FixedBuf, IoBufMut, IoVecBufMut};
+use crate::driver::buf_group::{BorrowedBuf, BufGroup};
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Read at `offset` from a regular descriptor.
+pub(crate) struct ReadAt<T> {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Vectored read at `offset` from a regular descriptor.
+pub(crate) struct Readv<T> {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Read at `offset` into a registered buffer.
+pub(crate) struct ReadFixed {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: FixedBuf,
+}
+
+/// Read at `offset` into a buffer of a provided buffer group.
+pub(crate) struct ReadFromGroup {
+    fd: RawFd,
+    offset: u64,
+    group: BufGroup,
+}
+
+impl<T: IoBufMut> Op<ReadAt<T>> {
+    pub(crate) fn read_at(
+        fd: RawFd,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<ReadAt<T>>, (io::Error, ReadAt<T>)> {
+        Op::submit_or_return(ReadAt { fd, offset, buf })
+    }
+
+    /// Wait for the read and mark what the kernel filled as initialized.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes.
+            unsafe { buf.set_init(n) };
+        }
+        (n, buf)
+    }
+}
+
+impl<T: IoVecBufMut> Op<Readv<T>> {
+    pub(crate) fn readv(
+        fd: RawFd,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<Readv<T>>, (io::Error, Readv<T>)> {
+        Op::submit_or_return(Readv { fd, offset, buf })
+    }
+
+    /// Wait for the read and mark what the kernel filled as initialized.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes.
+            unsafe { buf.set_init(n) };
+        }
+        (n, buf)
+    }
+}
+
+impl Op<ReadFixed> {
+    pub(crate) fn read_fixed(
+        fd: RawFd,
+        offset: u64,
+        buf: FixedBuf,
+    ) -> Result<Op<ReadFixed>, (io::Error, ReadFixed)> {
+        Op::submit_or_return(ReadFixed { fd, offset, buf })
+    }
+
+    /// Wait for the read and mark what the kernel filled as initialized.
+    pub(crate) async fn result(self) -> BufResult<usize, FixedBuf> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes.
+            unsafe { buf.set_init(n) };
+        }
+        (n, buf)
+    }
+}
+
+impl Op<ReadFromGroup> {
+    /// Read at `offset` into a buffer the kernel selects from `group`.
+    pub(crate) fn read_from_group(
+        fd: RawFd,
+        offset: u64,
+        group: &BufGroup,
+    ) -> io::Result<Op<ReadFromGroup>> {
+        Op::submit_with(ReadFromGroup {
+            fd,
+            offset,
+            group: group.clone(),
+        })
+    }
+
+    pub(crate) async fn result(self) -> io::Result<BorrowedBuf> {
+        let complete = self.await;
+        complete.data.group.take(complete.meta)
+    }
+}
+
+impl<T: IoBufMut> Mappable for ReadAt<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let len = self.buf.bytes_total() as u32;
+        opcode::Read::new(types::Fd(self.fd), self.buf.write_ptr(), len)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl<T: IoVecBufMut> Mappable for Readv<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (iovecs, count) = self.buf.write_iovecs();
+        opcode::Readv::new(types::Fd(self.fd), iovecs.cast_const(), count as u32)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl Mappable for ReadFromGroup {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Read::new(
+            types::Fd(self.fd),
+            std::ptr::null_mut(),
+            self.group.buf_len() as u32,
+        )
+        .offset(self.offset)
+        .buf_group(self.group.bgid())
+        .build()
+        .flags(squeue::Flags::BUFFER_SELECT)
+    }
+}
+
+impl Mappable for ReadFixed {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let len = self.buf.bytes_total() as u32;
+        let index = self.buf.buf_index();
+        opcode::ReadFixed::new(types::Fd(self.fd), self.buf.write_ptr(), len, index)
+            .offset(self.offset)
+            .build()
+    }
+}
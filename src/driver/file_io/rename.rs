@@ -0,0 +1,36 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
+use std::ffi::CString;
+use std::io;
+use std::path::Path;
+
+/// Rename `from` to `to`, with `renameat2` flags.
+pub(crate) struct RenameAt {
+    from: CString,
+    to: CString,
+    flags: u32,
+}
+
+impl Op<RenameAt> {
+    pub(crate) fn rename(from: &Path, to: &Path, flags: u32) -> io::Result<Op<RenameAt>> {
+        Op::submit_with(RenameAt {
+            from: cstr(from)?,
+            to: cstr(to)?,
+            flags,
+        })
+    }
+}
+
+impl Mappable for RenameAt {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::RenameAt::new(
+            types::Fd(libc::AT_FDCWD),
+            self.from.as_ptr(),
+            types::Fd(libc::AT_FDCWD),
+            self.to.as_ptr(),
+        )
+        .flags(self.flags)
+        .build()
+    }
+}
@@ -0,0 +1,48 @@
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Move up to `len` bytes from `fd_in` to `fd_out` without copying them, one
+/// of them must be a pipe. An offset of -1 uses and advances the position of
+/// the file, pipes take no other.
+pub(crate) struct Splice {
+    fd_in: RawFd,
+    off_in: i64,
+    fd_out: RawFd,
+    off_out: i64,
+    len: u32,
+    flags: u32,
+}
+
+impl Op<Splice> {
+    pub(crate) fn splice(
+        (fd_in, off_in): (RawFd, i64),
+        (fd_out, off_out): (RawFd, i64),
+        len: u32,
+        flags: u32,
+    ) -> io::Result<Op<Splice>> {
+        Op::submit_with(Splice {
+            fd_in,
+            off_in,
+            fd_out,
+            off_out,
+            len,
+            flags,
+        })
+    }
+}
+
+impl Mappable for Splice {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Splice::new(
+            types::Fd(self.fd_in),
+            self.off_in,
+            types::Fd(self.fd_out),
+            self.off_out,
+            self.len,
+        )
+        .flags(self.flags)
+        .build()
+    }
+}
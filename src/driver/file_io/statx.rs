@@ -0,0 +1,66 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::RawFd;
+use std::path::Path;
+
+// Direct io alignments(6.1+), missing from libc.
+pub(crate) const STATX_DIOALIGN: u32 = 0x2000;
+
+/// Query the status of a path relative to `fd`, or of `fd` itself.
+pub(crate) struct Statx {
+    fd: RawFd,
+    path: CString,
+    flags: i32,
+    // Boxed so the kernel writes to a stable address while the op moves.
+    statx: Box<MaybeUninit<libc::statx>>,
+}
+
+impl Op<Statx> {
+    /// Stat `path`, not following a final symlink when `follow` is unset.
+    pub(crate) fn statx(path: &Path, follow: bool) -> io::Result<Op<Statx>> {
+        let flags = match follow {
+            true => 0,
+            false => libc::AT_SYMLINK_NOFOLLOW,
+        };
+        Op::submit_with(Statx {
+            fd: libc::AT_FDCWD,
+            path: cstr(path)?,
+            flags,
+            statx: Box::new(MaybeUninit::uninit()),
+        })
+    }
+
+    /// Stat the file `fd` refers to.
+    pub(crate) fn statx_fd(fd: RawFd) -> io::Result<Op<Statx>> {
+        Op::submit_with(Statx {
+            fd,
+            path: CString::default(),
+            flags: libc::AT_EMPTY_PATH,
+            statx: Box::new(MaybeUninit::uninit()),
+        })
+    }
+
+    pub(crate) async fn result(self) -> io::Result<libc::statx> {
+        let completion = self.await;
+        completion.meta.result?;
+        // The kernel filled the buffer on success.
+        Ok(unsafe { completion.data.statx.assume_init_read() })
+    }
+}
+
+impl Mappable for Statx {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Statx::new(
+            types::Fd(self.fd),
+            self.path.as_ptr(),
+            self.statx.as_mut_ptr().cast::<types::statx>(),
+        )
+        .flags(self.flags)
+        .mask(libc::STATX_BASIC_STATS | STATX_DIOALIGN)
+        .build()
+    }
+}
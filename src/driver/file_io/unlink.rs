@@ -0,0 +1,33 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::cstr;
+use io_uring::{opcode, squeue, types};
+use std::ffi::CString;
+use std::io;
+use std::path::Path;
+
+/// Remove a path, a directory when `AT_REMOVEDIR` is set.
+pub(crate) struct UnlinkAt {
+    path: CString,
+    flags: i32,
+}
+
+impl Op<UnlinkAt> {
+    pub(crate) fn unlink(path: &Path, dir: bool) -> io::Result<Op<UnlinkAt>> {
+        let flags = match dir {
+            true => libc::AT_REMOVEDIR,
+            false => 0,
+        };
+        Op::submit_with(UnlinkAt {
+            path: cstr(path)?,
+            flags,
+        })
+    }
+}
+
+impl Mappable for UnlinkAt {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::UnlinkAt::new(types::Fd(libc::AT_FDCWD), self.path.as_ptr())
+            .flags(self.flags)
+            .build()
+    }
+}
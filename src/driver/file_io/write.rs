@@ -0,0 +1,111 @@
+use crate::buf::{BufResult, FixedBuf, IoBuf, IoVecBuf};
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Write at `offset` to a regular descriptor.
+pub(crate) struct WriteAt<T> {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Vectored write at `offset` to a regular descriptor.
+pub(crate) struct Writev<T> {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: T,
+}
+
+/// Write at `offset` from a registered buffer.
+pub(crate) struct WriteFixed {
+    fd: RawFd,
+    offset: u64,
+    pub(crate) buf: FixedBuf,
+}
+
+impl<T: IoBuf> Op<WriteAt<T>> {
+    pub(crate) fn write_at(
+        fd: RawFd,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<WriteAt<T>>, (io::Error, WriteAt<T>)> {
+        Op::submit_or_return(WriteAt { fd, offset, buf })
+    }
+
+    /// Wait for the write, returning the number of bytes written.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl<T: IoBuf> Mappable for WriteAt<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Write::new(
+            types::Fd(self.fd),
+            self.buf.read_ptr(),
+            self.buf.bytes_init() as u32,
+        )
+        .offset(self.offset)
+        .build()
+    }
+}
+
+impl<T: IoVecBuf> Op<Writev<T>> {
+    pub(crate) fn writev(
+        fd: RawFd,
+        offset: u64,
+        buf: T,
+    ) -> Result<Op<Writev<T>>, (io::Error, Writev<T>)> {
+        Op::submit_or_return(Writev { fd, offset, buf })
+    }
+
+    /// Wait for the write, returning the number of bytes written.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl<T: IoVecBuf> Mappable for Writev<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (iovecs, count) = self.buf.read_iovecs();
+        opcode::Writev::new(types::Fd(self.fd), iovecs, count as u32)
+            .offset(self.offset)
+            .build()
+    }
+}
+
+impl Op<WriteFixed> {
+    pub(crate) fn write_fixed(
+        fd: RawFd,
+        offset: u64,
+        buf: FixedBuf,
+    ) -> Result<Op<WriteFixed>, (io::Error, WriteFixed)> {
+        Op::submit_or_return(WriteFixed { fd, offset, buf })
+    }
+
+    /// Wait for the write, returning the number of bytes written.
+    pub(crate) async fn result(self) -> BufResult<usize, FixedBuf> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl Mappable for WriteFixed {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::WriteFixed::new(
+            types::Fd(self.fd),
+            self.buf.read_ptr(),
+            self.buf.bytes_init() as u32,
+            self.buf.buf_index(),
+        )
+        .offset(self.offset)
+        .build()
+    }
+}
@@ -0,0 +1,142 @@
+//! Registered buffer table.
+//!
+//! Buffers registered with the ring stay pinned, `ReadFixed` and `WriteFixed`
+//! refer to them by index and skip mapping the pages on every op.
+
+use std::{
+    io,
+    task::{Context, Poll, Waker},
+};
+
+use crate::buf::{AlignedBuf, IoBufMut};
+
+// The most buffers a ring takes, IORING_MAX_REG_BUFFERS.
+const MAX_BUFFERS: usize = 1 << 14;
+
+pub(crate) struct BufTable {
+    // Ascending by size, each with `count` buffers of consecutive indexes.
+    classes: Vec<SizeClass>,
+    count: usize,
+    waiters: Vec<Waker>,
+}
+
+struct SizeClass {
+    size: usize,
+    free: Vec<(u16, AlignedBuf)>,
+}
+
+impl BufTable {
+    /// Allocate `count` page aligned buffers of each of `sizes`, returned
+    /// with the iovecs to register.
+    pub(crate) fn new(sizes: &[usize], count: usize) -> io::Result<(BufTable, Vec<libc::iovec>)> {
+        let total = sizes.len().saturating_mul(count);
+        if total == 0 || total > MAX_BUFFERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("between 1 and {MAX_BUFFERS} buffers can be registered, not {total}"),
+            ));
+        }
+        let mut sizes = sizes.to_vec();
+        sizes.sort_unstable();
+        let mut iovecs = Vec::with_capacity(total);
+        let classes = sizes
+            .iter()
+            .enumerate()
+            .map(|(class, &size)| {
+                let free = (0..count)
+                    .map(|i| {
+                        let mut buf = AlignedBuf::new(size);
+                        iovecs.push(libc::iovec {
+                            iov_base: buf.write_ptr().cast(),
+                            iov_len: buf.capacity(),
+                        });
+                        ((class * count + i) as u16, buf)
+                    })
+                    // Hand out low indexes first.
+                    .rev()
+                    .collect::<Vec<_>>();
+                SizeClass { size, free }
+            })
+            .collect();
+        let table = BufTable {
+            classes,
+            count,
+            waiters: Vec::new(),
+        };
+        Ok((table, iovecs))
+    }
+
+    /// Take a free buffer of the smallest size that holds `len` bytes.
+    pub(crate) fn poll_take(
+        &mut self,
+        len: usize,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(u16, AlignedBuf)>> {
+        let mut fitting = self.classes.iter_mut().filter(|c| c.size >= len).peekable();
+        if fitting.peek().is_none() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no registered buffer holds {len} bytes"),
+            )));
+        }
+        if let Some(buf) = fitting.find_map(|class| class.free.pop()) {
+            return Poll::Ready(Ok(buf));
+        }
+        self.waiters.push(cx.waker().clone());
+        Poll::Pending
+    }
+
+    pub(crate) fn release(&mut self, index: u16, mut buf: AlignedBuf) {
+        buf.clear();
+        self.classes[index as usize / self.count]
+            .free
+            .push((index, buf));
+        // Waiters may want different sizes, let each of them look again.
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smallest_fitting_size_first() {
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let (mut table, iovecs) = BufTable::new(&[16384, 4096], 1).unwrap();
+        assert_eq!(iovecs.len(), 2);
+        assert_eq!(iovecs[0].iov_len, 4096);
+
+        let Poll::Ready(Ok((small, small_buf))) = table.poll_take(100, &mut cx) else {
+            panic!("a buffer is free");
+        };
+        assert_eq!((small, small_buf.capacity()), (0, 4096));
+        // The small one is taken, the large one fits too.
+        let Poll::Ready(Ok((large, mut large_buf))) = table.poll_take(100, &mut cx) else {
+            panic!("a buffer is free");
+        };
+        assert_eq!(large, 1);
+        assert!(table.poll_take(1, &mut cx).is_pending());
+        match table.poll_take(20000, &mut cx) {
+            Poll::Ready(Err(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            _ => panic!("nothing holds 20000 bytes"),
+        }
+
+        // Given back empty.
+        large_buf.extend_from_slice(b"stale");
+        table.release(large, large_buf);
+        let Poll::Ready(Ok((index, buf))) = table.poll_take(5000, &mut cx) else {
+            panic!("the large buffer is back");
+        };
+        assert_eq!((index, buf.len()), (1, 0));
+        table.release(index, buf);
+        table.release(small, small_buf);
+        assert!(table.poll_take(1, &mut cx).is_ready());
+
+        assert!(BufTable::new(&[4096], 0).is_err());
+        assert!(BufTable::new(&[4096; 2], MAX_BUFFERS).is_err());
+    }
+}
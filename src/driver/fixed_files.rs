@@ -0,0 +1,140 @@
+//! Registered file table.
+//!
+//! Slots of a sparse table registered with the ring can hold files opened
+//! straight into them (5.19+). Ops on such direct descriptors use
+//! `IOSQE_FIXED_FILE` and skip the fd table on every submission.
+
+use std::{
+    collections::VecDeque,
+    io,
+    task::{Context, Poll, Waker},
+};
+
+use io_uring::{opcode, types};
+
+use crate::driver;
+
+/// What opening a direct descriptor does when every slot is taken.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SlotPolicy {
+    /// Fail with `ENFILE`.
+    #[default]
+    Error,
+    /// Wait until a slot is released.
+    Wait,
+}
+
+pub(crate) struct FileTable {
+    free: Vec<u32>,
+    policy: SlotPolicy,
+    waiters: VecDeque<Waker>,
+}
+
+impl FileTable {
+    pub(crate) fn new(slots: u32, policy: SlotPolicy) -> Self {
+        FileTable {
+            // Hand out low slots first.
+            free: (0..slots).rev().collect(),
+            policy,
+            waiters: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn poll_alloc(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u32>> {
+        if let Some(slot) = self.free.pop() {
+            return Poll::Ready(Ok(slot));
+        }
+        match self.policy {
+            SlotPolicy::Error => Poll::Ready(Err(io::Error::from_raw_os_error(libc::ENFILE))),
+            SlotPolicy::Wait => {
+                self.waiters.push_back(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    pub(crate) fn release(&mut self, slot: u32) {
+        self.free.push(slot);
+        if let Some(waker) = self.waiters.pop_front() {
+            waker.wake();
+        }
+    }
+}
+
+/// A slot taken from the file table, given back on drop.
+pub(crate) struct FixedSlot {
+    index: u32,
+    driver: driver::Inner,
+}
+
+impl FixedSlot {
+    /// Take a free slot of the current driver's table.
+    pub(crate) async fn alloc() -> io::Result<FixedSlot> {
+        let driver = driver::CURRENT.with(|inner| inner.clone());
+        let index = std::future::poll_fn(|cx| driver.poll_alloc_slot(cx)).await?;
+        Ok(FixedSlot { index, driver })
+    }
+
+    pub(crate) fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Close the file in the slot without waiting, then give the slot back.
+    pub(crate) fn close_detached(self) {
+        let entry = opcode::Close::new(types::Fixed(self.index)).build();
+        // # Safety
+        // Close references no memory.
+        if let Err(e) = unsafe { self.driver.submit_detached(entry) } {
+            log::warn!("failed to close fixed file {}: {e}", self.index);
+        }
+    }
+}
+
+impl Drop for FixedSlot {
+    fn drop(&mut self) {
+        self.driver.release_slot(self.index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    struct Flag(std::sync::atomic::AtomicBool);
+
+    impl Wake for Flag {
+        fn wake(self: Arc<Self>) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn slots_are_reused() {
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut table = FileTable::new(2, SlotPolicy::Error);
+        assert!(matches!(table.poll_alloc(&mut cx), Poll::Ready(Ok(0))));
+        assert!(matches!(table.poll_alloc(&mut cx), Poll::Ready(Ok(1))));
+        match table.poll_alloc(&mut cx) {
+            Poll::Ready(Err(e)) => assert_eq!(e.raw_os_error(), Some(libc::ENFILE)),
+            _ => panic!("table should be full"),
+        }
+        table.release(0);
+        assert!(matches!(table.poll_alloc(&mut cx), Poll::Ready(Ok(0))));
+    }
+
+    #[test]
+    fn wait_for_release() {
+        let flag = Arc::new(Flag(Default::default()));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut table = FileTable::new(1, SlotPolicy::Wait);
+        assert!(matches!(table.poll_alloc(&mut cx), Poll::Ready(Ok(0))));
+        assert!(table.poll_alloc(&mut cx).is_pending());
+        table.release(0);
+        assert!(flag.0.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(matches!(table.poll_alloc(&mut cx), Poll::Ready(Ok(0))));
+    }
+}
@@ -0,0 +1,18 @@
+//! Driver counters.
+
+/// A snapshot of the counters kept by a driver.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Completions reaped from the completion queue.
+    pub cqes: u64,
+    /// Detached operations the kernel reported as failed.
+    pub detached_failures: u64,
+    /// Completions for operations the driver does not track, like duplicate
+    /// CQEs. They are ignored.
+    pub spurious_completions: u64,
+    /// Operations tracked by the driver right now.
+    pub ops: usize,
+    /// Operations the driver can track before allocating more memory, see
+    /// `Runtime::compact`.
+    pub ops_capacity: usize,
+}
@@ -1,14 +1,33 @@
-pub mod file_io;
+//! The io_uring driver under the runtime.
+
+pub(crate) mod buf_group;
+pub(crate) mod file_io;
+pub(crate) mod fixed_bufs;
+pub(crate) mod fixed_files;
+pub(crate) mod metrics;
+pub(crate) mod net;
 pub(crate) mod op;
+pub(crate) mod poll;
+pub(crate) mod timeout;
+#[cfg(feature = "uring-trace")]
+mod trace;
 mod uring;
-mod util;
+pub(crate) mod util;
 
+use crate::buf::AlignedBuf;
+use crate::driver::fixed_bufs::BufTable;
+use crate::driver::fixed_files::FileTable;
 use crate::driver::op::{CompletionMeta, Mappable, Op};
-use crate::driver::uring::Ops;
+use crate::driver::uring::{Discard, Ops};
 use crate::driver::util::timespec;
 use crate::scoped_thread_local;
-use io_uring::types::Timespec;
-use io_uring::{cqueue, opcode, IoUring};
+pub use fixed_files::SlotPolicy;
+use io_uring::types::{CancelBuilder, Timespec};
+use io_uring::{cqueue, opcode, squeue, IoUring, Probe};
+pub use metrics::Metrics;
+#[cfg(feature = "io-uring-cmd")]
+pub use op::{CmdBuf, CmdCompletion, UringCmd};
+use std::any::Any;
 use std::cell::UnsafeCell;
 use std::io;
 use std::mem::ManuallyDrop;
@@ -16,29 +35,95 @@ use std::rc::Rc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 
-#[allow(unused)]
 pub(crate) const CANCEL_USERDATA: u64 = u64::MAX;
 pub(crate) const TIMEOUT_USERDATA: u64 = u64::MAX - 1;
+pub(crate) const CANCEL_ALL_USERDATA: u64 = u64::MAX - 2;
+pub(crate) const DETACHED_USERDATA: u64 = u64::MAX - 3;
 
 pub(crate) const MIN_REVERSED_USERDATA: u64 = u64::MAX - 3;
 
 pub struct IoUringDriver {
-    inner: Rc<UnsafeCell<UringInner>>,
+    inner: Inner,
 
     // Used as timeout buffer
     timespec: *mut Timespec,
 }
 
-pub(crate) struct UringInner {
+pub(crate) struct UringInner<S: SqEntry = squeue::Entry, C: CqEntry = cqueue::Entry> {
     /// Record Submitted Operations
     ops: Ops,
 
     /// IoUring bindings
-    uring: ManuallyDrop<IoUring>,
+    uring: ManuallyDrop<IoUring<S, C>>,
 
     // Uring support ext_arg
     ext_arg: bool,
+
+    // Opcodes supported by the kernel
+    probe: Probe,
+
+    // Uring support IOSQE_CQE_SKIP_SUCCESS
+    skip_success: bool,
+
+    // Slots of the registered file table
+    files: Option<FileTable>,
+
+    // Registered buffers not checked out
+    bufs: Option<BufTable>,
+
+    metrics: Metrics,
+
+    #[cfg(feature = "uring-trace")]
+    tracer: trace::Tracer,
+}
+
+/// Submission queue entry of a ring, 64 or 128 bytes.
+pub(crate) trait SqEntry: squeue::EntryMarker {
+    /// Whether the ring was set up with `IORING_SETUP_SQE128`.
+    const SQE128: bool;
+
+    fn from_op<T: Mappable>(data: &mut T, user_data: u64) -> Self;
+}
+
+impl SqEntry for squeue::Entry {
+    const SQE128: bool = false;
+
+    #[inline]
+    fn from_op<T: Mappable>(data: &mut T, user_data: u64) -> Self {
+        data.uring_op().user_data(user_data)
+    }
+}
+
+impl SqEntry for squeue::Entry128 {
+    const SQE128: bool = true;
+
+    #[inline]
+    fn from_op<T: Mappable>(data: &mut T, user_data: u64) -> Self {
+        data.uring_op128().user_data(user_data)
+    }
+}
+
+/// Completion queue entry of a ring, 16 or 32 bytes.
+pub(crate) trait CqEntry: cqueue::EntryMarker {
+    /// Split off the extra data of a 32-byte entry.
+    fn split(self) -> (cqueue::Entry, [u64; 2]);
+}
+
+impl CqEntry for cqueue::Entry {
+    #[inline]
+    fn split(self) -> (cqueue::Entry, [u64; 2]) {
+        (self, [0; 2])
+    }
 }
+
+impl CqEntry for cqueue::Entry32 {
+    #[inline]
+    fn split(self) -> (cqueue::Entry, [u64; 2]) {
+        let big_cqe = *self.big_cqe();
+        (self.into(), big_cqe)
+    }
+}
+
 pub trait Driver {
     /// Run with driver TLS.
     fn with<R>(&self, f: impl FnOnce() -> R) -> R;
@@ -48,125 +133,552 @@ pub trait Driver {
     fn park(&self) -> io::Result<()>;
     /// Wait with timeout and process returned events.
     fn park_timeout(&self, duration: Duration) -> io::Result<()>;
+    /// Cancel all in-flight operations and wait until the kernel returns them.
+    fn cancel_all(&self) -> io::Result<()>;
 }
 scoped_thread_local!(pub(crate) static CURRENT: Inner);
+
+type Shared<S, C> = Rc<UnsafeCell<UringInner<S, C>>>;
+
+// The entry sizes are part of the ring type, every combination gets its own
+// monomorphized driver.
 #[derive(Clone)]
 pub(crate) enum Inner {
-    Uring(Rc<UnsafeCell<UringInner>>),
+    Uring(Shared<squeue::Entry, cqueue::Entry>),
+    UringSqe128(Shared<squeue::Entry128, cqueue::Entry>),
+    UringCqe32(Shared<squeue::Entry, cqueue::Entry32>),
+    UringBig(Shared<squeue::Entry128, cqueue::Entry32>),
 }
-impl Inner {
-    fn submit_with<T: Mappable>(&self, data: T) -> io::Result<Op<T>> {
-        match self {
-            Inner::Uring(this) => UringInner::submit_with_data(this, data),
+
+macro_rules! with_uring {
+    ($inner: expr, $this: ident => $body: expr) => {
+        match $inner {
+            Inner::Uring($this) => $body,
+            Inner::UringSqe128($this) => $body,
+            Inner::UringCqe32($this) => $body,
+            Inner::UringBig($this) => $body,
         }
+    };
+}
+
+impl Inner {
+    // The data is handed back if the operation could not be submitted.
+    fn submit_with<T: Mappable>(&self, data: T) -> Result<Op<T>, (io::Error, T)> {
+        with_uring!(self, this => UringInner::submit_with_data(this, self.clone(), data))
     }
 
     #[allow(unused)]
     fn poll_op<T: Mappable>(
         &self,
         data: &mut T,
-        index: usize,
+        user_data: u64,
         cx: &mut Context<'_>,
     ) -> Poll<CompletionMeta> {
-        match self {
-            Inner::Uring(this) => UringInner::poll_op(this, index, cx),
-        }
+        with_uring!(self, this => UringInner::poll_op(this, user_data, cx))
+    }
+
+    fn poll_next(&self, user_data: u64, cx: &mut Context<'_>) -> Poll<CompletionMeta> {
+        with_uring!(self, this => UringInner::poll_next(this, user_data, cx))
     }
 
     #[inline]
-    fn drop_op<T: 'static>(&self, index: usize, data: &mut Option<T>, skip_cancel: bool) {
-        match self {
-            Inner::Uring(this) => UringInner::drop_op(this, index, data, skip_cancel),
-        }
+    fn drop_op<T: 'static>(
+        &self,
+        user_data: u64,
+        data: &mut Option<T>,
+        skip_cancel: bool,
+        discard: Option<Discard>,
+    ) {
+        with_uring!(self, this => UringInner::drop_op(this, user_data, data, skip_cancel, discard))
     }
 
+    /// Submit an operation nobody waits for.
+    ///
+    /// # Safety
+    /// The entry must not reference memory, nothing keeps it alive until the
+    /// kernel is done.
+    pub(crate) unsafe fn submit_detached(&self, entry: squeue::Entry) -> io::Result<()> {
+        with_uring!(self, this => UringInner::submit_detached(this, entry))
+    }
+
+    fn cancel_op(&self, op_canceller: &op::OpCanceller) {
+        // The cancel references no memory, and a user_data that matches
+        // nothing fails harmlessly.
+        with_uring!(self, this => unsafe { UringInner::cancel_op(this, op_canceller.user_data) })
+    }
     #[allow(unused)]
-    pub(super) unsafe fn cancel_op(&self, op_canceller: &op::OpCanceller) {
+    fn is_legacy(&self) -> bool {
+        false
+    }
+
+    /// Whether the kernel supports the given opcode.
+    pub(crate) fn is_supported(&self, opcode: u8) -> bool {
+        with_uring!(self, this => unsafe { (*this.get()).probe.is_supported(opcode) })
+    }
+
+    /// Whether the ring uses 32-byte completion entries.
+    pub(crate) fn is_cqe32(&self) -> bool {
+        matches!(self, Inner::UringCqe32(_) | Inner::UringBig(_))
+    }
+
+    /// Whether success completions of detached operations are skipped.
+    #[allow(unused)]
+    pub(crate) fn skip_success(&self) -> bool {
+        with_uring!(self, this => unsafe { (*this.get()).skip_success })
+    }
+
+    /// Take a free slot of the registered file table.
+    pub(crate) fn poll_alloc_slot(&self, cx: &mut Context<'_>) -> Poll<io::Result<u32>> {
+        with_uring!(self, this => match unsafe { &mut (*this.get()).files } {
+            Some(files) => files.poll_alloc(cx),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no file table registered, see RuntimeBuilder::fixed_files",
+            ))),
+        })
+    }
+
+    pub(crate) fn release_slot(&self, slot: u32) {
+        with_uring!(self, this => if let Some(files) = unsafe { &mut (*this.get()).files } {
+            files.release(slot)
+        })
+    }
+
+    /// Take a free registered buffer that holds `len` bytes.
+    pub(crate) fn poll_take_buf(
+        &self,
+        len: usize,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(u16, AlignedBuf)>> {
+        with_uring!(self, this => match unsafe { &mut (*this.get()).bufs } {
+            Some(bufs) => bufs.poll_take(len, cx),
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no buffers registered, see RuntimeBuilder::with_registered_buffers",
+            ))),
+        })
+    }
+
+    pub(crate) fn release_buf(&self, index: u16, buf: AlignedBuf) {
+        with_uring!(self, this => if let Some(bufs) = unsafe { &mut (*this.get()).bufs } {
+            bufs.release(index, buf)
+        })
+    }
+
+    pub(crate) unsafe fn register_buf_ring(
+        &self,
+        ring_addr: u64,
+        ring_entries: u16,
+        bgid: u16,
+    ) -> io::Result<()> {
+        with_uring!(self, this => {
+            (*this.get())
+                .uring
+                .submitter()
+                .register_buf_ring(ring_addr, ring_entries, bgid)
+        })
+    }
+
+    pub(crate) fn unregister_buf_ring(&self, bgid: u16) -> io::Result<()> {
+        with_uring!(self, this => unsafe {
+            (*this.get()).uring.submitter().unregister_buf_ring(bgid)
+        })
+    }
+}
+
+/// [`io_uring::Builder`] for any of the supported entry sizes.
+pub(crate) enum UringBuilder {
+    Uring(io_uring::Builder<squeue::Entry, cqueue::Entry>),
+    UringSqe128(io_uring::Builder<squeue::Entry128, cqueue::Entry>),
+    UringCqe32(io_uring::Builder<squeue::Entry, cqueue::Entry32>),
+    UringBig(io_uring::Builder<squeue::Entry128, cqueue::Entry32>),
+}
+
+impl UringBuilder {
+    /// A default builder for the given entry sizes.
+    pub(crate) fn new(sqe128: bool, cqe32: bool) -> Self {
+        match (sqe128, cqe32) {
+            (false, false) => UringBuilder::Uring(IoUring::builder()),
+            (true, false) => UringBuilder::UringSqe128(IoUring::builder()),
+            (false, true) => UringBuilder::UringCqe32(IoUring::builder()),
+            (true, true) => UringBuilder::UringBig(IoUring::builder()),
+        }
+    }
+
+    pub(crate) fn from_builder<S, C>(urb: io_uring::Builder<S, C>) -> Self
+    where
+        S: squeue::EntryMarker + 'static,
+        C: cqueue::EntryMarker + 'static,
+    {
+        let mut urb = Some(urb);
+        let urb = &mut urb as &mut dyn Any;
+        if let Some(urb) = urb.downcast_mut::<Option<io_uring::Builder>>() {
+            UringBuilder::Uring(urb.take().unwrap())
+        } else if let Some(urb) = urb.downcast_mut::<Option<io_uring::Builder<squeue::Entry128>>>()
+        {
+            UringBuilder::UringSqe128(urb.take().unwrap())
+        } else if let Some(urb) =
+            urb.downcast_mut::<Option<io_uring::Builder<squeue::Entry, cqueue::Entry32>>>()
+        {
+            UringBuilder::UringCqe32(urb.take().unwrap())
+        } else if let Some(urb) =
+            urb.downcast_mut::<Option<io_uring::Builder<squeue::Entry128, cqueue::Entry32>>>()
+        {
+            UringBuilder::UringBig(urb.take().unwrap())
+        } else {
+            unreachable!("io_uring has no other entry types")
+        }
+    }
+
+    /// Entry sizes of the ring as `(sqe128, cqe32)`.
+    pub(crate) fn entry_sizes(&self) -> (bool, bool) {
         match self {
-            Inner::Uring(this) => UringInner::cancel_op(this, op_canceller.index),
+            UringBuilder::Uring(_) => (false, false),
+            UringBuilder::UringSqe128(_) => (true, false),
+            UringBuilder::UringCqe32(_) => (false, true),
+            UringBuilder::UringBig(_) => (true, true),
         }
     }
-    fn is_legacy(&self) -> bool {
-        false
+
+    fn build(&self, entries: u32) -> io::Result<Inner> {
+        Ok(match self {
+            UringBuilder::Uring(urb) => Inner::Uring(UringInner::new(build_ring(urb, entries)?)),
+            UringBuilder::UringSqe128(urb) => {
+                Inner::UringSqe128(UringInner::new(build_ring(urb, entries)?))
+            }
+            UringBuilder::UringCqe32(urb) => {
+                Inner::UringCqe32(UringInner::new(build_ring(urb, entries)?))
+            }
+            UringBuilder::UringBig(urb) => {
+                Inner::UringBig(UringInner::new(build_ring(urb, entries)?))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    // Rings larger than this fail to build with ENOMEM, to exercise the
+    // fallback without touching the process rlimits.
+    pub(crate) static MAX_RING_ENTRIES: std::cell::Cell<u32> = const { std::cell::Cell::new(u32::MAX) };
+}
+
+fn build_ring<S, C>(urb: &io_uring::Builder<S, C>, entries: u32) -> io::Result<IoUring<S, C>>
+where
+    S: squeue::EntryMarker,
+    C: cqueue::EntryMarker,
+{
+    #[cfg(test)]
+    if entries > MAX_RING_ENTRIES.with(|max| max.get()) {
+        return Err(io::Error::from_raw_os_error(libc::ENOMEM));
+    }
+    urb.build(entries)
+}
+
+// Ring memory is charged against RLIMIT_MEMLOCK on older kernels, which
+// report ENOMEM when it runs out. EPERM is not memory, it means io_uring
+// is disabled by `kernel.io_uring_disabled` or a seccomp filter.
+fn is_memory_pressure(e: &io::Error) -> bool {
+    e.raw_os_error() == Some(libc::ENOMEM)
+}
+
+fn with_memlock_limit(e: io::Error, entries: u32) -> io::Error {
+    if !is_memory_pressure(&e) {
+        return e;
+    }
+    io::Error::new(
+        e.kind(),
+        format!(
+            "io_uring setup with {entries} entries failed: {e} (RLIMIT_MEMLOCK: {})",
+            memlock_limit()
+        ),
+    )
+}
+
+fn memlock_limit() -> String {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } {
+        0 if limit.rlim_cur == libc::RLIM_INFINITY => "unlimited".to_string(),
+        0 => format!("{} bytes", limit.rlim_cur),
+        _ => "unknown".to_string(),
     }
 }
 
 impl IoUringDriver {
     const DEFAULT_ENTRIES: u32 = 1024;
+    // Smaller rings tried in turn when the default one hits memory limits.
+    const FALLBACK_ENTRIES: [u32; 3] = [512, 256, 64];
 
-    pub(crate) fn new(b: &io_uring::Builder) -> io::Result<IoUringDriver> {
-        Self::new_with_entries(b, Self::DEFAULT_ENTRIES)
+    pub(crate) fn new(b: &UringBuilder) -> io::Result<IoUringDriver> {
+        Self::new_sized(b, Self::DEFAULT_ENTRIES)
     }
 
-    pub(crate) fn new_with_entries(
-        urb: &io_uring::Builder,
-        entries: u32,
-    ) -> io::Result<IoUringDriver> {
-        let uring = ManuallyDrop::new(urb.build(entries)?);
+    /// Create a driver with `entries` entries, retrying with smaller rings
+    /// while the allocation hits memory limits.
+    pub(crate) fn new_sized(b: &UringBuilder, entries: u32) -> io::Result<IoUringDriver> {
+        let halved = std::iter::successors(Some(entries / 2), |n| Some(n / 2))
+            .take_while(|&n| n > Self::DEFAULT_ENTRIES);
+        let smaller = halved
+            .chain([Self::DEFAULT_ENTRIES])
+            .chain(Self::FALLBACK_ENTRIES)
+            .filter(|&n| n < entries);
 
-        let inner = Rc::new(UnsafeCell::new(UringInner {
-            ops: Ops::new(),
-            ext_arg: uring.params().is_feature_ext_arg(),
-            uring,
-        }));
+        let mut result = Self::with_ring(b, entries);
+        let mut last = entries;
+        for entries in smaller {
+            match result {
+                Err(ref e) if is_memory_pressure(e) => {
+                    log::warn!("io_uring setup failed: {e}, retrying with {entries} entries");
+                    result = Self::with_ring(b, entries);
+                    last = entries;
+                }
+                _ => break,
+            }
+        }
+        result.map_err(|e| with_memlock_limit(e, last))
+    }
 
+    /// Create a driver with exactly `entries` entries, failing fast when the
+    /// ring can not be allocated.
+    pub(crate) fn new_with_entries(urb: &UringBuilder, entries: u32) -> io::Result<IoUringDriver> {
+        Self::with_ring(urb, entries).map_err(|e| with_memlock_limit(e, entries))
+    }
+
+    fn with_ring(urb: &UringBuilder, entries: u32) -> io::Result<IoUringDriver> {
         Ok(IoUringDriver {
-            inner,
+            inner: urb.build(entries)?,
             timespec: Box::leak(Box::new(Timespec::new())) as *mut Timespec,
         })
     }
 
-    #[allow(unused)]
-    fn num_operations(&self) -> usize {
-        let inner = self.inner.get();
-        unsafe { (*inner).ops.slab.len() }
+    /// Get the number of submission queue entries the ring was created with.
+    pub fn ring_entries(&self) -> u32 {
+        with_uring!(&self.inner, this => unsafe { (*this.get()).uring.params().sq_entries() })
+    }
+
+    /// Whether the ring uses 128-byte submission entries.
+    pub fn is_sqe128(&self) -> bool {
+        matches!(self.inner, Inner::UringSqe128(_) | Inner::UringBig(_))
+    }
+
+    /// Whether the ring uses 32-byte completion entries.
+    pub fn is_cqe32(&self) -> bool {
+        self.inner.is_cqe32()
+    }
+
+    /// Limit the io-wq workers the kernel spawns for blocking ops, per NUMA
+    /// node. `bounded` covers regular file and block device io, `unbounded`
+    /// covers io that may never complete, like sockets. Zero leaves a limit
+    /// unchanged.
+    pub(crate) fn set_max_io_workers(&self, bounded: u32, unbounded: u32) -> io::Result<()> {
+        self.register_max_io_workers([bounded, unbounded]).map(drop)
+    }
+
+    /// Register a sparse file table of `slots` entries for direct descriptors.
+    pub(crate) fn register_files(&self, slots: u32, policy: SlotPolicy) -> io::Result<()> {
+        with_uring!(&self.inner, this => {
+            let inner = unsafe { &mut *this.get() };
+            match inner.uring.submitter().register_files_sparse(slots) {
+                Ok(()) => {
+                    inner.files = Some(FileTable::new(slots, policy));
+                    Ok(())
+                }
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "sparse file tables are not supported by the kernel(5.19+)",
+                )),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// Register `count` buffers of each of `sizes` bytes for fixed reads and
+    /// writes.
+    pub(crate) fn register_buffers(&self, sizes: &[usize], count: usize) -> io::Result<()> {
+        let (table, iovecs) = BufTable::new(sizes, count)?;
+        with_uring!(&self.inner, this => {
+            let inner = unsafe { &mut *this.get() };
+            // # Safety
+            // The table owns the memory, the ring is dropped before it.
+            match unsafe { inner.uring.submitter().register_buffers(&iovecs) } {
+                Ok(()) => {
+                    inner.bufs = Some(table);
+                    Ok(())
+                }
+                // Pinned pages are charged against RLIMIT_MEMLOCK on every
+                // kernel, unless the process has CAP_IPC_LOCK.
+                Err(e) if is_memory_pressure(&e) => {
+                    let bytes: usize = iovecs.iter().map(|iov| iov.iov_len).sum();
+                    Err(io::Error::new(
+                        e.kind(),
+                        format!(
+                            "registering {} buffers of {bytes} bytes in total failed: {e} \
+                             (RLIMIT_MEMLOCK: {})",
+                            iovecs.len(),
+                            memlock_limit()
+                        ),
+                    ))
+                }
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// Get the io-wq worker limits as `(bounded, unbounded)`.
+    pub fn max_io_workers(&self) -> io::Result<(u32, u32)> {
+        self.register_max_io_workers([0, 0])
+            .map(|[bounded, unbounded]| (bounded, unbounded))
+    }
+
+    // Returns the previous limits.
+    fn register_max_io_workers(&self, mut max: [u32; 2]) -> io::Result<[u32; 2]> {
+        let res = with_uring!(&self.inner, this => unsafe {
+            (*this.get()).uring.submitter().register_iowq_max_workers(&mut max)
+        });
+        match res {
+            Ok(()) => Ok(max),
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "io-wq worker limits are not supported by the kernel(5.15+)",
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get a snapshot of the driver counters.
+    pub fn metrics(&self) -> Metrics {
+        with_uring!(&self.inner, this => unsafe {
+            let this = &*this.get();
+            Metrics {
+                ops: this.ops.slab.len(),
+                ops_capacity: this.ops.slab.capacity(),
+                ..this.metrics
+            }
+        })
+    }
+
+    /// Release memory held for operations no longer in flight.
+    pub fn compact(&self) {
+        with_uring!(&self.inner, this => unsafe { (*this.get()).ops.slab.compact() })
+    }
+}
+
+impl Driver for IoUringDriver {
+    /// Enter the driver context. This enables using uring types.
+    fn with<R>(&self, f: impl FnOnce() -> R) -> R {
+        CURRENT.set(&self.inner, f)
+    }
+
+    fn submit(&self) -> io::Result<()> {
+        with_uring!(&self.inner, this => {
+            let inner = unsafe { &mut *this.get() };
+            inner.submit()?;
+            inner.tick()
+        })
+    }
+
+    fn park(&self) -> io::Result<()> {
+        with_uring!(&self.inner, this => unsafe { (*this.get()).park(None, self.timespec) })
+    }
+
+    fn park_timeout(&self, duration: Duration) -> io::Result<()> {
+        with_uring!(&self.inner, this => unsafe {
+            (*this.get()).park(Some(duration), self.timespec)
+        })
+    }
+
+    fn cancel_all(&self) -> io::Result<()> {
+        with_uring!(&self.inner, this => unsafe { (*this.get()).cancel_all() })
+    }
+}
+
+impl Drop for IoUringDriver {
+    fn drop(&mut self) {
+        // The timeout op copies the timespec on submission, so the buffer can
+        // be freed even if a timeout is still armed.
+        unsafe { drop(Box::from_raw(self.timespec)) };
+    }
+}
+
+impl<S: SqEntry, C: CqEntry> UringInner<S, C> {
+    fn new(uring: IoUring<S, C>) -> Shared<S, C> {
+        // Kernels before 5.6 can not be probed, treat every opcode as unsupported there.
+        let mut probe = Probe::new();
+        let _ = uring.submitter().register_probe(&mut probe);
+
+        Rc::new(UnsafeCell::new(UringInner {
+            ops: Ops::new(),
+            ext_arg: uring.params().is_feature_ext_arg(),
+            probe,
+            skip_success: uring.params().is_feature_skip_cqe_on_success(),
+            files: None,
+            bufs: None,
+            metrics: Metrics::default(),
+            #[cfg(feature = "uring-trace")]
+            tracer: trace::Tracer::new(),
+            uring: ManuallyDrop::new(uring),
+        }))
+    }
+
+    // Push a regular sized entry, widened to the ring entry size.
+    #[inline]
+    unsafe fn push(&mut self, entry: &squeue::Entry) -> Result<(), squeue::PushError> {
+        self.push_entry(&S::from(entry.clone()))
+    }
+
+    #[inline]
+    unsafe fn push_entry(&mut self, entry: &S) -> Result<(), squeue::PushError> {
+        self.uring.submission().push(entry)?;
+        #[cfg(feature = "uring-trace")]
+        self.tracer.sqe(entry);
+        Ok(())
     }
 
     // Flush to make enough space
-    fn flush_space(inner: &mut UringInner, need: usize) -> io::Result<()> {
-        let sq = inner.uring.submission();
+    fn flush_space(&mut self, need: usize) -> io::Result<()> {
+        let sq = self.uring.submission();
         debug_assert!(sq.capacity() >= need);
         if sq.len() + need > sq.capacity() {
             drop(sq);
-            inner.submit()?;
+            self.submit()?;
         }
         Ok(())
     }
 
-    fn install_timeout(&self, inner: &mut UringInner, duration: Duration) {
+    fn install_timeout(&mut self, buf: *mut Timespec, duration: Duration) {
         let timespec = timespec(duration);
         unsafe {
-            std::ptr::replace(self.timespec, timespec);
+            std::ptr::replace(buf, timespec);
         }
-        let entry = opcode::Timeout::new(self.timespec as *const Timespec)
+        let entry = opcode::Timeout::new(buf as *const Timespec)
             .build()
             .user_data(TIMEOUT_USERDATA);
 
-        let mut sq = inner.uring.submission();
-        let _ = unsafe { sq.push(&entry) };
+        let _ = unsafe { self.push(&entry) };
     }
 
-    fn inner_park(&self, timeout: Option<Duration>) -> io::Result<()> {
-        let inner = unsafe { &mut *self.inner.get() };
-
+    fn park(&mut self, timeout: Option<Duration>, timespec_buf: *mut Timespec) -> io::Result<()> {
         if timeout.is_some() {
-            Self::flush_space(inner, 1)?;
+            self.flush_space(1)?;
         }
 
         if let Some(duration) = timeout {
-            match inner.ext_arg {
+            match self.ext_arg {
                 // Submit and Wait with timeout in an TimeoutOp way.
                 // Better compatibility(5.4+).
                 false => {
-                    self.install_timeout(inner, duration);
-                    inner.uring.submit_and_wait(1)?;
+                    self.install_timeout(timespec_buf, duration);
+                    self.uring.submit_and_wait(1)?;
                 }
                 // Submit and Wait with enter args.
                 // Better performance(5.11+).
                 true => {
                     let timespec = timespec(duration);
                     let args = io_uring::types::SubmitArgs::new().timespec(&timespec);
-                    if let Err(e) = inner.uring.submitter().submit_with_args(1, &args) {
+                    if let Err(e) = self.uring.submitter().submit_with_args(1, &args) {
                         if e.raw_os_error() != Some(libc::ETIME) {
                             return Err(e);
                         }
@@ -174,54 +686,119 @@ impl IoUringDriver {
                 }
             }
         } else {
-            inner.uring.submit_and_wait(1)?;
+            self.uring.submit_and_wait(1)?;
         }
         // Process CQ
-        inner.tick()?;
+        self.tick()?;
 
         Ok(())
     }
-}
 
-impl Driver for IoUringDriver {
-    /// Enter the driver context. This enables using uring types.
-    fn with<R>(&self, f: impl FnOnce() -> R) -> R {
-        let inner = Inner::Uring(self.inner.clone());
-        CURRENT.set(&inner, f)
-    }
+    fn tick(&mut self) -> io::Result<()> {
+        let cq = self.uring.completion();
 
-    fn submit(&self) -> io::Result<()> {
-        let inner = unsafe { &mut *self.inner.get() };
-        inner.submit()?;
-        inner.tick()?;
+        for cqe in cq {
+            let (cqe, big_cqe) = cqe.split();
+            #[cfg(feature = "uring-trace")]
+            self.tracer.cqe(&cqe);
+            Self::dispatch(&mut self.ops, &mut self.metrics, &cqe, big_cqe);
+        }
         Ok(())
     }
 
-    fn park(&self) -> io::Result<()> {
-        self.inner_park(None)
+    #[inline]
+    fn dispatch(ops: &mut Ops, metrics: &mut Metrics, cqe: &cqueue::Entry, big_cqe: [u64; 2]) {
+        metrics.cqes += 1;
+        let user_data = cqe.user_data();
+        match user_data {
+            DETACHED_USERDATA => {
+                if let Err(e) = unwrap_to_result(cqe) {
+                    metrics.detached_failures += 1;
+                    log::warn!("detached operation failed: {e}");
+                }
+            }
+            _ if user_data >= MIN_REVERSED_USERDATA => (),
+            // # Safety
+            // Here we can make sure the result is valid.
+            _ => unsafe {
+                // A stale generation means the slot was reused, the completion
+                // is for an operation that is gone.
+                if !ops.complete(user_data, unwrap_to_result(cqe), cqe.flags(), big_cqe) {
+                    Self::spurious(metrics, user_data);
+                }
+            },
+        }
     }
 
-    fn park_timeout(&self, duration: Duration) -> io::Result<()> {
-        self.inner_park(Some(duration))
+    #[cold]
+    fn spurious(metrics: &mut Metrics, user_data: u64) {
+        metrics.spurious_completions += 1;
+        let (index, generation) = uring::decode_user_data(user_data);
+        log::warn!("ignored completion for unknown operation {index} (generation {generation})");
     }
-}
 
-impl UringInner {
-    fn tick(&mut self) -> io::Result<()> {
-        let cq = self.uring.completion();
-
-        for cqe in cq {
-            let index = cqe.user_data();
-            match index {
-                _ if index >= MIN_REVERSED_USERDATA => (),
-                // # Safety
-                // Here we can make sure the result is valid.
-                _ => unsafe { self.ops.complete(index as _, unwrap_to_result(&cqe), cqe.flags()) },
+    /// Cancel all in-flight operations and wait until the kernel has returned
+    /// every one of them, so the buffers and fds they hold can be reclaimed.
+    ///
+    /// Kernels 5.19+ cancel everything with a single `IORING_ASYNC_CANCEL_ANY`
+    /// request, older ones get one `AsyncCancel` per operation.
+    pub(crate) fn cancel_all(&mut self) -> io::Result<()> {
+        if self.ops.in_flight().next().is_none() {
+            return Ok(());
+        }
+        if !self.cancel_any()? {
+            let in_flight = self.ops.in_flight().collect::<Vec<_>>();
+            for user_data in in_flight {
+                let cancel = opcode::AsyncCancel::new(user_data)
+                    .build()
+                    .user_data(CANCEL_USERDATA);
+                unsafe {
+                    if self.push(&cancel).is_err() {
+                        self.submit()?;
+                        let _ = self.push(&cancel);
+                    }
+                }
             }
         }
+        // Cancelled operations still complete with ECANCELED, route them to
+        // their lifecycles.
+        while self.ops.in_flight().next().is_some() {
+            self.uring.submit_and_wait(1)?;
+            self.tick()?;
+        }
         Ok(())
     }
 
+    // Returns false if the kernel does not support IORING_ASYNC_CANCEL_ANY.
+    fn cancel_any(&mut self) -> io::Result<bool> {
+        let cancel = opcode::AsyncCancel2::new(CancelBuilder::any())
+            .build()
+            .user_data(CANCEL_ALL_USERDATA);
+        unsafe {
+            if self.push(&cancel).is_err() {
+                self.submit()?;
+                let _ = self.push(&cancel);
+            }
+        }
+        loop {
+            self.uring.submit_and_wait(1)?;
+            let mut result = None;
+            for cqe in self.uring.completion() {
+                let (cqe, big_cqe) = cqe.split();
+                #[cfg(feature = "uring-trace")]
+                self.tracer.cqe(&cqe);
+                if cqe.user_data() == CANCEL_ALL_USERDATA {
+                    result = Some(cqe.result());
+                } else {
+                    Self::dispatch(&mut self.ops, &mut self.metrics, &cqe, big_cqe);
+                }
+            }
+            if let Some(res) = result {
+                return Ok(res != -libc::EINVAL);
+            }
+        }
+    }
+
     fn submit(&mut self) -> io::Result<()> {
         loop {
             match self.uring.submit() {
@@ -239,95 +816,165 @@ impl UringInner {
         }
     }
 
-    fn new_op<T: Mappable>(data: T, inner: &mut UringInner, driver: Inner) -> Op<T> {
+    fn new_op<T: Mappable>(data: T, inner: &mut Self, driver: Inner) -> Op<T> {
         Op {
             driver,
-            index: inner.ops.insert(T::RET_IS_FD),
+            user_data: inner.ops.insert(T::RET_IS_FD, T::MULTISHOT),
             data: Some(data),
         }
     }
 
     pub(crate) fn submit_with_data<T>(
-        this: &Rc<UnsafeCell<UringInner>>,
+        this: &Shared<S, C>,
+        driver: Inner,
         data: T,
-    ) -> io::Result<Op<T>>
+    ) -> Result<Op<T>, (io::Error, T)>
     where
         T: Mappable,
     {
+        if T::SQE128 && !S::SQE128 {
+            let e = io::Error::new(
+                io::ErrorKind::Unsupported,
+                "operation needs a ring built with setup_sqe128",
+            );
+            return Err((e, data));
+        }
+
         let inner = unsafe { &mut *this.get() };
         // If the submission queue is full, flush it to the kernel
         if inner.uring.submission().is_full() {
-            inner.submit()?;
+            if let Err(e) = inner.submit() {
+                return Err((e, data));
+            }
         }
 
         // Create the operation
-        let mut op = Self::new_op(data, inner, Inner::Uring(this.clone()));
+        let mut op = Self::new_op(data, inner, driver);
 
         // Configure the SQE
         let data_mut = unsafe { op.data.as_mut().unwrap_unchecked() };
-        let sqe = Mappable::uring_op(data_mut).user_data(op.index as _);
+        let sqe = S::from_op(data_mut, op.user_data);
 
-        {
-            let mut sq = inner.uring.submission();
+        // Push the new operation
+        if unsafe { inner.push_entry(&sqe).is_err() } {
+            unimplemented!("when is this hit?");
+        }
+        Ok(op)
+    }
+
+    #[cold]
+    fn untracked() -> CompletionMeta {
+        CompletionMeta {
+            result: Err(io::Error::other("operation is not tracked by the driver")),
+            flags: op::CqeFlags::default(),
+            big_cqe: [0; 2],
+        }
+    }
 
-            // Push the new operation
-            if unsafe { sq.push(&sqe).is_err() } {
-                unimplemented!("when is this hit?");
+    // Detached operations get no slab entry. When the kernel supports it,
+    // the success completion is skipped entirely and only a failure comes
+    // back, with a reserved user_data.
+    pub(crate) unsafe fn submit_detached(
+        this: &Shared<S, C>,
+        entry: squeue::Entry,
+    ) -> io::Result<()> {
+        let inner = &mut *this.get();
+        let mut entry = entry.user_data(DETACHED_USERDATA);
+        if inner.skip_success {
+            entry = entry.flags(squeue::Flags::SKIP_SUCCESS);
+        }
+        if inner.push(&entry).is_err() {
+            inner.submit()?;
+            if inner.push(&entry).is_err() {
+                return Err(io::Error::from_raw_os_error(libc::EBUSY));
             }
         }
-        Ok(op)
+        Ok(())
     }
 
     pub(crate) fn poll_op(
-        this: &Rc<UnsafeCell<UringInner>>,
-        index: usize,
+        this: &Shared<S, C>,
+        user_data: u64,
         cx: &mut Context<'_>,
     ) -> Poll<CompletionMeta> {
         let inner = unsafe { &mut *this.get() };
-        let lifecycle = unsafe { inner.ops.slab.get(index).unwrap_unchecked() };
-        lifecycle.poll_op(cx)
+        match inner.ops.get(user_data) {
+            Some(lifecycle) => lifecycle.poll_op(cx),
+            None => Poll::Ready(Self::untracked()),
+        }
+    }
+
+    pub(crate) fn poll_next(
+        this: &Shared<S, C>,
+        user_data: u64,
+        cx: &mut Context<'_>,
+    ) -> Poll<CompletionMeta> {
+        let inner = unsafe { &mut *this.get() };
+        match inner.ops.get(user_data) {
+            Some(lifecycle) => lifecycle.poll_next(cx),
+            None => Poll::Ready(Self::untracked()),
+        }
     }
 
     pub(crate) fn drop_op<T: 'static>(
-        this: &Rc<UnsafeCell<UringInner>>,
-        index: usize,
+        this: &Shared<S, C>,
+        user_data: u64,
         data: &mut Option<T>,
         _skip_cancel: bool,
+        discard: Option<Discard>,
     ) {
         let inner = unsafe { &mut *this.get() };
-        if index == usize::MAX {
+        if user_data == u64::MAX {
             // already finished
             return;
         }
-        if let Some(lifecycle) = inner.ops.slab.get(index) {
-            let _must_finished = lifecycle.drop_op(data);
+        if let Some(lifecycle) = inner.ops.get(user_data) {
+            let _must_finished = lifecycle.drop_op(data, discard);
             if !_must_finished && !_skip_cancel {
                 unsafe {
-                    let cancel = opcode::AsyncCancel::new(index as u64)
+                    let cancel = opcode::AsyncCancel::new(user_data)
                         .build()
                         .user_data(u64::MAX);
 
                     // Try push cancel, if failed, will submit and re-push.
-                    if inner.uring.submission().push(&cancel).is_err() {
+                    if inner.push(&cancel).is_err() {
                         let _ = inner.submit();
-                        let _ = inner.uring.submission().push(&cancel);
+                        let _ = inner.push(&cancel);
                     }
                 }
             }
         }
     }
 
-    pub(crate) unsafe fn cancel_op(this: &Rc<UnsafeCell<UringInner>>, index: usize) {
+    pub(crate) unsafe fn cancel_op(this: &Shared<S, C>, user_data: u64) {
         let inner = &mut *this.get();
-        let cancel = opcode::AsyncCancel::new(index as u64)
+        let cancel = opcode::AsyncCancel::new(user_data)
             .build()
             .user_data(u64::MAX);
-        if inner.uring.submission().push(&cancel).is_err() {
+        if inner.push(&cancel).is_err() {
             let _ = inner.submit();
-            let _ = inner.uring.submission().push(&cancel);
+            let _ = inner.push(&cancel);
         }
     }
 }
+
+impl<S: SqEntry, C: CqEntry> Drop for UringInner<S, C> {
+    fn drop(&mut self) {
+        // Only ignored operations can be left here, the kernel may still write
+        // into the buffers they hold.
+        if let Err(e) = self.cancel_all() {
+            log::warn!("failed to cancel in-flight operations: {e}");
+            // Leak them rather than free memory the kernel still uses.
+            std::mem::forget(std::mem::replace(&mut self.ops, Ops::new()));
+        }
+        // Detached closes may still sit in the queue, the descriptors would
+        // leak if the ring went away without seeing them.
+        if let Err(e) = self.submit() {
+            log::warn!("failed to submit queued operations: {e}");
+        }
+        unsafe { ManuallyDrop::drop(&mut self.uring) };
+    }
+}
 fn unwrap_to_result(cqe: &cqueue::Entry) -> io::Result<u32> {
     let res = cqe.result();
 
@@ -337,3 +984,295 @@ fn unwrap_to_result(cqe: &cqueue::Entry) -> io::Result<u32> {
         Err(io::Error::from_raw_os_error(-res))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::{builder::RuntimeBuilder, runtime::spawn};
+    use io_uring::{squeue, types};
+    use std::cell::Cell;
+
+    struct TrackedBuf {
+        buf: Vec<u8>,
+        dropped: Rc<Cell<usize>>,
+    }
+
+    impl Drop for TrackedBuf {
+        fn drop(&mut self) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+
+    struct PipeRead {
+        fd: i32,
+        buf: TrackedBuf,
+    }
+
+    impl Mappable for PipeRead {
+        fn uring_op(&mut self) -> squeue::Entry {
+            let buf = &mut self.buf.buf;
+            opcode::Read::new(types::Fd(self.fd), buf.as_mut_ptr(), buf.len() as _).build()
+        }
+    }
+
+    #[test]
+    fn cancel_all_reclaims_buffers_on_shutdown() {
+        const OPS: usize = 16;
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        let dropped = Rc::new(Cell::new(0));
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for _ in 0..OPS {
+                let buf = TrackedBuf {
+                    buf: vec![0; 4 << 20],
+                    dropped: dropped.clone(),
+                };
+                spawn(async move {
+                    // Nothing is ever written to the pipe.
+                    let op = Op::submit_with(PipeRead { fd: fds[0], buf }).unwrap();
+                    let _ = op.await;
+                });
+            }
+        });
+        assert_eq!(dropped.get(), 0);
+
+        drop(rt);
+        assert_eq!(dropped.get(), OPS);
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    struct BigNop;
+
+    impl Mappable for BigNop {
+        const SQE128: bool = true;
+
+        fn uring_op(&mut self) -> squeue::Entry {
+            unreachable!("only submitted on rings with 128-byte SQEs")
+        }
+
+        fn uring_op128(&mut self) -> squeue::Entry128 {
+            opcode::Nop::new().build().into()
+        }
+    }
+
+    #[test]
+    fn ops_run_on_every_entry_size() {
+        for (sqe128, cqe32) in [(false, false), (true, false), (false, true), (true, true)] {
+            let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+                .setup_sqe128(sqe128)
+                .setup_cqe32(cqe32)
+                .build()
+                .unwrap();
+            assert_eq!(rt.driver.is_sqe128(), sqe128);
+            assert_eq!(rt.driver.is_cqe32(), cqe32);
+
+            let mut fds = [0; 2];
+            assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+            assert_eq!(
+                unsafe { libc::write(fds[1], b"hello".as_ptr().cast(), 5) },
+                5
+            );
+            rt.block_on(async {
+                let buf = TrackedBuf {
+                    buf: vec![0; 16],
+                    dropped: Rc::new(Cell::new(0)),
+                };
+                let completion = Op::submit_with(PipeRead { fd: fds[0], buf }).unwrap().await;
+                assert_eq!(completion.meta.result.unwrap().into_inner(), 5);
+                assert_eq!(&completion.data.buf.buf[..5], b"hello");
+
+                match Op::submit_with(BigNop) {
+                    Ok(op) => {
+                        assert!(sqe128);
+                        assert_eq!(op.await.meta.result.unwrap().into_inner(), 0);
+                    }
+                    Err(e) => {
+                        assert!(!sqe128);
+                        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+                    }
+                }
+            });
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+        }
+    }
+
+    struct Nop;
+
+    impl Mappable for Nop {
+        fn uring_op(&mut self) -> squeue::Entry {
+            opcode::Nop::new().build()
+        }
+    }
+
+    // Queue a nop completing with the user_data of a tracked operation.
+    fn push_duplicate(user_data: u64) {
+        let nop = opcode::Nop::new().build().user_data(user_data);
+        CURRENT
+            .with(|inner| with_uring!(inner, this => unsafe { (*this.get()).push(&nop).unwrap() }));
+    }
+
+    #[test]
+    fn duplicate_completions_are_ignored() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let op = Op::submit_with(Nop).unwrap();
+            let user_data = op.user_data;
+            // Completes the operation before the kernel does.
+            push_duplicate(user_data);
+            assert!(op.await.meta.result.is_ok());
+
+            // The slot is vacant now.
+            push_duplicate(user_data);
+            let op = Op::submit_with(Nop).unwrap();
+            assert!(op.await.meta.result.is_ok());
+        });
+        assert_eq!(rt.driver.metrics().spurious_completions, 2);
+    }
+
+    #[test]
+    fn stale_generation_is_ignored() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Reuse one slot over and over.
+            let mut stale = Vec::new();
+            for _ in 0..100 {
+                let op = Op::submit_with(Nop).unwrap();
+                stale.push(op.user_data);
+                op.await.meta.result.unwrap();
+            }
+            // Nobody writes to the pipe, the read stays in flight.
+            let pending = Op::submit_with(PipeRead {
+                fd: fds[0],
+                buf: TrackedBuf {
+                    buf: vec![0; 1],
+                    dropped: Rc::new(Cell::new(0)),
+                },
+            })
+            .unwrap();
+            let (index, generation) = uring::decode_user_data(pending.user_data);
+            assert!(stale.iter().all(|u| uring::decode_user_data(*u).0 == index));
+            assert_eq!(generation, 100);
+
+            // Completions that name the slot with an old generation.
+            for user_data in &stale[95..] {
+                push_duplicate(*user_data);
+            }
+            Op::submit_with(Nop).unwrap().await.meta.result.unwrap();
+            let mut pending = std::pin::pin!(pending);
+            let mut cx = Context::from_waker(std::task::Waker::noop());
+            assert!(std::future::Future::poll(pending.as_mut(), &mut cx).is_pending());
+        });
+        assert_eq!(rt.driver.metrics().spurious_completions, 5);
+        drop(rt);
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    fn ops_capacity() -> usize {
+        CURRENT
+            .with(|inner| with_uring!(inner, this => unsafe { (*this.get()).ops.slab.capacity() }))
+    }
+
+    #[test]
+    fn compact_after_bursts() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let mut peak = 0;
+        for burst in 0..4 {
+            let capacity = rt.block_on(async {
+                let ops = (0..10_000)
+                    .map(|_| Op::submit_with(Nop).unwrap())
+                    .collect::<Vec<_>>();
+                let capacity = ops_capacity();
+                for op in ops {
+                    op.await.meta.result.unwrap();
+                }
+                capacity
+            });
+            if burst == 0 {
+                peak = capacity;
+            }
+            assert!(capacity >= 10_000 && capacity <= peak);
+            assert_eq!(rt.driver.metrics().ops, 0);
+        }
+        rt.compact();
+        assert_eq!(rt.driver.metrics().ops_capacity, 64);
+    }
+
+    #[test]
+    fn stale_canceller_after_compact() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        // A canceller of an op in the second page, which compact releases.
+        let stale = rt.block_on(async {
+            let ops = (0..100)
+                .map(|_| Op::submit_with(Nop).unwrap())
+                .collect::<Vec<_>>();
+            let canceller = ops[80].op_canceller();
+            for op in ops {
+                op.await.meta.result.unwrap();
+            }
+            canceller
+        });
+        rt.compact();
+        rt.block_on(async {
+            let dropped = Rc::new(Cell::new(0));
+            let ops = (0..100)
+                .map(|_| {
+                    let buf = TrackedBuf {
+                        buf: vec![0; 1],
+                        dropped: dropped.clone(),
+                    };
+                    Op::submit_with(PipeRead { fd: fds[0], buf }).unwrap()
+                })
+                .collect::<Vec<_>>();
+            let reused = &ops[80];
+            let index = |user_data| uring::decode_user_data(user_data).0;
+            assert_eq!(index(reused.user_data), index(stale.user_data));
+            assert_ne!(reused.user_data, stale.user_data);
+
+            stale.cancel();
+            Op::submit_with(Nop).unwrap().await.meta.result.unwrap();
+            let mut cx = Context::from_waker(std::task::Waker::noop());
+            for op in ops {
+                let mut op = std::pin::pin!(op);
+                assert!(std::future::Future::poll(op.as_mut(), &mut cx).is_pending());
+            }
+        });
+        drop(rt);
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    #[test]
+    fn disabled_io_uring_is_not_memory_pressure() {
+        let err = with_memlock_limit(io::Error::from_raw_os_error(libc::EPERM), 1024);
+        assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+        let err = with_memlock_limit(io::Error::from_raw_os_error(libc::ENOMEM), 1024);
+        assert!(err.to_string().contains("RLIMIT_MEMLOCK"), "{err}");
+    }
+
+    #[test]
+    fn poll_untracked_operation() {
+        let rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let mut cx = Context::from_waker(std::task::Waker::noop());
+        match rt.driver.inner.poll_op(&mut Nop, 12345, &mut cx) {
+            Poll::Ready(meta) => assert!(meta.result.is_err()),
+            Poll::Pending => panic!("untracked operation must not be pending"),
+        }
+    }
+}
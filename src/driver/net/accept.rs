@@ -0,0 +1,88 @@
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
+use crate::net::addr::{self, RawAddr};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::{OwnedFd, RawFd};
+
+pub(crate) struct Accept {
+    fd: RawFd,
+    // The kernel writes the peer address and its length here after the op
+    // is submitted, boxed so they stay put while the op moves.
+    addr: Box<RawAddr>,
+}
+
+/// Multishot accept (5.19+): one entry that completes with a new connection
+/// each time, until it fails or is cancelled.
+pub(crate) struct AcceptMulti {
+    fd: RawFd,
+}
+
+impl Op<Accept> {
+    /// Accept a connection on the listening socket `fd`, close-on-exec.
+    pub(crate) fn accept(fd: RawFd) -> io::Result<Op<Accept>> {
+        // Zeroed is a valid sockaddr_storage.
+        let storage = unsafe { std::mem::zeroed() };
+        let len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        Op::submit_with(Accept {
+            fd,
+            addr: Box::new((storage, len)),
+        })
+    }
+
+    pub(crate) async fn result(self) -> io::Result<(OwnedFd, SocketAddr)> {
+        let (fd, (storage, len)) = self.result_raw().await?;
+        Ok((fd, addr::from_raw(&storage, len)?))
+    }
+
+    /// The connection with the peer address of whatever family it is.
+    pub(crate) async fn result_raw(self) -> io::Result<(OwnedFd, RawAddr)> {
+        let completion = self.await;
+        let fd = completion.meta.result?;
+        let fd = fd.into_owned().expect("accept returns an fd");
+        Ok((fd, *completion.data.addr))
+    }
+}
+
+impl Op<AcceptMulti> {
+    pub(crate) fn accept_multi(fd: RawFd) -> io::Result<Op<AcceptMulti>> {
+        Op::submit_with(AcceptMulti { fd })
+    }
+
+    /// Whether the ring of the current thread accepts multishot, the probe
+    /// has no flag for it. Socket came with the same release.
+    pub(crate) fn is_accept_multi_supported() -> bool {
+        driver::CURRENT
+            .try_with(|inner| inner.is_supported(opcode::Socket::CODE))
+            .unwrap_or(false)
+    }
+}
+
+impl Mappable for AcceptMulti {
+    // Connections queued and never taken are closed with the op.
+    const RET_IS_FD: bool = true;
+    const MULTISHOT: bool = true;
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::AcceptMulti::new(types::Fd(self.fd))
+            .flags(libc::SOCK_CLOEXEC)
+            .build()
+    }
+}
+
+impl Mappable for Accept {
+    // A connection accepted after the op is dropped is closed, not leaked.
+    const RET_IS_FD: bool = true;
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (storage, len) = &mut *self.addr;
+        opcode::Accept::new(
+            types::Fd(self.fd),
+            (storage as *mut libc::sockaddr_storage).cast(),
+            len,
+        )
+        .flags(libc::SOCK_CLOEXEC)
+        .build()
+    }
+}
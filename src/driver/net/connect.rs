@@ -0,0 +1,42 @@
+use crate::driver::op::{Mappable, Op};
+use crate::fs::File;
+use crate::net::addr::RawAddr;
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::AsRawFd;
+
+pub(crate) struct Connect {
+    // Owned by the op, so it is only closed once the kernel is done.
+    socket: File,
+    // Boxed so the kernel reads it at a stable address while the op moves.
+    addr: Box<RawAddr>,
+}
+
+impl Op<Connect> {
+    /// Connect `socket` to `addr`, of the family of the socket.
+    pub(crate) fn connect(socket: File, addr: RawAddr) -> io::Result<Op<Connect>> {
+        Op::submit_with(Connect {
+            socket,
+            addr: Box::new(addr),
+        })
+    }
+
+    /// The socket, once connected.
+    pub(crate) async fn result(self) -> io::Result<File> {
+        let completion = self.await;
+        completion.meta.result?;
+        Ok(completion.data.socket)
+    }
+}
+
+impl Mappable for Connect {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (storage, len) = &*self.addr;
+        opcode::Connect::new(
+            types::Fd(self.socket.as_raw_fd()),
+            (storage as *const libc::sockaddr_storage).cast(),
+            *len,
+        )
+        .build()
+    }
+}
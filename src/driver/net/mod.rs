@@ -0,0 +1,6 @@
+pub(crate) mod accept;
+mod connect;
+pub(crate) mod msg;
+pub(crate) mod recv;
+mod send;
+mod send_zc;
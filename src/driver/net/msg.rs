@@ -0,0 +1,312 @@
+use crate::buf::{BufResult, IoBuf, IoBufMut};
+use crate::driver::buf_group::{BorrowedBuf, BufGroup};
+use crate::driver::op::{CompletionMeta, Mappable, Op};
+use crate::net::addr::{self, RawAddr};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::RawFd;
+
+// What the header of a msg op points at. Boxed, the kernel reads and
+// writes it at the address it was given while the op moves.
+struct Header {
+    msg: libc::msghdr,
+    iov: libc::iovec,
+    addr: libc::sockaddr_storage,
+}
+
+impl Header {
+    // `ctrl_len` bytes of `ctrl` are sent, or its capacity received into.
+    fn new(
+        ptr: *mut u8,
+        len: usize,
+        addr: Option<RawAddr>,
+        ctrl: &mut Vec<u8>,
+        ctrl_len: usize,
+    ) -> Box<Header> {
+        // Zeroed is a valid msghdr and sockaddr_storage, the pointers are set
+        // once the header has its place.
+        let mut header: Box<Header> = Box::new(unsafe { std::mem::zeroed() });
+        header.iov = libc::iovec {
+            iov_base: ptr.cast(),
+            iov_len: len,
+        };
+        let addr_len = match addr {
+            Some((storage, len)) => {
+                header.addr = storage;
+                len
+            }
+            // Room for the source address of a receive.
+            None => std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t,
+        };
+        header.msg.msg_name = (&mut header.addr as *mut libc::sockaddr_storage).cast();
+        header.msg.msg_namelen = addr_len;
+        header.msg.msg_iov = &mut header.iov;
+        header.msg.msg_iovlen = 1;
+        if ctrl_len > 0 {
+            // The heap part of the vec stays put when it moves with the op.
+            header.msg.msg_control = ctrl.as_mut_ptr().cast();
+            header.msg.msg_controllen = ctrl_len;
+        }
+        header
+    }
+}
+
+/// Send a message, to an address for unconnected sockets, with control
+/// data.
+pub(crate) struct SendMsg<T> {
+    fd: RawFd,
+    pub(crate) buf: T,
+    pub(crate) ctrl: Vec<u8>,
+    header: Box<Header>,
+}
+
+/// Receive a message with the address it came from and its control data.
+pub(crate) struct RecvMsg<T> {
+    fd: RawFd,
+    flags: i32,
+    pub(crate) buf: T,
+    pub(crate) ctrl: Vec<u8>,
+    header: Box<Header>,
+}
+
+/// A received message.
+pub(crate) struct Received {
+    /// The length of the whole message, more than the buffer holds when it
+    /// was truncated.
+    pub(crate) len: usize,
+    /// The source, empty for connected sockets of some families.
+    pub(crate) addr: RawAddr,
+}
+
+impl Received {
+    /// The source as an IP socket address.
+    pub(crate) fn source(&self) -> io::Result<SocketAddr> {
+        addr::from_raw(&self.addr.0, self.addr.1)
+    }
+}
+
+impl<T: IoBuf> Op<SendMsg<T>> {
+    /// Send `buf` with the control data `ctrl`, to `addr` unless the socket
+    /// is connected.
+    pub(crate) fn send_msg(
+        fd: RawFd,
+        buf: T,
+        addr: Option<RawAddr>,
+        mut ctrl: Vec<u8>,
+    ) -> Result<Op<SendMsg<T>>, (io::Error, SendMsg<T>)> {
+        // Only read through, the iovec has no const pointer.
+        let ptr = buf.read_ptr() as *mut u8;
+        let ctrl_len = ctrl.len();
+        let mut header = Header::new(ptr, buf.bytes_init(), addr, &mut ctrl, ctrl_len);
+        if addr.is_none() {
+            header.msg.msg_name = std::ptr::null_mut();
+            header.msg.msg_namelen = 0;
+        }
+        Op::submit_or_return(SendMsg {
+            fd,
+            buf,
+            ctrl,
+            header,
+        })
+    }
+
+    /// Wait for the send, returning the number of bytes sent.
+    pub(crate) async fn result(self) -> BufResult<usize, (T, Vec<u8>)> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, (completion.data.buf, completion.data.ctrl))
+    }
+}
+
+impl<T: IoBufMut> Op<RecvMsg<T>> {
+    /// Receive into `buf`, and control data into the capacity of `ctrl`.
+    /// `MSG_TRUNC` in `flags` reports the whole length of a datagram, on
+    /// stream sockets it would drop the bytes instead.
+    pub(crate) fn recv_msg(
+        fd: RawFd,
+        mut buf: T,
+        mut ctrl: Vec<u8>,
+        flags: i32,
+    ) -> Result<Op<RecvMsg<T>>, (io::Error, RecvMsg<T>)> {
+        ctrl.clear();
+        let ctrl_len = ctrl.capacity();
+        let header = Header::new(
+            buf.write_ptr(),
+            buf.bytes_total(),
+            None,
+            &mut ctrl,
+            ctrl_len,
+        );
+        Op::submit_or_return(RecvMsg {
+            fd,
+            flags,
+            buf,
+            ctrl,
+            header,
+        })
+    }
+
+    /// Wait for the message, the control data the kernel wrote is the
+    /// length of `ctrl` then.
+    pub(crate) async fn result(self) -> BufResult<Received, (T, Vec<u8>)> {
+        let completion = self.await;
+        let RecvMsg {
+            mut buf,
+            mut ctrl,
+            header,
+            ..
+        } = completion.data;
+        let n = match completion.meta.result {
+            Ok(n) => n.into_inner() as usize,
+            Err(e) => return (Err(e), (buf, ctrl)),
+        };
+        // The kernel filled what fit.
+        let filled = n.min(buf.bytes_total());
+        unsafe { buf.set_init(filled) };
+        let ctrl_len = header.msg.msg_controllen.min(ctrl.capacity());
+        unsafe { ctrl.set_len(ctrl_len) };
+        let received = Received {
+            len: n,
+            addr: (header.addr, header.msg.msg_namelen),
+        };
+        (Ok(received), (buf, ctrl))
+    }
+}
+
+/// Multishot receive of messages (6.0+) into buffers the kernel selects
+/// from `group`. Each buffer starts with an `io_uring_recvmsg_out` and the
+/// source address, the datagram follows.
+pub(crate) struct RecvMsgMulti {
+    fd: RawFd,
+    group: BufGroup,
+    // Only the lengths of name and control data are read, they lay out the
+    // buffers.
+    header: Box<libc::msghdr>,
+}
+
+impl Op<RecvMsgMulti> {
+    pub(crate) fn recv_msg_multi(fd: RawFd, group: &BufGroup) -> io::Result<Op<RecvMsgMulti>> {
+        Op::submit_with(RecvMsgMulti {
+            fd,
+            group: group.clone(),
+            header: Box::new(multi_header()),
+        })
+    }
+}
+
+// Room for an IPv4 or IPv6 source, no control data.
+fn multi_header() -> libc::msghdr {
+    // Zeroed is a valid msghdr.
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_namelen = std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+    msg
+}
+
+/// The datagram in a buffer of [`RecvMsgMulti`], with its whole length and
+/// source. The buffer is narrowed to the part of it that fit.
+pub(crate) fn parse_msg_out(mut buf: BorrowedBuf) -> io::Result<(BorrowedBuf, Received)> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed multishot message");
+    let header = multi_header();
+    let out = types::RecvMsgOut::parse(&buf, &header).map_err(|()| invalid())?;
+    let len = out.incoming_payload_len() as usize;
+    // Zeroed is a valid sockaddr_storage, the name fits in it.
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let name = out.name_data();
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            name.as_ptr(),
+            (&mut storage as *mut libc::sockaddr_storage).cast::<u8>(),
+            name.len(),
+        )
+    };
+    let namelen = (out.incoming_name_len() as usize).min(name.len());
+    let start = out.payload_data().as_ptr() as usize - buf.as_ptr() as usize;
+    let end = start + out.payload_data().len();
+    buf.narrow(start..end);
+    let received = Received {
+        len,
+        addr: (storage, namelen as libc::socklen_t),
+    };
+    Ok((buf, received))
+}
+
+impl<T: IoBuf> Mappable for SendMsg<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        // A peer that went away fails the send instead of raising SIGPIPE.
+        opcode::SendMsg::new(types::Fd(self.fd), &self.header.msg)
+            .flags(libc::MSG_NOSIGNAL as u32)
+            .build()
+    }
+}
+
+impl<T: IoBufMut> Mappable for RecvMsg<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        // Received fds are close-on-exec like every other opened here.
+        opcode::RecvMsg::new(types::Fd(self.fd), &mut self.header.msg)
+            .flags((self.flags | libc::MSG_CMSG_CLOEXEC) as u32)
+            .build()
+    }
+}
+
+impl Mappable for RecvMsgMulti {
+    const MULTISHOT: bool = true;
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::RecvMsgMulti::new(types::Fd(self.fd), &*self.header, self.group.bgid())
+            .flags(libc::MSG_TRUNC as u32)
+            .build()
+    }
+
+    fn discard(&mut self, meta: CompletionMeta) {
+        drop(self.group.take(meta));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::net::{CmsgBuilder, Cmsgs};
+    use crate::runtime::RuntimeBuilder;
+    use std::io::{Read, Write};
+    use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+
+    #[test]
+    fn pass_an_fd() {
+        let mut fds = [0; 2];
+        let flags = libc::SOCK_DGRAM | libc::SOCK_CLOEXEC;
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()) },
+            0
+        );
+        let (a, b) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        let (mut rx, tx) = std::io::pipe().unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let passed = rt.block_on(async {
+            let ctrl = CmsgBuilder::new().rights(&[tx.as_fd()]).build();
+            let op = Op::send_msg(a.as_raw_fd(), b"fd".to_vec(), None, ctrl);
+            let (n, _) = op.ok().unwrap().result().await;
+            assert_eq!(n.unwrap(), 2);
+            drop(tx);
+
+            let ctrl = Vec::with_capacity(crate::net::cmsg::space(4));
+            let op = Op::recv_msg(b.as_raw_fd(), Vec::with_capacity(8), ctrl, 0);
+            let (received, (buf, ctrl)) = op.ok().unwrap().result().await;
+            assert_eq!(received.unwrap().len, 2);
+            assert_eq!(buf, b"fd");
+            let mut cmsgs = Cmsgs::new(&ctrl);
+            let fds = cmsgs.next().unwrap().rights().unwrap();
+            assert!(cmsgs.next().is_none());
+            assert_eq!(fds.len(), 1);
+            // Opened for this side, owned here now.
+            unsafe { OwnedFd::from_raw_fd(fds[0]) }
+        });
+
+        // The passed fd is the write end of the pipe.
+        std::fs::File::from(passed).write_all(b"through").unwrap();
+        let mut got = String::new();
+        rx.read_to_string(&mut got).unwrap();
+        assert_eq!(got, "through");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::buf::{BufResult, IoBufMut};
+use crate::driver;
+use crate::driver::buf_group::BufGroup;
+use crate::driver::op::{CompletionMeta, Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Receive from a connected socket, with `MSG_*` flags.
+pub(crate) struct Recv<T> {
+    fd: RawFd,
+    flags: i32,
+    pub(crate) buf: T,
+}
+
+/// Receive into a buffer of a provided buffer group.
+pub(crate) struct RecvFromGroup {
+    fd: RawFd,
+    group: BufGroup,
+}
+
+/// Multishot receive (6.0+): one entry that completes each time data
+/// arrives, into a buffer the kernel selects from `group`. It ends on an
+/// error, at the end of the stream, or when the group runs dry.
+pub(crate) struct RecvMulti {
+    fd: RawFd,
+    group: BufGroup,
+}
+
+impl<T: IoBufMut> Op<Recv<T>> {
+    pub(crate) fn recv(fd: RawFd, buf: T, flags: i32) -> Result<Op<Recv<T>>, (io::Error, Recv<T>)> {
+        Op::submit_or_return(Recv { fd, flags, buf })
+    }
+
+    /// Wait for the receive and mark what the kernel filled as initialized.
+    /// 0 once the peer shut down its write side. With `MSG_TRUNC` a
+    /// datagram too long for the buffer counts whole.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let mut buf = completion.data.buf;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        if let Ok(n) = n {
+            // The kernel filled the first `n` bytes, or all of them.
+            let filled = n.min(buf.bytes_total());
+            unsafe { buf.set_init(filled) };
+        }
+        (n, buf)
+    }
+}
+
+impl Op<RecvFromGroup> {
+    /// Receive into a buffer the kernel selects from `group`.
+    pub(crate) fn recv_from_group(fd: RawFd, group: &BufGroup) -> io::Result<Op<RecvFromGroup>> {
+        Op::submit_with(RecvFromGroup {
+            fd,
+            group: group.clone(),
+        })
+    }
+}
+
+impl Op<RecvMulti> {
+    pub(crate) fn recv_multi(fd: RawFd, group: &BufGroup) -> io::Result<Op<RecvMulti>> {
+        Op::submit_with(RecvMulti {
+            fd,
+            group: group.clone(),
+        })
+    }
+
+    /// Whether the ring of the current thread receives multishot, the probe
+    /// has no flag for it. Zero copy sends came with the same release.
+    pub(crate) fn is_recv_multi_supported() -> bool {
+        driver::CURRENT
+            .try_with(|inner| inner.is_supported(opcode::SendZc::CODE))
+            .unwrap_or(false)
+    }
+}
+
+impl<T: IoBufMut> Mappable for Recv<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let len = self.buf.bytes_total() as u32;
+        opcode::Recv::new(types::Fd(self.fd), self.buf.write_ptr(), len)
+            .flags(self.flags)
+            .build()
+    }
+}
+
+impl Mappable for RecvFromGroup {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Recv::new(
+            types::Fd(self.fd),
+            std::ptr::null_mut(),
+            self.group.buf_len() as u32,
+        )
+        .buf_group(self.group.bgid())
+        .build()
+        .flags(squeue::Flags::BUFFER_SELECT)
+    }
+}
+
+impl Mappable for RecvMulti {
+    const MULTISHOT: bool = true;
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::RecvMulti::new(types::Fd(self.fd), self.group.bgid()).build()
+    }
+
+    // The buffer of a result nobody takes goes back to the group.
+    fn discard(&mut self, meta: CompletionMeta) {
+        drop(self.group.take(meta));
+    }
+}
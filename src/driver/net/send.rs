@@ -0,0 +1,34 @@
+use crate::buf::{BufResult, IoBuf};
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Send on a connected socket.
+pub(crate) struct Send<T> {
+    fd: RawFd,
+    pub(crate) buf: T,
+}
+
+impl<T: IoBuf> Op<Send<T>> {
+    pub(crate) fn send(fd: RawFd, buf: T) -> Result<Op<Send<T>>, (io::Error, Send<T>)> {
+        Op::submit_or_return(Send { fd, buf })
+    }
+
+    /// Wait for the send, returning the number of bytes sent.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl<T: IoBuf> Mappable for Send<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (ptr, len) = (self.buf.read_ptr(), self.buf.bytes_init() as u32);
+        // A peer that went away fails the send instead of raising SIGPIPE.
+        opcode::Send::new(types::Fd(self.fd), ptr, len)
+            .flags(libc::MSG_NOSIGNAL)
+            .build()
+    }
+}
@@ -0,0 +1,85 @@
+use crate::buf::{BufResult, IoBuf};
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::RawFd;
+
+/// Zero copy send on a socket (6.0+), a plain send on older kernels.
+///
+/// The kernel sends from the pages of `buf` directly, it completes twice:
+/// with the number of bytes sent, then with a notification once it no
+/// longer uses them. The op only finishes with the second.
+pub(crate) struct SendZc<T> {
+    fd: RawFd,
+    zero_copy: bool,
+    pub(crate) buf: T,
+}
+
+impl<T: IoBuf> Op<SendZc<T>> {
+    pub(crate) fn send_zc(fd: RawFd, buf: T) -> Result<Op<SendZc<T>>, (io::Error, SendZc<T>)> {
+        let zero_copy = driver::CURRENT
+            .try_with(|inner| inner.is_supported(opcode::SendZc::CODE))
+            .unwrap_or(false);
+        Op::submit_or_return(SendZc { fd, zero_copy, buf })
+    }
+
+    /// Wait until the kernel is done with the buffer, returning the number
+    /// of bytes sent.
+    pub(crate) async fn result(self) -> BufResult<usize, T> {
+        let completion = self.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        (n, completion.data.buf)
+    }
+}
+
+impl<T: IoBuf> Mappable for SendZc<T> {
+    fn uring_op(&mut self) -> squeue::Entry {
+        let (ptr, len) = (self.buf.read_ptr(), self.buf.bytes_init() as u32);
+        // A peer that went away fails the send instead of raising SIGPIPE.
+        match self.zero_copy {
+            true => opcode::SendZc::new(types::Fd(self.fd), ptr, len)
+                .flags(libc::MSG_NOSIGNAL)
+                .build(),
+            false => opcode::Send::new(types::Fd(self.fd), ptr, len)
+                .flags(libc::MSG_NOSIGNAL)
+                .build(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn send_zc_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let fd = client.as_raw_fd();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let data: Vec<u8> = (0..50_000u32).map(|i| i as u8).collect();
+            let Ok(op) = Op::send_zc(fd, data.clone()) else {
+                panic!("send_zc submits");
+            };
+            let (n, buf) = op.result().await;
+            let n = n.unwrap();
+            assert!(n > 0);
+            assert_eq!(buf, data);
+
+            let mut received = vec![0; n];
+            server.read_exact(&mut received).unwrap();
+            assert_eq!(received, data[..n]);
+        });
+    }
+}
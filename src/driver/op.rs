@@ -1,21 +1,28 @@
+use crate::driver;
+use crate::driver::uring::Discard;
+use io_uring::cqueue;
+use std::any::Any;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::task::ready;
 use std::{
     future::Future,
     io,
     pin::Pin,
     task::{Context, Poll},
 };
-use std::task::ready;
-use crate::driver;
-
 
+#[cfg(feature = "io-uring-cmd")]
+mod uring_cmd;
+#[cfg(feature = "io-uring-cmd")]
+pub use uring_cmd::{CmdBuf, CmdCompletion, UringCmd};
 
 /// In-flight operation
 pub(crate) struct Op<T: 'static + Mappable> {
     // Driver running the operation
     pub(super) driver: driver::Inner,
 
-    // Operation index in the slab(useless for legacy)
-    pub(super) index: usize,
+    // user_data of the operation, its slab index and generation
+    pub(super) user_data: u64,
 
     // Per-operation data
     pub(super) data: Option<T>,
@@ -32,8 +39,43 @@ pub(crate) struct Completion<T> {
 #[derive(Debug)]
 pub(crate) struct CompletionMeta {
     pub(crate) result: io::Result<MaybeFd>,
+    pub(crate) flags: CqeFlags,
+    // Extra data of 32-byte CQEs, zero on regular rings.
     #[allow(unused)]
-    pub(crate) flags: u32,
+    pub(crate) big_cqe: [u64; 2],
+}
+
+/// Flags of a completion queue entry.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CqeFlags(u32);
+
+impl CqeFlags {
+    // IORING_CQE_F_NOTIF, the io-uring crate has no helper for it.
+    const NOTIF: u32 = 1 << 3;
+
+    #[inline]
+    pub const fn from_bits(bits: u32) -> Self {
+        CqeFlags(bits)
+    }
+
+    /// A multishot operation will post more completions (`IORING_CQE_F_MORE`).
+    #[inline]
+    pub fn more(self) -> bool {
+        cqueue::more(self.0)
+    }
+
+    /// Id of the buffer the kernel selected from a provided buffer group
+    /// (`IORING_CQE_F_BUFFER`).
+    #[inline]
+    pub fn buffer_id(self) -> Option<u16> {
+        cqueue::buffer_select(self.0)
+    }
+
+    /// The completion is a zero copy notification (`IORING_CQE_F_NOTIF`).
+    #[inline]
+    pub fn notif(self) -> bool {
+        self.0 & Self::NOTIF != 0
+    }
 }
 
 /// MaybeFd is a wrapper for fd or a normal number. If it is marked as fd, it will close the fd when
@@ -48,6 +90,7 @@ pub(crate) struct MaybeFd {
     fd: u32,
 }
 
+#[allow(unused)]
 impl MaybeFd {
     #[inline]
     pub(crate) unsafe fn new_result(fdr: io::Result<u32>, is_fd: bool) -> io::Result<Self> {
@@ -81,6 +124,15 @@ impl MaybeFd {
         fd
     }
 
+    /// Take ownership of the fd, `None` if the result is a number.
+    #[inline]
+    pub(crate) fn into_owned(self) -> Option<OwnedFd> {
+        let is_fd = self.is_fd;
+        let fd = self.into_inner();
+        // An fd result was handed to us by the kernel and is closed by no one else.
+        is_fd.then(|| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+    }
+
     #[inline]
     pub(crate) const fn zero() -> Self {
         Self {
@@ -114,18 +166,33 @@ impl Drop for MaybeFd {
 pub(crate) trait Mappable {
     const RET_IS_FD: bool = false;
     const SKIP_CANCEL: bool = false;
+    /// The op only fits in a 128-byte SQE. Submitting it on a ring without
+    /// `IORING_SETUP_SQE128` fails with [`io::ErrorKind::Unsupported`].
+    const SQE128: bool = false;
+    /// The op completes many times, see [`Op::poll_next`].
+    const MULTISHOT: bool = false;
+    /// Give back what a result of a dropped multishot op holds, like the
+    /// buffer the kernel selected. Fds are closed without this.
+    fn discard(&mut self, _meta: CompletionMeta) {}
     fn uring_op(&mut self) -> io_uring::squeue::Entry;
-
+    /// Build the entry for a ring with 128-byte SQEs.
+    fn uring_op128(&mut self) -> io_uring::squeue::Entry128 {
+        self.uring_op().into()
+    }
 }
 
-
-
 impl<T: Mappable> Op<T> {
     /// Submit an operation to uring.
     ///
     /// `state` is stored during the operation tracking any state submitted to
     /// the kernel.
     pub(super) fn submit_with(data: T) -> io::Result<Op<T>> {
+        Op::submit_or_return(data).map_err(|(e, _)| e)
+    }
+
+    /// Submit an operation to uring, handing `data` back on failure so the
+    /// buffers it owns are not lost.
+    pub(crate) fn submit_or_return(data: T) -> Result<Op<T>, (io::Error, T)> {
         driver::CURRENT.with(|this| this.submit_with(data))
     }
 
@@ -141,11 +208,30 @@ impl<T: Mappable> Op<T> {
 
     pub(crate) fn op_canceller(&self) -> OpCanceller {
         OpCanceller {
-            index: self.index,
+            driver: self.driver.clone(),
+            user_data: self.user_data,
         }
     }
 }
 
+impl<T: Mappable> Op<T> {
+    /// The next result of a multishot op. The one without `more` is its
+    /// last, after it the op is over and polling again is a bug.
+    pub(crate) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<CompletionMeta> {
+        debug_assert!(T::MULTISHOT, "only multishot ops complete many times");
+        let meta = ready!(self.driver.poll_next(self.user_data, cx));
+        if !meta.flags.more() {
+            self.user_data = u64::MAX;
+        }
+        Poll::Ready(meta)
+    }
+
+    /// Whether a multishot op gave its last result.
+    pub(crate) fn is_terminated(&self) -> bool {
+        self.user_data == u64::MAX
+    }
+}
+
 impl<T> Future for Op<T>
 where
     T: Unpin + Mappable + 'static,
@@ -155,8 +241,8 @@ where
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let me = &mut *self;
         let data_mut = me.data.as_mut().expect("unexpected operation state");
-        let meta = ready!(me.driver.poll_op::<T>(data_mut, me.index, cx));
-        me.index = usize::MAX;
+        let meta = ready!(me.driver.poll_op::<T>(data_mut, me.user_data, cx));
+        me.user_data = u64::MAX;
         let data = me.data.take().expect("unexpected operation state");
         Poll::Ready(Completion { data, meta })
     }
@@ -165,18 +251,59 @@ where
 impl<T: Mappable> Drop for Op<T> {
     #[inline]
     fn drop(&mut self) {
+        let discard = T::MULTISHOT.then_some(discard::<T> as Discard);
         self.driver
-            .drop_op(self.index, &mut self.data, T::SKIP_CANCEL);
+            .drop_op(self.user_data, &mut self.data, T::SKIP_CANCEL, discard);
+    }
+}
+
+fn discard<T: Mappable + 'static>(data: &mut dyn Any, meta: CompletionMeta) {
+    if let Some(data) = data.downcast_mut::<T>() {
+        data.discard(meta);
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Hash)]
+/// Cancels an operation by its user_data. The generation in it keeps a
+/// stale one from matching an operation that reused the slot, so cancelling
+/// after completion does nothing.
+#[derive(Clone)]
 pub(crate) struct OpCanceller {
-    pub(super) index: usize,
+    driver: driver::Inner,
+    pub(super) user_data: u64,
 }
 
 impl OpCanceller {
-    pub(crate) unsafe fn cancel(&self) {
-        super::CURRENT.with(|inner| inner.cancel_op(self))
+    /// Push an `AsyncCancel`, submitted with the next batch. The operation
+    /// still completes, with `ECANCELED` unless it finished first.
+    pub(crate) fn cancel(&self) {
+        self.driver.cancel_op(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // From include/uapi/linux/io_uring.h
+    const F_BUFFER: u32 = 1 << 0;
+    const F_MORE: u32 = 1 << 1;
+    const F_NOTIF: u32 = 1 << 3;
+    const BUFFER_SHIFT: u32 = 16;
+
+    #[test]
+    fn cqe_flags() {
+        let flags = CqeFlags::from_bits(0);
+        assert!(!flags.more());
+        assert_eq!(flags.buffer_id(), None);
+        assert!(!flags.notif());
+
+        let flags = CqeFlags::from_bits(F_MORE | F_BUFFER | (9 << BUFFER_SHIFT));
+        assert!(flags.more());
+        assert_eq!(flags.buffer_id(), Some(9));
+        assert!(!flags.notif());
+
+        let flags = CqeFlags::from_bits(F_NOTIF);
+        assert!(flags.notif());
+        assert!(!flags.more());
     }
 }
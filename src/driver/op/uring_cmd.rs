@@ -0,0 +1,191 @@
+//! Passthrough commands for NVMe, ublk and other drivers implementing
+//! `IORING_OP_URING_CMD`.
+
+use crate::driver::{
+    self,
+    op::{Mappable, Op},
+};
+use io_uring::{opcode, squeue, types};
+use std::io;
+use std::os::fd::{AsRawFd, RawFd};
+
+mod sealed {
+    use io_uring::{squeue, types};
+
+    pub trait Sealed {
+        const SQE128: bool;
+        fn entry(&self, fd: types::Fd, cmd_op: u32) -> squeue::Entry;
+        fn entry128(&self, fd: types::Fd, cmd_op: u32) -> squeue::Entry128;
+    }
+}
+
+/// Command payload of a [`UringCmd`], 16 bytes fit a regular SQE and 80 bytes
+/// need a ring built with `setup_sqe128`.
+pub trait CmdBuf: sealed::Sealed + Unpin + 'static {}
+
+impl CmdBuf for [u8; 16] {}
+impl CmdBuf for [u8; 80] {}
+
+impl sealed::Sealed for [u8; 16] {
+    const SQE128: bool = false;
+
+    fn entry(&self, fd: types::Fd, cmd_op: u32) -> squeue::Entry {
+        opcode::UringCmd16::new(fd, cmd_op).cmd(*self).build()
+    }
+
+    fn entry128(&self, fd: types::Fd, cmd_op: u32) -> squeue::Entry128 {
+        self.entry(fd, cmd_op).into()
+    }
+}
+
+impl sealed::Sealed for [u8; 80] {
+    const SQE128: bool = true;
+
+    fn entry(&self, _fd: types::Fd, _cmd_op: u32) -> squeue::Entry {
+        unreachable!("80-byte commands are only submitted on rings with 128-byte SQEs")
+    }
+
+    fn entry128(&self, fd: types::Fd, cmd_op: u32) -> squeue::Entry128 {
+        opcode::UringCmd80::new(fd, cmd_op).cmd(*self).build()
+    }
+}
+
+/// A file or device specific command, akin to `ioctl(2)`.
+pub struct UringCmd<C: CmdBuf> {
+    fd: RawFd,
+    cmd_op: u32,
+    cmd: C,
+}
+
+/// Completion of a [`UringCmd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CmdCompletion {
+    /// The non-negative result of the command.
+    pub result: u32,
+    /// Extra completion data, only present on rings built with `setup_cqe32`.
+    pub big_cqe: Option<[u64; 2]>,
+}
+
+impl<C: CmdBuf> UringCmd<C> {
+    /// Create a command `cmd_op` with the opaque payload `cmd` for `fd`.
+    ///
+    /// # Safety
+    /// The target driver interprets the payload, any memory it points to must
+    /// stay valid until the command completes, even if it is cancelled.
+    pub unsafe fn new(fd: &impl AsRawFd, cmd_op: u32, cmd: C) -> Self {
+        UringCmd {
+            fd: fd.as_raw_fd(),
+            cmd_op,
+            cmd,
+        }
+    }
+
+    /// Submit the command and wait for its completion.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] for 80-byte commands on a
+    /// ring without 128-byte SQEs.
+    pub async fn submit(self) -> io::Result<CmdCompletion> {
+        let cqe32 = driver::CURRENT.with(|inner| inner.is_cqe32());
+        let complete = Op::submit_with(self)?.await;
+        let result = complete.meta.result?.into_inner();
+        Ok(CmdCompletion {
+            result,
+            big_cqe: cqe32.then_some(complete.meta.big_cqe),
+        })
+    }
+}
+
+impl<C: CmdBuf> Mappable for UringCmd<C> {
+    const SQE128: bool = C::SQE128;
+
+    fn uring_op(&mut self) -> squeue::Entry {
+        self.cmd.entry(types::Fd(self.fd), self.cmd_op)
+    }
+
+    fn uring_op128(&mut self) -> squeue::Entry128 {
+        self.cmd.entry128(types::Fd(self.fd), self.cmd_op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::builder::RuntimeBuilder;
+    use std::net::UdpSocket;
+
+    // From include/uapi/linux/io_uring.h, sockets answer it since 6.7.
+    const SOCKET_URING_OP_SIOCINQ: u32 = 0;
+
+    fn sqe_bytes<T>(entry: &T) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(entry as *const T as *const u8, std::mem::size_of::<T>())
+        }
+    }
+
+    fn payload<const N: usize>() -> [u8; N] {
+        std::array::from_fn(|i| i as u8 + 1)
+    }
+
+    #[test]
+    fn cmd16_sqe_layout() {
+        let fd = std::fs::File::open("/dev/null").unwrap();
+        let mut cmd = unsafe { UringCmd::new(&fd, 0xdead_beef, payload::<16>()) };
+        let sqe = cmd.uring_op();
+        let bytes = sqe_bytes(&sqe);
+        assert_eq!(bytes.len(), 64);
+        assert_eq!(bytes[0], opcode::UringCmd16::CODE);
+        assert_eq!(&bytes[4..8], fd.as_raw_fd().to_ne_bytes());
+        assert_eq!(&bytes[8..12], 0xdead_beef_u32.to_ne_bytes());
+        assert_eq!(&bytes[48..64], payload::<16>());
+    }
+
+    #[test]
+    fn cmd80_sqe_layout() {
+        let fd = std::fs::File::open("/dev/null").unwrap();
+        let mut cmd = unsafe { UringCmd::new(&fd, 7, payload::<80>()) };
+        let sqe = cmd.uring_op128();
+        let bytes = sqe_bytes(&sqe);
+        assert_eq!(bytes.len(), 128);
+        assert_eq!(bytes[0], opcode::UringCmd80::CODE);
+        assert_eq!(&bytes[4..8], fd.as_raw_fd().to_ne_bytes());
+        assert_eq!(&bytes[8..12], 7_u32.to_ne_bytes());
+        assert_eq!(&bytes[48..128], payload::<80>());
+    }
+
+    #[test]
+    fn socket_inq() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .send_to(b"hello", socket.local_addr().unwrap())
+            .unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+            .setup_cqe32(true)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let cmd = unsafe { UringCmd::new(&socket, SOCKET_URING_OP_SIOCINQ, [0; 16]) };
+            match cmd.submit().await {
+                Ok(complete) => {
+                    assert_eq!(complete.result, 5);
+                    assert_eq!(complete.big_cqe, Some([0; 2]));
+                }
+                // Older kernels have no socket commands.
+                Err(e) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::EINVAL)) => {}
+                Err(e) => panic!("{e}"),
+            }
+        });
+    }
+
+    #[test]
+    fn cmd80_needs_sqe128() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let cmd = unsafe { UringCmd::new(&socket, SOCKET_URING_OP_SIOCINQ, [0; 80]) };
+            let err = cmd.submit().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        });
+    }
+}
@@ -0,0 +1,32 @@
+use crate::driver::op::{Mappable, Op};
+use io_uring::{opcode, squeue, types};
+use std::future::Future;
+use std::io;
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Wait until `fd` has one of `events`, like `poll(2)` on a single fd.
+pub(crate) struct PollAdd {
+    fd: RawFd,
+    events: u32,
+}
+
+impl Op<PollAdd> {
+    pub(crate) fn poll_add(fd: RawFd, events: u32) -> io::Result<Op<PollAdd>> {
+        Op::submit_with(PollAdd { fd, events })
+    }
+
+    /// Poll for the events that are ready. `POLLERR` and `POLLHUP` are among
+    /// them when they happen, asked for or not.
+    pub(crate) fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<u32>> {
+        let completion = ready!(Pin::new(self).poll(cx));
+        Poll::Ready(completion.meta.result.map(|n| n.into_inner()))
+    }
+}
+
+impl Mappable for PollAdd {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::PollAdd::new(types::Fd(self.fd), self.events).build()
+    }
+}
@@ -0,0 +1,68 @@
+use crate::driver::op::{Mappable, Op};
+use crate::driver::util::timespec;
+use io_uring::{opcode, squeue, types::Timespec};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+/// Complete after a relative duration.
+pub(crate) struct Timeout {
+    // Boxed so the pointer in the entry stays put while the op moves.
+    timespec: Box<Timespec>,
+}
+
+impl Op<Timeout> {
+    pub(crate) fn timeout(duration: Duration) -> io::Result<Op<Timeout>> {
+        Op::submit_with(Timeout {
+            timespec: Box::new(timespec(duration)),
+        })
+    }
+
+    /// Poll for the expiry, the kernel reports it as `ETIME`.
+    pub(crate) fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let completion = ready!(Pin::new(self).poll(cx));
+        match completion.meta.result {
+            Ok(_) => Poll::Ready(Ok(())),
+            Err(e) if e.raw_os_error() == Some(libc::ETIME) => Poll::Ready(Ok(())),
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl Mappable for Timeout {
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::Timeout::new(&*self.timespec).build()
+    }
+}
+
+/// Move a timeout in flight (`IORING_TIMEOUT_UPDATE`, 5.11+).
+pub(crate) struct TimeoutUpdate {
+    user_data: u64,
+    timespec: Box<Timespec>,
+}
+
+impl Op<Timeout> {
+    /// Fire `duration` from now instead, completing with `ETIME` as before.
+    /// Nobody waits for the update: one that comes after the expiry, or on
+    /// a kernel without it, leaves the timeout as it was.
+    pub(crate) fn update(&self, duration: Duration) -> io::Result<()> {
+        let update = Op::submit_with(TimeoutUpdate {
+            user_data: self.user_data,
+            timespec: Box::new(timespec(duration)),
+        })?;
+        // The driver keeps the timespec until the update completes.
+        drop(update);
+        Ok(())
+    }
+}
+
+impl Mappable for TimeoutUpdate {
+    // Nothing to cancel, it completes right away.
+    const SKIP_CANCEL: bool = true;
+
+    fn uring_op(&mut self) -> squeue::Entry {
+        opcode::TimeoutUpdate::new(self.user_data, &*self.timespec).build()
+    }
+}
@@ -0,0 +1,144 @@
+//! SQE/CQE trace logging, enabled with the `uring-trace` feature.
+//!
+//! Every traced event of a driver gets the next number of its sequence, so
+//! the log of one ring reads in submission and completion order. Completions
+//! are matched to their submissions by `user_data`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use io_uring::cqueue;
+use log::Level;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) struct Tracer {
+    id: usize,
+    seq: u64,
+}
+
+impl Tracer {
+    pub(crate) fn new() -> Self {
+        Tracer {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            seq: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn sqe<S>(&mut self, entry: &S) {
+        if !log::log_enabled!(Level::Trace) {
+            return;
+        }
+        // Both entry sizes start with the 64-byte io_uring_sqe: opcode at 0,
+        // flags at 1, fd at 4 and user_data at 32.
+        let sqe = unsafe { &*(entry as *const S as *const [u8; 64]) };
+        let fd = i32::from_ne_bytes(sqe[4..8].try_into().unwrap());
+        let user_data = u64::from_ne_bytes(sqe[32..40].try_into().unwrap());
+        self.seq += 1;
+        log::trace!(
+            "uring[{}] #{} submit opcode={} fd={} flags={:#x} user_data={}",
+            self.id,
+            self.seq,
+            sqe[0],
+            fd,
+            sqe[1],
+            user_data
+        );
+    }
+
+    #[inline]
+    pub(crate) fn cqe(&mut self, cqe: &cqueue::Entry) {
+        if !log::log_enabled!(Level::Trace) {
+            return;
+        }
+        self.seq += 1;
+        log::trace!(
+            "uring[{}] #{} complete user_data={} res={} flags={:#x}",
+            self.id,
+            self.seq,
+            cqe.user_data(),
+            cqe.result(),
+            cqe.flags()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::driver::op::Op;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::builder::RuntimeBuilder;
+    use io_uring::opcode;
+    use log::{Log, Metadata, Record};
+    use std::cell::RefCell;
+    use std::os::fd::AsRawFd;
+
+    thread_local! {
+        static LINES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    // Keeps the trace lines of the logging thread, tests run in parallel.
+    struct Capture;
+
+    impl Log for Capture {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.target() == module_path!().trim_end_matches("::tests")
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if self.enabled(record.metadata()) {
+                LINES.with(|lines| lines.borrow_mut().push(record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture;
+
+    fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+        line.split(' ')
+            .find_map(|kv| kv.strip_prefix(name)?.strip_prefix('='))
+    }
+
+    #[test]
+    fn openat_submit_and_complete() {
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let fd = rt.block_on(async {
+            let op = Op::openat(
+                libc::AT_FDCWD,
+                "/dev/null",
+                libc::O_RDONLY | libc::O_CLOEXEC,
+                0,
+            );
+            op.unwrap().await.meta.result.unwrap().into_owned().unwrap()
+        });
+
+        let lines = LINES.with(|lines| lines.take());
+        let opcode = opcode::OpenAt::CODE.to_string();
+        let submit = lines
+            .iter()
+            .find(|line| line.contains(" submit ") && field(line, "opcode") == Some(&opcode))
+            .expect("openat submission is traced");
+        assert_eq!(
+            field(submit, "fd"),
+            Some(libc::AT_FDCWD.to_string().as_str())
+        );
+        let user_data = field(submit, "user_data").unwrap();
+
+        let complete = lines
+            .iter()
+            .find(|line| line.contains(" complete ") && field(line, "user_data") == Some(user_data))
+            .expect("openat completion is traced");
+        assert_eq!(
+            field(complete, "res"),
+            Some(fd.as_raw_fd().to_string().as_str())
+        );
+
+        let seq = |line: &str| -> u64 { line.split(' ').nth(1).unwrap()[1..].parse().unwrap() };
+        assert!(seq(submit) < seq(complete));
+    }
+}
@@ -1,13 +1,16 @@
 //! Uring state lifecycle.
 //! Partly borrow from tokio-uring.
 
+use io_uring::cqueue;
 use std::{
+    any::Any,
+    collections::VecDeque,
     io,
     task::{Context, Poll, Waker},
 };
 
 use crate::{
-    driver::op::{CompletionMeta, MaybeFd},
+    driver::op::{CompletionMeta, CqeFlags, MaybeFd},
     utils::slab::Ref,
 };
 
@@ -23,38 +26,122 @@ enum Lifecycle {
     #[allow(dead_code)]
     Ignored(Box<dyn std::any::Any>),
 
+    /// The operation has a result, but the kernel still holds its buffer
+    /// until a notification completion (`IORING_CQE_F_NOTIF`) follows, like
+    /// for zero copy sends.
+    Notifying(io::Result<u32>, u32, [u64; 2], Option<Waker>),
+
     /// The operation has completed.
-    Completed(io::Result<MaybeFd>, u32),
+    Completed(io::Result<MaybeFd>, u32, [u64; 2]),
+
+    /// A multishot operation the kernel is done with, its last results are
+    /// still queued.
+    Finished,
 }
 
+type Queued = (io::Result<MaybeFd>, u32, [u64; 2]);
+
+/// Gives back what a result nobody takes holds, with the data of the op.
+pub(crate) type Discard = fn(&mut dyn Any, CompletionMeta);
+
 pub(crate) struct MaybeFdLifecycle {
     is_fd: bool,
+    multishot: bool,
+    // Results of a multishot operation not taken yet, in order. An fd among
+    // them is closed when it is dropped unclaimed.
+    queue: VecDeque<Queued>,
+    // Set when a multishot operation is dropped, for the results after.
+    discard: Option<Discard>,
     lifecycle: Lifecycle,
 }
 
 impl MaybeFdLifecycle {
     #[inline]
-    pub(crate) const fn new(is_fd: bool) -> Self {
+    pub(crate) const fn new(is_fd: bool, multishot: bool) -> Self {
         Self {
             is_fd,
+            multishot,
+            queue: VecDeque::new(),
+            discard: None,
             lifecycle: Lifecycle::Submitted,
         }
     }
+
+    /// Whether the kernel has returned the operation.
+    #[inline]
+    pub(crate) fn is_completed(&self) -> bool {
+        matches!(
+            self.lifecycle,
+            Lifecycle::Completed(..) | Lifecycle::Finished
+        )
+    }
 }
 
 impl Ref<'_, MaybeFdLifecycle> {
+    // Returns false if the operation had completed already.
     // # Safety
     // Caller must make sure the result is valid since it may contain fd or a length hint.
-    pub(crate) unsafe fn complete(mut self, result: io::Result<u32>, flags: u32) {
+    pub(crate) unsafe fn complete(
+        mut self,
+        result: io::Result<u32>,
+        flags: u32,
+        big_cqe: [u64; 2],
+    ) -> bool {
+        if self.is_completed() {
+            return false;
+        }
+        if self.multishot {
+            self.complete_multishot(result, flags, big_cqe);
+            return true;
+        }
+        let cqe_flags = CqeFlags::from_bits(flags);
+        // A result with more to come, a notification follows.
+        if cqe_flags.more() {
+            match std::mem::replace(&mut self.lifecycle, Lifecycle::Submitted) {
+                Lifecycle::Submitted => {
+                    self.lifecycle = Lifecycle::Notifying(result, flags, big_cqe, None)
+                }
+                Lifecycle::Waiting(waker) => {
+                    self.lifecycle = Lifecycle::Notifying(result, flags, big_cqe, Some(waker))
+                }
+                // Kept until the last one.
+                other => self.lifecycle = other,
+            }
+            return true;
+        }
+        let (result, flags, big_cqe) =
+            match std::mem::replace(&mut self.lifecycle, Lifecycle::Submitted) {
+                // The notification, the buffer is free now and the op ends
+                // with the result that came first.
+                Lifecycle::Notifying(first, first_flags, first_big_cqe, waker)
+                    if cqe_flags.notif() =>
+                {
+                    debug_assert!(cqueue::more(first_flags), "result before a notification");
+                    self.lifecycle = match waker {
+                        Some(waker) => Lifecycle::Waiting(waker),
+                        None => Lifecycle::Submitted,
+                    };
+                    (first, first_flags, first_big_cqe)
+                }
+                // Only the notification ends it.
+                notifying @ Lifecycle::Notifying(..) => {
+                    self.lifecycle = notifying;
+                    return true;
+                }
+                other => {
+                    self.lifecycle = other;
+                    (result, flags, big_cqe)
+                }
+            };
         let result = MaybeFd::new_result(result, self.is_fd);
         let ref_mut = &mut self.lifecycle;
         match ref_mut {
             Lifecycle::Submitted => {
-                *ref_mut = Lifecycle::Completed(result, flags);
+                *ref_mut = Lifecycle::Completed(result, flags, big_cqe);
             }
             //note: the path is currently unreachable
             Lifecycle::Waiting(_) => {
-                let old = std::mem::replace(ref_mut, Lifecycle::Completed(result, flags));
+                let old = std::mem::replace(ref_mut, Lifecycle::Completed(result, flags, big_cqe));
                 match old {
                     Lifecycle::Waiting(waker) => {
                         waker.wake();
@@ -65,8 +152,62 @@ impl Ref<'_, MaybeFdLifecycle> {
             Lifecycle::Ignored(..) => {
                 self.remove();
             }
-            Lifecycle::Completed(..) => std::hint::unreachable_unchecked(),
+            Lifecycle::Notifying(..) | Lifecycle::Completed(..) | Lifecycle::Finished => {
+                std::hint::unreachable_unchecked()
+            }
         }
+        true
+    }
+
+    // Queue one of the results of a multishot operation, the one without
+    // `IORING_CQE_F_MORE` is the last.
+    unsafe fn complete_multishot(mut self, result: io::Result<u32>, flags: u32, big_cqe: [u64; 2]) {
+        let result = MaybeFd::new_result(result, self.is_fd);
+        let last = !cqueue::more(flags);
+        let discard = self.discard;
+        match &mut self.lifecycle {
+            Lifecycle::Ignored(data) => {
+                // Nobody takes it, an fd is closed right here.
+                let meta = CompletionMeta {
+                    result,
+                    flags: CqeFlags::from_bits(flags),
+                    big_cqe,
+                };
+                if let Some(discard) = discard {
+                    discard(data.as_mut(), meta);
+                }
+                if last {
+                    self.remove();
+                }
+                return;
+            }
+            Lifecycle::Waiting(waker) => waker.wake_by_ref(),
+            _ => {}
+        }
+        self.queue.push_back((result, flags, big_cqe));
+        if last {
+            self.lifecycle = Lifecycle::Finished;
+        }
+    }
+
+    /// The next result of a multishot operation. The slot is freed with the
+    /// last one, the one without `more`.
+    pub(crate) fn poll_next(mut self, cx: &mut Context<'_>) -> Poll<CompletionMeta> {
+        let Some((result, flags, big_cqe)) = self.queue.pop_front() else {
+            match &mut self.lifecycle {
+                Lifecycle::Waiting(waker) if waker.will_wake(cx.waker()) => {}
+                lifecycle => *lifecycle = Lifecycle::Waiting(cx.waker().clone()),
+            }
+            return Poll::Pending;
+        };
+        if !cqueue::more(flags) {
+            self.remove();
+        }
+        Poll::Ready(CompletionMeta {
+            result,
+            flags: CqeFlags::from_bits(flags),
+            big_cqe,
+        })
     }
 
     #[allow(clippy::needless_pass_by_ref_mut)]
@@ -83,20 +224,48 @@ impl Ref<'_, MaybeFdLifecycle> {
                 }
                 return Poll::Pending;
             }
+            Lifecycle::Notifying(.., waker) => {
+                if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                    *waker = Some(cx.waker().clone());
+                }
+                return Poll::Pending;
+            }
             _ => {}
         }
 
         match self.remove().lifecycle {
-            Lifecycle::Completed(result, flags) => Poll::Ready(CompletionMeta { result, flags }),
+            Lifecycle::Completed(result, flags, big_cqe) => Poll::Ready(CompletionMeta {
+                result,
+                flags: CqeFlags::from_bits(flags),
+                big_cqe,
+            }),
             _ => unsafe { std::hint::unreachable_unchecked() },
         }
     }
 
     // return if the op must has been finished
-    pub(crate) fn drop_op<T: 'static>(mut self, data: &mut Option<T>) -> bool {
+    // `discard` gives back what the results of a multishot op hold, the ones
+    // queued now and the ones still to come.
+    pub(crate) fn drop_op<T: 'static>(
+        mut self,
+        data: &mut Option<T>,
+        discard: Option<Discard>,
+    ) -> bool {
+        // Results nobody claimed, fds among them are closed.
+        for (result, flags, big_cqe) in self.queue.drain(..) {
+            let meta = CompletionMeta {
+                result,
+                flags: CqeFlags::from_bits(flags),
+                big_cqe,
+            };
+            if let (Some(discard), Some(data)) = (discard, data.as_mut()) {
+                discard(data, meta);
+            }
+        }
+        self.discard = discard;
         let ref_mut = &mut self.lifecycle;
         match ref_mut {
-            Lifecycle::Submitted | Lifecycle::Waiting(_) => {
+            Lifecycle::Submitted | Lifecycle::Waiting(_) | Lifecycle::Notifying(..) => {
                 if let Some(data) = data.take() {
                     *ref_mut = Lifecycle::Ignored(Box::new(data));
                 } else {
@@ -105,7 +274,7 @@ impl Ref<'_, MaybeFdLifecycle> {
                 };
                 return false;
             }
-            Lifecycle::Completed(..) => {
+            Lifecycle::Completed(..) | Lifecycle::Finished => {
                 self.remove();
             }
             Lifecycle::Ignored(..) => unsafe { std::hint::unreachable_unchecked() },
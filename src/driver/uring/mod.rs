@@ -1,8 +1,31 @@
-use std::io;
 use crate::driver::uring::lifecycle::MaybeFdLifecycle;
-use crate::utils::slab::Slab;
+use crate::utils::slab::{Ref, Slab};
+use std::io;
 
 mod lifecycle;
+
+pub(crate) use lifecycle::Discard;
+
+// The user_data of an operation holds its slab index in the low bits and the
+// generation of the slot above. The top bits stay clear, so the values the
+// driver reserves near u64::MAX never match an operation.
+const INDEX_BITS: u32 = 40;
+const GENERATION_BITS: u32 = 22;
+const GENERATION_MASK: u64 = (1 << GENERATION_BITS) - 1;
+
+#[inline]
+pub(crate) fn encode_user_data(index: usize, generation: u32) -> u64 {
+    debug_assert!((index as u64) < 1 << INDEX_BITS);
+    index as u64 | (generation as u64 & GENERATION_MASK) << INDEX_BITS
+}
+
+#[inline]
+pub(crate) fn decode_user_data(user_data: u64) -> (usize, u32) {
+    let index = user_data & ((1 << INDEX_BITS) - 1);
+    let generation = (user_data >> INDEX_BITS) & GENERATION_MASK;
+    (index as usize, generation as u32)
+}
+
 // When dropping the driver, all in-flight operations must have completed. This
 // type wraps the slab and ensures that, on drop, the slab is empty.
 pub struct Ops {
@@ -13,18 +36,179 @@ impl Ops {
         Ops { slab: Slab::new() }
     }
 
-    // Insert a new operation
+    // Insert a new operation, returns its user_data. A multishot one queues
+    // its results until they are taken.
+    #[inline]
+    pub(crate) fn insert(&mut self, is_fd: bool, multishot: bool) -> u64 {
+        let index = self.slab.insert(MaybeFdLifecycle::new(is_fd, multishot));
+        encode_user_data(index, self.slab.generation(index))
+    }
+
+    // Get the operation with `user_data`, none if its slot has been reused
+    // since.
     #[inline]
-    pub(crate) fn insert(&mut self, is_fd: bool) -> usize {
-        self.slab.insert(MaybeFdLifecycle::new(is_fd))
+    pub(crate) fn get(&mut self, user_data: u64) -> Option<Ref<'_, MaybeFdLifecycle>> {
+        let (index, generation) = decode_user_data(user_data);
+        if self.slab.generation(index) as u64 & GENERATION_MASK != generation as u64 {
+            return None;
+        }
+        self.slab.get(index)
     }
 
-    // Complete an operation
+    // Complete an operation, returns false if the user_data is not an in-flight
+    // operation.
     // # Safety
     // Caller must make sure the result is valid.
     #[inline]
-    pub(crate) unsafe fn complete(&mut self, index: usize, result: io::Result<u32>, flags: u32) {
-        let lifecycle = unsafe { self.slab.get(index).unwrap_unchecked() };
-        lifecycle.complete(result, flags);
+    pub(crate) unsafe fn complete(
+        &mut self,
+        user_data: u64,
+        result: io::Result<u32>,
+        flags: u32,
+        big_cqe: [u64; 2],
+    ) -> bool {
+        match self.get(user_data) {
+            Some(lifecycle) => lifecycle.complete(result, flags, big_cqe),
+            None => false,
+        }
+    }
+
+    // user_data of operations the kernel still owns
+    pub(crate) fn in_flight(&self) -> impl Iterator<Item = u64> + '_ {
+        self.slab
+            .iter()
+            .filter(|(_, lifecycle)| !lifecycle.is_completed())
+            .map(|(index, _)| encode_user_data(index, self.slab.generation(index)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::task::{Context, Poll, Waker};
+
+    // From include/uapi/linux/io_uring.h
+    const F_MORE: u32 = 1 << 1;
+    const F_NOTIF: u32 = 1 << 3;
+
+    #[test]
+    fn notification_ends_the_op() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut ops = Ops::new();
+        let user_data = ops.insert(false, false);
+        assert!(ops.get(user_data).unwrap().poll_op(&mut cx).is_pending());
+
+        // The result with more to come leaves the op pending and in flight.
+        assert!(unsafe { ops.complete(user_data, Ok(5), F_MORE, [0; 2]) });
+        assert!(ops.get(user_data).unwrap().poll_op(&mut cx).is_pending());
+        assert_eq!(ops.in_flight().collect::<Vec<_>>(), [user_data]);
+
+        // Only one flagged `IORING_CQE_F_NOTIF` ends it.
+        assert!(unsafe { ops.complete(user_data, Ok(0), 0, [0; 2]) });
+        assert!(ops.get(user_data).unwrap().poll_op(&mut cx).is_pending());
+
+        // The notification completes it with the first result.
+        assert!(unsafe { ops.complete(user_data, Ok(0), F_NOTIF, [0; 2]) });
+        let Poll::Ready(meta) = ops.get(user_data).unwrap().poll_op(&mut cx) else {
+            panic!("the notification arrived");
+        };
+        assert_eq!(meta.result.unwrap().into_inner(), 5);
+        assert!(meta.flags.more());
+        assert!(ops.get(user_data).is_none());
+
+        // Dropped in between, the slot is freed by the notification only.
+        let user_data = ops.insert(false, false);
+        assert!(unsafe { ops.complete(user_data, Ok(1), F_MORE, [0; 2]) });
+        assert!(!ops
+            .get(user_data)
+            .unwrap()
+            .drop_op(&mut Some(vec![0u8; 4]), None));
+        assert!(ops.get(user_data).is_some());
+        assert!(unsafe { ops.complete(user_data, Ok(0), F_NOTIF, [0; 2]) });
+        assert!(ops.get(user_data).is_none());
+    }
+
+    #[test]
+    fn multishot_queues_results() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut ops = Ops::new();
+        let user_data = ops.insert(false, true);
+        assert!(ops.get(user_data).unwrap().poll_next(&mut cx).is_pending());
+
+        // Taken in order, the slot lives until the last one is.
+        for n in [1, 2] {
+            assert!(unsafe { ops.complete(user_data, Ok(n), F_MORE, [0; 2]) });
+        }
+        assert!(unsafe { ops.complete(user_data, Ok(3), 0, [0; 2]) });
+        assert!(ops.in_flight().next().is_none());
+        for n in [1, 2, 3] {
+            let Poll::Ready(meta) = ops.get(user_data).unwrap().poll_next(&mut cx) else {
+                panic!("result {n} is queued");
+            };
+            assert_eq!(meta.result.unwrap().into_inner(), n);
+            assert_eq!(meta.flags.more(), n < 3);
+        }
+        assert!(ops.get(user_data).is_none());
+
+        // Fds queued when the op is dropped are closed, and so are those
+        // completing after.
+        let pipe = || {
+            let mut fds = [0; 2];
+            assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+            fds
+        };
+        let (queued, late) = (pipe(), pipe());
+        let user_data = ops.insert(true, true);
+        let more = |fds: [i32; 2]| Ok(fds[1] as u32);
+        assert!(unsafe { ops.complete(user_data, more(queued), F_MORE, [0; 2]) });
+        assert!(!ops.get(user_data).unwrap().drop_op(&mut Some(()), None));
+        assert!(unsafe { ops.complete(user_data, more(late), F_MORE, [0; 2]) });
+        assert!(ops.get(user_data).is_some());
+        let canceled = Err(io::Error::from_raw_os_error(libc::ECANCELED));
+        assert!(unsafe { ops.complete(user_data, canceled, 0, [0; 2]) });
+        assert!(ops.get(user_data).is_none());
+        for [read, _] in [queued, late] {
+            // The write ends are gone, the read ends see the end of the pipe.
+            let n = unsafe { libc::read(read, [0u8; 1].as_mut_ptr().cast(), 1) };
+            assert_eq!(n, 0);
+            unsafe { libc::close(read) };
+        }
+    }
+
+    #[test]
+    fn multishot_discards_unclaimed() {
+        use std::{cell::RefCell, rc::Rc};
+        type Seen = Rc<RefCell<Vec<u32>>>;
+        // Records the results it is handed, queued ones and late ones alike.
+        fn discard(data: &mut dyn std::any::Any, meta: crate::driver::op::CompletionMeta) {
+            let seen = data.downcast_mut::<Seen>().unwrap();
+            seen.borrow_mut()
+                .push(meta.result.map_or(0, |n| n.into_inner()));
+        }
+        let mut ops = Ops::new();
+        let user_data = ops.insert(false, true);
+        assert!(unsafe { ops.complete(user_data, Ok(1), F_MORE, [0; 2]) });
+        let seen = Seen::default();
+        let mut data = Some(seen.clone());
+        assert!(!ops
+            .get(user_data)
+            .unwrap()
+            .drop_op(&mut data, Some(discard)));
+        assert!(unsafe { ops.complete(user_data, Ok(2), F_MORE, [0; 2]) });
+        let canceled = Err(io::Error::from_raw_os_error(libc::ECANCELED));
+        assert!(unsafe { ops.complete(user_data, canceled, 0, [0; 2]) });
+        assert!(ops.get(user_data).is_none());
+        assert_eq!(*seen.borrow(), [1, 2, 0]);
+    }
+
+    #[test]
+    fn user_data_round_trip() {
+        for (index, generation) in [(0, 0), (1, 1), ((1 << 40) - 1, (1 << 22) - 1)] {
+            let user_data = encode_user_data(index, generation);
+            assert_eq!(decode_user_data(user_data), (index, generation));
+            assert!(user_data < 1 << 62);
+        }
+        // Generations wrap within their bits.
+        assert_eq!(decode_user_data(encode_user_data(7, 1 << 22)), (7, 0));
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,7 @@
 use std::{ffi::CString, io, path::Path};
 
 #[allow(unused_variables)]
-pub(super) fn cstr(p: &Path) -> io::Result<CString> {
+pub(crate) fn cstr(p: &Path) -> io::Result<CString> {
     #[cfg(unix)]
     {
         use std::os::unix::ffi::OsStrExt;
@@ -22,11 +22,12 @@ pub(super) fn timespec(duration: std::time::Duration) -> io_uring::types::Timesp
 /// designed to close the fd when it is dropped.
 /// If use syscall@RAW, the return value is raw value. The requirement to explicitly add @RAW is to
 /// avoid misuse.
+/// The arguments are evaluated in order before the call, outside of `unsafe`.
 #[cfg(unix)]
 #[macro_export]
 macro_rules! syscall {
     ($fn: ident @FD ( $($arg: expr),* $(,)* ) ) => {{
-        let res = unsafe { libc::$fn($($arg, )*) };
+        let res = $crate::syscall!(@call $fn () $($arg),*);
         if res == -1 {
             Err(std::io::Error::last_os_error())
         } else {
@@ -34,7 +35,7 @@ macro_rules! syscall {
         }
     }};
     ($fn: ident @NON_FD ( $($arg: expr),* $(,)* ) ) => {{
-        let res = unsafe { libc::$fn($($arg, )*) };
+        let res = $crate::syscall!(@call $fn () $($arg),*);
         if res == -1 {
             Err(std::io::Error::last_os_error())
         } else {
@@ -42,13 +43,19 @@ macro_rules! syscall {
         }
     }};
     ($fn: ident @RAW ( $($arg: expr),* $(,)* ) ) => {{
-        let res = unsafe { libc::$fn($($arg, )*) };
+        let res = $crate::syscall!(@call $fn () $($arg),*);
         if res == -1 {
             Err(std::io::Error::last_os_error())
         } else {
             Ok(res)
         }
     }};
+    // Binds one argument to a local per step, each `arg` is a distinct one.
+    (@call $fn: ident ($($bound: ident),*) $head: expr $(, $tail: expr)*) => {{
+        let arg = $head;
+        $crate::syscall!(@call $fn ($($bound,)* arg) $($tail),*)
+    }};
+    (@call $fn: ident ($($bound: ident),*)) => {
+        unsafe { libc::$fn($($bound),*) }
+    };
 }
-
-
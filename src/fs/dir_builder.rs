@@ -0,0 +1,142 @@
+use crate::driver::op::Op;
+use crate::fs::metadata;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::path::Path;
+
+/// Options for creating directories.
+#[derive(Debug, Clone)]
+pub struct DirBuilder {
+    recursive: bool,
+    mode: u32,
+}
+
+impl Default for DirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirBuilder {
+    /// Create directories one at a time, with mode `0o777` masked by the umask.
+    pub fn new() -> Self {
+        DirBuilder {
+            recursive: false,
+            mode: 0o777,
+        }
+    }
+
+    /// Create missing parents as well, and succeed if the directory exists.
+    pub fn recursive(&mut self, recursive: bool) -> &mut Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Set the mode of new directories, masked by the umask.
+    pub fn mode(&mut self, mode: u32) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Create the directory at `path`.
+    pub async fn create(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if !self.recursive {
+            return self.mkdir(path).await;
+        }
+
+        // Walk up to the first ancestor that exists, then create downwards.
+        let mut missing = Vec::new();
+        let mut current = path;
+        loop {
+            match self.mkdir(current).await {
+                Ok(()) => break,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    missing.push(current);
+                    match current.parent() {
+                        Some(parent) if !parent.as_os_str().is_empty() => current = parent,
+                        _ => return Err(e),
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && is_dir(current).await => {
+                    break
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        for dir in missing.into_iter().rev() {
+            match self.mkdir(dir).await {
+                Ok(()) => {}
+                // Created by someone else in the meantime.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && is_dir(dir).await => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn mkdir(&self, path: &Path) -> io::Result<()> {
+        let op = Op::mkdir(path, self.mode).op_path("mkdirat", path)?;
+        op.await.meta.result.map(drop).op_path("mkdirat", path)
+    }
+}
+
+async fn is_dir(path: &Path) -> bool {
+    metadata(path).await.is_ok_and(|meta| meta.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::{create_dir, create_dir_all};
+    use crate::runtime::RuntimeBuilder;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn create_nested() {
+        let dir = std::env::temp_dir().join(format!("loop-dir-builder-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let deep = dir.join("a/b/c/d/");
+            let err = create_dir(&deep).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+            create_dir_all(&deep).await.unwrap();
+            for level in ["a", "a/b", "a/b/c", "a/b/c/d"] {
+                assert!(dir.join(level).is_dir(), "{level}");
+            }
+            // Existing directories are fine, existing files are not.
+            create_dir_all(&deep).await.unwrap();
+            std::fs::write(dir.join("file"), b"").unwrap();
+            let err = create_dir_all(dir.join("file/x")).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+            let err = create_dir_all(dir.join("file")).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            let err = create_dir(dir.join("a")).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+
+            DirBuilder::new()
+                .mode(0o700)
+                .create(dir.join("private"))
+                .await
+                .unwrap();
+            let mode = std::fs::metadata(dir.join("private"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        });
+
+        // Relative to the working directory; the path is unique to the
+        // process, the working directory is shared by the test threads.
+        let relative = Path::new("target").join(format!("loop-dir-builder-{}", std::process::id()));
+        rt.block_on(async {
+            create_dir_all(relative.join("x/y")).await.unwrap();
+        });
+        assert!(relative.join("x/y").is_dir());
+        std::fs::remove_dir_all(&relative).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
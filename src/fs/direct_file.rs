@@ -0,0 +1,681 @@
+use crate::buf::{self, BufResult, IoBuf, IoBufMut, IoBufMutExt, IoVecBuf, IoVecBufMut, VecBuf};
+use crate::driver::fixed_files::FixedSlot;
+use crate::driver::op::Op;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+
+/// A file living only in the ring's registered file table.
+///
+/// It has no entry in the process fd table, all io goes through the fixed
+/// file slot. Dropping it closes the file in the background.
+pub struct DirectFile {
+    slot: Option<FixedSlot>,
+}
+
+impl DirectFile {
+    pub(crate) fn new(slot: FixedSlot) -> Self {
+        DirectFile { slot: Some(slot) }
+    }
+
+    fn slot(&self) -> u32 {
+        // Only taken by close and drop.
+        self.slot.as_ref().map_or(u32::MAX, FixedSlot::index)
+    }
+
+    /// Read into `buf` at `pos`, returning the number of bytes read together
+    /// with the buffer.
+    pub async fn read_at<T: IoBufMut>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let op = match Op::read_direct(self.slot(), pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_slot("read", self.slot()), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_slot("read", self.slot()), buf)
+    }
+
+    /// Write `buf` at `pos`, returning the number of bytes written together
+    /// with the buffer.
+    pub async fn write_at<T: IoBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let op = match Op::write_direct(self.slot(), pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_slot("write", self.slot()), data.buf),
+        };
+        let completion = op.await;
+        let n = completion.meta.result.map(|n| n.into_inner() as usize);
+        let n = n.op_slot("write", self.slot());
+        (n, completion.data.buf)
+    }
+
+    /// Write all of `buf` at `pos`, resubmitting the rest after short writes.
+    pub async fn write_all_at<T: IoBuf>(&self, mut buf: T, pos: u64) -> BufResult<(), T> {
+        let len = buf.bytes_init();
+        let mut written = 0;
+        while written < len {
+            let (res, slice) = self
+                .write_at(buf.slice(written..), pos + written as u64)
+                .await;
+            buf = slice.into_inner();
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Fill all of `buf` from `pos`, resubmitting the rest after short reads.
+    pub async fn read_exact_at<T: IoBuf + IoBufMut>(
+        &self,
+        mut buf: T,
+        pos: u64,
+    ) -> BufResult<(), T> {
+        let len = buf.bytes_total();
+        let mut read = 0;
+        while read < len {
+            let (res, slice) = self.read_at(buf.slice_mut(read..), pos + read as u64).await;
+            buf = slice.into_inner();
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "failed to fill whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Read from `pos` to the end of the file, appending to `buf` and growing it
+    /// as needed. Returns the number of bytes appended.
+    pub async fn read_to_end_at<T: IoBufMutExt>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        buf::read_to_end(buf, |slice, read| self.read_at(slice, pos + read as u64)).await
+    }
+
+    /// Read at `pos` into the buffers of `buf` in order, returning the number
+    /// of bytes read together with the buffers.
+    pub async fn read_vectored_at<T: IoVecBufMut>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let op = match Op::readv_direct(self.slot(), pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_slot("readv", self.slot()), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_slot("readv", self.slot()), buf)
+    }
+
+    /// Write the buffers of `buf` in order at `pos`, returning the number of
+    /// bytes written together with the buffers.
+    pub async fn write_vectored_at<T: IoVecBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let op = match Op::writev_direct(self.slot(), pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_slot("writev", self.slot()), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_slot("writev", self.slot()), buf)
+    }
+
+    /// Write all of `buf` at `pos`, resubmitting the rest after short writes.
+    pub async fn write_all_vectored_at(
+        &self,
+        mut buf: VecBuf,
+        mut pos: u64,
+    ) -> BufResult<(), VecBuf> {
+        while !buf.is_empty() {
+            let (res, rest) = self.write_vectored_at(buf, pos).await;
+            buf = rest;
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => {
+                    buf.advance(n);
+                    pos += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Close the file and wait for the result.
+    pub async fn close(mut self) -> io::Result<()> {
+        let slot = self.slot.take().expect("file is open");
+        let op = Op::close_direct(slot.index()).op_slot("close", slot.index())?;
+        op.await.meta.result.op_slot("close", slot.index())?;
+        drop(slot);
+        Ok(())
+    }
+}
+
+impl Drop for DirectFile {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            slot.close_detached();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buf::{IoBufMut, VecBuf};
+    use crate::driver::fixed_files::SlotPolicy;
+    use crate::driver::IoUringDriver;
+    use crate::fs::OpenOptions;
+    use crate::runtime::builder::RuntimeBuilder;
+    use crate::utils::alloc_counter;
+    use crate::utils::testing::{fixed_runtime, supported};
+    use std::io::{self, Read, Write};
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::process::Command;
+    use std::rc::Rc;
+
+    const CHILD_ENV: &str = "LOOP_DIRECT_FILE_CHILD";
+
+    fn open_fds() -> usize {
+        std::fs::read_dir("/proc/self/fd").unwrap().count()
+    }
+
+    #[test]
+    fn direct_files_bypass_fd_table() {
+        // Other tests open fds concurrently, count them in a process of our own.
+        if std::env::var_os(CHILD_ENV).is_none() {
+            let name = concat!(module_path!(), "::direct_files_bypass_fd_table");
+            let name = name.split_once("::").unwrap().1;
+            let status = Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", name, "--test-threads=1", "--quiet"])
+                .env(CHILD_ENV, "1")
+                .status()
+                .unwrap();
+            assert!(status.success());
+            return;
+        }
+
+        let path = std::env::temp_dir().join(format!("loop-direct-{}", std::process::id()));
+        std::fs::write(&path, b"direct").unwrap();
+
+        let Some(mut rt) = fixed_runtime(4) else {
+            return;
+        };
+        rt.block_on(async {
+            let before = open_fds();
+            for i in 0..1000 {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .open_direct(&path)
+                    .await
+                    .unwrap();
+                let (n, buf) = file.read_at(vec![0; 16], 0).await;
+                assert_eq!(&buf[..n.unwrap()], b"direct");
+                // Alternate between waiting for the close and closing in the background.
+                if i % 2 == 0 {
+                    file.close().await.unwrap();
+                } else {
+                    drop(file);
+                }
+                assert!(open_fds() <= before);
+            }
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_then_read() {
+        let path = std::env::temp_dir().join(format!("loop-direct-rw-{}", std::process::id()));
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            let (n, _) = file.write_at(b"hello".to_vec(), 0).await;
+            assert_eq!(n.unwrap(), 5);
+            let (n, buf) = file.read_at(vec![0; 5], 0).await;
+            assert_eq!(n.unwrap(), 5);
+            assert_eq!(buf, b"hello");
+
+            // The only slot is taken.
+            let err = OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .err()
+                .unwrap();
+            // The errno is kept under the error context.
+            let errno = err
+                .get_ref()
+                .and_then(|e| e.source())
+                .and_then(|e| e.downcast_ref::<io::Error>())
+                .and_then(io::Error::raw_os_error);
+            assert_eq!(errno, Some(libc::ENFILE));
+            file.close().await.unwrap();
+            OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn any_owned_buffer() {
+        let path = std::env::temp_dir().join(format!("loop-direct-bufs-{}", std::process::id()));
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            let (n, _) = file.write_at(&b"static data"[..], 0).await;
+            assert_eq!(n.unwrap(), 11);
+            let (n, buf) = file.read_at(Box::<[u8]>::from(&[0; 6][..]), 7).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(&buf[..], b"data\0\0");
+            // A vec is read into its spare capacity and grows to what was read.
+            let (n, buf) = file.read_at(Vec::with_capacity(64), 0).await;
+            assert_eq!(n.unwrap(), 11);
+            assert_eq!(buf, b"static data");
+            file.close().await.unwrap();
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn wait_for_slot() {
+        let path = std::env::temp_dir().join(format!("loop-direct-wait-{}", std::process::id()));
+        std::fs::write(&path, b"").unwrap();
+        let builder = RuntimeBuilder::<IoUringDriver>::new()
+            .fixed_files(1)
+            .fixed_file_policy(SlotPolicy::Wait);
+        let Some(mut rt) = supported(builder.build()) else {
+            return;
+        };
+        rt.block_on(async {
+            let first = OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            let waiting = crate::runtime::runtime::spawn({
+                let path = path.clone();
+                async move {
+                    OpenOptions::new()
+                        .read(true)
+                        .open_direct(&path)
+                        .await
+                        .is_ok()
+                }
+            });
+            first.close().await.unwrap();
+            assert!(waiting.await);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Deterministic pseudo random sizes, so failures replay.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    // A pipe holding a single page, opened as a direct file on one end.
+    fn small_pipe() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+        let (rx, tx) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        assert!(unsafe { libc::fcntl(fds[1], libc::F_SETPIPE_SZ, 4096) } >= 0);
+        (rx, tx)
+    }
+
+    fn proc_path(fd: &OwnedFd) -> String {
+        format!("/proc/self/fd/{}", fd.as_raw_fd())
+    }
+
+    fn data(rng: &mut XorShift, len: usize) -> Vec<u8> {
+        (0..len).map(|_| rng.below(256) as u8).collect()
+    }
+
+    #[test]
+    fn write_all_through_fragmenting_pipe() {
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        let mut rng = XorShift(0x5eed);
+        for _ in 0..16 {
+            let len = rng.below(64 * 1024);
+            let expected = data(&mut rng, len);
+            let (rx, tx) = small_pipe();
+            let seed = rng.0;
+            let reader = std::thread::spawn(move || {
+                // Drain in small random pieces.
+                let mut rng = XorShift(seed);
+                let mut rx = std::fs::File::from(rx);
+                let mut out = Vec::new();
+                let mut chunk = [0; 512];
+                loop {
+                    let want = 1 + rng.below(chunk.len());
+                    match rx.read(&mut chunk[..want]).unwrap() {
+                        0 => return out,
+                        n => out.extend_from_slice(&chunk[..n]),
+                    }
+                }
+            });
+            let sent = rt.block_on(async {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open_direct(proc_path(&tx))
+                    .await
+                    .unwrap();
+                drop(tx);
+                let (res, buf) = file.write_all_at(expected, 0).await;
+                res.unwrap();
+                file.close().await.unwrap();
+                buf
+            });
+            assert_eq!(reader.join().unwrap(), sent);
+        }
+    }
+
+    #[test]
+    fn read_exact_through_fragmenting_pipe() {
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        let mut rng = XorShift(0xfeed);
+        for round in 0..16 {
+            let len = rng.below(64 * 1024);
+            let expected = data(&mut rng, len);
+            let (rx, tx) = small_pipe();
+            let seed = rng.0;
+            let writer = std::thread::spawn({
+                let expected = expected.clone();
+                move || {
+                    // Feed small random pieces, so reads come back short.
+                    let mut rng = XorShift(seed);
+                    let mut tx = std::fs::File::from(tx);
+                    let mut rest = &expected[..];
+                    while !rest.is_empty() {
+                        let n = (1 + rng.below(512)).min(rest.len());
+                        tx.write_all(&rest[..n]).unwrap();
+                        rest = &rest[n..];
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let got = rt.block_on(async {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .open_direct(proc_path(&rx))
+                    .await
+                    .unwrap();
+                drop(rx);
+                // Alternate between buffers initialized up front and spare capacity.
+                let buf = if round % 2 == 0 {
+                    let (res, buf) = file
+                        .read_exact_at(Vec::with_capacity(expected.len()), 0)
+                        .await;
+                    res.unwrap();
+                    buf
+                } else {
+                    let buf = vec![0; expected.len()].into_boxed_slice();
+                    let (res, buf) = file.read_exact_at(buf, 0).await;
+                    res.unwrap();
+                    buf.into_vec()
+                };
+                // The writer is done, the pipe is at its end.
+                let (res, _) = file.read_exact_at(vec![0; 1], 0).await;
+                assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+                buf
+            });
+            writer.join().unwrap();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn empty_buffers_short_circuit() {
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        let (rx, _tx) = small_pipe();
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .open_direct(proc_path(&rx))
+                .await
+                .unwrap();
+            // Would block on the empty pipe if it reached the kernel.
+            let (res, _) = file.read_exact_at(Vec::new(), 0).await;
+            res.unwrap();
+            let (res, _) = file.write_all_at(&b""[..], 0).await;
+            res.unwrap();
+        });
+    }
+
+    #[test]
+    fn vectored_through_fragmenting_pipe() {
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        let mut rng = XorShift(0xbeef);
+        for _ in 0..8 {
+            let bufs: Vec<_> = (0..1 + rng.below(32))
+                .map(|_| {
+                    let len = rng.below(8 * 1024);
+                    data(&mut rng, len)
+                })
+                .collect();
+            let expected = bufs.concat();
+            let (rx, tx) = small_pipe();
+            let reader = std::thread::spawn(move || {
+                let mut out = Vec::new();
+                std::fs::File::from(rx).read_to_end(&mut out).unwrap();
+                out
+            });
+            let bufs = rt.block_on(async {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open_direct(proc_path(&tx))
+                    .await
+                    .unwrap();
+                drop(tx);
+                let (res, buf) = file.write_all_vectored_at(VecBuf::from(bufs), 0).await;
+                res.unwrap();
+                file.close().await.unwrap();
+                buf.into_inner()
+            });
+            assert_eq!(bufs.concat(), expected);
+            assert_eq!(reader.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn read_vectored() {
+        let path = std::env::temp_dir().join(format!("loop-direct-readv-{}", std::process::id()));
+        std::fs::write(&path, b"hello vectored").unwrap();
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            let buf = VecBuf::from(vec![vec![0; 5], vec![0; 1], vec![0; 16]]);
+            let (n, buf) = file.read_vectored_at(buf, 0).await;
+            assert_eq!(n.unwrap(), 14);
+            let bufs = buf.into_inner();
+            assert_eq!(bufs[0], b"hello");
+            assert_eq!(bufs[1], b" ");
+            assert_eq!(&bufs[2][..8], b"vectored");
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reads_into_spare_capacity() {
+        const LEN: usize = 32 << 20;
+        let path = std::env::temp_dir().join(format!("loop-direct-uninit-{}", std::process::id()));
+        std::fs::write(&path, data(&mut XorShift(0xface), LEN)).unwrap();
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .open_direct(&path)
+                .await
+                .unwrap();
+            // One byte more than the file, marked, to see it is never written.
+            let mut buf = Vec::with_capacity(LEN + 1);
+            buf.spare_capacity_mut()[LEN].write(0xaa);
+
+            let before = alloc_counter::snapshot();
+            let (res, buf) = file.read_exact_at(buf.slice_mut(..LEN), 0).await;
+            let counts = alloc_counter::snapshot().since(before);
+            res.unwrap();
+            let mut buf = buf.into_inner();
+            assert_eq!(buf.len(), LEN);
+            assert_eq!(unsafe { *buf.as_ptr().add(LEN) }, 0xaa);
+            assert_eq!(counts.zeroed_bytes, 0);
+            assert!(counts.bytes < 4096, "{counts:?}");
+            buf.clear();
+
+            // The control path: zeroing up front shows up in the counts.
+            let before = alloc_counter::snapshot();
+            let zeroed = vec![0u8; LEN];
+            let counts = alloc_counter::snapshot().since(before);
+            assert!(counts.zeroed_bytes >= LEN);
+            let (res, _) = file.read_exact_at(zeroed, 0).await;
+            res.unwrap();
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_to_end_grows() {
+        let Some(mut rt) = fixed_runtime(1) else {
+            return;
+        };
+        let mut rng = XorShift(0xc0de);
+        for _ in 0..4 {
+            let len = (2 << 20) + rng.below(2 << 20);
+            let expected = data(&mut rng, len);
+            let (rx, tx) = small_pipe();
+            let seed = rng.0;
+            let writer = std::thread::spawn({
+                let expected = expected.clone();
+                move || {
+                    let mut rng = XorShift(seed);
+                    let mut tx = std::fs::File::from(tx);
+                    let mut rest = &expected[..];
+                    while !rest.is_empty() {
+                        let n = (1 + rng.below(16 * 1024)).min(rest.len());
+                        tx.write_all(&rest[..n]).unwrap();
+                        rest = &rest[n..];
+                    }
+                }
+            });
+            let got = rt.block_on(async {
+                let file = OpenOptions::new()
+                    .read(true)
+                    .open_direct(proc_path(&rx))
+                    .await
+                    .unwrap();
+                drop(rx);
+                let mut buf = Vec::with_capacity(64);
+                buf.extend_from_slice(b"prefix");
+                let (n, buf) = file.read_to_end_at(buf, 0).await;
+                assert_eq!(n.unwrap(), len);
+                assert_eq!(&buf[..6], b"prefix");
+                buf[6..].to_vec()
+            });
+            writer.join().unwrap();
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn shared_payload_is_not_copied() {
+        const CONNS: usize = 100;
+        const LEN: usize = 16 * 1024;
+        let Some(mut rt) = fixed_runtime(CONNS as u32) else {
+            return;
+        };
+        let payload: Rc<[u8]> = data(&mut XorShift(0xab), LEN).into();
+        // The default pipe capacity holds the whole payload, no reader needed.
+        let pipes: Vec<_> = (0..CONNS)
+            .map(|_| {
+                let mut fds = [0; 2];
+                assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) }, 0);
+                unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+            })
+            .collect();
+        let counts = rt.block_on(async {
+            let mut files = Vec::new();
+            for (_, tx) in &pipes {
+                let file = OpenOptions::new()
+                    .write(true)
+                    .open_direct(proc_path(tx))
+                    .await
+                    .unwrap();
+                files.push(Rc::new(file));
+            }
+            let before = alloc_counter::snapshot();
+            let writers: Vec<_> = files
+                .iter()
+                .map(|file| {
+                    let file = file.clone();
+                    let payload = payload.clone();
+                    crate::runtime::runtime::spawn(async move {
+                        let (res, _) = file.write_all_at(payload, 0).await;
+                        res.unwrap();
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.await;
+            }
+            alloc_counter::snapshot().since(before)
+        });
+        // Tasks and ops allocate a little, a copy per connection would be MiBs.
+        assert!(counts.bytes < CONNS * LEN / 8, "{counts:?}");
+        assert_eq!(Rc::strong_count(&payload), 1);
+        for (rx, _) in pipes {
+            let mut got = Vec::new();
+            std::fs::File::from(rx)
+                .take(LEN as u64)
+                .read_to_end(&mut got)
+                .unwrap();
+            assert_eq!(got, &payload[..]);
+        }
+    }
+}
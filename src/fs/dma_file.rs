@@ -0,0 +1,216 @@
+use crate::buf::{AlignedBuf, BufResult, IoBuf, IoBufMut};
+use crate::fs::{File, Metadata, OpenOptions};
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::path::Path;
+
+// When neither statx nor the device tell, the page size every device works with.
+const FALLBACK_ALIGN: usize = 4096;
+
+/// A file opened with `O_DIRECT`, its io bypasses the page cache.
+///
+/// Direct io needs offsets, lengths and buffer addresses aligned to what the
+/// device and filesystem support. The alignments are queried at open time and
+/// every read and write is checked against them, a misaligned one fails with
+/// [`io::ErrorKind::InvalidInput`] before reaching the kernel.
+/// [`AlignedBuf`] provides buffers that pass, see [`DmaFile::alloc_buf`].
+pub struct DmaFile {
+    file: File,
+    mem_align: usize,
+    offset_align: usize,
+}
+
+impl DmaFile {
+    /// Open `path` for reading.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<DmaFile> {
+        Self::open_with(OpenOptions::new().read(true), path).await
+    }
+
+    /// Open `path` for reading and writing, creating it or truncating it.
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<DmaFile> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(true);
+        Self::open_with(&options, path).await
+    }
+
+    /// Open `path` with `options`, adding `O_DIRECT` to its flags.
+    pub async fn open_with(options: &OpenOptions, path: impl AsRef<Path>) -> io::Result<DmaFile> {
+        let mut options = options.clone();
+        let custom = options.custom_flags;
+        let file = options
+            .custom_flags(custom | libc::O_DIRECT)
+            .open(path)
+            .await?;
+        let (mem_align, offset_align) = alignments(&file, &file.metadata().await?)?;
+        Ok(DmaFile {
+            file,
+            mem_align,
+            offset_align,
+        })
+    }
+
+    /// The alignment of buffer addresses.
+    pub fn memory_alignment(&self) -> usize {
+        self.mem_align
+    }
+
+    /// The alignment of file offsets and io lengths.
+    pub fn offset_alignment(&self) -> usize {
+        self.offset_align
+    }
+
+    /// An empty buffer for at least `capacity` bytes that passes the checks,
+    /// its capacity rounded up to a multiple of both alignments.
+    pub fn alloc_buf(&self, capacity: usize) -> AlignedBuf {
+        let align = self.mem_align.max(self.offset_align);
+        AlignedBuf::with_alignment(capacity.next_multiple_of(self.offset_align), align)
+    }
+
+    /// Read into all of `buf`'s capacity at `pos`, returning the number of
+    /// bytes read together with the buffer. Short only at the end of the file.
+    pub async fn read_at<T: IoBufMut>(&self, mut buf: T, pos: u64) -> BufResult<usize, T> {
+        let (ptr, len) = (buf.write_ptr(), buf.bytes_total());
+        if let Err(e) = self.check(ptr, len, pos) {
+            return (Err(e).op_fd("read", self.as_raw_fd()), buf);
+        }
+        self.file.read_at(buf, pos).await
+    }
+
+    /// Write the initialized bytes of `buf` at `pos`, returning the number of
+    /// bytes written together with the buffer.
+    pub async fn write_at<T: IoBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        if let Err(e) = self.check(buf.read_ptr(), buf.bytes_init(), pos) {
+            return (Err(e).op_fd("write", self.as_raw_fd()), buf);
+        }
+        self.file.write_at(buf, pos).await
+    }
+
+    fn check(&self, ptr: *const u8, len: usize, pos: u64) -> io::Result<()> {
+        let misaligned = |what, value: u64, align: usize| {
+            let msg = format!("{what} {value} is not a multiple of {align} for direct io");
+            Err(io::Error::new(io::ErrorKind::InvalidInput, msg))
+        };
+        if !pos.is_multiple_of(self.offset_align as u64) {
+            return misaligned("offset", pos, self.offset_align);
+        }
+        if !len.is_multiple_of(self.offset_align) {
+            return misaligned("length", len as u64, self.offset_align);
+        }
+        if !(ptr as usize).is_multiple_of(self.mem_align) {
+            return misaligned("buffer address", ptr as u64, self.mem_align);
+        }
+        Ok(())
+    }
+
+    /// Query the metadata of the file.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        self.file.metadata().await
+    }
+
+    /// Truncate or extend the file to `len` bytes.
+    pub async fn set_len(&self, len: u64) -> io::Result<()> {
+        self.file.set_len(len).await
+    }
+
+    /// Flush the data and metadata of the file to storage. Direct writes skip
+    /// the page cache but may still sit in the device's cache.
+    pub async fn sync_all(&self) -> io::Result<()> {
+        self.file.sync_all().await
+    }
+
+    /// Flush the data of the file to storage.
+    pub async fn sync_data(&self) -> io::Result<()> {
+        self.file.sync_data().await
+    }
+
+    /// Close the file and wait for the result.
+    pub async fn close(self) -> io::Result<()> {
+        self.file.close().await
+    }
+}
+
+// The memory and offset alignments: statx knows them for most filesystems
+// since 6.1, block devices tell their logical block size.
+fn alignments(file: &File, meta: &Metadata) -> io::Result<(usize, usize)> {
+    if let Some((mem, offset)) = meta.dio_align() {
+        return Ok((mem.max(1) as usize, offset as usize));
+    }
+    if meta.is_block_device() {
+        let fd = file.as_raw_fd();
+        let mut size: libc::c_int = 0;
+        crate::syscall!(ioctl@RAW(fd, libc::BLKSSZGET, &mut size)).op_fd("ioctl", fd)?;
+        let size = size as usize;
+        return Ok((size, size));
+    }
+    Ok((FALLBACK_ALIGN, FALLBACK_ALIGN))
+}
+
+impl AsRawFd for DmaFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for DmaFile {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn aligned_round_trip() {
+        // tmpfs takes O_DIRECT only since 6.6, /tmp may be one.
+        let path = std::env::temp_dir().join(format!("loop-dma-file-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = match DmaFile::create(&path).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::InvalidInput => return,
+                Err(e) => panic!("{e}"),
+            };
+            let align = file.offset_alignment();
+            assert!(align.is_power_of_two() && file.memory_alignment().is_power_of_two());
+
+            let mut buf = file.alloc_buf(2 * align);
+            let data: Vec<u8> = (0..2 * align).map(|i| i as u8).collect();
+            buf.extend_from_slice(&data);
+            let (res, _) = file.write_at(buf, align as u64).await;
+            assert_eq!(res.unwrap(), 2 * align);
+            file.sync_data().await.unwrap();
+            assert_eq!(file.metadata().await.unwrap().len(), 3 * align as u64);
+
+            // The whole capacity is read, short at the end.
+            let (res, buf) = file.read_at(file.alloc_buf(4 * align), 0).await;
+            assert_eq!(res.unwrap(), 3 * align);
+            assert!(buf[..align].iter().all(|b| *b == 0));
+            assert_eq!(&buf[align..], &data[..]);
+            assert_eq!(std::fs::read(&path).unwrap(), &buf[..]);
+
+            let invalid = |res: io::Result<usize>| {
+                assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+            };
+            let (res, _) = file.read_at(file.alloc_buf(align), 1).await;
+            invalid(res);
+            let mut short = file.alloc_buf(align);
+            short.extend_from_slice(&[1; 100]);
+            let (res, _) = file.write_at(short, 0).await;
+            invalid(res);
+            // Aligned length at a misaligned address.
+            let buf = AlignedBuf::zeroed(2 * align, file.memory_alignment().max(2));
+            let (res, _) = file.read_at(buf.slice_mut(1..align + 1), 0).await;
+            invalid(res);
+            let (res, _) = file.read_at(Vec::<u8>::with_capacity(align + 1), 0).await;
+            invalid(res);
+
+            file.close().await.unwrap();
+        });
+        std::fs::remove_file(&path).ok();
+    }
+}
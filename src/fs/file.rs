@@ -0,0 +1,1033 @@
+use crate::buf::{
+    self, BorrowedBuf, BufResult, BufRing, FixedBuf, IoBuf, IoBufMut, IoBufMutExt, IoVecBuf,
+    IoVecBufMut, VecBuf,
+};
+use crate::driver::file_io::{Close, Fadvise, Ftruncate};
+use crate::driver::op::Op;
+use crate::fs::{Metadata, OpenOptions};
+use crate::io::{AsyncReadRent, AsyncWriteRent, CancelHandle, Split};
+use crate::utils::error_ctx::ResultExt;
+use std::future::Future;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+// Offset -1 makes the kernel use and advance the file position.
+const CURRENT_POSITION: u64 = u64::MAX;
+
+/// An expected access pattern, see [`File::advise`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// No particular pattern, the default.
+    Normal,
+    /// Read from lower to higher offsets, the kernel reads ahead further.
+    Sequential,
+    /// Read in no particular order, the kernel reads ahead less.
+    Random,
+    /// The range is needed soon, the kernel starts reading it in.
+    WillNeed,
+    /// The range is not needed soon, the kernel may drop it from the cache.
+    DontNeed,
+    /// The range is read only once.
+    NoReuse,
+}
+
+impl Advice {
+    fn as_raw(self) -> i32 {
+        match self {
+            Advice::Normal => libc::POSIX_FADV_NORMAL,
+            Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Advice::Random => libc::POSIX_FADV_RANDOM,
+            Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+            Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+            Advice::NoReuse => libc::POSIX_FADV_NOREUSE,
+        }
+    }
+}
+
+/// A file opened into a regular descriptor, its io goes through the ring.
+///
+/// Buffers are moved into each operation and handed back with the result,
+/// so an operation dropped midway never leaves the kernel with freed memory.
+/// Dropping the file closes it in the background.
+pub struct File {
+    // Only taken by close, into_std and drop.
+    fd: Option<OwnedFd>,
+}
+
+impl File {
+    /// Open `path` for reading.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<File> {
+        OpenOptions::new().read(true).open(path).await
+    }
+
+    /// Open `path` for writing, creating it or truncating it.
+    pub async fn create(path: impl AsRef<Path>) -> io::Result<File> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .await
+    }
+
+    fn raw(&self) -> RawFd {
+        self.fd.as_ref().expect("file is open").as_raw_fd()
+    }
+
+    /// Read into `buf` at the file position, advancing it. Returns the number
+    /// of bytes read together with the buffer.
+    pub async fn read<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.submit_read(buf, CURRENT_POSITION, None).await
+    }
+
+    /// Read into `buf` at `pos`, leaving the file position alone. Returns the
+    /// number of bytes read, short at the end of the file, together with the
+    /// buffer.
+    pub async fn read_at<T: IoBufMut>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        self.submit_read(buf, pos, None).await
+    }
+
+    /// Like [`read`](File::read), cancelled through `handle`, see
+    /// [`Canceller`](crate::io::Canceller). The buffer comes back either way.
+    pub async fn read_cancelable<T: IoBufMut>(
+        &self,
+        buf: T,
+        handle: &CancelHandle,
+    ) -> BufResult<usize, T> {
+        self.submit_read(buf, CURRENT_POSITION, Some(handle)).await
+    }
+
+    /// Like [`read_at`](File::read_at), cancelled through `handle`.
+    pub async fn read_at_cancelable<T: IoBufMut>(
+        &self,
+        buf: T,
+        pos: u64,
+        handle: &CancelHandle,
+    ) -> BufResult<usize, T> {
+        self.submit_read(buf, pos, Some(handle)).await
+    }
+
+    /// Read at the file position, advancing it, into a buffer the kernel
+    /// picks from `ring` once data is there. Empty at the end of the file.
+    pub async fn read_with_ring(&self, ring: &BufRing) -> io::Result<BorrowedBuf> {
+        self.read_at_with_ring(ring, CURRENT_POSITION).await
+    }
+
+    /// Read at `pos` into a buffer the kernel picks from `ring`, waiting for
+    /// one to be given back when all are borrowed. Empty at the end of the
+    /// file.
+    pub async fn read_at_with_ring(&self, ring: &BufRing, pos: u64) -> io::Result<BorrowedBuf> {
+        let fd = self.raw();
+        let group = ring.group();
+        group
+            .select(|| Ok(Op::read_from_group(fd, pos, group)?.result()))
+            .await
+            .op_fd("read", fd)
+    }
+
+    /// Read from `pos` to the end of the file, appending to `buf` and growing it
+    /// as needed. Returns the number of bytes appended.
+    pub async fn read_to_end_at<T: IoBufMutExt>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        buf::read_to_end(buf, |slice, read| self.read_at(slice, pos + read as u64)).await
+    }
+
+    /// Write `buf` at the file position, advancing it. Returns the number of
+    /// bytes written together with the buffer.
+    pub async fn write<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        self.submit_write(buf, CURRENT_POSITION, None).await
+    }
+
+    /// Write `buf` at `pos`, leaving the file position alone. Writing past the
+    /// end extends the file. An empty buffer completes with `Ok(0)` right away.
+    pub async fn write_at<T: IoBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        if buf.bytes_init() == 0 {
+            return (Ok(0), buf);
+        }
+        self.submit_write(buf, pos, None).await
+    }
+
+    /// Like [`write`](File::write), cancelled through `handle`, see
+    /// [`Canceller`](crate::io::Canceller). The buffer comes back either way.
+    pub async fn write_cancelable<T: IoBuf>(
+        &self,
+        buf: T,
+        handle: &CancelHandle,
+    ) -> BufResult<usize, T> {
+        self.submit_write(buf, CURRENT_POSITION, Some(handle)).await
+    }
+
+    /// Like [`write_at`](File::write_at), cancelled through `handle`.
+    pub async fn write_at_cancelable<T: IoBuf>(
+        &self,
+        buf: T,
+        pos: u64,
+        handle: &CancelHandle,
+    ) -> BufResult<usize, T> {
+        if buf.bytes_init() == 0 {
+            return (Ok(0), buf);
+        }
+        self.submit_write(buf, pos, Some(handle)).await
+    }
+
+    /// Write all of `buf` at `pos`, resubmitting the rest after short writes.
+    pub async fn write_all_at<T: IoBuf>(&self, mut buf: T, pos: u64) -> BufResult<(), T> {
+        let len = buf.bytes_init();
+        let mut written = 0;
+        while written < len {
+            let (res, slice) = self
+                .write_at(buf.slice(written..), pos + written as u64)
+                .await;
+            buf = slice.into_inner();
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Read at `pos` into the buffers of `buf` in order, returning the number
+    /// of bytes read together with the buffers.
+    ///
+    /// A short read fills the buffers in order up to the count, after
+    /// [`VecBuf::advance`] by it the next read resumes where this one stopped.
+    pub async fn read_vectored_at<T: IoVecBufMut>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let fd = self.raw();
+        let op = match Op::readv(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("readv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("readv", fd), buf)
+    }
+
+    /// Write the buffers of `buf` in order at `pos`, returning the number of
+    /// bytes written together with the buffers.
+    pub async fn write_vectored_at<T: IoVecBuf>(&self, buf: T, pos: u64) -> BufResult<usize, T> {
+        let fd = self.raw();
+        let op = match Op::writev(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("writev", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("writev", fd), buf)
+    }
+
+    /// Write all of `buf` at `pos`, resubmitting the rest after short writes.
+    pub async fn write_all_vectored_at(
+        &self,
+        mut buf: VecBuf,
+        mut pos: u64,
+    ) -> BufResult<(), VecBuf> {
+        while !buf.is_empty() {
+            let (res, rest) = self.write_vectored_at(buf, pos).await;
+            buf = rest;
+            match res {
+                Ok(0) => {
+                    let err =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (Err(err), buf);
+                }
+                Ok(n) => {
+                    buf.advance(n);
+                    pos += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (Err(e), buf),
+            }
+        }
+        (Ok(()), buf)
+    }
+
+    /// Read into the registered `buf` at `pos`, like [`read_at`](Self::read_at)
+    /// but without pinning the buffer's pages for the op.
+    pub async fn read_fixed_at(&self, buf: FixedBuf, pos: u64) -> BufResult<usize, FixedBuf> {
+        let fd = self.raw();
+        let op = match Op::read_fixed(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("read_fixed", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("read_fixed", fd), buf)
+    }
+
+    /// Write the registered `buf` at `pos`, like [`write_at`](Self::write_at)
+    /// but without pinning the buffer's pages for the op.
+    pub async fn write_fixed_at(&self, buf: FixedBuf, pos: u64) -> BufResult<usize, FixedBuf> {
+        let fd = self.raw();
+        let op = match Op::write_fixed(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("write_fixed", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("write_fixed", fd), buf)
+    }
+
+    async fn submit_read<T: IoBufMut>(
+        &self,
+        buf: T,
+        pos: u64,
+        cancel: Option<&CancelHandle>,
+    ) -> BufResult<usize, T> {
+        let fd = self.raw();
+        if let Some(Err(e)) = cancel.map(CancelHandle::check) {
+            return (Err(e).op_fd("read", fd), buf);
+        }
+        let op = match Op::read_at(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("read", fd), data.buf),
+        };
+        let _registered = cancel.map(|handle| handle.register(&op));
+        let (n, buf) = op.result().await;
+        (n.op_fd("read", fd), buf)
+    }
+
+    async fn submit_write<T: IoBuf>(
+        &self,
+        buf: T,
+        pos: u64,
+        cancel: Option<&CancelHandle>,
+    ) -> BufResult<usize, T> {
+        let fd = self.raw();
+        if let Some(Err(e)) = cancel.map(CancelHandle::check) {
+            return (Err(e).op_fd("write", fd), buf);
+        }
+        let op = match Op::write_at(fd, pos, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("write", fd), data.buf),
+        };
+        let _registered = cancel.map(|handle| handle.register(&op));
+        let (n, buf) = op.result().await;
+        (n.op_fd("write", fd), buf)
+    }
+
+    /// Query the metadata of the file.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        let fd = self.raw();
+        let op = Op::statx_fd(fd).op_fd("statx", fd)?;
+        let stat = op.result().await.op_fd("statx", fd)?;
+        Ok(Metadata::from_statx(stat))
+    }
+
+    /// Truncate or extend the file to `len` bytes, an extension reads as
+    /// zeroes. The file must be open for writing.
+    pub async fn set_len(&self, len: u64) -> io::Result<()> {
+        let fd = self.raw();
+        if !Ftruncate::is_supported() {
+            let len = libc::off_t::try_from(len)
+                .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))
+                .op_fd("ftruncate", fd)?;
+            return crate::syscall!(ftruncate@RAW(fd, len))
+                .map(drop)
+                .op_fd("ftruncate", fd);
+        }
+        let op = Op::ftruncate(fd, len).op_fd("ftruncate", fd)?;
+        op.await.meta.result.map(drop).op_fd("ftruncate", fd)
+    }
+
+    /// Manipulate the space of `len` bytes at `offset`, `mode` takes the
+    /// `FALLOC_FL_*` flags of `fallocate(2)`. Mode 0 allocates and extends
+    /// the file, `FALLOC_FL_KEEP_SIZE` allocates without extending it, and
+    /// `FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE` deallocates, the range
+    /// then reads as zeroes.
+    ///
+    /// Filesystems without support for a mode fail with `EOPNOTSUPP`.
+    pub async fn allocate(&self, offset: u64, len: u64, mode: i32) -> io::Result<()> {
+        let fd = self.raw();
+        let op = Op::fallocate(fd, offset, len, mode).op_fd("fallocate", fd)?;
+        op.await.meta.result.map(drop).op_fd("fallocate", fd)
+    }
+
+    /// Allocate space for the first `len` bytes, extending the file if it
+    /// is shorter.
+    pub async fn preallocate(&self, len: u64) -> io::Result<()> {
+        self.allocate(0, len, 0).await
+    }
+
+    /// Tell the kernel how `len` bytes at `offset` will be accessed, a `len`
+    /// of 0 reaches to the end of the file. Only a hint for the page cache.
+    pub async fn advise(&self, offset: u64, len: u64, advice: Advice) -> io::Result<()> {
+        let fd = self.raw();
+        let (offset_raw, len) = match (libc::off_t::try_from(offset), libc::off_t::try_from(len)) {
+            (Ok(offset), Ok(len)) => (offset, len),
+            _ => return Err(io::Error::from_raw_os_error(libc::EINVAL)).op_fd("fadvise", fd),
+        };
+        if !Fadvise::is_supported() {
+            // Returns the error number instead of setting errno.
+            return match unsafe { libc::posix_fadvise(fd, offset_raw, len, advice.as_raw()) } {
+                0 => Ok(()),
+                errno => Err(io::Error::from_raw_os_error(errno)).op_fd("fadvise", fd),
+            };
+        }
+        let op = Op::fadvise(fd, offset, len, advice.as_raw()).op_fd("fadvise", fd)?;
+        op.await.meta.result.map(drop).op_fd("fadvise", fd)
+    }
+
+    /// Flush the data and metadata of the file to storage.
+    pub async fn sync_all(&self) -> io::Result<()> {
+        self.fsync(false).await
+    }
+
+    /// Flush the data of the file to storage, metadata only as far as needed
+    /// to read the data back.
+    pub async fn sync_data(&self) -> io::Result<()> {
+        self.fsync(true).await
+    }
+
+    async fn fsync(&self, data_only: bool) -> io::Result<()> {
+        let fd = self.raw();
+        let op = Op::fsync(fd, data_only).op_fd("fsync", fd)?;
+        op.await.meta.result.map(drop).op_fd("fsync", fd)
+    }
+
+    /// Close the file and wait for the result.
+    pub async fn close(mut self) -> io::Result<()> {
+        let fd = self.fd.take().expect("file is open").into_raw_fd();
+        let op = match Op::close(fd) {
+            Ok(op) => op,
+            // No ring to close it on.
+            Err(_) => return syscall_close(fd).op_fd("close", fd),
+        };
+        op.await.meta.result.map(drop).op_fd("close", fd)
+    }
+
+    /// A second handle to the same open file, sharing its position and
+    /// flags. Each handle closes on its own, closing one leaves the other
+    /// usable.
+    ///
+    /// The ring has no dup, this is a blocking syscall on the current thread.
+    pub fn try_clone(&self) -> io::Result<File> {
+        let fd = self.raw();
+        let dup = crate::syscall!(fcntl@RAW(fd, libc::F_DUPFD_CLOEXEC, 0)).op_fd("fcntl", fd)?;
+        // Just created, owned by nothing else.
+        Ok(File::from(unsafe { OwnedFd::from_raw_fd(dup) }))
+    }
+
+    /// Turn into a [`std::fs::File`], for blocking io.
+    pub fn into_std(mut self) -> std::fs::File {
+        std::fs::File::from(self.fd.take().expect("file is open"))
+    }
+}
+
+fn syscall_close(fd: RawFd) -> io::Result<()> {
+    match unsafe { libc::close(fd) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+// Both go through the file position, like `read` and `write`.
+impl AsyncReadRent for File {
+    fn read<T: IoBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        File::read(self, buf)
+    }
+
+    fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.read_vectored_at(buf, CURRENT_POSITION)
+    }
+}
+
+impl AsyncWriteRent for File {
+    fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        File::write(self, buf)
+    }
+
+    fn writev<T: IoVecBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.write_vectored_at(buf, CURRENT_POSITION)
+    }
+
+    /// Writes are not buffered in userspace, this does nothing. See
+    /// [`File::sync_data`] for getting them to storage.
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Shut down the write side of a socket, other files stay as they are.
+    async fn shutdown(&mut self) -> io::Result<()> {
+        let fd = self.raw();
+        shutdown_write(fd).op_fd("shutdown", fd)
+    }
+}
+
+// Reads and writes are separate ops on the descriptor.
+impl Split for File {
+    fn read_shared<T: IoBufMut>(&self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        File::read(self, buf)
+    }
+
+    fn readv_shared<T: IoVecBufMut>(&self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.read_vectored_at(buf, CURRENT_POSITION)
+    }
+
+    fn write_shared<T: IoBuf>(&self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        File::write(self, buf)
+    }
+
+    fn writev_shared<T: IoVecBuf>(&self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.write_vectored_at(buf, CURRENT_POSITION)
+    }
+
+    fn shutdown_write(&self) -> io::Result<()> {
+        let fd = self.raw();
+        shutdown_write(fd).op_fd("shutdown", fd)
+    }
+}
+
+// Shut down the write side if `fd` is a socket.
+fn shutdown_write(fd: RawFd) -> io::Result<()> {
+    match crate::syscall!(shutdown@RAW(fd, libc::SHUT_WR)) {
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSOCK) => Ok(()),
+        res => res.map(drop),
+    }
+}
+
+impl From<OwnedFd> for File {
+    fn from(fd: OwnedFd) -> Self {
+        File { fd: Some(fd) }
+    }
+}
+
+impl From<std::fs::File> for File {
+    fn from(file: std::fs::File) -> Self {
+        File::from(OwnedFd::from(file))
+    }
+}
+
+impl From<File> for OwnedFd {
+    fn from(mut file: File) -> Self {
+        file.fd.take().expect("file is open")
+    }
+}
+
+impl FromRawFd for File {
+    /// # Safety
+    /// `fd` must be an open descriptor owned by nothing else, the file closes
+    /// it.
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        File::from(OwnedFd::from_raw_fd(fd))
+    }
+}
+
+impl IntoRawFd for File {
+    /// Give up the descriptor without closing it, the caller owns it now.
+    fn into_raw_fd(self) -> RawFd {
+        OwnedFd::from(self).into_raw_fd()
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.raw()
+    }
+}
+
+impl AsFd for File {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_ref().expect("file is open").as_fd()
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let Some(fd) = self.fd.take() else {
+            return;
+        };
+        // The ring closes it in the background when there is one.
+        let raw = fd.into_raw_fd();
+        if Close::detached(raw).is_err() {
+            let _ = syscall_close(raw);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn write_then_read_back() {
+        let path = std::env::temp_dir().join(format!("loop-file-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let (n, _) = file.write(b"hello ".to_vec()).await;
+            assert_eq!(n.unwrap(), 6);
+            // The position moved past the first write.
+            let (n, _) = file.write("world").await;
+            assert_eq!(n.unwrap(), 5);
+            file.close().await.unwrap();
+
+            let file = File::open(&path).await.unwrap();
+            let (n, buf) = file.read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 8);
+            assert_eq!(buf, b"hello wo");
+            let (n, buf) = file.read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 3);
+            assert_eq!(buf, b"rld");
+            let (n, _) = file.read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 0);
+
+            // Reading a file opened for writing only fails with the fd named.
+            let file = File::create(&path).await.unwrap();
+            let fd = file.as_raw_fd();
+            let (res, _) = file.read(Vec::with_capacity(8)).await;
+            let err = res.unwrap_err();
+            assert!(
+                err.to_string().starts_with(&format!("read fd {fd}: ")),
+                "{err}"
+            );
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn read_at_offsets() {
+        let path = std::env::temp_dir().join(format!("loop-file-read-at-{}", std::process::id()));
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        std::fs::write(&path, &data).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::open(&path).await.unwrap();
+            for (pos, len) in [(0, 16), (1, 255), (4096, 4096), (9_990, 10)] {
+                let (n, buf) = file.read_at(Vec::with_capacity(len), pos as u64).await;
+                assert_eq!(n.unwrap(), len);
+                assert_eq!(buf, data[pos..pos + len]);
+            }
+
+            // Short at the end of the file, the length only covers what was read.
+            let (n, buf) = file.read_at(Vec::with_capacity(64), 9_980).await;
+            assert_eq!(n.unwrap(), 20);
+            assert_eq!(buf.len(), 20);
+            assert_eq!(buf, data[9_980..]);
+            let (n, buf) = file.read_at(Vec::with_capacity(64), 20_000).await;
+            assert_eq!(n.unwrap(), 0);
+            assert!(buf.is_empty());
+
+            // The file position is untouched.
+            let (n, buf) = file.read(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, data[..4]);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_at_extends_the_file() {
+        let path = std::env::temp_dir().join(format!("loop-file-write-at-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let (n, _) = file.write_at("tail", 8).await;
+            assert_eq!(n.unwrap(), 4);
+            let (res, buf) = file.write_all_at(b"head".to_vec(), 0).await;
+            res.unwrap();
+            assert_eq!(buf, b"head");
+            file.close().await.unwrap();
+        });
+        assert_eq!(std::fs::read(&path).unwrap(), b"head\0\0\0\0tail");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn empty_write_at_is_not_submitted() {
+        let file = File::from(std::fs::File::open("/dev/null").unwrap());
+        // No runtime: a submitted write would fail instead of being ready.
+        let mut fut = std::pin::pin!(file.write_at(Vec::new(), 0));
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        match std::future::Future::poll(fut.as_mut(), &mut cx) {
+            std::task::Poll::Ready((res, _)) => assert_eq!(res.unwrap(), 0),
+            std::task::Poll::Pending => panic!("empty write was submitted"),
+        }
+    }
+
+    #[test]
+    fn vectored_at() {
+        let path = std::env::temp_dir().join(format!("loop-file-vectored-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let bufs = vec![b"abc".to_vec(), Vec::new(), b"de".to_vec(), b"fgh".to_vec()];
+            let (res, _) = file.write_all_vectored_at(VecBuf::from(bufs), 2).await;
+            res.unwrap();
+            let (n, _) = file
+                .write_vectored_at(VecBuf::from(vec![b"@!".to_vec()]), 0)
+                .await;
+            assert_eq!(n.unwrap(), 2);
+            assert_eq!(std::fs::read(&path).unwrap(), b"@!abcdefgh");
+
+            // The end of the file falls into the last buffer.
+            let file = File::open(&path).await.unwrap();
+            let mut buf = VecBuf::from(vec![vec![0; 4]; 3]);
+            let (n, b) = file.read_vectored_at(buf, 0).await;
+            buf = b;
+            let n = n.unwrap();
+            assert_eq!(n, 10);
+            buf.advance(n);
+            assert_eq!(buf.len(), 2);
+            let (n, buf) = file.read_vectored_at(buf, n as u64).await;
+            assert_eq!(n.unwrap(), 0);
+            assert_eq!(buf.into_inner(), [&b"@!ab"[..], b"cdef", b"gh\0\0"]);
+
+            // A read that stops within a buffer resumes in the middle of it.
+            let mut buf = VecBuf::from(vec![vec![0; 3], vec![0; 4]]);
+            let (n, b) = file.read_vectored_at(buf, 5).await;
+            buf = b;
+            buf.advance(n.unwrap());
+            let (n, buf) = file.read_vectored_at(buf, 0).await;
+            assert_eq!(n.unwrap(), 2);
+            assert_eq!(buf.into_inner(), [&b"def"[..], b"gh@!"]);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resume_short_write_with_slice() {
+        use crate::buf::IoBuf;
+        use crate::fs::Pipe;
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let pipe = Pipe::new().unwrap();
+            // A full pipe takes part of a write instead of waiting.
+            let fd = pipe.writer().as_raw_fd();
+            assert_eq!(
+                unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) },
+                0
+            );
+            let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+
+            let (mut buf, mut written, mut writes) = (data.clone(), 0, 0);
+            let mut read = Vec::new();
+            while written < data.len() {
+                // Resume after the bytes the last write took.
+                let (n, slice) = pipe.writer().write(buf.slice(written..)).await;
+                assert_eq!(slice.begin(), written);
+                buf = slice.into_inner();
+                written += n.unwrap();
+                writes += 1;
+                // Drain the pipe for the rest.
+                let (n, chunk) = pipe
+                    .reader()
+                    .read(Vec::with_capacity(written - read.len()))
+                    .await;
+                assert_eq!(n.unwrap(), written - read.len());
+                read.extend_from_slice(&chunk);
+            }
+            assert!(writes > 1);
+            assert_eq!(read, data);
+            assert_eq!(buf, data);
+        });
+    }
+
+    #[test]
+    fn fixed_buffers() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let path = std::env::temp_dir().join(format!("loop-file-fixed-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+            .with_registered_buffers(&[4096], 1)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let mut buf = FixedBuf::checkout(100).await.unwrap();
+            assert_eq!((buf.buf_index(), buf.capacity()), (0, 4096));
+            buf.extend_from_slice(b"fixed buffers");
+            let (n, buf) = file.write_fixed_at(buf, 0).await;
+            assert_eq!(n.unwrap(), 13);
+            drop(buf);
+
+            let buf = FixedBuf::checkout(4096).await.unwrap();
+            let file = File::open(&path).await.unwrap();
+            let (n, mut buf) = file.read_fixed_at(buf, 6).await;
+            assert_eq!(n.unwrap(), 7);
+            assert_eq!(&buf[..], b"buffers");
+
+            // Exhausted, the next checkout waits for this one.
+            let done = Rc::new(Cell::new(false));
+            let waiter = crate::runtime::spawn({
+                let done = done.clone();
+                async move {
+                    let buf = FixedBuf::checkout(1).await.unwrap();
+                    done.set(true);
+                    buf.len()
+                }
+            });
+            file.metadata().await.unwrap();
+            assert!(!done.get());
+            buf.truncate(0);
+            drop(buf);
+            assert_eq!(waiter.await, 0);
+
+            let err = FixedBuf::checkout(4097).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        });
+
+        // Without registered buffers.
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let err = rt.block_on(FixedBuf::checkout(1)).unwrap_err();
+        assert!(err.to_string().contains("with_registered_buffers"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn vectored_at_with_small_io_vec() {
+        use crate::buf::SmallIoVec;
+
+        let path = std::env::temp_dir().join(format!("loop-file-small-iov-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut bufs = SmallIoVec::<Box<[u8]>, 2>::new();
+            bufs.push(Box::from(&b"boxed "[..])).unwrap();
+            bufs.push(Box::from(&b"chunks"[..])).unwrap();
+            let file = File::create(&path).await.unwrap();
+            let (n, _) = file.write_vectored_at(bufs, 0).await;
+            assert_eq!(n.unwrap(), 12);
+
+            // Growable buffers become as long as what was read into them.
+            let file = File::open(&path).await.unwrap();
+            let mut bufs = SmallIoVec::<Vec<u8>, 4>::new();
+            for cap in [4, 4, 16, 1] {
+                bufs.push(Vec::with_capacity(cap)).unwrap();
+            }
+            let (n, bufs) = file.read_vectored_at(bufs, 0).await;
+            assert_eq!(n.unwrap(), 12);
+            let bufs = bufs.into_inner();
+            assert_eq!(bufs[0][..], *b"boxe");
+            assert_eq!(bufs[1][..], *b"d ch");
+            assert_eq!(bufs[2][..], *b"unks");
+            assert!(bufs[3].is_empty());
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn allocate() {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = std::env::temp_dir().join(format!("loop-file-allocate-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .await
+                .unwrap();
+            file.preallocate(64 * 1024).await.unwrap();
+            assert_eq!(file.metadata().await.unwrap().len(), 64 * 1024);
+
+            // Space past the end, the size stays.
+            let blocks = std::fs::metadata(&path).unwrap().blocks();
+            file.allocate(64 * 1024, 64 * 1024, libc::FALLOC_FL_KEEP_SIZE)
+                .await
+                .unwrap();
+            assert_eq!(file.metadata().await.unwrap().len(), 64 * 1024);
+            assert!(std::fs::metadata(&path).unwrap().blocks() > blocks);
+
+            let (res, _) = file.write_all_at(vec![1; 16 * 1024], 0).await;
+            res.unwrap();
+            let punch = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+            match file.allocate(4096, 8192, punch).await {
+                Ok(()) => {
+                    let (n, buf) = file.read_at(Vec::with_capacity(16 * 1024), 0).await;
+                    assert_eq!(n.unwrap(), 16 * 1024);
+                    assert!(buf[..4096].iter().all(|b| *b == 1));
+                    assert!(buf[4096..12288].iter().all(|b| *b == 0));
+                    assert!(buf[12288..].iter().all(|b| *b == 1));
+                }
+                // Passed through from filesystems that cannot punch holes.
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::Unsupported, "{e}"),
+            }
+            assert_eq!(file.metadata().await.unwrap().len(), 64 * 1024);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn set_len() {
+        let path = std::env::temp_dir().join(format!("loop-file-set-len-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let (res, _) = file.write_all_at("0123456789", 0).await;
+            res.unwrap();
+            file.set_len(4).await.unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"0123");
+            file.set_len(8).await.unwrap();
+            assert_eq!(std::fs::read(&path).unwrap(), b"0123\0\0\0\0");
+
+            let file = File::open(&path).await.unwrap();
+            let err = file.set_len(0).await.unwrap_err();
+            let source = err.get_ref().and_then(|e| e.source()).unwrap();
+            let source = source.downcast_ref::<io::Error>().unwrap();
+            assert_eq!(source.raw_os_error(), Some(libc::EINVAL));
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), 8);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn advise() {
+        let path = std::env::temp_dir().join(format!("loop-file-advise-{}", std::process::id()));
+        std::fs::write(&path, vec![0; 64 * 1024]).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::open(&path).await.unwrap();
+            for advice in [
+                Advice::Normal,
+                Advice::Sequential,
+                Advice::Random,
+                Advice::WillNeed,
+                Advice::DontNeed,
+                Advice::NoReuse,
+            ] {
+                file.advise(0, 0, advice).await.unwrap();
+            }
+            file.advise(4096, 8192, Advice::WillNeed).await.unwrap();
+
+            let (rx, _tx) = io::pipe().unwrap();
+            let pipe = File::from(OwnedFd::from(rx));
+            let err = pipe.advise(0, 0, Advice::Sequential).await.unwrap_err();
+            let source = err.get_ref().and_then(|e| e.source()).unwrap();
+            let source = source.downcast_ref::<io::Error>().unwrap();
+            assert_eq!(source.raw_os_error(), Some(libc::ESPIPE));
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn sync() {
+        let path = std::env::temp_dir().join(format!("loop-file-sync-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::create(&path).await.unwrap();
+            let (res, _) = file.write_all_at("durable", 0).await;
+            res.unwrap();
+            file.sync_data().await.unwrap();
+            file.sync_all().await.unwrap();
+
+            // Linux syncs regular files opened read only as well.
+            let file = File::open(&path).await.unwrap();
+            file.sync_all().await.unwrap();
+
+            // A pipe can not be synced.
+            let (rx, _tx) = io::pipe().unwrap();
+            let file = File::from(OwnedFd::from(rx));
+            let err = file.sync_data().await.unwrap_err();
+            let source = err.get_ref().and_then(|e| e.source()).unwrap();
+            let source = source.downcast_ref::<io::Error>().unwrap();
+            assert_eq!(source.raw_os_error(), Some(libc::EINVAL));
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    fn open_count(path: &Path) -> usize {
+        std::fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok())
+            .filter(|target| target == path)
+            .count()
+    }
+
+    #[test]
+    fn drop_reclaims_the_fd() {
+        let path = std::env::temp_dir().join(format!("loop-file-drop-{}", std::process::id()));
+        std::fs::write(&path, b"fd").unwrap();
+        // A small ring, the drops below overflow its submission queue.
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+            .with_entries(8)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            for _ in 0..100 {
+                let files = [
+                    File::open(&path).await.unwrap(),
+                    File::open(&path).await.unwrap(),
+                    File::open(&path).await.unwrap(),
+                    File::open(&path).await.unwrap(),
+                ];
+                drop(files);
+            }
+            // Closed by the round trip of the next op.
+            let file = File::open(&path).await.unwrap();
+            assert_eq!(open_count(&path), 1);
+            drop(file);
+            let many: Vec<_> = (0..20)
+                .map(|_| std::fs::File::open(&path).unwrap())
+                .collect();
+            many.into_iter().map(File::from).for_each(drop);
+        });
+        // Closes still queued when the runtime goes away are not lost.
+        drop(rt);
+        assert_eq!(open_count(&path), 0);
+
+        // Without a runtime the descriptor is closed right away.
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let file = rt.block_on(File::open(&path)).unwrap();
+        drop(rt);
+        assert_eq!(open_count(&path), 1);
+        drop(file);
+        assert_eq!(open_count(&path), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn into_std_keeps_the_fd() {
+        let path = std::env::temp_dir().join(format!("loop-file-std-{}", std::process::id()));
+        std::fs::write(&path, b"std").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let file = rt.block_on(File::open(&path)).unwrap();
+        let raw = file.as_raw_fd();
+        let file = file.into_std();
+        assert_eq!(file.as_raw_fd(), raw);
+        assert_eq!(io::read_to_string(&file).unwrap(), "std");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn try_clone_closes_independently() {
+        let path = std::env::temp_dir().join(format!("loop-file-clone-{}", std::process::id()));
+        std::fs::write(&path, b"shared contents").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let file = File::open(&path).await.unwrap();
+            let clone = file.try_clone().unwrap();
+            assert_ne!(clone.as_raw_fd(), file.as_raw_fd());
+            assert_eq!(open_count(&path), 2);
+
+            let (a, b) = crate::join!(
+                file.read_at(Vec::with_capacity(6), 0),
+                clone.read_at(Vec::with_capacity(8), 7)
+            );
+            assert_eq!((a.0.unwrap(), b.0.unwrap()), (6, 8));
+            assert_eq!((&a.1[..], &b.1[..]), (&b"shared"[..], &b"contents"[..]));
+
+            // The clone outlives the original.
+            file.close().await.unwrap();
+            let (n, buf) = clone.read_at(Vec::with_capacity(6), 0).await;
+            assert_eq!(n.unwrap(), 6);
+            assert_eq!(buf, b"shared");
+
+            // Not closed once handed out, until owned again.
+            let raw = clone.into_raw_fd();
+            File::open(&path).await.unwrap().close().await.unwrap();
+            assert_eq!(open_count(&path), 1);
+            unsafe { File::from_raw_fd(raw) }.close().await.unwrap();
+            assert_eq!(open_count(&path), 0);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use crate::driver::file_io::STATX_DIOALIGN;
+use crate::driver::op::Op;
+use crate::fs::Permissions;
+use crate::utils::error_ctx::ResultExt;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+/// Metadata of a file, as returned by `statx`.
+#[derive(Clone)]
+pub struct Metadata {
+    stat: libc::statx,
+}
+
+/// The type of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileType {
+    // The `S_IFMT` bits of the mode.
+    mode: u32,
+}
+
+impl FileType {
+    pub(crate) fn from_mode(mode: u32) -> Self {
+        FileType {
+            mode: mode & libc::S_IFMT,
+        }
+    }
+
+    /// Whether this is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.mode == libc::S_IFREG
+    }
+
+    /// Whether this is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.mode == libc::S_IFDIR
+    }
+
+    /// Whether this is a symlink.
+    pub fn is_symlink(&self) -> bool {
+        self.mode == libc::S_IFLNK
+    }
+}
+
+/// Query the metadata of `path`, following symlinks.
+pub async fn metadata(path: impl AsRef<Path>) -> io::Result<Metadata> {
+    stat_path(path.as_ref(), true).await
+}
+
+/// Query the metadata of `path` itself, without following a final symlink.
+pub async fn symlink_metadata(path: impl AsRef<Path>) -> io::Result<Metadata> {
+    stat_path(path.as_ref(), false).await
+}
+
+async fn stat_path(path: &Path, follow: bool) -> io::Result<Metadata> {
+    let op = Op::statx(path, follow).op_path("statx", path)?;
+    let stat = op.result().await.op_path("statx", path)?;
+    Ok(Metadata { stat })
+}
+
+impl Metadata {
+    pub(crate) fn from_statx(stat: libc::statx) -> Self {
+        Metadata { stat }
+    }
+
+    /// The size of the file in bytes.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.stat.stx_size
+    }
+
+    /// The type of the file.
+    pub fn file_type(&self) -> FileType {
+        FileType::from_mode(u32::from(self.stat.stx_mode))
+    }
+
+    /// Whether this is a regular file.
+    pub fn is_file(&self) -> bool {
+        self.file_type().is_file()
+    }
+
+    /// Whether this is a directory.
+    pub fn is_dir(&self) -> bool {
+        self.file_type().is_dir()
+    }
+
+    /// Whether this is a symlink, only ever set by [`symlink_metadata`].
+    pub fn is_symlink(&self) -> bool {
+        self.file_type().is_symlink()
+    }
+
+    /// The number of hard links to the file.
+    pub fn nlink(&self) -> u64 {
+        u64::from(self.stat.stx_nlink)
+    }
+
+    /// The alignments of memory and of file offsets that direct io on the
+    /// file needs, if the kernel and filesystem report them.
+    pub(crate) fn dio_align(&self) -> Option<(u32, u32)> {
+        let stat = &self.stat;
+        (stat.stx_mask & STATX_DIOALIGN != 0 && stat.stx_dio_offset_align != 0)
+            .then_some((stat.stx_dio_mem_align, stat.stx_dio_offset_align))
+    }
+
+    /// Whether this is a block device.
+    pub(crate) fn is_block_device(&self) -> bool {
+        FileType::from_mode(u32::from(self.stat.stx_mode)).mode == libc::S_IFBLK
+    }
+
+    /// The permissions of the file, the full mode as [`std::fs::Metadata`]
+    /// has it.
+    pub fn permissions(&self) -> Permissions {
+        Permissions::from_mode(u32::from(self.stat.stx_mode))
+    }
+
+    /// The time of the last modification.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        self.time(libc::STATX_MTIME, self.stat.stx_mtime)
+    }
+
+    /// The time of the last access.
+    pub fn accessed(&self) -> io::Result<SystemTime> {
+        self.time(libc::STATX_ATIME, self.stat.stx_atime)
+    }
+
+    fn time(&self, field: u32, ts: libc::statx_timestamp) -> io::Result<SystemTime> {
+        // Some filesystems do not keep every timestamp.
+        if self.stat.stx_mask & field == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "timestamp not available on this filesystem",
+            ));
+        }
+        let nanos = Duration::from_nanos(u64::from(ts.tv_nsec));
+        let time = match ts.tv_sec {
+            sec @ 0.. => SystemTime::UNIX_EPOCH + Duration::from_secs(sec as u64) + nanos,
+            sec => SystemTime::UNIX_EPOCH - Duration::from_secs(sec.unsigned_abs()) + nanos,
+        };
+        Ok(time)
+    }
+}
+
+impl fmt::Debug for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Metadata")
+            .field("len", &self.len())
+            .field("mode", &format_args!("{:o}", self.stat.stx_mode))
+            .field("modified", &self.modified().ok())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::runtime::RuntimeBuilder;
+
+    fn assert_matches_std(ours: &Metadata, std: &std::fs::Metadata) {
+        assert_eq!(ours.len(), std.len());
+        assert_eq!(ours.is_file(), std.is_file());
+        assert_eq!(ours.is_dir(), std.is_dir());
+        assert_eq!(ours.is_symlink(), std.is_symlink());
+        assert_eq!(ours.nlink(), std::os::unix::fs::MetadataExt::nlink(std));
+        assert_eq!(ours.permissions(), std.permissions().into());
+        assert_eq!(ours.modified().unwrap(), std.modified().unwrap());
+        assert_eq!(ours.accessed().unwrap(), std.accessed().unwrap());
+    }
+
+    #[test]
+    fn matches_std() {
+        let dir = std::env::temp_dir().join(format!("loop-metadata-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let file = dir.join("file");
+        let link = dir.join("link");
+        std::fs::write(&file, b"twelve bytes").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let meta = metadata(&file).await.unwrap();
+            assert!(meta.is_file());
+            assert_eq!(meta.len(), 12);
+            assert_matches_std(&meta, &std::fs::metadata(&file).unwrap());
+
+            let meta = metadata(&dir).await.unwrap();
+            assert!(meta.is_dir());
+            assert_matches_std(&meta, &std::fs::metadata(&dir).unwrap());
+
+            // Followed unless asked otherwise.
+            let meta = metadata(&link).await.unwrap();
+            assert!(meta.is_file());
+            assert_matches_std(&meta, &std::fs::metadata(&link).unwrap());
+            let meta = symlink_metadata(&link).await.unwrap();
+            assert!(meta.is_symlink());
+            assert_matches_std(&meta, &std::fs::symlink_metadata(&link).unwrap());
+
+            let opened = File::open(&file).await.unwrap();
+            let meta = opened.metadata().await.unwrap();
+            assert_matches_std(&meta, &std::fs::metadata(&file).unwrap());
+
+            let err = metadata(dir.join("missing")).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
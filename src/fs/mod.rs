@@ -1 +1,426 @@
-mod Opener;
\ No newline at end of file
+//! Filesystem access through the ring.
+
+mod dir_builder;
+mod direct_file;
+mod dma_file;
+mod file;
+mod metadata;
+mod open_options;
+mod permissions;
+pub(crate) mod pipe;
+mod read_dir;
+
+pub use dir_builder::DirBuilder;
+pub use direct_file::DirectFile;
+pub use dma_file::DmaFile;
+pub use file::{Advice, File};
+pub use metadata::{metadata, symlink_metadata, FileType, Metadata};
+pub use open_options::{OpenOptions, ResolveFlags};
+pub use permissions::{set_permissions, set_symlink_permissions, Permissions};
+pub use pipe::{splice_copy, Pipe};
+pub use read_dir::{read_dir, DirEntry, ReadDir};
+
+use crate::buf::IoBuf;
+use crate::driver::op::Op;
+use crate::driver::util::cstr;
+use crate::utils::error_ctx::ResultExt;
+use std::ffi::OsString;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+/// Read the whole file at `path`.
+///
+/// The size from `statx` only sizes the buffer, reading goes on until the
+/// end, so files that grow meanwhile or report no size, like those in
+/// `/proc`, are read whole.
+pub async fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let file = File::open(path).await?;
+    let len = match file.metadata().await {
+        Ok(meta) => usize::try_from(meta.len()).unwrap_or(0),
+        Err(_) => 0,
+    };
+    // One spare byte so the end shows without growing the buffer.
+    let (res, buf) = file.read_to_end_at(Vec::with_capacity(len + 1), 0).await;
+    res?;
+    file.close().await?;
+    Ok(buf)
+}
+
+/// Write `contents` to the file at `path`, creating or truncating it.
+pub async fn write(path: impl AsRef<Path>, contents: impl IoBuf) -> io::Result<()> {
+    write_file(path.as_ref(), contents, false).await
+}
+
+/// Like [`write()`], then flush the file to storage before returning.
+pub async fn write_sync(path: impl AsRef<Path>, contents: impl IoBuf) -> io::Result<()> {
+    write_file(path.as_ref(), contents, true).await
+}
+
+async fn write_file(path: &Path, contents: impl IoBuf, sync: bool) -> io::Result<()> {
+    let file = File::create(path).await?;
+    let (res, _) = file.write_all_at(contents, 0).await;
+    res?;
+    if sync {
+        file.sync_all().await?;
+    }
+    file.close().await
+}
+
+/// Copy the contents and permissions of the file at `from` to `to`,
+/// creating or truncating it. Returns the number of bytes copied.
+///
+/// The kernel copies with `copy_file_range`, a blocking syscall on the
+/// current thread. Where it can not, across some filesystems for one, the
+/// bytes go through a buffer with ring reads and writes.
+pub async fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    let src = File::open(from).await?;
+    let perm = src.metadata().await?.permissions();
+    let dst = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(perm.mode())
+        .open(to)
+        .await?;
+    // An existing file keeps its mode on open, like std it gets the mode of
+    // the source as well.
+    dst.set_permissions(perm).await?;
+    let copied = match copy_file_range(&src, &dst)
+        .await
+        .op_path("copy_file_range", to)?
+    {
+        Some(copied) => copied,
+        None => copy_buffered(&src, &dst).await?,
+    };
+    dst.close().await?;
+    Ok(copied)
+}
+
+// None when the kernel can not copy between the two, before anything was
+// copied.
+async fn copy_file_range(src: &File, dst: &File) -> io::Result<Option<u64>> {
+    let (src, dst) = (src.as_raw_fd(), dst.as_raw_fd());
+    let (mut off_in, mut off_out): (libc::off64_t, libc::off64_t) = (0, 0);
+    let (p_in, p_out) = (&mut off_in as *mut _, &mut off_out as *mut _);
+    let mut copied = 0;
+    loop {
+        let res = crate::syscall!(copy_file_range@RAW(src, p_in, dst, p_out, 1 << 30, 0));
+        match res {
+            // Files in /proc and the like report no size and copy nothing.
+            Ok(0) if copied == 0 => return Ok(None),
+            Ok(0) => return Ok(Some(copied)),
+            Ok(n) => copied += n as u64,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if copied == 0 && cannot_copy_range(&e) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn cannot_copy_range(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EXDEV | libc::EOPNOTSUPP | libc::ENOSYS | libc::EINVAL | libc::EPERM)
+    )
+}
+
+async fn copy_buffered(src: &File, dst: &File) -> io::Result<u64> {
+    let mut buf = Vec::with_capacity(64 * 1024);
+    let mut copied = 0;
+    loop {
+        buf.clear();
+        let (res, b) = src.read_at(buf, copied).await;
+        let n = match res {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                buf = b;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let (res, b) = dst.write_all_at(b, copied).await;
+        res?;
+        buf = b;
+        copied += n as u64;
+    }
+}
+
+/// Create the directory at `path`, its parent must exist.
+pub async fn create_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    DirBuilder::new().create(path).await
+}
+
+/// Create the directory at `path` and any missing parents.
+pub async fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    DirBuilder::new().recursive(true).create(path).await
+}
+
+/// Remove the file at `path`.
+pub async fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    unlink(path.as_ref(), false).await
+}
+
+/// Remove the empty directory at `path`.
+pub async fn remove_dir(path: impl AsRef<Path>) -> io::Result<()> {
+    unlink(path.as_ref(), true).await
+}
+
+async fn unlink(path: &Path, dir: bool) -> io::Result<()> {
+    let op = Op::unlink(path, dir).op_path("unlinkat", path)?;
+    op.await.meta.result.map(drop).op_path("unlinkat", path)
+}
+
+/// Rename `from` to `to`, replacing `to` if it exists.
+///
+/// Both must be on the same filesystem, otherwise the error is `EXDEV` and
+/// the caller can fall back to copying and removing.
+pub async fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    rename_with(from.as_ref(), to.as_ref(), 0).await
+}
+
+/// Rename `from` to `to`, failing with [`io::ErrorKind::AlreadyExists`] if
+/// `to` exists.
+pub async fn rename_noreplace(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    rename_with(from.as_ref(), to.as_ref(), libc::RENAME_NOREPLACE).await
+}
+
+async fn rename_with(from: &Path, to: &Path, flags: u32) -> io::Result<()> {
+    let op = Op::rename(from, to, flags).op_path("renameat", from)?;
+    op.await.meta.result.map(drop).op_path("renameat", from)
+}
+
+/// Create a symlink at `link` pointing to `original`.
+pub async fn symlink(original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+    let link = link.as_ref();
+    let op = Op::symlink(original.as_ref(), link).op_path("symlinkat", link)?;
+    op.await.meta.result.map(drop).op_path("symlinkat", link)
+}
+
+/// Create a hard link at `link` to the file at `original`.
+///
+/// Both must be on the same filesystem, otherwise the error is `EXDEV`.
+pub async fn hard_link(original: impl AsRef<Path>, link: impl AsRef<Path>) -> io::Result<()> {
+    let link = link.as_ref();
+    let op = Op::link(original.as_ref(), link).op_path("linkat", link)?;
+    op.await.meta.result.map(drop).op_path("linkat", link)
+}
+
+/// Read the target of the symlink at `path`.
+///
+/// The ring has no readlink, this is a blocking syscall on the current thread.
+pub async fn read_link(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let c_path = cstr(path).op_path("readlink", path)?;
+    let mut buf = Vec::<u8>::with_capacity(256);
+    loop {
+        let n = crate::syscall!(readlink@RAW(
+            c_path.as_ptr(),
+            buf.as_mut_ptr().cast(),
+            buf.capacity()
+        ))
+        .op_path("readlink", path)? as usize;
+        // A full buffer may have cut the target short.
+        if n < buf.capacity() {
+            unsafe { buf.set_len(n) };
+            return Ok(PathBuf::from(OsString::from_vec(buf)));
+        }
+        buf.reserve(buf.capacity() * 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn raw_os_error(err: &io::Error) -> Option<i32> {
+        let source = err.get_ref()?.source()?;
+        source.downcast_ref::<io::Error>()?.raw_os_error()
+    }
+
+    #[test]
+    fn read_and_write() {
+        let path = std::env::temp_dir().join(format!("loop-fs-read-write-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+            write(&path, data.clone()).await.unwrap();
+            let got = read(&path).await.unwrap();
+            assert_eq!(got, data);
+            // Sized from statx, no growing on the way.
+            assert_eq!(got.capacity(), data.len() + 1);
+
+            // Truncated by the next write.
+            write_sync(&path, "short").await.unwrap();
+            assert_eq!(read(&path).await.unwrap(), b"short");
+            write(&path, Vec::new()).await.unwrap();
+            assert!(read(&path).await.unwrap().is_empty());
+
+            // No size, still content.
+            assert_eq!(std::fs::metadata("/proc/self/status").unwrap().len(), 0);
+            let status = read("/proc/self/status").await.unwrap();
+            assert!(status.starts_with(b"Name:"));
+
+            let err = read(path.with_extension("missing")).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn copy_files() {
+        let dir = std::env::temp_dir().join(format!("loop-fs-copy-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let (src, dst) = (dir.join("src"), dir.join("dst"));
+        let data: Vec<u8> = (0..=255).cycle().take(300_000).collect();
+        std::fs::write(&src, &data).unwrap();
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o640)).unwrap();
+        // Longer than the source and with another mode, both are replaced.
+        std::fs::write(&dst, vec![1; 400_000]).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            assert_eq!(copy(&src, &dst).await.unwrap(), data.len() as u64);
+            assert_eq!(std::fs::read(&dst).unwrap(), data);
+            let mode = std::fs::metadata(&dst).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+
+            // The fallback, as taken when the kernel can not copy.
+            let (from, to) = (
+                File::open(&src).await.unwrap(),
+                File::create(&dst).await.unwrap(),
+            );
+            assert_eq!(copy_buffered(&from, &to).await.unwrap(), data.len() as u64);
+            assert_eq!(std::fs::read(&dst).unwrap(), data);
+
+            std::fs::write(&src, b"").unwrap();
+            assert_eq!(copy(&src, &dst).await.unwrap(), 0);
+            assert!(std::fs::read(&dst).unwrap().is_empty());
+
+            // No size, still content.
+            let n = copy("/proc/self/status", &dst).await.unwrap();
+            assert!(n > 0);
+            assert!(std::fs::read(&dst).unwrap().starts_with(b"Name:"));
+
+            let err = copy(dir.join("missing"), &dst).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove() {
+        let dir = std::env::temp_dir().join(format!("loop-fs-remove-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let file = dir.join("file");
+        std::fs::write(&file, b"gone").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // A directory is not a file, and a file is not a directory.
+            let err = remove_file(&dir).await.unwrap_err();
+            assert_eq!(raw_os_error(&err), Some(libc::EISDIR));
+            let err = remove_dir(&file).await.unwrap_err();
+            assert_eq!(raw_os_error(&err), Some(libc::ENOTDIR));
+
+            let err = remove_dir(&dir).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::DirectoryNotEmpty);
+            assert_eq!(raw_os_error(&err), Some(libc::ENOTEMPTY));
+
+            remove_file(&file).await.unwrap();
+            assert!(!file.exists());
+            let err = remove_file(&file).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+            remove_dir(&dir).await.unwrap();
+            assert!(!dir.exists());
+            let err = remove_dir(&dir).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        });
+    }
+
+    #[test]
+    fn rename_files() {
+        let dir = std::env::temp_dir().join(format!("loop-fs-rename-{}", std::process::id()));
+        let sub = dir.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        let (a, b, c) = (dir.join("a"), dir.join("b"), sub.join("c"));
+        std::fs::write(&a, b"a").unwrap();
+        std::fs::write(&b, b"b").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let err = rename_noreplace(&a, &b).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            assert_eq!(std::fs::read(&b).unwrap(), b"b");
+
+            rename(&a, &b).await.unwrap();
+            assert!(!a.exists());
+            assert_eq!(std::fs::read(&b).unwrap(), b"a");
+
+            // Into another directory.
+            rename_noreplace(&b, &c).await.unwrap();
+            assert_eq!(std::fs::read(&c).unwrap(), b"a");
+
+            let err = rename(&a, &b).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err.to_string().starts_with("renameat "), "{err}");
+
+            // The errno of a move across filesystems is kept for the fallback.
+            let shm =
+                std::path::PathBuf::from(format!("/dev/shm/loop-rename-{}", std::process::id()));
+            let dev =
+                |p: &Path| std::os::unix::fs::MetadataExt::dev(&std::fs::metadata(p).unwrap());
+            if Path::new("/dev/shm").is_dir() && dev(Path::new("/dev/shm")) != dev(&dir) {
+                let err = rename(&c, &shm).await.unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::CrossesDevices);
+                assert_eq!(raw_os_error(&err), Some(libc::EXDEV));
+            }
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn links() {
+        let dir = std::env::temp_dir().join(format!("loop-fs-links-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let (file, soft, hard) = (dir.join("file"), dir.join("soft"), dir.join("hard"));
+        std::fs::write(&file, b"linked").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            symlink(&file, &soft).await.unwrap();
+            assert_eq!(read_link(&soft).await.unwrap(), file);
+            assert!(symlink_metadata(&soft).await.unwrap().is_symlink());
+
+            assert_eq!(metadata(&file).await.unwrap().nlink(), 1);
+            hard_link(&file, &hard).await.unwrap();
+            assert_eq!(metadata(&file).await.unwrap().nlink(), 2);
+            assert_eq!(read(&hard).await.unwrap(), b"linked");
+
+            let err = symlink(&file, &hard).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            let err = hard_link(&file, &soft).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            let err = read_link(&file).await.unwrap_err();
+            assert_eq!(raw_os_error(&err), Some(libc::EINVAL));
+
+            // Targets longer than the first buffer come back whole.
+            let long = Path::new("x").join("y".repeat(250)).join("z".repeat(250));
+            symlink(&long, dir.join("long")).await.unwrap();
+            assert_eq!(read_link(dir.join("long")).await.unwrap(), long);
+
+            let shm =
+                std::path::PathBuf::from(format!("/dev/shm/loop-links-{}", std::process::id()));
+            let dev =
+                |p: &Path| std::os::unix::fs::MetadataExt::dev(&std::fs::metadata(p).unwrap());
+            if Path::new("/dev/shm").is_dir() && dev(Path::new("/dev/shm")) != dev(&dir) {
+                let err = hard_link(&file, &shm).await.unwrap_err();
+                assert_eq!(raw_os_error(&err), Some(libc::EXDEV));
+            }
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
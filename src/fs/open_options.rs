@@ -0,0 +1,652 @@
+use crate::driver::file_io::OpenAt;
+use crate::driver::fixed_files::FixedSlot;
+use crate::driver::op::Op;
+use crate::fs::{DirectFile, File};
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::ops::{BitOr, BitOrAssign};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::path::Path;
+
+/// Options for opening files, like [`std::fs::OpenOptions`].
+///
+/// Invalid combinations fail to open with `EINVAL`: no access mode, or
+/// `truncate`, `create` or `create_new` without `write` or `append`, or
+/// `append` with `truncate`.
+#[derive(Clone, Debug)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    pub(crate) custom_flags: libc::c_int,
+    mode: libc::mode_t,
+    resolve: ResolveFlags,
+}
+
+/// Restrictions on resolving a path, the `RESOLVE_*` flags of `openat2(2)`,
+/// see [`OpenOptions::resolve`]. Combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ResolveFlags(u64);
+
+impl ResolveFlags {
+    /// Fail with `EXDEV` when resolving leaves the directory, through `..`,
+    /// an absolute path or a symlink.
+    pub const BENEATH: ResolveFlags = ResolveFlags(libc::RESOLVE_BENEATH);
+    /// Resolve as if the directory was the root, `..` and absolute symlinks
+    /// stay inside it.
+    pub const IN_ROOT: ResolveFlags = ResolveFlags(libc::RESOLVE_IN_ROOT);
+    /// Fail with `ELOOP` on any symlink.
+    pub const NO_SYMLINKS: ResolveFlags = ResolveFlags(libc::RESOLVE_NO_SYMLINKS);
+    /// Fail with `ELOOP` on magic links, like those in `/proc/<pid>/fd`.
+    pub const NO_MAGICLINKS: ResolveFlags = ResolveFlags(libc::RESOLVE_NO_MAGICLINKS);
+    /// Fail with `EXDEV` when crossing a mount point.
+    pub const NO_XDEV: ResolveFlags = ResolveFlags(libc::RESOLVE_NO_XDEV);
+    /// Only resolve from the cache, `EAGAIN` otherwise.
+    pub const CACHED: ResolveFlags = ResolveFlags(libc::RESOLVE_CACHED);
+
+    /// No restrictions.
+    pub const fn empty() -> Self {
+        ResolveFlags(0)
+    }
+
+    /// Whether no flag is set.
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all flags of `other` are set.
+    pub const fn contains(&self, other: ResolveFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The `RESOLVE_*` bits.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+impl BitOr for ResolveFlags {
+    type Output = ResolveFlags;
+
+    fn bitor(self, rhs: ResolveFlags) -> ResolveFlags {
+        ResolveFlags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for ResolveFlags {
+    fn bitor_assign(&mut self, rhs: ResolveFlags) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    /// Options with everything off and mode `0o666`.
+    pub fn new() -> Self {
+        OpenOptions {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            custom_flags: 0,
+            mode: 0o666,
+            resolve: ResolveFlags::empty(),
+        }
+    }
+
+    /// Open for reading.
+    pub fn read(&mut self, read: bool) -> &mut OpenOptions {
+        self.read = read;
+        self
+    }
+
+    /// Open for writing.
+    pub fn write(&mut self, write: bool) -> &mut OpenOptions {
+        self.write = write;
+        self
+    }
+
+    /// Open for writing, every write goes to the end of the file.
+    pub fn append(&mut self, append: bool) -> &mut OpenOptions {
+        self.append = append;
+        self
+    }
+
+    /// Truncate the file to zero length if it exists.
+    pub fn truncate(&mut self, truncate: bool) -> &mut OpenOptions {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create the file if it does not exist.
+    pub fn create(&mut self, create: bool) -> &mut OpenOptions {
+        self.create = create;
+        self
+    }
+
+    /// Create the file, failing if it exists. Overrides `create` and
+    /// `truncate`.
+    pub fn create_new(&mut self, create_new: bool) -> &mut OpenOptions {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Permission bits of a created file, before the umask. `0o666` by
+    /// default.
+    pub fn mode(&mut self, mode: u32) -> &mut OpenOptions {
+        self.mode = mode as libc::mode_t;
+        self
+    }
+
+    /// Extra `open(2)` flags, like `O_DIRECT`, `O_NOATIME` or `O_NOFOLLOW`.
+    /// The access mode bits are ignored, [`read`](OpenOptions::read) and
+    /// [`write`](OpenOptions::write) decide it.
+    pub fn custom_flags(&mut self, flags: i32) -> &mut OpenOptions {
+        self.custom_flags = flags;
+        self
+    }
+
+    /// Restrict how the path is resolved, opening with `openat2(2)`(5.6+)
+    /// unless empty. Kernels without it fail with
+    /// [`io::ErrorKind::Unsupported`].
+    pub fn resolve(&mut self, resolve: ResolveFlags) -> &mut OpenOptions {
+        self.resolve = resolve;
+        self
+    }
+
+    // The name errors carry, and the check for kernels without openat2,
+    // whose ring would fail the op with a confusing EINVAL.
+    fn open_op(&self) -> io::Result<&'static str> {
+        if self.resolve.is_empty() {
+            return Ok("openat");
+        }
+        match OpenAt::is_openat2_supported() {
+            true => Ok("openat2"),
+            false => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "resolve flags need openat2, which this kernel lacks",
+            )),
+        }
+    }
+
+    fn flags(&self) -> io::Result<libc::c_int> {
+        let custom = self.custom_flags & !libc::O_ACCMODE;
+        Ok(self.access_mode()? | self.creation_mode()? | custom)
+    }
+
+    /// Open `path` relative to `dir_fd` into a regular descriptor.
+    pub(crate) async fn openat(&self, dir_fd: i32, path: impl AsRef<Path>) -> io::Result<OwnedFd> {
+        let path = path.as_ref();
+        let name = self.open_op().op_path("openat2", path)?;
+        // Not inherited by programs we exec, like std does.
+        let flags = self.flags()? | libc::O_CLOEXEC;
+        let op = match self.resolve.bits() {
+            0 => Op::openat(dir_fd, path, flags, self.mode),
+            resolve => Op::openat2(dir_fd, path, flags, self.mode, resolve),
+        }
+        .op_path(name, path)?;
+
+        let completion = op.await;
+        let fd = completion.meta.result.op_path(name, path)?;
+        Ok(fd.into_owned().expect("openat returns an fd"))
+    }
+
+    /// Open `path`, relative to the working directory.
+    pub async fn open(&self, path: impl AsRef<Path>) -> io::Result<File> {
+        self.openat(libc::AT_FDCWD, path).await.map(File::from)
+    }
+
+    /// Open `path` relative to the directory `dir`, an absolute `path`
+    /// ignores it.
+    pub async fn open_at(&self, dir: &impl AsFd, path: impl AsRef<Path>) -> io::Result<File> {
+        let dir_fd = dir.as_fd().as_raw_fd();
+        self.openat(dir_fd, path).await.map(File::from)
+    }
+
+    /// Open `path` straight into a slot of the registered file table(5.19+),
+    /// see [`RuntimeBuilder::fixed_files`](crate::runtime::builder::RuntimeBuilder::fixed_files).
+    pub async fn open_direct(&self, path: impl AsRef<Path>) -> io::Result<DirectFile> {
+        // The kernel rejects O_CLOEXEC here, direct descriptors are never
+        // inherited anyway.
+        let path = path.as_ref();
+        let name = self.open_op().op_path("openat2", path)?;
+        let flags = self.flags()? & !libc::O_CLOEXEC;
+        let slot = FixedSlot::alloc().await.op_path(name, path)?;
+        let op = Op::openat_direct(
+            libc::AT_FDCWD,
+            path,
+            flags,
+            self.mode,
+            self.resolve.bits(),
+            slot,
+        )
+        .op_path(name, path)?;
+        let completion = op.await;
+        completion.meta.result.op_path(name, path)?;
+        Ok(DirectFile::new(completion.data.slot))
+    }
+
+    pub(crate) fn access_mode(&self) -> io::Result<libc::c_int> {
+        match (self.read, self.write, self.append) {
+            (true, false, false) => Ok(libc::O_RDONLY),
+            (false, true, false) => Ok(libc::O_WRONLY),
+            (true, true, false) => Ok(libc::O_RDWR),
+            (false, _, true) => Ok(libc::O_WRONLY | libc::O_APPEND),
+            (true, _, true) => Ok(libc::O_RDWR | libc::O_APPEND),
+            (false, false, false) => Err(io::Error::from_raw_os_error(libc::EINVAL)),
+        }
+    }
+    pub(crate) fn creation_mode(&self) -> io::Result<libc::c_int> {
+        match (self.write, self.append) {
+            (true, false) => {}
+            (false, false) => {
+                if self.truncate || self.create || self.create_new {
+                    return Err(io::Error::from_raw_os_error(libc::EINVAL));
+                }
+            }
+            (_, true) => {
+                if self.truncate && !self.create_new {
+                    return Err(io::Error::from_raw_os_error(libc::EINVAL));
+                }
+            }
+        }
+
+        Ok(match (self.create, self.truncate, self.create_new) {
+            (false, false, false) => 0,
+            (true, false, false) => libc::O_CREAT,
+            (false, true, false) => libc::O_TRUNC,
+            (true, true, false) => libc::O_CREAT | libc::O_TRUNC,
+            (_, _, true) => libc::O_CREAT | libc::O_EXCL,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use crate::utils::testing::fixed_runtime;
+    use std::future::Future;
+    use std::os::fd::AsRawFd;
+    use std::path::PathBuf;
+
+    fn open_missing(error_context: bool) -> io::Error {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+            .error_context(error_context)
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            OpenOptions::new()
+                .read(true)
+                .open("/nonexistent")
+                .await
+                .err()
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn errors_name_op_and_path() {
+        let err = open_missing(true);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let msg = err.to_string();
+        assert!(
+            msg.contains("openat") && msg.contains("/nonexistent"),
+            "{msg}"
+        );
+        let source = err.get_ref().and_then(|e| e.source()).unwrap();
+        let source = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.raw_os_error(), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn error_context_opt_out() {
+        let err = open_missing(false);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
+    }
+
+    #[test]
+    fn invalid_flags() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Rejected before anything is submitted, the path need not exist.
+            for options in [
+                OpenOptions::new(),
+                OpenOptions::new().truncate(true).clone(),
+            ] {
+                let err = options.open("/nonexistent").await.err().unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{options:?}");
+                assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+            }
+        });
+    }
+
+    #[test]
+    fn open_direct_missing() {
+        let Some(mut rt) = fixed_runtime(4) else {
+            return;
+        };
+        rt.block_on(async {
+            let err = OpenOptions::new()
+                .read(true)
+                .open_direct("/nonexistent")
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err.to_string().contains("/nonexistent"), "{err}");
+            assert_eq!(errno(&err), Some(libc::ENOENT));
+        });
+    }
+
+    // Descriptors of this process open on `path`, the file names are unique
+    // so other tests do not interfere.
+    fn open_count(path: &Path) -> usize {
+        std::fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok())
+            .filter(|target| target == path)
+            .count()
+    }
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("loop-{name}-{}", std::process::id()));
+        std::fs::write(&path, b"fd").unwrap();
+        path
+    }
+
+    #[test]
+    fn openat_owns_the_fd() {
+        let path = temp_file("openat-owned");
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let fd = OpenOptions::new()
+                .read(true)
+                .openat(libc::AT_FDCWD, &path)
+                .await
+                .unwrap();
+            assert_eq!(open_count(&path), 1);
+            drop(fd);
+            assert_eq!(open_count(&path), 0);
+
+            // Handing it to std moves the ownership along.
+            let fd = OpenOptions::new()
+                .read(true)
+                .openat(libc::AT_FDCWD, &path)
+                .await
+                .unwrap();
+            let raw = fd.as_raw_fd();
+            let file = std::fs::File::from(fd);
+            assert_eq!(file.as_raw_fd(), raw);
+            assert_eq!(std::io::read_to_string(&file).unwrap(), "fd");
+            drop(file);
+            assert_eq!(open_count(&path), 0);
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cancelled_openat_closes_the_fd() {
+        let path = temp_file("openat-cancel");
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for _ in 0..64 {
+                let options = OpenOptions::new().read(true).clone();
+                let mut open = std::pin::pin!(options.openat(libc::AT_FDCWD, &path));
+                // Submit and give up before the result is seen.
+                let waker = std::task::Waker::noop();
+                let mut cx = std::task::Context::from_waker(waker);
+                if let std::task::Poll::Ready(fd) = open.as_mut().poll(&mut cx) {
+                    drop(fd.unwrap());
+                }
+            }
+            // Let every open complete, opened or cancelled.
+            let fd = OpenOptions::new()
+                .read(true)
+                .openat(libc::AT_FDCWD, &path)
+                .await
+                .unwrap();
+            drop(fd);
+        });
+        drop(rt);
+        assert_eq!(open_count(&path), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flag_combinations_match_std() {
+        let path = temp_file("open-options");
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for bits in 0..64u32 {
+                let flag = |n: u32| bits & (1 << n) != 0;
+                let mut ours = OpenOptions::new();
+                ours.read(flag(0))
+                    .write(flag(1))
+                    .append(flag(2))
+                    .truncate(flag(3))
+                    .create(flag(4))
+                    .create_new(flag(5));
+                let mut std = std::fs::OpenOptions::new();
+                std.read(flag(0))
+                    .write(flag(1))
+                    .append(flag(2))
+                    .truncate(flag(3))
+                    .create(flag(4))
+                    .create_new(flag(5));
+
+                // The file exists for both, create_new fails on it.
+                let expected = std.open(&path).map(drop).map_err(|e| e.kind());
+                let got = ours.open(&path).await.map(drop).map_err(|e| e.kind());
+                assert_eq!(got, expected, "{ours:?}");
+            }
+        });
+
+        // The invalid ones are rejected before anything is submitted.
+        let invalid = OpenOptions::new().truncate(true).clone();
+        assert_eq!(
+            invalid.creation_mode().err().unwrap().raw_os_error(),
+            Some(libc::EINVAL)
+        );
+        let none = OpenOptions::new();
+        assert_eq!(
+            none.access_mode().err().unwrap().raw_os_error(),
+            Some(libc::EINVAL)
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_at_and_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir();
+        let name = format!("loop-open-at-{}", std::process::id());
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let dir_file = std::fs::File::open(&dir).unwrap();
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open_at(&dir_file, &name)
+                .await
+                .unwrap();
+            let (n, _) = file.write("at").await;
+            assert_eq!(n.unwrap(), 2);
+        });
+        let meta = std::fs::metadata(dir.join(&name)).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::read(dir.join(&name)).unwrap(), b"at");
+        std::fs::remove_file(dir.join(&name)).unwrap();
+    }
+
+    fn errno(err: &io::Error) -> Option<i32> {
+        let source = err.get_ref()?.source()?;
+        source.downcast_ref::<io::Error>()?.raw_os_error()
+    }
+
+    // A 4096 aligned window into `buf`, for O_DIRECT.
+    fn aligned(buf: &[u8]) -> std::ops::Range<usize> {
+        let start = buf.as_ptr().align_offset(4096);
+        start..start + 4096
+    }
+
+    #[test]
+    fn custom_flags() {
+        use crate::buf::{IoBuf, IoBufMut};
+
+        let dir = std::env::temp_dir().join(format!("loop-custom-flags-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let file = dir.join("file");
+        let link = dir.join("link");
+        std::fs::write(&file, b"").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Access mode bits among the custom flags change nothing.
+            let opened = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_RDWR | libc::O_NOATIME)
+                .open(&file)
+                .await
+                .unwrap();
+            let (res, _) = opened.write("ro").await;
+            assert_eq!(errno(&res.unwrap_err()), Some(libc::EBADF));
+            let flags = unsafe { libc::fcntl(opened.as_raw_fd(), libc::F_GETFL) };
+            assert_eq!(flags & libc::O_ACCMODE, libc::O_RDONLY);
+            assert_ne!(flags & libc::O_NOATIME, 0);
+
+            let err = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(&link)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(errno(&err), Some(libc::ELOOP));
+
+            // Transfers bypass the page cache, in aligned blocks.
+            let direct = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_DIRECT)
+                .open(&file)
+                .await
+                .unwrap();
+            let mut data = vec![7u8; 8192];
+            let window = aligned(&data);
+            data[window.clone()].copy_from_slice(&[b'd'; 4096]);
+            let (n, data) = direct.write_at(data.slice(window.clone()), 0).await;
+            assert_eq!(n.unwrap(), 4096);
+            let mut buf = data.into_inner();
+            buf.truncate(window.start);
+            let (n, buf) = direct.read_at(buf.slice_mut(window.clone()), 0).await;
+            assert_eq!(n.unwrap(), 4096);
+            let buf = buf.into_inner();
+            assert!(buf[window].iter().all(|b| *b == b'd'));
+
+            // Misaligned, or not supported by the filesystem at all.
+            let (res, _) = direct.write_at(vec![1; 100], 0).await;
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+            let shm = format!("/dev/shm/loop-custom-flags-{}", std::process::id());
+            if Path::new("/dev/shm").is_dir() {
+                let res = OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .custom_flags(libc::O_DIRECT)
+                    .open(&shm)
+                    .await;
+                // tmpfs takes O_DIRECT since 6.6.
+                if let Err(e) = res {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{e}");
+                }
+                let _ = std::fs::remove_file(&shm);
+            }
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_restrictions() {
+        let dir = std::env::temp_dir().join(format!("loop-resolve-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("file"), b"inside").unwrap();
+        std::os::unix::fs::symlink("../file", dir.join("sub/link")).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            if !OpenAt::is_openat2_supported() {
+                let err = OpenOptions::new()
+                    .read(true)
+                    .resolve(ResolveFlags::BENEATH)
+                    .open(&dir)
+                    .await
+                    .err()
+                    .unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+                return;
+            }
+            let root = std::fs::File::open(&dir).unwrap();
+            let open = |resolve, path: &'static str| {
+                let root = &root;
+                async move {
+                    let mut options = OpenOptions::new();
+                    options.read(true).resolve(resolve);
+                    options.open_at(root, path).await
+                }
+            };
+
+            let beneath = ResolveFlags::BENEATH;
+            let file = open(beneath, "sub/../file").await.unwrap();
+            let (n, buf) = file.read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 6);
+            assert_eq!(buf, b"inside");
+            open(beneath, "sub/link").await.unwrap();
+            // Escapes, by `..` or an absolute path.
+            for path in ["../../etc/passwd", "/etc/passwd"] {
+                let err = open(beneath, path).await.err().unwrap();
+                assert_eq!(errno(&err), Some(libc::EXDEV), "{path}");
+                assert!(err.to_string().contains("openat2"), "{err}");
+            }
+
+            let err = open(beneath | ResolveFlags::NO_SYMLINKS, "sub/link")
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(errno(&err), Some(libc::ELOOP));
+            // Stays inside, `..` at the root is the root.
+            let file = open(ResolveFlags::IN_ROOT, "../../file").await.unwrap();
+            assert_eq!(file.metadata().await.unwrap().len(), 6);
+
+            // The mode only goes along when creating.
+            let created = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o640)
+                .resolve(beneath)
+                .open_at(&root, "created")
+                .await
+                .unwrap();
+            let mode = created.metadata().await.unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o640);
+        });
+
+        let flags = ResolveFlags::BENEATH | ResolveFlags::NO_XDEV;
+        assert!(flags.contains(ResolveFlags::NO_XDEV) && !flags.contains(ResolveFlags::CACHED));
+        assert!(ResolveFlags::default().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
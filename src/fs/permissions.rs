@@ -0,0 +1,161 @@
+use crate::driver::util::cstr;
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// The permissions of a file, its mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permissions {
+    mode: u32,
+}
+
+impl Permissions {
+    /// Permissions of `mode`, like `0o644`.
+    pub fn from_mode(mode: u32) -> Self {
+        Permissions { mode }
+    }
+
+    /// The mode, with the `S_IFMT` type bits when read from [`Metadata`](crate::fs::Metadata).
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// Replace the mode.
+    pub fn set_mode(&mut self, mode: u32) {
+        self.mode = mode;
+    }
+
+    /// Whether nobody may write the file.
+    pub fn readonly(&self) -> bool {
+        self.mode & 0o222 == 0
+    }
+
+    /// Take write permission from everybody, or give it to everybody.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        match readonly {
+            true => self.mode &= !0o222,
+            false => self.mode |= 0o222,
+        }
+    }
+
+    fn raw(&self) -> libc::mode_t {
+        // Only the permission bits, chmod ignores the type.
+        (self.mode & 0o7777) as libc::mode_t
+    }
+}
+
+impl From<std::fs::Permissions> for Permissions {
+    fn from(perm: std::fs::Permissions) -> Self {
+        Permissions::from_mode(perm.mode())
+    }
+}
+
+impl From<Permissions> for std::fs::Permissions {
+    fn from(perm: Permissions) -> Self {
+        std::fs::Permissions::from_mode(perm.mode)
+    }
+}
+
+/// Change the permissions of `path`, following symlinks.
+///
+/// The ring has no chmod, this is a blocking syscall on the current thread.
+pub async fn set_permissions(path: impl AsRef<Path>, perm: Permissions) -> io::Result<()> {
+    chmod_path(path.as_ref(), perm, 0)
+}
+
+/// Change the permissions of `path` itself, without following a final
+/// symlink. Linux has no modes on symlinks, changing one fails with
+/// [`io::ErrorKind::Unsupported`].
+pub async fn set_symlink_permissions(path: impl AsRef<Path>, perm: Permissions) -> io::Result<()> {
+    chmod_path(path.as_ref(), perm, libc::AT_SYMLINK_NOFOLLOW)
+}
+
+fn chmod_path(path: &Path, perm: Permissions, flags: i32) -> io::Result<()> {
+    let c_path = cstr(path).op_path("fchmodat", path)?;
+    crate::syscall!(fchmodat@NON_FD(libc::AT_FDCWD, c_path.as_ptr(), perm.raw(), flags))
+        .map(drop)
+        .op_path("fchmodat", path)
+}
+
+impl File {
+    /// Change the permissions of the file.
+    ///
+    /// The ring has no chmod, this is a blocking syscall on the current thread.
+    pub async fn set_permissions(&self, perm: Permissions) -> io::Result<()> {
+        let fd = self.as_raw_fd();
+        crate::syscall!(fchmod@NON_FD(fd, perm.raw()))
+            .map(drop)
+            .op_fd("fchmod", fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::{metadata, symlink_metadata};
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn chmod() {
+        let dir = std::env::temp_dir().join(format!("loop-permissions-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let (file, link) = (dir.join("file"), dir.join("link"));
+        std::fs::write(&file, b"mode").unwrap();
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let old = metadata(&file).await.unwrap().permissions();
+            set_permissions(&file, Permissions::from_mode(0o400))
+                .await
+                .unwrap();
+            let perm = metadata(&file).await.unwrap().permissions();
+            assert_eq!(perm.mode() & 0o7777, 0o400);
+            assert!(perm.readonly());
+            assert_eq!(perm, std::fs::metadata(&file).unwrap().permissions().into());
+
+            // Through the link, then on the open file.
+            set_permissions(&link, Permissions::from_mode(0o640))
+                .await
+                .unwrap();
+            assert_eq!(
+                metadata(&file).await.unwrap().permissions().mode() & 0o7777,
+                0o640
+            );
+            let opened = File::open(&file).await.unwrap();
+            opened.set_permissions(old).await.unwrap();
+            assert_eq!(metadata(&file).await.unwrap().permissions(), old);
+
+            let link_mode = symlink_metadata(&link).await.unwrap().permissions();
+            let err = set_symlink_permissions(&link, Permissions::from_mode(0o600))
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+            assert_eq!(
+                symlink_metadata(&link).await.unwrap().permissions(),
+                link_mode
+            );
+            set_symlink_permissions(&file, Permissions::from_mode(0o600))
+                .await
+                .unwrap();
+            assert_eq!(
+                metadata(&file).await.unwrap().permissions().mode() & 0o7777,
+                0o600
+            );
+
+            let err = set_permissions(dir.join("missing"), old).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        });
+
+        let mut perm = Permissions::from_mode(0o644);
+        perm.set_readonly(true);
+        assert_eq!(perm.mode(), 0o444);
+        let std: std::fs::Permissions = perm.into();
+        assert_eq!(std.mode(), 0o444);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,332 @@
+use crate::driver::op::Op;
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::cell::RefCell;
+use std::future::poll_fn;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+// Offset -1 makes the kernel use and advance the file position, the only
+// offset pipes and sockets take.
+const CURRENT_POSITION: i64 = -1;
+
+// The default capacity of a pipe, more does not fit in one splice.
+pub(crate) const CHUNK: usize = 64 * 1024;
+
+// Empty pipes kept for the next copy of this thread.
+const POOLED: usize = 4;
+
+thread_local! {
+    static POOL: RefCell<Vec<Pipe>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An anonymous pipe, the buffer for moving data between descriptors with
+/// `splice(2)` without copying it through userspace.
+///
+/// Both ends are [`File`]s, dropping the pipe closes them in the background.
+pub struct Pipe {
+    reader: File,
+    writer: File,
+}
+
+impl Pipe {
+    /// Create a pipe, its ends are not inherited by programs we exec.
+    pub fn new() -> io::Result<Pipe> {
+        let mut fds = [0; 2];
+        crate::syscall!(pipe2@RAW(fds.as_mut_ptr(), libc::O_CLOEXEC))?;
+        // Just created, owned by nothing else.
+        let (reader, writer) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        Ok(Pipe {
+            reader: File::from(reader),
+            writer: File::from(writer),
+        })
+    }
+
+    /// The end data is read from.
+    pub fn reader(&self) -> &File {
+        &self.reader
+    }
+
+    /// The end data is written to.
+    pub fn writer(&self) -> &File {
+        &self.writer
+    }
+
+    /// Move up to `len` bytes from `fd`, at its position, into the pipe.
+    /// Returns 0 at the end of `fd`.
+    pub async fn splice_from(&self, fd: &impl AsFd, len: usize) -> io::Result<usize> {
+        let fd = fd.as_fd().as_raw_fd();
+        splice(
+            (fd, CURRENT_POSITION),
+            (self.writer.as_raw_fd(), CURRENT_POSITION),
+            len,
+        )
+        .await
+        .op_fd("splice", fd)
+    }
+
+    /// Move up to `len` bytes out of the pipe into `fd`, at its position.
+    pub async fn splice_to(&self, fd: &impl AsFd, len: usize) -> io::Result<usize> {
+        let fd = fd.as_fd().as_raw_fd();
+        splice(
+            (self.reader.as_raw_fd(), CURRENT_POSITION),
+            (fd, CURRENT_POSITION),
+            len,
+        )
+        .await
+        .op_fd("splice", fd)
+    }
+}
+
+// One splice, resubmitted when the kernel had nothing to move yet.
+async fn splice(src: (RawFd, i64), dst: (RawFd, i64), len: usize) -> io::Result<usize> {
+    let len = u32::try_from(len).unwrap_or(u32::MAX);
+    loop {
+        let op = Op::splice(src, dst, len, libc::SPLICE_F_MOVE)?;
+        match op.await.meta.result {
+            Ok(n) => return Ok(n.into_inner() as usize),
+            // A nonblocking end was empty or full. Resubmitting right away
+            // would spin on the ring, wait until both ends are ready.
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                ready(src.0, libc::POLLIN).await?;
+                ready(dst.0, libc::POLLOUT).await?;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Wait for `events` on `fd`, errors and hangups are left to the next splice.
+async fn ready(fd: RawFd, events: libc::c_short) -> io::Result<()> {
+    let mut op = Op::poll_add(fd, events as u32)?;
+    poll_fn(|cx| op.poll_ready(cx)).await.map(drop)
+}
+
+/// Move up to `len` bytes from `src` to `dst` through a pipe, at the
+/// positions of both, like `sendfile(2)`. Stops early at the end of `src`,
+/// returns the number of bytes moved.
+///
+/// Neither needs to be a pipe, but one of them must support splicing, like
+/// regular files and sockets do.
+pub async fn splice_copy(src: &impl AsFd, dst: &impl AsFd, len: u64) -> io::Result<u64> {
+    let (src, dst) = (src.as_fd().as_raw_fd(), dst.as_fd().as_raw_fd());
+    splice_through_pool(src, None, dst, len)
+        .await
+        .map_err(|(e, _)| e)
+}
+
+/// Like [`splice_copy`], reading `src` from `offset` instead of its
+/// position when one is given. Goes through a pipe of the pool of this
+/// thread, given back once it is empty again. An error comes with the
+/// number of bytes that reached `dst` before it.
+pub(crate) async fn splice_through_pool(
+    src: RawFd,
+    offset: Option<u64>,
+    dst: RawFd,
+    len: u64,
+) -> Result<u64, (io::Error, u64)> {
+    let pipe = match POOL.with(|pool| pool.borrow_mut().pop()) {
+        Some(pipe) => pipe,
+        None => Pipe::new().map_err(|e| (e, 0))?,
+    };
+    let mut moved = 0;
+    // Dropped on errors, bytes may be left in it.
+    if let Err(e) = pump(&pipe, src, offset, dst, len, &mut moved).await {
+        return Err((e, moved));
+    }
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < POOLED {
+            pool.push(pipe);
+        }
+    });
+    Ok(moved)
+}
+
+// Move through `pipe`, leaving it empty unless it fails. `moved` counts
+// the bytes that reached `dst`, the ones stuck in the pipe are not.
+async fn pump(
+    pipe: &Pipe,
+    src: RawFd,
+    offset: Option<u64>,
+    dst: RawFd,
+    len: u64,
+    moved: &mut u64,
+) -> io::Result<()> {
+    while *moved < len {
+        let want = usize::try_from(len - *moved)
+            .unwrap_or(usize::MAX)
+            .min(CHUNK);
+        let off_in = match offset {
+            Some(offset) => i64::try_from(offset + *moved)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?,
+            None => CURRENT_POSITION,
+        };
+        let mut filled = splice(
+            (src, off_in),
+            (pipe.writer.as_raw_fd(), CURRENT_POSITION),
+            want,
+        )
+        .await
+        .op_fd("splice", src)?;
+        if filled == 0 {
+            break;
+        }
+        // Drain the pipe, `dst` may take less at a time.
+        while filled > 0 {
+            let n = splice(
+                (pipe.reader.as_raw_fd(), CURRENT_POSITION),
+                (dst, CURRENT_POSITION),
+                filled,
+            )
+            .await
+            .op_fd("splice", dst)?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::WriteZero,
+                    "failed to splice whole pipe",
+                ));
+            }
+            filled -= n;
+            *moved += n as u64;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::rc::Rc;
+    use std::task::{Context, Waker};
+
+    #[test]
+    fn splice_between_files() {
+        let dir = std::env::temp_dir().join(format!("loop-splice-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.join("src"), &data).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // More than a pipe holds, in chunks.
+            let src = File::open(dir.join("src")).await.unwrap();
+            let dst = File::create(dir.join("dst")).await.unwrap();
+            assert_eq!(splice_copy(&src, &dst, 200_000).await.unwrap(), 200_000);
+            // Continues at the positions, stops at the end.
+            assert_eq!(splice_copy(&src, &dst, u64::MAX).await.unwrap(), 100_000);
+            assert_eq!(splice_copy(&src, &dst, 10).await.unwrap(), 0);
+            drop((src, dst));
+            assert_eq!(std::fs::read(dir.join("dst")).unwrap(), data);
+
+            // A partial drain leaves the rest in the pipe.
+            let pipe = Pipe::new().unwrap();
+            let src = File::open(dir.join("src")).await.unwrap();
+            assert_eq!(pipe.splice_from(&src, 10).await.unwrap(), 10);
+            let dst = File::create(dir.join("part")).await.unwrap();
+            assert_eq!(pipe.splice_to(&dst, 4).await.unwrap(), 4);
+            let (n, buf) = pipe.reader().read(Vec::with_capacity(16)).await;
+            assert_eq!(n.unwrap(), 6);
+            assert_eq!(buf, &data[4..10]);
+
+            // Neither end a pipe.
+            let a = File::open(dir.join("src")).await.unwrap();
+            let err = splice(
+                (a.as_raw_fd(), CURRENT_POSITION),
+                (dst.as_raw_fd(), CURRENT_POSITION),
+                1,
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(libc::EINVAL));
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failure_counts_what_was_moved() {
+        let path = std::env::temp_dir().join(format!("loop-splice-fail-{}", std::process::id()));
+        std::fs::write(&path, vec![7u8; 4 * CHUNK]).unwrap();
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        // Takes one chunk, then closes its end under the next splice.
+        let peer = std::thread::spawn(move || {
+            let mut chunk = vec![0; CHUNK];
+            std::io::Read::read_exact(&mut reader, &mut chunk).unwrap();
+        });
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let src = File::open(&path).await.unwrap();
+            let res = splice_through_pool(src.as_raw_fd(), Some(0), writer.as_raw_fd(), u64::MAX);
+            let (err, moved) = res.await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe, "{err}");
+            assert!(moved >= CHUNK as u64 && moved < 4 * CHUNK as u64, "{moved}");
+        });
+        peer.join().unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn nonblocking_end_waits_for_data() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let (src, dst) = (Pipe::new().unwrap(), Pipe::new().unwrap());
+        let fd = src.reader().as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        assert_eq!(
+            unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) },
+            0
+        );
+        let (src, dst) = (Rc::new(src), Rc::new(dst));
+        let mut copy = None;
+        rt.block_on(async {
+            let (src, dst) = (src.clone(), dst.clone());
+            copy = Some(crate::spawn(async move {
+                src.splice_to(dst.writer(), 16).await
+            }));
+            crate::time::sleep(std::time::Duration::from_millis(20)).await;
+        });
+        // Parked on a poll, not resubmitting the splice.
+        assert!(rt.driver.metrics().cqes < 10, "{:?}", rt.driver.metrics());
+        rt.block_on(async {
+            let (n, _) = src.writer().write(b"data".to_vec()).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(copy.unwrap().await.unwrap(), 4);
+            let (n, buf) = dst.reader().read(Vec::with_capacity(8)).await;
+            assert_eq!((n.unwrap(), &buf[..]), (4, &b"data"[..]));
+        });
+    }
+
+    #[test]
+    fn drop_mid_splice() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let (src, dst) = (Pipe::new().unwrap(), Pipe::new().unwrap());
+        let fd_path = |file: &File| format!("/proc/self/fd/{}", file.as_raw_fd());
+        let target = std::fs::read_link(fd_path(src.reader())).unwrap();
+        rt.block_on(async {
+            {
+                // Waits in the kernel, nothing was written to `src`.
+                let mut copy = pin!(splice_copy(src.reader(), dst.writer(), 1));
+                let mut cx = Context::from_waker(Waker::noop());
+                assert!(copy.as_mut().poll(&mut cx).is_pending());
+            }
+            // Closing the writer ends the splice still in flight, the round
+            // trip of the next op submits the closes.
+            drop(src);
+            dst.writer().metadata().await.unwrap();
+        });
+        drop(rt);
+        // Each end closed once, by the pipe.
+        let open = std::fs::read_dir("/proc/self/fd")
+            .unwrap()
+            .filter_map(|entry| std::fs::read_link(entry.ok()?.path()).ok())
+            .filter(|link| *link == target)
+            .count();
+        assert_eq!(open, 0);
+        drop(dst);
+    }
+}
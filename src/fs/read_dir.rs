@@ -0,0 +1,179 @@
+use crate::fs::{symlink_metadata, File, FileType, Metadata, OpenOptions};
+use crate::utils::error_ctx::ResultExt;
+use std::ffi::{CStr, OsStr, OsString};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// Room for a few hundred entries per getdents64 call.
+const BUF_LEN: usize = 32 * 1024;
+
+// Offsets into `struct linux_dirent64`.
+const RECLEN: usize = 16;
+const TYPE: usize = 18;
+const NAME: usize = 19;
+
+/// Open the directory at `path` for listing its entries.
+pub async fn read_dir(path: impl AsRef<Path>) -> io::Result<ReadDir> {
+    let path = path.as_ref();
+    let fd = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECTORY)
+        .openat(libc::AT_FDCWD, path)
+        .await?;
+    Ok(ReadDir {
+        dir: File::from(fd),
+        path: Rc::from(path),
+        buf: vec![0; BUF_LEN].into_boxed_slice(),
+        pos: 0,
+        filled: 0,
+        done: false,
+    })
+}
+
+/// The entries of a directory, without `.` and `..`, see [`read_dir`].
+///
+/// The ring has no getdents, entries are fetched in batches with a blocking
+/// syscall on the current thread.
+pub struct ReadDir {
+    dir: File,
+    path: Rc<Path>,
+    buf: Box<[u8]>,
+    // The unread records are `buf[pos..filled]`.
+    pos: usize,
+    filled: usize,
+    done: bool,
+}
+
+impl ReadDir {
+    /// The next entry, `None` once all were returned.
+    pub async fn next_entry(&mut self) -> io::Result<Option<DirEntry>> {
+        loop {
+            if self.pos == self.filled && (self.done || !self.fill()?) {
+                self.done = true;
+                return Ok(None);
+            }
+            let record = &self.buf[self.pos..self.filled];
+            let reclen = u16::from_ne_bytes([record[RECLEN], record[RECLEN + 1]]) as usize;
+            self.pos += reclen;
+            let name = CStr::from_bytes_until_nul(&record[NAME..reclen])
+                .expect("dirent names are terminated")
+                .to_bytes();
+            if name == b"." || name == b".." {
+                continue;
+            }
+            return Ok(Some(DirEntry {
+                dir: self.path.clone(),
+                name: OsStr::from_bytes(name).to_owned(),
+                d_type: record[TYPE],
+            }));
+        }
+    }
+
+    // Returns false at the end of the directory.
+    fn fill(&mut self) -> io::Result<bool> {
+        let (fd, len) = (self.dir.as_raw_fd(), self.buf.len());
+        let buf = self.buf.as_mut_ptr();
+        let n = crate::syscall!(syscall@RAW(libc::SYS_getdents64, fd, buf, len))
+            .op_path("getdents64", &self.path)?;
+        self.pos = 0;
+        self.filled = n as usize;
+        Ok(n > 0)
+    }
+}
+
+/// An entry of a directory.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    dir: Rc<Path>,
+    name: OsString,
+    // DT_* from the kernel, DT_UNKNOWN on filesystems that do not say.
+    d_type: u8,
+}
+
+impl DirEntry {
+    /// The name of the entry, without the directory.
+    pub fn file_name(&self) -> OsString {
+        self.name.clone()
+    }
+
+    /// The path of the entry, the directory joined with the name.
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.name)
+    }
+
+    /// The type of the entry, a symlink is not followed. Usually known from
+    /// the listing, otherwise the entry is stat'ed.
+    pub async fn file_type(&self) -> io::Result<FileType> {
+        let mode = match self.d_type {
+            libc::DT_REG => libc::S_IFREG,
+            libc::DT_DIR => libc::S_IFDIR,
+            libc::DT_LNK => libc::S_IFLNK,
+            libc::DT_FIFO => libc::S_IFIFO,
+            libc::DT_SOCK => libc::S_IFSOCK,
+            libc::DT_CHR => libc::S_IFCHR,
+            libc::DT_BLK => libc::S_IFBLK,
+            _ => return Ok(self.metadata().await?.file_type()),
+        };
+        Ok(FileType::from_mode(mode))
+    }
+
+    /// The metadata of the entry, a symlink is not followed.
+    pub async fn metadata(&self) -> io::Result<Metadata> {
+        symlink_metadata(self.path()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn lists_large_directory() {
+        let dir = std::env::temp_dir().join(format!("loop-read-dir-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        // Far more than one buffer holds.
+        let names: BTreeSet<OsString> = (0..10_000)
+            .map(|i| OsString::from(format!("entry-{i:05}")))
+            .collect();
+        for name in &names {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::os::unix::fs::symlink("sub", dir.join("link")).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut entries = read_dir(&dir).await.unwrap();
+            let mut seen = BTreeSet::new();
+            while let Some(entry) = entries.next_entry().await.unwrap() {
+                assert_eq!(entry.path(), dir.join(entry.file_name()));
+                let file_type = entry.file_type().await.unwrap();
+                match entry.file_name().to_str().unwrap() {
+                    "sub" => assert!(file_type.is_dir()),
+                    "link" => assert!(file_type.is_symlink()),
+                    _ => {
+                        assert!(file_type.is_file());
+                        assert_eq!(entry.metadata().await.unwrap().len(), 0);
+                    }
+                }
+                assert!(seen.insert(entry.file_name()), "{entry:?} twice");
+            }
+            // Stays at the end.
+            assert!(entries.next_entry().await.unwrap().is_none());
+
+            assert!(seen.remove(OsStr::new("sub")));
+            assert!(seen.remove(OsStr::new("link")));
+            assert_eq!(seen, names);
+
+            let err = read_dir(dir.join("entry-00000")).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+use super::maybe_done::{maybe_done, MaybeDone};
+use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+/// Wait for all of `futures`, returning their outputs in the same order.
+///
+/// Every future gets a waker of its own, so a poll only polls the futures
+/// that were woken since the last one. Waiting on thousands of futures costs
+/// work in proportion to the wakeups, not to the number of futures.
+///
+/// If a future panics, the others are dropped before the panic goes on.
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    let futures: Box<[_]> = futures.into_iter().map(maybe_done).collect();
+    let shared = Arc::new(Shared {
+        // Everything needs a first poll.
+        woken: Mutex::new((0..futures.len()).collect()),
+        waker: Mutex::new(None),
+    });
+    let wakers = (0..futures.len())
+        .map(|index| {
+            Arc::new(IndexWaker {
+                index,
+                queued: AtomicBool::new(true),
+                shared: shared.clone(),
+            })
+        })
+        .collect();
+    JoinAll {
+        pending: futures.len(),
+        futures: Box::into_pin(futures),
+        wakers,
+        shared,
+    }
+}
+
+/// Future returned by [`join_all`].
+pub struct JoinAll<F: Future> {
+    futures: Pin<Box<[MaybeDone<F>]>>,
+    wakers: Vec<Arc<IndexWaker>>,
+    shared: Arc<Shared>,
+    pending: usize,
+}
+
+struct Shared {
+    // Indexes woken since the last poll.
+    woken: Mutex<Vec<usize>>,
+    // Waker of the task polling the JoinAll.
+    waker: Mutex<Option<Waker>>,
+}
+
+struct IndexWaker {
+    index: usize,
+    // Set while the index is in `woken`, so it is queued once.
+    queued: AtomicBool,
+    shared: Arc<Shared>,
+}
+
+impl Wake for IndexWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if self.queued.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        self.shared.woken.lock().unwrap().push(self.index);
+        if let Some(waker) = &*self.shared.waker.lock().unwrap() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+impl<F: Future> JoinAll<F> {
+    fn poll_woken(&mut self) {
+        let woken = std::mem::take(&mut *self.shared.woken.lock().unwrap());
+        for index in woken {
+            let waker = &self.wakers[index];
+            waker.queued.store(false, Ordering::Release);
+            let waker = Waker::from(waker.clone());
+            let mut cx = Context::from_waker(&waker);
+            // Only the slot is projected, the box keeps it pinned.
+            let fut = unsafe {
+                self.futures
+                    .as_mut()
+                    .get_unchecked_mut()
+                    .get_unchecked_mut(index)
+            };
+            if !matches!(fut, MaybeDone::Future(_)) {
+                continue;
+            }
+            if unsafe { Pin::new_unchecked(fut) }.poll(&mut cx).is_ready() {
+                self.pending -= 1;
+            }
+        }
+    }
+}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The futures are pinned in their own box, JoinAll itself may move.
+        let this = unsafe { self.get_unchecked_mut() };
+        {
+            let mut waker = this.shared.waker.lock().unwrap();
+            match &*waker {
+                Some(w) if w.will_wake(cx.waker()) => {}
+                _ => *waker = Some(cx.waker().clone()),
+            }
+        }
+
+        if let Err(panic) = catch_unwind(AssertUnwindSafe(|| this.poll_woken())) {
+            this.futures = Box::into_pin(Box::new([]));
+            resume_unwind(panic);
+        }
+        if this.pending > 0 {
+            return Poll::Pending;
+        }
+        let outputs = unsafe { this.futures.as_mut().get_unchecked_mut() }
+            .iter_mut()
+            .map(|fut| {
+                unsafe { Pin::new_unchecked(fut) }
+                    .take_output()
+                    .expect("JoinAll polled after completion")
+            })
+            .collect();
+        Poll::Ready(outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    // Pending until the waker it registered is taken and woken.
+    struct Gate {
+        open: Rc<Cell<bool>>,
+        waker: Rc<RefCell<Option<Waker>>>,
+        polls: Rc<Cell<usize>>,
+    }
+
+    impl Future for Gate {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.polls.set(self.polls.get() + 1);
+            if self.open.get() {
+                return Poll::Ready(());
+            }
+            *self.waker.borrow_mut() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn outputs_in_order() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let futures = (0..10).rev().map(|i| async move { i * 2 });
+        let mut join = std::pin::pin!(join_all(futures));
+        let Poll::Ready(out) = join.as_mut().poll(&mut cx) else {
+            panic!("not ready");
+        };
+        assert_eq!(out, (0..10).rev().map(|i| i * 2).collect::<Vec<_>>());
+
+        let empty = std::pin::pin!(join_all(Vec::<std::future::Ready<()>>::new()));
+        assert_eq!(empty.poll(&mut cx), Poll::Ready(vec![]));
+    }
+
+    #[test]
+    fn polls_only_woken() {
+        const N: usize = 1000;
+        let polls = Rc::new(Cell::new(0));
+        let gates = (0..N)
+            .map(|_| (Rc::new(Cell::new(false)), Rc::new(RefCell::new(None))))
+            .collect::<Vec<_>>();
+        let futures = gates
+            .iter()
+            .map(|(open, waker)| Gate {
+                open: open.clone(),
+                waker: waker.clone(),
+                polls: polls.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut join = std::pin::pin!(join_all(futures));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(join.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(polls.get(), N);
+
+        // Like sleeps of different lengths, one expires per wakeup.
+        for (i, (open, waker)) in gates.iter().enumerate() {
+            open.set(true);
+            waker.borrow_mut().take().unwrap().wake();
+            let ready = join.as_mut().poll(&mut cx).is_ready();
+            assert_eq!(ready, i == N - 1);
+        }
+        // Polling everything on every wakeup would be N * N.
+        assert_eq!(polls.get(), 2 * N);
+    }
+
+    #[test]
+    fn wakes_the_task() {
+        struct Count(std::sync::atomic::AtomicUsize);
+        impl Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        let count = Arc::new(Count(Default::default()));
+        let waker = Waker::from(count.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let open = Rc::new(Cell::new(false));
+        let gate_waker = Rc::new(RefCell::new(None));
+        let gate = Gate {
+            open: open.clone(),
+            waker: gate_waker.clone(),
+            polls: Rc::new(Cell::new(0)),
+        };
+        let mut join = std::pin::pin!(join_all([gate]));
+        assert!(join.as_mut().poll(&mut cx).is_pending());
+        let inner = gate_waker.borrow_mut().take().unwrap();
+        // Waking twice queues the future once.
+        inner.wake_by_ref();
+        inner.wake();
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        open.set(true);
+        assert!(join.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn panic_drops_the_others_first() {
+        struct Guard(Rc<Cell<usize>>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let dropped = Rc::new(Cell::new(0));
+        let futures = (0..4)
+            .map(|i| {
+                let guard = Guard(dropped.clone());
+                Box::pin(async move {
+                    let _guard = guard;
+                    if i == 1 {
+                        panic!("member {i} failed");
+                    }
+                    std::future::pending::<()>().await;
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut join = std::pin::pin!(join_all(futures));
+        let mut cx = Context::from_waker(Waker::noop());
+        let panic = catch_unwind(AssertUnwindSafe(|| join.as_mut().poll(&mut cx))).unwrap_err();
+        assert_eq!(panic.downcast_ref::<String>().unwrap(), "member 1 failed");
+        // The JoinAll is still alive, its futures are not.
+        assert_eq!(dropped.get(), 4);
+    }
+}
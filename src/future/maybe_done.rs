@@ -0,0 +1,51 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// A future that keeps the output of the future it wraps once it is done.
+pub enum MaybeDone<Fut: Future> {
+    /// Not done yet.
+    Future(Fut),
+    /// The output, waiting to be taken.
+    Done(Fut::Output),
+    /// The output was taken.
+    Gone,
+}
+
+/// Wrap `future` into a [`MaybeDone`].
+pub fn maybe_done<Fut: Future>(future: Fut) -> MaybeDone<Fut> {
+    MaybeDone::Future(future)
+}
+
+impl<Fut: Future> MaybeDone<Fut> {
+    /// Take the output, if the future is done and it was not taken yet.
+    pub fn take_output(self: Pin<&mut Self>) -> Option<Fut::Output> {
+        match *self {
+            MaybeDone::Done(_) => {}
+            MaybeDone::Future(_) | MaybeDone::Gone => return None,
+        }
+        // Moving the output out is fine, only the future is pinned.
+        unsafe {
+            match std::mem::replace(self.get_unchecked_mut(), MaybeDone::Gone) {
+                MaybeDone::Done(output) => Some(output),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+impl<Fut: Future> Future for MaybeDone<Fut> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // The future is never moved while it lives in `Future`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let output = match this {
+            MaybeDone::Future(fut) => ready!(unsafe { Pin::new_unchecked(fut) }.poll(cx)),
+            MaybeDone::Done(_) => return Poll::Ready(()),
+            MaybeDone::Gone => panic!("MaybeDone polled after value taken"),
+        };
+        *this = MaybeDone::Done(output);
+        Poll::Ready(())
+    }
+}
@@ -0,0 +1,7 @@
+//! Utilities for futures.
+
+mod join_all;
+mod maybe_done;
+
+pub use join_all::{join_all, JoinAll};
+pub use maybe_done::{maybe_done, MaybeDone};
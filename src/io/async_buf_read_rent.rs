@@ -0,0 +1,271 @@
+use super::AsyncReadRent;
+use crate::buf::BufResult;
+use std::future::Future;
+use std::io;
+
+// The longest line `Lines` takes unless told otherwise.
+const DEFAULT_MAX_LINE: usize = 64 * 1024;
+
+/// A reader with a buffer of its own that can be looked at in place, like
+/// [`BufReader`](super::BufReader).
+pub trait AsyncBufReadRent: AsyncReadRent {
+    /// The buffered bytes, reading more first if there are none. Empty at the
+    /// end of the stream.
+    fn fill_buf(&mut self) -> impl Future<Output = io::Result<&[u8]>>;
+
+    /// Mark `n` buffered bytes as used, they are not returned again.
+    fn consume(&mut self, n: usize);
+}
+
+/// Line reading over [`AsyncBufReadRent`], implemented for every buffered
+/// reader.
+pub trait AsyncBufReadRentExt: AsyncBufReadRent {
+    /// Read up to and including the next `\n`, appending it to `buf`.
+    /// Returns the number of bytes appended, 0 at the end of the stream. The
+    /// last line may have no `\n`.
+    ///
+    /// A line that is not UTF-8 fails with [`io::ErrorKind::InvalidData`],
+    /// leaving `buf` as it was. Its bytes stay consumed, the next read starts
+    /// after it.
+    fn read_line(&mut self, buf: String) -> impl Future<Output = BufResult<usize, String>> {
+        self.read_line_max(buf, usize::MAX)
+    }
+
+    /// Like [`read_line`](AsyncBufReadRentExt::read_line), for lines of at
+    /// most `max` bytes before the `\n`.
+    ///
+    /// A longer line fails with [`io::ErrorKind::InvalidData`] once `max`
+    /// bytes are read, leaving `buf` as it was. Those bytes stay consumed,
+    /// the rest of the line is read next.
+    fn read_line_max(
+        &mut self,
+        mut buf: String,
+        max: usize,
+    ) -> impl Future<Output = BufResult<usize, String>> {
+        async move {
+            let mut line = Vec::new();
+            if let Err(e) = read_until_max(self, b'\n', &mut line, max).await {
+                return (Err(e), buf);
+            }
+            match String::from_utf8(line) {
+                Ok(line) => {
+                    buf.push_str(&line);
+                    (Ok(line.len()), buf)
+                }
+                Err(_) => {
+                    let err = io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "stream did not contain valid UTF-8",
+                    );
+                    (Err(err), buf)
+                }
+            }
+        }
+    }
+
+    /// The lines of the reader, see [`Lines`].
+    fn lines(self) -> Lines<Self>
+    where
+        Self: Sized,
+    {
+        Lines {
+            reader: self,
+            max: DEFAULT_MAX_LINE,
+            strip_cr: true,
+        }
+    }
+}
+
+impl<A: AsyncBufReadRent + ?Sized> AsyncBufReadRentExt for A {}
+
+impl<A: AsyncBufReadRent + ?Sized> AsyncBufReadRent for &mut A {
+    fn fill_buf(&mut self) -> impl Future<Output = io::Result<&[u8]>> {
+        (**self).fill_buf()
+    }
+
+    fn consume(&mut self, n: usize) {
+        (**self).consume(n)
+    }
+}
+
+// Append to `out` up to and including `byte`, failing after `max` bytes
+// without it.
+async fn read_until_max<R: AsyncBufReadRent + ?Sized>(
+    reader: &mut R,
+    byte: u8,
+    out: &mut Vec<u8>,
+    max: usize,
+) -> io::Result<()> {
+    let mut read = 0;
+    loop {
+        let (used, done) = {
+            let available = match reader.fill_buf().await {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if available.is_empty() {
+                return Ok(());
+            }
+            let room = max - read;
+            match available.iter().position(|&b| b == byte) {
+                Some(i) if i <= room => {
+                    out.extend_from_slice(&available[..=i]);
+                    (i + 1, true)
+                }
+                _ if room == 0 => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("line longer than {max} bytes"),
+                    ));
+                }
+                _ => {
+                    let n = available.len().min(room);
+                    out.extend_from_slice(&available[..n]);
+                    (n, false)
+                }
+            }
+        };
+        reader.consume(used);
+        if done {
+            return Ok(());
+        }
+        read += used;
+    }
+}
+
+/// The lines of a buffered reader, without their `\n` or `\r\n`, from
+/// [`AsyncBufReadRentExt::lines`].
+///
+/// Lines are at most 64KiB by default, so a peer that never sends a `\n`
+/// cannot make it buffer without limit. Errors are those of
+/// [`read_line_max`](AsyncBufReadRentExt::read_line_max).
+#[derive(Debug)]
+pub struct Lines<R> {
+    reader: R,
+    max: usize,
+    strip_cr: bool,
+}
+
+impl<R> Lines<R> {
+    /// Take lines of at most `max` bytes, without the line ending.
+    pub fn max_line_len(mut self, max: usize) -> Self {
+        self.max = max;
+        self
+    }
+
+    /// Whether to strip the `\r` of `\r\n` too, the default. Otherwise only
+    /// the `\n` is.
+    pub fn strip_cr(mut self, strip: bool) -> Self {
+        self.strip_cr = strip;
+        self
+    }
+
+    /// The reader underneath.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Give back the reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: AsyncBufReadRent> Lines<R> {
+    /// The next line, `None` at the end of the stream.
+    pub async fn next_line(&mut self) -> io::Result<Option<String>> {
+        // Room for the `\r` that is stripped.
+        let max = match self.strip_cr {
+            true => self.max.saturating_add(1),
+            false => self.max,
+        };
+        let (n, mut line) = self.reader.read_line_max(String::new(), max).await;
+        if n? == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if self.strip_cr && line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        if line.len() > self.max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line longer than {} bytes", self.max),
+            ));
+        }
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::io::BufReader;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn mixed_line_endings() {
+        let path = std::env::temp_dir().join(format!("loop-lines-{}", std::process::id()));
+        std::fs::write(&path, "unix\ndos\r\n\nlong line\nlast").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Lines cross the boundaries of the small buffer.
+            let file = File::open(&path).await.unwrap();
+            let mut lines = BufReader::with_capacity(3, file).lines();
+            let mut all = Vec::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                all.push(line);
+            }
+            assert_eq!(all, ["unix", "dos", "", "long line", "last"]);
+
+            let file = File::open(&path).await.unwrap();
+            let mut lines = BufReader::new(file).lines().strip_cr(false);
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "unix");
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "dos\r");
+
+            // With the endings, the last line has none.
+            let mut reader = BufReader::new(File::open(&path).await.unwrap());
+            let (n, buf) = reader.read_line(String::from("> ")).await;
+            assert_eq!((n.unwrap(), buf.as_str()), (5, "> unix\n"));
+            let mut last = String::new();
+            loop {
+                let (n, line) = reader.read_line(String::new()).await;
+                if n.unwrap() == 0 {
+                    break;
+                }
+                last = line;
+            }
+            assert_eq!(last, "last");
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn long_lines_and_invalid_utf8() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut lines = BufReader::with_capacity(4, &b"12345\n1234567\r\nok\n"[..])
+                .lines()
+                .max_line_len(5);
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "12345");
+            let err = lines.next_line().await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            // The consumed part stays consumed, the rest comes next.
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "7");
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), "ok");
+            assert_eq!(lines.next_line().await.unwrap(), None);
+
+            let mut reader = BufReader::new(&b"a\xffb\nnext\n"[..]);
+            let (n, buf) = reader.read_line(String::from("kept")).await;
+            assert_eq!(n.unwrap_err().kind(), io::ErrorKind::InvalidData);
+            assert_eq!(buf, "kept");
+            let (n, buf) = reader.read_line(String::new()).await;
+            assert_eq!((n.unwrap(), buf.as_str()), (5, "next\n"));
+        });
+    }
+}
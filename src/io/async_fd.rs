@@ -0,0 +1,314 @@
+use crate::driver::op::Op;
+use crate::utils::error_ctx::ResultExt;
+use std::cell::Cell;
+use std::future::poll_fn;
+use std::io;
+use std::ops::BitOr;
+use std::os::fd::{AsRawFd, RawFd};
+use std::task::{ready, Poll};
+use std::time::Duration;
+
+// Reported for any interest, the next syscall tells what happened.
+const ALWAYS: u32 = (libc::POLLERR | libc::POLLHUP) as u32;
+
+// How often a wait checks that its fd is still open. A poll in flight holds
+// the file, closing the fd does not end it.
+const CLOSE_CHECK: Duration = Duration::from_millis(500);
+
+/// The readiness an [`AsyncFd`] waits for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interest(u32);
+
+impl Interest {
+    /// There is data to read, `POLLIN`.
+    pub const READABLE: Interest = Interest(libc::POLLIN as u32);
+    /// There is room to write, `POLLOUT`.
+    pub const WRITABLE: Interest = Interest(libc::POLLOUT as u32);
+    /// There is urgent data, `POLLPRI`, like out of band TCP data or a GPIO
+    /// line event.
+    pub const PRIORITY: Interest = Interest(libc::POLLPRI as u32);
+
+    pub fn is_readable(self) -> bool {
+        self.0 & Self::READABLE.0 != 0
+    }
+
+    pub fn is_writable(self) -> bool {
+        self.0 & Self::WRITABLE.0 != 0
+    }
+
+    pub fn is_priority(self) -> bool {
+        self.0 & Self::PRIORITY.0 != 0
+    }
+}
+
+impl BitOr for Interest {
+    type Output = Interest;
+
+    fn bitor(self, other: Interest) -> Interest {
+        Interest(self.0 | other.0)
+    }
+}
+
+/// The events an [`AsyncFd`] was found ready for, the `revents` of a poll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ready(u32);
+
+impl Ready {
+    pub fn is_readable(self) -> bool {
+        self.0 & libc::POLLIN as u32 != 0
+    }
+
+    pub fn is_writable(self) -> bool {
+        self.0 & libc::POLLOUT as u32 != 0
+    }
+
+    pub fn is_priority(self) -> bool {
+        self.0 & libc::POLLPRI as u32 != 0
+    }
+
+    /// An error is pending on the fd, `POLLERR`.
+    pub fn is_error(self) -> bool {
+        self.0 & libc::POLLERR as u32 != 0
+    }
+
+    /// The peer hung up, `POLLHUP`. Reads drain what is left, then see the
+    /// end.
+    pub fn is_hangup(self) -> bool {
+        self.0 & libc::POLLHUP as u32 != 0
+    }
+
+    /// The raw `revents` bits.
+    pub fn as_raw(self) -> u32 {
+        self.0
+    }
+}
+
+/// Readiness of an fd the runtime does not otherwise know about, for
+/// doing its syscalls yourself: wait until it is ready, make nonblocking
+/// calls until one fails with [`io::ErrorKind::WouldBlock`], clear the
+/// readiness on the guard and wait again.
+///
+/// Waits are level-triggered polls on the ring, each one armed when the
+/// readiness seen last was cleared. The fd should be nonblocking, a
+/// blocking call after a stale readiness stalls the whole runtime.
+///
+/// A wait whose fd gets closed under it fails with `EBADF`, within half a
+/// second of the close. Until then the poll keeps the file open.
+pub struct AsyncFd<T: AsRawFd> {
+    inner: T,
+    // Events seen by the last waits and not cleared since.
+    ready: Cell<u32>,
+}
+
+/// Readiness of an [`AsyncFd`], from [`AsyncFd::ready`]. Dropping it keeps
+/// the readiness for the next wait, [`clear_ready`](ReadyGuard::clear_ready)
+/// once a syscall would block.
+pub struct ReadyGuard<'a, T: AsRawFd> {
+    fd: &'a AsyncFd<T>,
+    ready: Ready,
+}
+
+impl<T: AsRawFd> AsyncFd<T> {
+    /// Wait on the fd of `inner`. Fails with `EBADF` if it is not open.
+    pub fn new(inner: T) -> io::Result<AsyncFd<T>> {
+        check_open(inner.as_raw_fd())?;
+        Ok(AsyncFd {
+            inner,
+            ready: Cell::new(0),
+        })
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Wait until the fd is readable.
+    pub async fn readable(&self) -> io::Result<ReadyGuard<'_, T>> {
+        self.ready(Interest::READABLE).await
+    }
+
+    /// Wait until the fd is writable.
+    pub async fn writable(&self) -> io::Result<ReadyGuard<'_, T>> {
+        self.ready(Interest::WRITABLE).await
+    }
+
+    /// Wait until the fd is ready for any of `interest`, or has an error or
+    /// hangup. Returns right away for readiness not cleared yet. Tasks may
+    /// wait at the same time, each with its own poll.
+    pub async fn ready(&self, interest: Interest) -> io::Result<ReadyGuard<'_, T>> {
+        let wanted = interest.0 | ALWAYS;
+        let seen = self.ready.get() & wanted;
+        if seen != 0 {
+            return Ok(self.guard(seen));
+        }
+        let fd = self.inner.as_raw_fd();
+        let mut poll = Op::poll_add(fd, interest.0).op_fd("poll", fd)?;
+        let mut check = Op::timeout(CLOSE_CHECK).op_fd("poll", fd)?;
+        let revents = poll_fn(|cx| loop {
+            if let Poll::Ready(res) = poll.poll_ready(cx) {
+                return Poll::Ready(res);
+            }
+            ready!(check.poll_expired(cx))?;
+            check_open(fd)?;
+            // Polled first thing, for its waker.
+            check = Op::timeout(CLOSE_CHECK)?;
+        })
+        .await
+        .op_fd("poll", fd)?;
+        if revents & libc::POLLNVAL as u32 != 0 {
+            return Err(io::Error::from_raw_os_error(libc::EBADF)).op_fd("poll", fd);
+        }
+        self.ready.set(self.ready.get() | revents);
+        Ok(self.guard(revents & wanted))
+    }
+
+    fn guard(&self, ready: u32) -> ReadyGuard<'_, T> {
+        ReadyGuard {
+            fd: self,
+            ready: Ready(ready),
+        }
+    }
+}
+
+impl<T: AsRawFd> ReadyGuard<'_, T> {
+    /// The events the fd was found ready for.
+    pub fn ready(&self) -> Ready {
+        self.ready
+    }
+
+    /// Forget the readiness, a syscall would block. The next wait polls the
+    /// fd again.
+    pub fn clear_ready(self) {
+        let fd = self.fd;
+        fd.ready.set(fd.ready.get() & !self.ready.0);
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.fd.inner
+    }
+}
+
+impl<T: AsRawFd> AsRawFd for AsyncFd<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+fn check_open(fd: RawFd) -> io::Result<()> {
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::future::Future;
+    use std::io::Write;
+    use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+    use std::pin::pin;
+    use std::time::Instant;
+
+    fn raw_os_error(err: &io::Error) -> Option<i32> {
+        let source = err.get_ref()?.source()?;
+        source.downcast_ref::<io::Error>()?.raw_os_error()
+    }
+
+    fn pipe() -> (OwnedFd, OwnedFd) {
+        let mut fds = [0; 2];
+        let flags = libc::O_NONBLOCK | libc::O_CLOEXEC;
+        assert_eq!(unsafe { libc::pipe2(fds.as_mut_ptr(), flags) }, 0);
+        unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) }
+    }
+
+    fn read(fd: &impl AsRawFd, buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    // Whether `fut` is still pending after the ring had a turn.
+    async fn stays_pending(fut: impl Future) -> bool {
+        let mut fut = pin!(fut);
+        let mut timer = Op::timeout(Duration::from_millis(20)).unwrap();
+        poll_fn(|cx| {
+            if fut.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(false);
+            }
+            timer.poll_expired(cx).map(|_| true)
+        })
+        .await
+    }
+
+    #[test]
+    fn level_triggered_rearm() {
+        let (rx, tx) = pipe();
+        let mut tx = std::fs::File::from(tx);
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let rx = AsyncFd::new(rx).unwrap();
+            assert!(stays_pending(rx.readable()).await);
+
+            tx.write_all(b"abcdef").unwrap();
+            let guard = rx.readable().await.unwrap();
+            assert!(guard.ready().is_readable());
+            let mut buf = [0; 4];
+            assert_eq!(read(guard.get_ref(), &mut buf).unwrap(), 4);
+            // Data is left, the poll after the clear reports it again.
+            guard.clear_ready();
+            let guard = rx.readable().await.unwrap();
+            assert_eq!(read(guard.get_ref(), &mut buf).unwrap(), 2);
+            let err = read(guard.get_ref(), &mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+            guard.clear_ready();
+            assert!(stays_pending(rx.readable()).await);
+
+            // Not cleared, kept for the next wait.
+            tx.write_all(b"x").unwrap();
+            let _ = rx.readable().await.unwrap();
+            assert!(rx.readable().await.unwrap().ready().is_readable());
+
+            let writer = AsyncFd::new(tx.as_raw_fd()).unwrap();
+            assert!(writer.writable().await.unwrap().ready().is_writable());
+
+            drop(tx);
+            let ready = rx.ready(Interest::PRIORITY).await.unwrap().ready();
+            assert!(ready.is_hangup());
+        });
+    }
+
+    #[test]
+    fn closed_under_a_wait() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (rx, _tx) = pipe();
+            let raw = rx.into_raw_fd();
+            let fd = AsyncFd::new(raw).unwrap();
+            let mut wait = pin!(fd.readable());
+            assert!(stays_pending(wait.as_mut()).await);
+            let closed = Instant::now();
+            unsafe { libc::close(raw) };
+            let Err(err) = wait.await else {
+                panic!("ready after the close")
+            };
+            assert_eq!(raw_os_error(&err), Some(libc::EBADF));
+            assert!(closed.elapsed() < 2 * CLOSE_CHECK);
+
+            let err = AsyncFd::new(raw).err().unwrap();
+            assert_eq!(err.raw_os_error(), Some(libc::EBADF));
+        });
+    }
+}
@@ -0,0 +1,200 @@
+use crate::buf::{self, BufResult, IoBuf, IoBufMut, IoBufMutExt, IoVecBufMut};
+use std::future::Future;
+use std::io;
+
+/// A source of bytes read into owned buffers.
+pub trait AsyncReadRent {
+    /// Read into all of `buf`'s capacity, returning the number of bytes read
+    /// together with the buffer. 0 means the end of the stream, for a buffer
+    /// with room.
+    fn read<T: IoBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>>;
+
+    /// Read into the buffers of `buf` in order, returning the number of bytes
+    /// read together with the buffers.
+    fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>>;
+}
+
+/// Loops over [`AsyncReadRent::read`], implemented for every reader.
+pub trait AsyncReadRentExt: AsyncReadRent {
+    /// Read until all of `buf`'s [`bytes_total`](IoBufMut::bytes_total) is
+    /// filled, returning that count together with the buffer.
+    ///
+    /// If the stream ends first, fails with [`io::ErrorKind::UnexpectedEof`]
+    /// and the buffer holds what was read.
+    fn read_exact<T: IoBuf + IoBufMut>(
+        &mut self,
+        mut buf: T,
+    ) -> impl Future<Output = BufResult<usize, T>> {
+        async move {
+            let total = buf.bytes_total();
+            let mut filled = 0;
+            while filled < total {
+                let (res, slice) = self.read(buf.slice_mut(filled..)).await;
+                buf = slice.into_inner();
+                match res {
+                    Ok(0) => {
+                        let err = io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!("stream ended after {filled} of {total} bytes"),
+                        );
+                        return (Err(err), buf);
+                    }
+                    Ok(n) => filled += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return (Err(e), buf),
+                }
+            }
+            (Ok(total), buf)
+        }
+    }
+
+    /// Read until the end of the stream, appending to `buf` and growing it
+    /// as needed. Returns the number of bytes appended.
+    ///
+    /// A full buffer doubles in size, by at most 8MiB at a time.
+    fn read_to_end<T: IoBufMutExt>(
+        &mut self,
+        mut buf: T,
+    ) -> impl Future<Output = BufResult<usize, T>> {
+        async move {
+            let start = buf.bytes_init();
+            loop {
+                buf::reserve_for_read(&mut buf);
+                let init = buf.bytes_init();
+                let (res, slice) = self.read(buf.slice_mut(init..)).await;
+                buf = slice.into_inner();
+                match res {
+                    Ok(0) => return (Ok(buf.bytes_init() - start), buf),
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return (Err(e), buf),
+                }
+            }
+        }
+    }
+}
+
+impl<A: AsyncReadRent + ?Sized> AsyncReadRentExt for A {}
+
+impl<A: AsyncReadRent + ?Sized> AsyncReadRent for &mut A {
+    fn read<T: IoBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        (**self).read(buf)
+    }
+
+    fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        (**self).readv(buf)
+    }
+}
+
+// Reads consume the front of the slice.
+impl AsyncReadRent for &[u8] {
+    async fn read<T: IoBufMut>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        let n = self.len().min(buf.bytes_total());
+        unsafe {
+            buf.write_ptr().copy_from_nonoverlapping(self.as_ptr(), n);
+            buf.set_init(n);
+        }
+        *self = &self[n..];
+        (Ok(n), buf)
+    }
+
+    async fn readv<T: IoVecBufMut>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        let (ptr, count) = buf.write_iovecs();
+        let mut n = 0;
+        for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+            let len = (self.len() - n).min(iov.iov_len);
+            if len == 0 {
+                continue;
+            }
+            unsafe {
+                iov.iov_base
+                    .cast::<u8>()
+                    .copy_from_nonoverlapping(self[n..].as_ptr(), len)
+            };
+            n += len;
+        }
+        // The first `n` bytes across the iovecs were just copied in.
+        unsafe { buf.set_init(n) };
+        *self = &self[n..];
+        (Ok(n), buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    // Hands out one byte per read, interrupted before every other one.
+    struct Trickle {
+        data: Vec<u8>,
+        pos: usize,
+        interrupt: bool,
+    }
+
+    impl Trickle {
+        fn new(data: &[u8]) -> Self {
+            Trickle {
+                data: data.to_vec(),
+                pos: 0,
+                interrupt: false,
+            }
+        }
+    }
+
+    impl AsyncReadRent for Trickle {
+        async fn read<T: IoBufMut>(&mut self, mut buf: T) -> BufResult<usize, T> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return (Err(io::ErrorKind::Interrupted.into()), buf);
+            }
+            let n = (self.data.len() - self.pos).min(buf.bytes_total()).min(1);
+            unsafe {
+                buf.write_ptr()
+                    .copy_from_nonoverlapping(self.data[self.pos..].as_ptr(), n);
+                buf.set_init(n);
+            }
+            self.pos += n;
+            (Ok(n), buf)
+        }
+
+        async fn readv<T: IoVecBufMut>(&mut self, buf: T) -> BufResult<usize, T> {
+            (Err(io::ErrorKind::Unsupported.into()), buf)
+        }
+    }
+
+    #[test]
+    fn read_exact_and_to_end() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut src = Trickle::new(b"exactly");
+            let (n, buf) = src.read_exact(Vec::with_capacity(5)).await;
+            assert_eq!(n.unwrap(), 5);
+            assert_eq!(buf, b"exact");
+            // Short of the capacity, still handed back with what arrived.
+            let (res, buf) = src.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+            assert_eq!(buf, b"ly");
+            let (n, buf) = src.read_exact(Vec::new()).await;
+            assert_eq!((n.unwrap(), buf.len()), (0, 0));
+
+            let data: Vec<u8> = (0..100).collect();
+            let mut src = Trickle::new(&data);
+            let mut buf = Vec::with_capacity(3);
+            buf.extend_from_slice(b"ab");
+            let (n, buf) = src.read_to_end(buf).await;
+            assert_eq!(n.unwrap(), 100);
+            assert_eq!(buf[..2], *b"ab");
+            assert_eq!(buf[2..], data);
+            // At the end already.
+            let (n, buf) = src.read_to_end(buf).await;
+            assert_eq!((n.unwrap(), buf.len()), (0, 102));
+
+            let mut src: &[u8] = b"";
+            let (n, buf) = src.read_to_end(Vec::new()).await;
+            assert_eq!(n.unwrap(), 0);
+            assert!(buf.capacity() >= 32);
+        });
+    }
+}
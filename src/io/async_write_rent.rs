@@ -0,0 +1,213 @@
+use crate::buf::{BufResult, IoBuf, IoVecBuf, VecBuf};
+use std::future::Future;
+use std::io;
+
+/// A sink of bytes written from owned buffers.
+pub trait AsyncWriteRent {
+    /// Write the initialized bytes of `buf`, returning the number of bytes
+    /// written together with the buffer.
+    fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>>;
+
+    /// Write the buffers of `buf` in order, returning the number of bytes
+    /// written together with the buffers.
+    fn writev<T: IoVecBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>>;
+
+    /// Push out everything buffered in userspace.
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>>;
+
+    /// Flush, then signal the other end that nothing more is written.
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>>;
+}
+
+/// Loops over [`AsyncWriteRent::write`], implemented for every writer.
+pub trait AsyncWriteRentExt: AsyncWriteRent {
+    /// Write all of `buf`, resubmitting the rest after short writes.
+    ///
+    /// A write of nothing fails with [`io::ErrorKind::WriteZero`].
+    fn write_all<T: IoBuf>(&mut self, mut buf: T) -> impl Future<Output = BufResult<(), T>> {
+        async move {
+            let len = buf.bytes_init();
+            let mut written = 0;
+            while written < len {
+                let (res, slice) = self.write(buf.slice(written..)).await;
+                buf = slice.into_inner();
+                match res {
+                    Ok(0) => return (Err(write_zero()), buf),
+                    Ok(n) => written += n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return (Err(e), buf),
+                }
+            }
+            (Ok(()), buf)
+        }
+    }
+
+    /// Write all of `buf`, resuming mid-buffer after short writes.
+    ///
+    /// A write of nothing fails with [`io::ErrorKind::WriteZero`], the
+    /// buffers are then advanced past what was written.
+    fn write_vectored_all(
+        &mut self,
+        mut buf: VecBuf,
+    ) -> impl Future<Output = BufResult<(), VecBuf>> {
+        async move {
+            while !buf.is_empty() {
+                let (res, rest) = self.writev(buf).await;
+                buf = rest;
+                match res {
+                    Ok(0) => return (Err(write_zero()), buf),
+                    Ok(n) => buf.advance(n),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return (Err(e), buf),
+                }
+            }
+            (Ok(()), buf)
+        }
+    }
+}
+
+impl<A: AsyncWriteRent + ?Sized> AsyncWriteRentExt for A {}
+
+fn write_zero() -> io::Error {
+    io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer")
+}
+
+impl<A: AsyncWriteRent + ?Sized> AsyncWriteRent for &mut A {
+    fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        (**self).write(buf)
+    }
+
+    fn writev<T: IoVecBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        (**self).writev(buf)
+    }
+
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+        (**self).flush()
+    }
+
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+        (**self).shutdown()
+    }
+}
+
+// Writes append, nothing is buffered.
+impl AsyncWriteRent for Vec<u8> {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        let data = unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) };
+        self.extend_from_slice(data);
+        (Ok(data.len()), buf)
+    }
+
+    async fn writev<T: IoVecBuf>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        let (ptr, count) = buf.read_iovecs();
+        let start = self.len();
+        for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+            if iov.iov_len == 0 {
+                continue;
+            }
+            let data =
+                unsafe { std::slice::from_raw_parts(iov.iov_base.cast::<u8>(), iov.iov_len) };
+            self.extend_from_slice(data);
+        }
+        (Ok(self.len() - start), buf)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    // Takes at most `max` bytes per write, then nothing once `room` is used.
+    struct Narrow {
+        out: Vec<u8>,
+        max: usize,
+        room: usize,
+        writes: usize,
+    }
+
+    impl Narrow {
+        fn new(max: usize, room: usize) -> Self {
+            Narrow {
+                out: Vec::new(),
+                max,
+                room,
+                writes: 0,
+            }
+        }
+
+        fn take(&mut self, data: &[u8]) -> usize {
+            let n = data.len().min(self.max).min(self.room - self.out.len());
+            self.out.extend_from_slice(&data[..n]);
+            self.writes += 1;
+            n
+        }
+    }
+
+    impl AsyncWriteRent for Narrow {
+        async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+            let data = unsafe { std::slice::from_raw_parts(buf.read_ptr(), buf.bytes_init()) };
+            (Ok(self.take(data)), buf)
+        }
+
+        async fn writev<T: IoVecBuf>(&mut self, mut buf: T) -> BufResult<usize, T> {
+            let (ptr, count) = buf.read_iovecs();
+            let mut data = Vec::new();
+            for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+                data.extend_from_slice(unsafe {
+                    std::slice::from_raw_parts(iov.iov_base.cast::<u8>(), iov.iov_len)
+                });
+            }
+            (Ok(self.take(&data)), buf)
+        }
+
+        async fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_all_resubmits_short_writes() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut dst = Narrow::new(3, usize::MAX);
+            let (res, buf) = dst.write_all(b"short writes".to_vec()).await;
+            res.unwrap();
+            assert_eq!(buf, b"short writes");
+            assert_eq!((dst.out.as_slice(), dst.writes), (&b"short writes"[..], 4));
+
+            // Writes end inside segments and on their boundaries.
+            let mut dst = Narrow::new(4, usize::MAX);
+            let bufs = VecBuf::new(vec![b"ab".to_vec(), b"cdefg".to_vec(), b"hi".to_vec()]);
+            let (res, buf) = dst.write_vectored_all(bufs).await;
+            res.unwrap();
+            assert!(buf.is_empty());
+            assert_eq!(dst.out, b"abcdefghi");
+            assert_eq!(dst.writes, 3);
+
+            // Nothing taken ends the loop.
+            let mut dst = Narrow::new(4, 6);
+            let (res, _) = dst.write_all(&b"overflowing"[..]).await;
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WriteZero);
+            assert_eq!(dst.out, b"overfl");
+            let mut dst = Narrow::new(4, 3);
+            let bufs = VecBuf::new(vec![b"ab".to_vec(), b"cd".to_vec()]);
+            let (res, buf) = dst.write_vectored_all(bufs).await;
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WriteZero);
+            assert_eq!(buf.len(), 1);
+        });
+    }
+}
@@ -0,0 +1,290 @@
+use super::{AsyncBufReadRent, AsyncReadRent};
+use crate::buf::{BufResult, IoBufMut, IoVecBufMut};
+use std::io;
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Buffers the reads of `R`, so many small reads cost one op.
+///
+/// [`fill_buf`](BufReader::fill_buf) and [`consume`](BufReader::consume)
+/// let parsers look at the buffered bytes in place. Reads of at least the
+/// capacity into an empty buffer go straight to `R`.
+///
+/// The buffer is only refilled once it is empty, so dropping a pending
+/// read loses no buffered bytes, only the allocation, which the next read
+/// makes again.
+pub struct BufReader<R> {
+    inner: R,
+    // Taken while a read fills it.
+    buf: Option<Vec<u8>>,
+    pos: usize,
+    capacity: usize,
+}
+
+impl<R> BufReader<R> {
+    /// Buffer `inner` with 8KiB.
+    pub fn new(inner: R) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Buffer `inner` with `capacity` bytes.
+    pub fn with_capacity(capacity: usize, inner: R) -> Self {
+        BufReader {
+            inner,
+            buf: Some(Vec::with_capacity(capacity)),
+            pos: 0,
+            capacity,
+        }
+    }
+
+    /// The reader underneath.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The reader underneath. Reading from it directly skips what is
+    /// buffered.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Give back the reader, dropping what is buffered.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// The bytes buffered and not consumed yet.
+    pub fn buffer(&self) -> &[u8] {
+        match &self.buf {
+            Some(buf) => &buf[self.pos..],
+            None => &[],
+        }
+    }
+
+    /// The size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Mark `n` buffered bytes as used, they are not returned again.
+    pub fn consume(&mut self, n: usize) {
+        let len = self.buf.as_ref().map_or(0, Vec::len);
+        self.pos = (self.pos + n).min(len);
+    }
+}
+
+impl<R: AsyncReadRent> BufReader<R> {
+    /// The buffered bytes, reading more first if there are none. Empty at the
+    /// end of the stream.
+    pub async fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.buffer().is_empty() {
+            let mut buf = self
+                .buf
+                .take()
+                .unwrap_or_else(|| Vec::with_capacity(self.capacity));
+            buf.clear();
+            let (res, buf) = self.inner.read(buf).await;
+            self.buf = Some(buf);
+            self.pos = 0;
+            res?;
+        }
+        Ok(self.buffer())
+    }
+}
+
+impl<R: AsyncReadRent> AsyncReadRent for BufReader<R> {
+    async fn read<T: IoBufMut>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        if self.buffer().is_empty() && buf.bytes_total() >= self.capacity {
+            return self.inner.read(buf).await;
+        }
+        let data = match self.fill_buf().await {
+            Ok(data) => data,
+            Err(e) => return (Err(e), buf),
+        };
+        let n = data.len().min(buf.bytes_total());
+        unsafe {
+            buf.write_ptr().copy_from_nonoverlapping(data.as_ptr(), n);
+            buf.set_init(n);
+        }
+        self.consume(n);
+        (Ok(n), buf)
+    }
+
+    async fn readv<T: IoVecBufMut>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        let (ptr, count) = buf.write_iovecs();
+        let total: usize = unsafe { std::slice::from_raw_parts(ptr, count) }
+            .iter()
+            .map(|iov| iov.iov_len)
+            .sum();
+        if self.buffer().is_empty() && total >= self.capacity {
+            return self.inner.readv(buf).await;
+        }
+        let data = match self.fill_buf().await {
+            Ok(data) => data,
+            Err(e) => return (Err(e), buf),
+        };
+        // The iovecs stay valid, the buffers did not move.
+        let mut n = 0;
+        for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+            let len = (data.len() - n).min(iov.iov_len);
+            if len == 0 {
+                continue;
+            }
+            unsafe {
+                iov.iov_base
+                    .cast::<u8>()
+                    .copy_from_nonoverlapping(data[n..].as_ptr(), len)
+            };
+            n += len;
+        }
+        unsafe { buf.set_init(n) };
+        self.consume(n);
+        (Ok(n), buf)
+    }
+}
+
+impl<R: AsyncReadRent> AsyncBufReadRent for BufReader<R> {
+    async fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        BufReader::fill_buf(self).await
+    }
+
+    fn consume(&mut self, n: usize) {
+        BufReader::consume(self, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::SmallIoVec;
+    use crate::driver::IoUringDriver;
+    use crate::fs::Pipe;
+    use crate::io::AsyncReadRentExt;
+    use crate::runtime::RuntimeBuilder;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Waker};
+
+    // Counts the reads that reach the source.
+    struct Counted<'a> {
+        src: &'a [u8],
+        reads: usize,
+    }
+
+    impl AsyncReadRent for Counted<'_> {
+        async fn read<T: IoBufMut>(&mut self, buf: T) -> BufResult<usize, T> {
+            self.reads += 1;
+            self.src.read(buf).await
+        }
+
+        async fn readv<T: IoVecBufMut>(&mut self, buf: T) -> BufResult<usize, T> {
+            self.reads += 1;
+            self.src.readv(buf).await
+        }
+    }
+
+    fn counted(src: &[u8]) -> Counted<'_> {
+        Counted { src, reads: 0 }
+    }
+
+    #[test]
+    fn small_reads_share_one_fill() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut reader = BufReader::with_capacity(64, counted(&data));
+            let mut out = Vec::new();
+            loop {
+                let (n, buf) = reader.read(Vec::with_capacity(3)).await;
+                if n.unwrap() == 0 {
+                    break;
+                }
+                out.extend_from_slice(&buf);
+            }
+            assert_eq!(out, data);
+            // Two fills and the one finding the end.
+            assert_eq!(reader.get_ref().reads, 3);
+        });
+    }
+
+    #[test]
+    fn large_reads_bypass_the_buffer() {
+        let data: Vec<u8> = (0..100).collect();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut reader = BufReader::with_capacity(16, counted(&data));
+            let (n, buf) = reader.read(Vec::with_capacity(40)).await;
+            assert_eq!(n.unwrap(), 40);
+            assert_eq!(buf, &data[..40]);
+            assert!(reader.buffer().is_empty());
+
+            // Buffered bytes come first, even for a large read.
+            let (n, _) = reader.read(Vec::with_capacity(1)).await;
+            assert_eq!(n.unwrap(), 1);
+            let (n, buf) = reader.read(Vec::with_capacity(40)).await;
+            assert_eq!(n.unwrap(), 15);
+            assert_eq!(buf, &data[41..56]);
+
+            let mut bufs = SmallIoVec::<Vec<u8>, 2>::new();
+            bufs.push(Vec::with_capacity(10)).unwrap();
+            bufs.push(Vec::with_capacity(10)).unwrap();
+            let (n, bufs) = reader.readv(bufs).await;
+            assert_eq!(n.unwrap(), 20);
+            assert_eq!(bufs.bufs()[1], &data[66..76]);
+            // The vectored read bypassed the buffer as well.
+            assert_eq!(reader.get_ref().reads, 3);
+        });
+    }
+
+    #[test]
+    fn fill_consume_and_read_interleaved() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut reader = BufReader::with_capacity(8, counted(b"key: value\nnext\n"));
+            assert_eq!(reader.fill_buf().await.unwrap(), b"key: val");
+            reader.consume(5);
+            // Peeking again does not read.
+            assert_eq!(reader.fill_buf().await.unwrap(), b"val");
+            assert_eq!(reader.get_ref().reads, 1);
+            let (n, buf) = reader.read_exact(Vec::with_capacity(6)).await;
+            assert_eq!(n.unwrap(), 6);
+            assert_eq!(buf, b"value\n");
+            assert_eq!(reader.buffer(), b"next\n");
+            reader.consume(3);
+            assert_eq!(reader.fill_buf().await.unwrap(), b"t\n");
+            reader.consume(100);
+            assert!(reader.fill_buf().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn dropped_fill_keeps_buffered_bytes() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let pipe = Pipe::new().unwrap();
+            let (n, _) = pipe.writer().write("ab").await;
+            assert_eq!(n.unwrap(), 2);
+            let mut reader = BufReader::new(pipe.reader().try_clone().unwrap());
+            assert_eq!(reader.fill_buf().await.unwrap(), b"ab");
+            reader.consume(1);
+            {
+                // Served from the buffer, never pending.
+                let mut read = pin!(reader.read(Vec::with_capacity(8)));
+                let mut cx = Context::from_waker(Waker::noop());
+                assert!(read.as_mut().poll(&mut cx).is_ready());
+            }
+            reader.consume(1);
+            {
+                // Waits for the pipe, then is dropped with the buffer.
+                let mut fill = pin!(reader.fill_buf());
+                let mut cx = Context::from_waker(Waker::noop());
+                assert!(fill.as_mut().poll(&mut cx).is_pending());
+            }
+            // The round trip submits the cancel before more is written.
+            pipe.writer().metadata().await.unwrap();
+            let (n, _) = pipe.writer().write("cd").await;
+            assert_eq!(n.unwrap(), 2);
+            assert_eq!(reader.fill_buf().await.unwrap(), b"cd");
+        });
+    }
+}
@@ -0,0 +1,278 @@
+use super::{AsyncWriteRent, AsyncWriteRentExt};
+use crate::buf::{BufResult, IoBuf, IoVecBuf, VecBuf};
+use std::io;
+
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/// Buffers the writes to `W`, so many small writes cost one op.
+///
+/// The buffer goes out in one vectored write once the next write does not
+/// fit, on [`flush`](AsyncWriteRent::flush) and on
+/// [`shutdown`](AsyncWriteRent::shutdown). Writes of at least the capacity
+/// go straight to `W` after what is buffered.
+///
+/// Drop can not wait for a write, bytes still buffered then are lost, with a
+/// warning in the log. Flush before dropping.
+pub struct BufWriter<W> {
+    inner: W,
+    buf: Unflushed,
+    capacity: usize,
+}
+
+// Taken while a flush writes it.
+struct Unflushed(Option<Vec<u8>>);
+
+impl Drop for Unflushed {
+    fn drop(&mut self) {
+        if let Some(buf) = self.0.as_ref().filter(|buf| !buf.is_empty()) {
+            log::warn!("BufWriter dropped with {} unflushed bytes", buf.len());
+        }
+    }
+}
+
+impl<W> BufWriter<W> {
+    /// Buffer `inner` with 8KiB.
+    pub fn new(inner: W) -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY, inner)
+    }
+
+    /// Buffer `inner` with `capacity` bytes.
+    pub fn with_capacity(capacity: usize, inner: W) -> Self {
+        BufWriter {
+            inner,
+            buf: Unflushed(Some(Vec::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// The writer underneath.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The writer underneath. Writing to it directly skips what is buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Give back the writer, dropping what is buffered like drop does.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// The bytes buffered and not written yet.
+    pub fn buffer(&self) -> &[u8] {
+        self.buf.0.as_deref().unwrap_or_default()
+    }
+
+    /// The size of the buffer.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn buffer_mut(&mut self) -> &mut Vec<u8> {
+        // A flush that was dropped took the buffer along.
+        let capacity = self.capacity;
+        self.buf
+            .0
+            .get_or_insert_with(|| Vec::with_capacity(capacity))
+    }
+}
+
+impl<W: AsyncWriteRent> BufWriter<W> {
+    // Write out the whole buffer, keeping what was not written on failure.
+    async fn flush_buf(&mut self) -> io::Result<()> {
+        let buf = std::mem::take(self.buffer_mut());
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let (res, rest) = self.inner.write_vectored_all(VecBuf::new(vec![buf])).await;
+        let unwritten = rest.len();
+        let mut buf = rest.into_inner().pop().unwrap_or_default();
+        buf.drain(..buf.len() - unwritten);
+        self.buf.0 = Some(buf);
+        res
+    }
+
+    // Whether `len` more bytes go into the buffer, flushing it first if they
+    // do not fit.
+    async fn make_room(&mut self, len: usize) -> io::Result<bool> {
+        if self.buffer().len() + len > self.capacity {
+            self.flush_buf().await?;
+        }
+        Ok(len < self.capacity)
+    }
+}
+
+impl<W: AsyncWriteRent> AsyncWriteRent for BufWriter<W> {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        let len = buf.bytes_init();
+        match self.make_room(len).await {
+            Ok(true) => {}
+            Ok(false) => return self.inner.write(buf).await,
+            Err(e) => return (Err(e), buf),
+        }
+        let data = unsafe { std::slice::from_raw_parts(buf.read_ptr(), len) };
+        self.buffer_mut().extend_from_slice(data);
+        (Ok(len), buf)
+    }
+
+    async fn writev<T: IoVecBuf>(&mut self, mut buf: T) -> BufResult<usize, T> {
+        let (ptr, count) = buf.read_iovecs();
+        let total = unsafe { std::slice::from_raw_parts(ptr, count) }
+            .iter()
+            .map(|iov| iov.iov_len)
+            .sum();
+        match self.make_room(total).await {
+            Ok(true) => {}
+            Ok(false) => return self.inner.writev(buf).await,
+            Err(e) => return (Err(e), buf),
+        }
+        // The iovecs stay valid, the buffers did not move.
+        for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+            if iov.iov_len == 0 {
+                continue;
+            }
+            let data =
+                unsafe { std::slice::from_raw_parts(iov.iov_base.cast::<u8>(), iov.iov_len) };
+            self.buffer_mut().extend_from_slice(data);
+        }
+        (Ok(total), buf)
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf().await?;
+        self.inner.flush().await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.flush_buf().await?;
+        self.inner.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::runtime::RuntimeBuilder;
+
+    // Takes at most `max` bytes per op and counts the ops.
+    struct Counted {
+        out: Vec<u8>,
+        max: usize,
+        writes: usize,
+        writevs: usize,
+        shut: bool,
+    }
+
+    impl Counted {
+        fn new(max: usize) -> Self {
+            Counted {
+                out: Vec::new(),
+                max,
+                writes: 0,
+                writevs: 0,
+                shut: false,
+            }
+        }
+    }
+
+    impl AsyncWriteRent for Counted {
+        async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+            self.writes += 1;
+            let n = buf.bytes_init().min(self.max);
+            let data = unsafe { std::slice::from_raw_parts(buf.read_ptr(), n) };
+            self.out.extend_from_slice(data);
+            (Ok(n), buf)
+        }
+
+        async fn writev<T: IoVecBuf>(&mut self, mut buf: T) -> BufResult<usize, T> {
+            self.writevs += 1;
+            let (ptr, count) = buf.read_iovecs();
+            let mut n = 0;
+            for iov in unsafe { std::slice::from_raw_parts(ptr, count) } {
+                let len = iov.iov_len.min(self.max - n);
+                let data = unsafe { std::slice::from_raw_parts(iov.iov_base.cast::<u8>(), len) };
+                self.out.extend_from_slice(data);
+                n += len;
+            }
+            (Ok(n), buf)
+        }
+
+        async fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn shutdown(&mut self) -> io::Result<()> {
+            self.shut = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn small_writes_coalesce() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut writer = BufWriter::with_capacity(100, Counted::new(usize::MAX));
+            for i in 0..1000u32 {
+                let (n, _) = writer.write(vec![i as u8]).await;
+                assert_eq!(n.unwrap(), 1);
+            }
+            // Flushed whenever the buffer was full, the rest sits there.
+            assert_eq!(writer.get_ref().writevs, 9);
+            assert_eq!(writer.buffer().len(), 100);
+            writer.flush().await.unwrap();
+            let inner = writer.into_inner();
+            assert_eq!((inner.writes, inner.writevs), (0, 10));
+            let expected: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+            assert_eq!(inner.out, expected);
+        });
+    }
+
+    #[test]
+    fn large_writes_go_through() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut writer = BufWriter::with_capacity(8, Counted::new(usize::MAX));
+            writer.write("abc").await.0.unwrap();
+            let (n, _) = writer.write("large write").await;
+            assert_eq!(n.unwrap(), 11);
+            // The buffered bytes went first.
+            let inner = writer.get_ref();
+            assert_eq!(inner.out, b"abclarge write");
+            assert_eq!((inner.writes, inner.writevs), (1, 1));
+            assert!(writer.buffer().is_empty());
+
+            let bufs = VecBuf::new(vec![b"ve".to_vec(), b"ct".to_vec()]);
+            let (n, _) = writer.writev(bufs).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(writer.buffer(), b"vect");
+            writer.shutdown().await.unwrap();
+            assert!(writer.get_ref().shut);
+            assert_eq!(writer.get_ref().out, b"abclarge writevect");
+        });
+    }
+
+    #[test]
+    fn flush_loops_on_short_writes() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut writer = BufWriter::with_capacity(64, Counted::new(5));
+            writer.write_all("twenty-two bytes total").await.0.unwrap();
+            writer.flush().await.unwrap();
+            assert_eq!(writer.get_ref().out, b"twenty-two bytes total");
+            assert_eq!(writer.get_ref().writevs, 5);
+
+            // Failure keeps what was not written.
+            let path = std::env::temp_dir().join(format!("loop-buf-writer-{}", std::process::id()));
+            std::fs::write(&path, b"").unwrap();
+            let mut writer = BufWriter::new(File::open(&path).await.unwrap());
+            writer.write("kept").await.0.unwrap();
+            assert!(writer.flush().await.is_err());
+            assert_eq!(writer.buffer(), b"kept");
+            std::fs::remove_file(&path).unwrap();
+        });
+    }
+}
@@ -0,0 +1,214 @@
+use crate::driver::op::{Mappable, Op, OpCanceller};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io;
+use std::rc::Rc;
+
+/// Cancels the operations started with its [`CancelHandle`]s, like
+/// [`File::read_cancelable`](crate::fs::File::read_cancelable).
+///
+/// Unlike dropping the future of an operation, cancelling lets it finish:
+/// the future resolves with its buffer and an error of `ECANCELED`
+/// ("Operation canceled"), or with the result the operation had before the
+/// cancel reached it. Once cancelled, operations started with its handles
+/// fail right away, handing their buffers back untouched.
+#[derive(Default)]
+pub struct Canceller {
+    shared: Rc<Shared>,
+}
+
+/// Passed to cancelable operations, see [`Canceller`]. Cancelling through
+/// any clone cancels them all.
+#[derive(Clone)]
+pub struct CancelHandle {
+    shared: Rc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    canceled: Cell<bool>,
+    // The operations in flight, by the id of their registration.
+    in_flight: RefCell<Vec<(u64, OpCanceller)>>,
+    next_id: Cell<u64>,
+}
+
+impl Shared {
+    fn cancel(&self) {
+        if self.canceled.replace(true) {
+            return;
+        }
+        for (_, op) in self.in_flight.borrow_mut().drain(..) {
+            op.cancel();
+        }
+    }
+}
+
+impl Canceller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A handle to start cancelable operations with.
+    pub fn handle(&self) -> CancelHandle {
+        CancelHandle {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Cancel the operations in flight and refuse new ones.
+    pub fn cancel(&self) {
+        self.shared.cancel();
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.shared.canceled.get()
+    }
+}
+
+impl CancelHandle {
+    /// Cancel the operations in flight and refuse new ones.
+    pub fn cancel(&self) {
+        self.shared.cancel();
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.shared.canceled.get()
+    }
+
+    /// Fail with `ECANCELED` once cancelled, checked before submitting.
+    pub(crate) fn check(&self) -> io::Result<()> {
+        match self.is_canceled() {
+            true => Err(io::Error::from_raw_os_error(libc::ECANCELED)),
+            false => Ok(()),
+        }
+    }
+
+    /// Cancel `op` along with the handle until the guard is dropped.
+    pub(crate) fn register<T: Mappable>(&self, op: &Op<T>) -> Registration {
+        let id = self.shared.next_id.get();
+        self.shared.next_id.set(id + 1);
+        let op = op.op_canceller();
+        if self.is_canceled() {
+            op.cancel();
+        } else {
+            self.shared.in_flight.borrow_mut().push((id, op));
+        }
+        Registration {
+            shared: self.shared.clone(),
+            id,
+        }
+    }
+}
+
+impl fmt::Debug for Canceller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Canceller")
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
+
+impl fmt::Debug for CancelHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelHandle")
+            .field("canceled", &self.is_canceled())
+            .finish()
+    }
+}
+
+/// Keeps an operation registered with a [`CancelHandle`] while it is in
+/// flight.
+pub(crate) struct Registration {
+    shared: Rc<Shared>,
+    id: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.shared
+            .in_flight
+            .borrow_mut()
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::Pipe;
+    use crate::runtime::RuntimeBuilder;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Waker};
+
+    fn is_canceled(e: &io::Error) -> bool {
+        e.to_string().contains("Operation canceled")
+    }
+
+    #[test]
+    fn cancel_from_another_task() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let pipe = Pipe::new().unwrap();
+            let canceller = Canceller::new();
+            let handle = canceller.handle();
+            let buf = Vec::with_capacity(64);
+            let ptr = buf.as_ptr();
+
+            // Nothing is written, only the cancel ends the read.
+            let cancel = crate::spawn(async move { handle.cancel() });
+            let (n, buf) = pipe
+                .reader()
+                .read_cancelable(buf, &canceller.handle())
+                .await;
+            assert!(is_canceled(&n.unwrap_err()));
+            assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, 64));
+            cancel.await;
+            assert!(canceller.is_canceled());
+
+            // Refused untouched from now on.
+            let (n, buf) = pipe
+                .reader()
+                .read_cancelable(buf, &canceller.handle())
+                .await;
+            assert!(is_canceled(&n.unwrap_err()));
+            let (n, _) = pipe
+                .writer()
+                .write_cancelable(buf, &canceller.handle())
+                .await;
+            assert!(is_canceled(&n.unwrap_err()));
+            // The pipe still works, and nothing was taken from it.
+            let (n, _) = pipe.writer().write("after").await;
+            assert_eq!(n.unwrap(), 5);
+            let (n, buf) = pipe.reader().read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 5);
+            assert_eq!(buf, b"after");
+        });
+    }
+
+    #[test]
+    fn completion_before_cancel_wins() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let pipe = Pipe::new().unwrap();
+            let canceller = Canceller::new();
+            let handle = canceller.handle();
+            let mut read = pin!(pipe
+                .reader()
+                .read_cancelable(Vec::with_capacity(8), &handle));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(read.as_mut().poll(&mut cx).is_pending());
+
+            // The read completes in the round trip, then the cancel comes too
+            // late for it.
+            let (n, _) = pipe.writer().write("done").await;
+            assert_eq!(n.unwrap(), 4);
+            pipe.writer().metadata().await.unwrap();
+            canceller.cancel();
+            let (n, buf) = read.await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"done");
+        });
+    }
+}
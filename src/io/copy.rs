@@ -0,0 +1,185 @@
+use super::{AsyncReadRent, AsyncWriteRent};
+use crate::buf::{BufResult, IoBuf, IoBufMut};
+use std::fmt;
+use std::io;
+
+// What `copy` reads at a time.
+const COPY_BUF: usize = 64 * 1024;
+
+/// The side of a [`copy`] that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    /// Reading from the source.
+    Read,
+    /// Writing to the destination.
+    Write,
+}
+
+/// A [`copy`] failed, carried as the payload of an [`io::Error`] of the same
+/// kind as the original error, which is its source.
+#[derive(Debug)]
+pub struct CopyError {
+    /// Which side failed.
+    pub direction: CopyDirection,
+    /// Bytes written to the destination before the failure.
+    pub copied: u64,
+    source: io::Error,
+}
+
+impl CopyError {
+    /// Get the payload if the error came from a [`copy`].
+    pub fn from_io_error(err: &io::Error) -> Option<&CopyError> {
+        err.get_ref().and_then(|e| e.downcast_ref())
+    }
+}
+
+impl fmt::Display for CopyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = match self.direction {
+            CopyDirection::Read => "read",
+            CopyDirection::Write => "write",
+        };
+        write!(
+            f,
+            "copy failed to {side} after {} bytes: {}",
+            self.copied, self.source
+        )
+    }
+}
+
+impl std::error::Error for CopyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Copy everything from `reader` to `writer` until the end of `reader`,
+/// through one 64KiB buffer. Returns the number of bytes copied.
+///
+/// A failure on either side ends the copy with a [`CopyError`] naming the
+/// side and the bytes copied so far.
+pub async fn copy<R, W>(reader: &mut R, writer: &mut W) -> io::Result<u64>
+where
+    R: AsyncReadRent + ?Sized,
+    W: AsyncWriteRent + ?Sized,
+{
+    let (res, _) = copy_with_buffer(reader, writer, Vec::with_capacity(COPY_BUF)).await;
+    res
+}
+
+/// Like [`copy`], reading into all of `buf`'s capacity each time, and
+/// handing `buf` back.
+pub async fn copy_with_buffer<R, W, B>(
+    reader: &mut R,
+    writer: &mut W,
+    mut buf: B,
+) -> BufResult<u64, B>
+where
+    R: AsyncReadRent + ?Sized,
+    W: AsyncWriteRent + ?Sized,
+    B: IoBuf + IoBufMut,
+{
+    if buf.bytes_total() == 0 {
+        let err = io::Error::new(io::ErrorKind::InvalidInput, "copy buffer has no capacity");
+        return (Err(err), buf);
+    }
+    let mut copied = 0;
+    let fail = |direction, copied, source: io::Error| {
+        let kind = source.kind();
+        let err = CopyError {
+            direction,
+            copied,
+            source,
+        };
+        Err(io::Error::new(kind, err))
+    };
+    loop {
+        let (res, read) = reader.read(buf).await;
+        buf = read;
+        let len = match res {
+            Ok(0) => return (Ok(copied), buf),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return (fail(CopyDirection::Read, copied, e), buf),
+        };
+        // The count, not the bytes initialized, some buffers keep a longer
+        // length from before.
+        let mut written = 0;
+        while written < len {
+            let (res, slice) = writer.write(buf.slice(written..len)).await;
+            buf = slice.into_inner();
+            match res {
+                Ok(0) => {
+                    let e =
+                        io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer");
+                    return (fail(CopyDirection::Write, copied, e), buf);
+                }
+                Ok(n) => {
+                    written += n;
+                    copied += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return (fail(CopyDirection::Write, copied, e), buf),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn file_to_file() {
+        let dir = std::env::temp_dir().join(format!("loop-io-copy-{}", std::process::id()));
+        std::fs::create_dir(&dir).unwrap();
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 253) as u8).collect();
+        std::fs::write(dir.join("src"), &data).unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut src = File::open(dir.join("src")).await.unwrap();
+            let mut dst = File::create(dir.join("dst")).await.unwrap();
+            assert_eq!(copy(&mut src, &mut dst).await.unwrap(), 200_000);
+            // At the end of the source already.
+            assert_eq!(copy(&mut src, &mut dst).await.unwrap(), 0);
+            assert_eq!(std::fs::read(dir.join("dst")).unwrap(), data);
+
+            // The destination is read only.
+            let mut src = File::open(dir.join("src")).await.unwrap();
+            let mut dst = File::open(dir.join("dst")).await.unwrap();
+            let err = copy(&mut src, &mut dst).await.unwrap_err();
+            let ctx = CopyError::from_io_error(&err).unwrap();
+            assert_eq!((ctx.direction, ctx.copied), (CopyDirection::Write, 0));
+            assert!(err
+                .to_string()
+                .starts_with("copy failed to write after 0 bytes"));
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn memory_round_trip() {
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i * 7) as u8).collect();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut out = Vec::new();
+            assert_eq!(copy(&mut &data[..], &mut out).await.unwrap(), 1_000_000);
+            assert_eq!(out, data);
+
+            // Many rounds through one small buffer, handed back.
+            let mut back = Vec::new();
+            let mut src = &out[..];
+            let (n, buf) = copy_with_buffer(&mut src, &mut back, Vec::with_capacity(1000)).await;
+            assert_eq!(n.unwrap(), 1_000_000);
+            assert_eq!(buf.capacity(), 1000);
+            assert_eq!(back, data);
+
+            let (res, _) = copy_with_buffer(&mut &data[..], &mut back, Vec::new()).await;
+            assert_eq!(res.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        });
+    }
+}
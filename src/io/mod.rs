@@ -0,0 +1,108 @@
+//! Traits for io on owned buffers.
+//!
+//! Like the ops themselves, the traits take buffers by value and hand them
+//! back with the result, so the kernel may fill them after the call
+//! returned. Code written against them works on files, pipes and in-memory
+//! streams alike.
+//!
+//! [`AsyncFd`] is the way out for fds the runtime has no ops for: it waits
+//! for readiness, the syscalls are made by the caller.
+
+mod async_buf_read_rent;
+mod async_fd;
+mod async_read_rent;
+mod async_write_rent;
+mod buf_reader;
+mod buf_writer;
+mod cancel;
+mod copy;
+mod prefixed;
+mod split;
+
+pub use async_buf_read_rent::{AsyncBufReadRent, AsyncBufReadRentExt, Lines};
+pub use async_fd::{AsyncFd, Interest, Ready, ReadyGuard};
+pub use async_read_rent::{AsyncReadRent, AsyncReadRentExt};
+pub use async_write_rent::{AsyncWriteRent, AsyncWriteRentExt};
+pub use buf_reader::BufReader;
+pub use buf_writer::BufWriter;
+pub(crate) use cancel::Registration;
+pub use cancel::{CancelHandle, Canceller};
+pub use copy::{copy, copy_with_buffer, CopyDirection, CopyError};
+pub use prefixed::PrefixedReader;
+pub use split::{OwnedReadHalf, OwnedWriteHalf, ReuniteError, Split};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::{IoBuf, SmallIoVec, VecBuf};
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::runtime::RuntimeBuilder;
+
+    // Copy all of `src` to `dst` through one small buffer.
+    async fn copy(mut src: impl AsyncReadRent, mut dst: impl AsyncWriteRent) -> usize {
+        let mut buf = Vec::with_capacity(4);
+        let mut copied = 0;
+        loop {
+            buf.clear();
+            let (n, read) = src.read(buf).await;
+            if n.unwrap() == 0 {
+                break;
+            }
+            let (n, written) = dst.write(read).await;
+            // Neither end takes less than it is given.
+            let n = n.unwrap();
+            assert_eq!(n, written.bytes_init());
+            copied += n;
+            buf = written;
+        }
+        dst.shutdown().await.unwrap();
+        copied
+    }
+
+    #[test]
+    fn generic_over_files_and_memory() {
+        let path = std::env::temp_dir().join(format!("loop-io-rent-{}", std::process::id()));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Memory to a file, then the file back to memory, at the position.
+            let mut file = File::create(&path).await.unwrap();
+            assert_eq!(copy(&b"rented buffers"[..], &mut file).await, 14);
+            let mut file = File::open(&path).await.unwrap();
+            let mut out = Vec::new();
+            assert_eq!(copy(&mut file, &mut out).await, 14);
+            assert_eq!(out, b"rented buffers");
+            assert_eq!(copy(&mut file, &mut out).await, 0);
+
+            // Vectored, through the position as well.
+            let mut file = File::create(&path).await.unwrap();
+            let bufs = VecBuf::new(vec![b"one ".to_vec(), b"two".to_vec()]);
+            let (n, _) = file.writev(bufs).await;
+            assert_eq!(n.unwrap(), 7);
+            let (n, _) = AsyncWriteRent::write(&mut file, " three").await;
+            assert_eq!(n.unwrap(), 6);
+            file.flush().await.unwrap();
+
+            let mut src: &[u8] = &std::fs::read(&path).unwrap();
+            let mut bufs = SmallIoVec::<Vec<u8>, 2>::new();
+            bufs.push(Vec::with_capacity(4)).unwrap();
+            bufs.push(Vec::with_capacity(32)).unwrap();
+            let (n, bufs) = src.readv(bufs).await;
+            assert_eq!(n.unwrap(), 13);
+            assert!(src.is_empty());
+            let mut out = Vec::new();
+            let (n, _) = out.writev(bufs).await;
+            assert_eq!(n.unwrap(), 13);
+            assert_eq!(out, b"one two three");
+
+            let mut file = File::open(&path).await.unwrap();
+            let mut bufs = SmallIoVec::<Vec<u8>, 2>::new();
+            bufs.push(Vec::with_capacity(8)).unwrap();
+            bufs.push(Vec::with_capacity(8)).unwrap();
+            let (n, bufs) = file.readv(bufs).await;
+            assert_eq!(n.unwrap(), 13);
+            assert_eq!(bufs.bufs()[1], b"three");
+        });
+        std::fs::remove_file(&path).unwrap();
+    }
+}
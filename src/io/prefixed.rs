@@ -0,0 +1,162 @@
+use super::{AsyncReadRent, AsyncWriteRent};
+use crate::buf::{BufResult, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use std::io;
+
+/// Serves `prefix` before the bytes of `R`, to put back what was read to
+/// sniff a protocol.
+///
+/// A read that reaches the end of the prefix returns the prefix part
+/// alone, it never waits on `R` with bytes in hand. Writes go straight to
+/// `R`, so a sniffed stream stays usable both ways.
+pub struct PrefixedReader<R> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: R,
+}
+
+impl<R> PrefixedReader<R> {
+    pub fn new(prefix: Vec<u8>, inner: R) -> Self {
+        PrefixedReader {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+
+    /// The part of the prefix not read yet.
+    pub fn prefix(&self) -> &[u8] {
+        &self.prefix[self.pos..]
+    }
+
+    /// The reader underneath. Reading from it directly skips the prefix.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The reader underneath. Reading from it directly skips the prefix.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Give back the part of the prefix not read yet, and the reader.
+    pub fn into_parts(mut self) -> (Vec<u8>, R) {
+        self.prefix.drain(..self.pos);
+        (self.prefix, self.inner)
+    }
+
+    // Account for `n` bytes read from the prefix, freeing it once used up.
+    fn advance(&mut self, n: usize) {
+        self.pos += n;
+        if self.pos == self.prefix.len() {
+            self.prefix = Vec::new();
+            self.pos = 0;
+        }
+    }
+}
+
+impl<R: AsyncReadRent> AsyncReadRent for PrefixedReader<R> {
+    async fn read<T: IoBufMut>(&mut self, buf: T) -> BufResult<usize, T> {
+        if self.prefix().is_empty() {
+            return self.inner.read(buf).await;
+        }
+        let (n, buf) = (&mut self.prefix()).read(buf).await;
+        if let Ok(n) = n {
+            self.advance(n);
+        }
+        (n, buf)
+    }
+
+    async fn readv<T: IoVecBufMut>(&mut self, buf: T) -> BufResult<usize, T> {
+        if self.prefix().is_empty() {
+            return self.inner.readv(buf).await;
+        }
+        let (n, buf) = (&mut self.prefix()).readv(buf).await;
+        if let Ok(n) = n {
+            self.advance(n);
+        }
+        (n, buf)
+    }
+}
+
+impl<R: AsyncWriteRent> AsyncWriteRent for PrefixedReader<R> {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        self.inner.write(buf).await
+    }
+
+    async fn writev<T: IoVecBuf>(&mut self, buf: T) -> BufResult<usize, T> {
+        self.inner.writev(buf).await
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.inner.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::SmallIoVec;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::runtime::RuntimeBuilder;
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    #[test]
+    fn sniffed_bytes_are_read_again() {
+        let mut fds = [0; 2];
+        let flags = libc::SOCK_STREAM | libc::SOCK_CLOEXEC;
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()) },
+            0
+        );
+        let (mut a, mut b) = unsafe {
+            (
+                File::from(OwnedFd::from_raw_fd(fds[0])),
+                File::from(OwnedFd::from_raw_fd(fds[1])),
+            )
+        };
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            a.write_all("GET / HTTP/1.1\r\n").await.0.unwrap();
+            let (n, magic) = b.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(magic, b"GET ");
+
+            // The first read ends with the prefix, the next ones reach the
+            // stream.
+            let mut stream = PrefixedReader::new(magic, b);
+            let (n, buf) = stream.read(Vec::with_capacity(64)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"GET ");
+            assert!(stream.prefix().is_empty());
+            let (n, buf) = stream.read_exact(Vec::with_capacity(12)).await;
+            assert_eq!(n.unwrap(), 12);
+            assert_eq!(buf, b"/ HTTP/1.1\r\n");
+
+            // Vectored and partial reads of the prefix, then the rest.
+            let mut stream = PrefixedReader::new(b"abc".to_vec(), stream.into_parts().1);
+            a.write_all("def").await.0.unwrap();
+            let mut bufs = SmallIoVec::<Vec<u8>, 2>::new();
+            bufs.push(Vec::with_capacity(1)).unwrap();
+            bufs.push(Vec::with_capacity(1)).unwrap();
+            let (n, bufs) = stream.readv(bufs).await;
+            assert_eq!(n.unwrap(), 2);
+            assert_eq!(bufs.bufs()[1], b"b");
+            assert_eq!(stream.prefix(), b"c");
+            let (n, buf) = stream.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"cdef");
+
+            // Writes reach the other end.
+            stream.write_all("pong").await.0.unwrap();
+            let (n, buf) = a.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"pong");
+        });
+    }
+}
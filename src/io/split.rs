@@ -0,0 +1,253 @@
+use super::{AsyncReadRent, AsyncWriteRent};
+use crate::buf::{BufResult, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+
+/// A stream whose reads and writes may be in flight at the same time, so it
+/// can be split into halves owned by different tasks.
+///
+/// The halves share the stream, they go through the methods here, which
+/// take it by shared reference. Reads and writes must touch disjoint state,
+/// like ops on one descriptor do.
+pub trait Split: AsyncReadRent + AsyncWriteRent + Sized {
+    /// Split into a read half and a write half, see [`OwnedReadHalf::reunite`]
+    /// for putting them back together.
+    fn split(self) -> (OwnedReadHalf<Self>, OwnedWriteHalf<Self>) {
+        let stream = Rc::new(self);
+        (OwnedReadHalf(stream.clone()), OwnedWriteHalf(stream))
+    }
+
+    /// Read into `buf`, like [`AsyncReadRent::read`].
+    fn read_shared<B: IoBufMut>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>>;
+
+    /// Read into the buffers of `buf`, like [`AsyncReadRent::readv`].
+    fn readv_shared<B: IoVecBufMut>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>>;
+
+    /// Write from `buf`, like [`AsyncWriteRent::write`].
+    fn write_shared<B: IoBuf>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>>;
+
+    /// Write from the buffers of `buf`, like [`AsyncWriteRent::writev`].
+    fn writev_shared<B: IoVecBuf>(&self, buf: B) -> impl Future<Output = BufResult<usize, B>>;
+
+    /// Flush, like [`AsyncWriteRent::flush`]. Does nothing by default, for
+    /// streams that buffer no writes.
+    fn flush_shared(&self) -> impl Future<Output = io::Result<()>> {
+        std::future::ready(Ok(()))
+    }
+
+    /// Tell the other end nothing more is written, for the shutdown of the
+    /// write half and when it is dropped. Does nothing by default, sockets
+    /// shut down their write side.
+    fn shutdown_write(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The read half of a [`Split`] stream.
+pub struct OwnedReadHalf<T>(Rc<T>);
+
+/// The write half of a [`Split`] stream. Dropping it shuts down the write
+/// side, see [`Split::shutdown_write`].
+pub struct OwnedWriteHalf<T: Split>(Rc<T>);
+
+impl<T: Split> OwnedReadHalf<T> {
+    /// Put the halves back together into the stream. Fails, handing both
+    /// back, if they were not split from the same stream.
+    pub fn reunite(self, other: OwnedWriteHalf<T>) -> Result<T, ReuniteError<T>> {
+        if !Rc::ptr_eq(&self.0, &other.0) {
+            return Err(ReuniteError(self, other));
+        }
+        // Reuniting is no shutdown, skip the drop of the write half.
+        let other = ManuallyDrop::new(other);
+        drop(unsafe { std::ptr::read(&other.0) });
+        Ok(Rc::try_unwrap(self.0).unwrap_or_else(|_| unreachable!("both halves given")))
+    }
+}
+
+impl<T: Split> OwnedWriteHalf<T> {
+    /// Put the halves back together, like [`OwnedReadHalf::reunite`].
+    pub fn reunite(self, other: OwnedReadHalf<T>) -> Result<T, ReuniteError<T>> {
+        other.reunite(self)
+    }
+}
+
+impl<T: Split> Drop for OwnedWriteHalf<T> {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.shutdown_write() {
+            log::warn!("failed to shut down the write half: {e}");
+        }
+    }
+}
+
+impl<T: Split> AsyncReadRent for OwnedReadHalf<T> {
+    fn read<B: IoBufMut>(&mut self, buf: B) -> impl Future<Output = BufResult<usize, B>> {
+        self.0.read_shared(buf)
+    }
+
+    fn readv<B: IoVecBufMut>(&mut self, buf: B) -> impl Future<Output = BufResult<usize, B>> {
+        self.0.readv_shared(buf)
+    }
+}
+
+impl<T: Split> AsyncWriteRent for OwnedWriteHalf<T> {
+    fn write<B: IoBuf>(&mut self, buf: B) -> impl Future<Output = BufResult<usize, B>> {
+        self.0.write_shared(buf)
+    }
+
+    fn writev<B: IoVecBuf>(&mut self, buf: B) -> impl Future<Output = BufResult<usize, B>> {
+        self.0.writev_shared(buf)
+    }
+
+    fn flush(&mut self) -> impl Future<Output = io::Result<()>> {
+        self.0.flush_shared()
+    }
+
+    fn shutdown(&mut self) -> impl Future<Output = io::Result<()>> {
+        std::future::ready(self.0.shutdown_write())
+    }
+}
+
+/// Halves of different streams were to be reunited, both are handed back.
+pub struct ReuniteError<T: Split>(pub OwnedReadHalf<T>, pub OwnedWriteHalf<T>);
+
+impl<T: Split> fmt::Debug for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish()
+    }
+}
+
+impl<T: Split> fmt::Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves of different streams")
+    }
+}
+
+impl<T: Split> std::error::Error for ReuniteError<T> {}
+
+impl<T> fmt::Debug for OwnedReadHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedReadHalf").finish_non_exhaustive()
+    }
+}
+
+impl<T: Split> fmt::Debug for OwnedWriteHalf<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedWriteHalf").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::File;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::runtime::{spawn, RuntimeBuilder};
+    use std::future::poll_fn;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::pin::pin;
+    use std::task::Poll;
+
+    fn socketpair() -> (File, File) {
+        let mut fds = [0; 2];
+        let flags = libc::SOCK_STREAM | libc::SOCK_CLOEXEC;
+        assert_eq!(
+            unsafe { libc::socketpair(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()) },
+            0
+        );
+        unsafe {
+            (
+                File::from(OwnedFd::from_raw_fd(fds[0])),
+                File::from(OwnedFd::from_raw_fd(fds[1])),
+            )
+        }
+    }
+
+    #[test]
+    fn echo_both_ways_at_once() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, b) = socketpair();
+            let (mut a_read, mut a_write) = a.split();
+            let (mut b_read, mut b_write) = b.split();
+            // More than the socket buffers hold, so both sides must run.
+            let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+
+            // `b` echoes everything back while `a` is still sending.
+            let echo =
+                spawn(async move { crate::io::copy(&mut b_read, &mut b_write).await.unwrap() });
+            let send = spawn({
+                let data = data.clone();
+                async move {
+                    a_write.write_all(data).await.0.unwrap();
+                    a_write
+                }
+            });
+            let (n, echoed) = a_read.read_exact(Vec::with_capacity(data.len())).await;
+            assert_eq!(n.unwrap(), data.len());
+            assert_eq!(echoed, data);
+
+            // Dropping the write half ends the echo, which drops its own.
+            drop(send.await);
+            assert_eq!(echo.await, data.len() as u64);
+            let (n, _) = a_read.read(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn read_and_write_in_flight_together() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, b) = socketpair();
+            let (mut a_read, mut a_write) = a.split();
+            let mut b = b;
+
+            // A read waits on `a` while it writes and its write half drops,
+            // both halves hold the stream at once.
+            let mut read = pin!(a_read.read(Vec::with_capacity(4)));
+            let pending = poll_fn(|cx| Poll::Ready(read.as_mut().poll(cx).is_pending())).await;
+            assert!(pending);
+            let (n, _) = a_write.write("ping").await;
+            assert_eq!(n.unwrap(), 4);
+            a_write.shutdown().await.unwrap();
+            drop(a_write);
+
+            let (n, buf) = b.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"ping");
+            let (n, _) = b.read(Vec::with_capacity(1)).await;
+            assert_eq!(n.unwrap(), 0);
+
+            b.write_all("pong").await.0.unwrap();
+            let (n, buf) = read.await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"pong");
+        });
+    }
+
+    #[test]
+    fn reunite() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, b) = socketpair();
+            let (a_read, a_write) = a.split();
+            let (b_read, b_write) = b.split();
+            let Err(ReuniteError(a_read, b_write)) = a_read.reunite(b_write) else {
+                panic!("halves of different streams");
+            };
+            let mut b = b_write.reunite(b_read).unwrap();
+            let mut a = a_read.reunite(a_write).unwrap();
+
+            // Reuniting shut nothing down.
+            b.write_all("still open").await.0.unwrap();
+            let (n, buf) = a.read_exact(Vec::with_capacity(10)).await;
+            assert_eq!(n.unwrap(), 10);
+            assert_eq!(buf, b"still open");
+            drop(b);
+        });
+    }
+}
@@ -1,29 +1,43 @@
+//! An io_uring based async runtime.
+//!
+//! Everything runs on the thread that built the [`Runtime`], io goes through
+//! the ring of that thread. Most programs only need the [`prelude`].
+
+// The crate is called `Loop`.
+#![allow(non_snake_case)]
+
+pub mod buf;
+#[cfg(any(feature = "compat", feature = "futures-compat"))]
+pub mod compat;
+pub mod driver;
+pub mod fs;
+pub mod future;
+pub mod io;
+pub mod macros;
+pub mod net;
+pub mod prelude;
+pub mod runtime;
+pub mod signal;
+pub mod stream;
 mod task;
+pub mod time;
 mod utils;
-mod runtime;
-pub mod macros;
-mod driver;
-mod fs;
 
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+pub use buf::BufResult;
+pub use runtime::{spawn, spawn_blocking, Runtime, RuntimeBuilder};
+pub use task::JoinHandle;
 
 #[cfg(test)]
 mod tests {
-    use super::*;
+    use crate::prelude::*;
 
     #[test]
     fn it_works() {
-        let mut rt = runtime::builder::RuntimeBuilder::<driver::IoUringDriver>::new()
-            .build()
-            .unwrap();
-        rt.block_on(async {
-            runtime::runtime::spawn(async  {
-                println!("it works 0");
-            });
-            println!("it works1!")
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let out = rt.block_on(async {
+            let task = spawn(async { 1 });
+            task.await + 1
         });
-
+        assert_eq!(out, 2);
     }
 }
@@ -0,0 +1,59 @@
+/// Take the value out of a [`BufResult`](crate::BufResult), or return the
+/// error from the enclosing function together with the buffer.
+///
+/// `buf_try!(res)` evaluates to the `(value, buf)` pair on success. The
+/// enclosing function has to return a `BufResult` itself, the error goes
+/// through `Into`. When the buffer comes back as a different type than the
+/// function returns, like a [`SliceMut`](crate::buf::SliceMut) of it, the
+/// second argument maps it before returning: `buf_try!(res, |buf| buf.into_inner())`.
+///
+/// # Examples
+///
+/// ```
+/// use Loop::buf::IoBufMut;
+/// use Loop::prelude::*;
+/// use Loop::{buf_try, BufResult};
+///
+/// // Fill the spare capacity of `buf` with up to two reads.
+/// async fn read_twice(file: &File, buf: Vec<u8>) -> BufResult<usize, Vec<u8>> {
+///     let (first, buf) = buf_try!(file.read_at(buf, 0).await);
+///     let rest = buf.slice_mut(first..);
+///     let (second, rest) = buf_try!(file.read_at(rest, first as u64).await, |rest| {
+///         rest.into_inner()
+///     });
+///     (Ok(first + second), rest.into_inner())
+/// }
+///
+/// let path = std::env::temp_dir().join(format!("loop-buf-try-{}", std::process::id()));
+/// std::fs::write(&path, b"some bytes").unwrap();
+/// let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+/// rt.block_on(async {
+///     let file = File::open(&path).await.unwrap();
+///     let (n, buf) = read_twice(&file, Vec::with_capacity(64)).await;
+///     assert_eq!(n.unwrap(), 10);
+///     assert_eq!(buf, b"some bytes");
+///
+///     // A failed read hands the buffer back.
+///     drop(file);
+///     let file = File::create(&path).await.unwrap();
+///     let (res, buf) = read_twice(&file, Vec::with_capacity(64)).await;
+///     assert!(res.is_err());
+///     assert_eq!(buf.capacity(), 64);
+/// });
+/// std::fs::remove_file(&path).unwrap();
+/// ```
+#[macro_export]
+macro_rules! buf_try {
+    ($e:expr $(,)?) => {
+        match $e {
+            (Ok(value), buf) => (value, buf),
+            (Err(e), buf) => return (Err(e.into()), buf),
+        }
+    };
+    ($e:expr, $map:expr $(,)?) => {
+        match $e {
+            (Ok(value), buf) => (value, buf),
+            (Err(e), buf) => return (Err(e.into()), $crate::macros::support::map_buf(buf, $map)),
+        }
+    };
+}
@@ -1,8 +1,10 @@
+#[allow(unused_macros)]
 #[cfg(all(debug_assertions, feature = "debug"))]
 macro_rules! trace {
-    ($( $args:expr ),*) => { tracing::trace!( $( $args ),* ); }
+    ($( $args:expr ),*) => { log::trace!( $( $args ),* ); }
 }
 
+#[allow(unused_macros)]
 #[cfg(not(all(debug_assertions, feature = "debug")))]
 macro_rules! trace {
     ($( $args:expr ),*) => {};
@@ -11,7 +13,7 @@ macro_rules! trace {
 #[allow(unused_macros)]
 #[cfg(all(debug_assertions, feature = "debug"))]
 macro_rules! info {
-    ($( $args:expr ),*) => { tracing::info!( $( $args ),* ); }
+    ($( $args:expr ),*) => { log::info!( $( $args ),* ); }
 }
 
 #[allow(unused_macros)]
@@ -7,17 +7,17 @@
 /// The `join!` macro takes a list of async expressions and evaluates them
 /// concurrently on the same task. Each async expression evaluates to a future
 /// and the futures from each expression are multiplexed on the current task.
+/// The outputs come back as a tuple in the order of the branches.
 ///
 /// When working with async expressions returning `Result`, `join!` will wait
-/// for **all** branches complete regardless if any complete with `Err`. Use
-/// [`try_join!`] to return early when `Err` is encountered.
-///
-/// [`try_join!`]: macro@try_join
+/// for **all** branches complete regardless if any complete with `Err`.
 ///
 /// # Notes
 ///
-/// The supplied futures are stored inline and does not require allocating a
-/// `Vec`.
+/// The supplied futures and their outputs are stored inline, `join!` never
+/// allocates. Each poll starts with the branch after the one that went first
+/// last time, so a busy branch cannot starve the others. For a number of
+/// futures only known at runtime, see [`join_all`](crate::future::join_all).
 ///
 /// ### Runtime characteristics
 ///
@@ -25,16 +25,14 @@
 /// able to run **concurrently** but not in **parallel**. This means all
 /// expressions are run on the same thread and if one branch blocks the thread,
 /// all other expressions will be unable to continue. If parallelism is
-/// required, spawn each async expression using [`monoio::spawn`] and pass the
-/// join handle to `join!`.
-///
-/// [`monoio::spawn`]: crate::spawn
+/// required, spawn each async expression and pass the join handles to
+/// `join!`.
 ///
 /// # Examples
 ///
 /// Basic join with two branches
 ///
-/// ```
+/// ```ignore
 /// async fn do_stuff_async() {
 ///     // async work
 /// }
@@ -43,15 +41,9 @@
 ///     // more here
 /// }
 ///
-/// #[monoio::main]
-/// async fn main() {
-///     let (first, second) = monoio::join!(do_stuff_async(), more_async_work());
-///
-///     // do something with the values
-/// }
+/// let (first, second) = Loop::join!(do_stuff_async(), more_async_work());
 /// ```
 #[macro_export]
-#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
 macro_rules! join {
     (@ {
         // One `_` for each branch in the `join!` macro. This is not used once
@@ -69,22 +61,41 @@ macro_rules! join {
         // the requirement of `Pin::new_unchecked` called below.
         let mut futures = ( $( maybe_done($e), )* );
 
+        const COUNT: u32 = 0 $( + { let _ = stringify!($e); 1 } )*;
+        // Branches to skip at the start of the next poll.
+        let mut skip_next_time: u32 = 0;
+
         poll_fn(move |cx| {
             let mut is_pending = false;
+            let mut to_run = COUNT;
+            let mut skip = skip_next_time;
+            skip_next_time = if skip + 1 == COUNT { 0 } else { skip + 1 };
+
+            // Wraps around until every branch was polled once.
+            loop {
+                $(
+                    if skip == 0 {
+                        if to_run == 0 {
+                            break;
+                        }
+                        to_run -= 1;
 
-            $(
-                // Extract the future for this branch from the tuple.
-                let ( $($skip,)* fut, .. ) = &mut futures;
+                        // Extract the future for this branch from the tuple.
+                        let ( $($skip,)* fut, .. ) = &mut futures;
 
-                // Safety: future is stored on the stack above
-                // and never moved.
-                let fut = unsafe { Pin::new_unchecked(fut) };
+                        // Safety: future is stored on the stack above
+                        // and never moved.
+                        let fut = unsafe { Pin::new_unchecked(fut) };
 
-                // Try polling
-                if fut.poll(cx).is_pending() {
-                    is_pending = true;
-                }
-            )*
+                        // Done branches return right away.
+                        if fut.poll(cx).is_pending() {
+                            is_pending = true;
+                        }
+                    } else {
+                        skip -= 1;
+                    }
+                )*
+            }
 
             if is_pending {
                 Pending
@@ -111,7 +122,100 @@ macro_rules! join {
 
     // ===== Entry point =====
 
-    ( $($e:expr),* $(,)?) => {
+    () => { async {}.await };
+
+    ( $($e:expr),+ $(,)?) => {
         $crate::join!(@{ () } $($e,)*)
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::alloc_counter;
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let mut fut = std::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    // Pending on its first `n` polls, logging each one.
+    async fn yields(name: &'static str, n: usize, log: Rc<RefCell<Vec<&'static str>>>) -> usize {
+        let mut left = n;
+        std::future::poll_fn(|cx| {
+            log.borrow_mut().push(name);
+            if left == 0 {
+                return Poll::Ready(());
+            }
+            left -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        })
+        .await;
+        n
+    }
+
+    #[test]
+    fn forms() {
+        block_on(async {
+            assert_eq!(crate::join!(async { 1 }), (1,));
+            assert_eq!(crate::join!(async { 1 }, async { "two" },), (1, "two"));
+            #[allow(clippy::let_unit_value)]
+            let () = crate::join!();
+        });
+    }
+
+    #[test]
+    fn outputs_in_branch_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let out = block_on(async {
+            crate::join!(
+                yields("a", 3, log.clone()),
+                yields("b", 0, log.clone()),
+                yields("c", 1, log.clone()),
+            )
+        });
+        assert_eq!(out, (3, 0, 1));
+    }
+
+    #[test]
+    fn round_robin() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        block_on(async {
+            crate::join!(
+                yields("a", 2, log.clone()),
+                yields("b", 2, log.clone()),
+                yields("c", 2, log.clone()),
+            )
+        });
+        // A different branch goes first on every poll, done ones are skipped.
+        assert_eq!(*log.borrow(), ["a", "b", "c", "b", "c", "a", "c", "a", "b"]);
+    }
+
+    #[test]
+    fn no_allocation() {
+        let before = alloc_counter::snapshot();
+        let out = block_on(async {
+            crate::join!(
+                async { 1u8 },
+                async { 2u16 },
+                async { 3u32 },
+                async { 4u64 },
+                async { [5u8; 64] },
+                async { "six" },
+                async { 7i8 },
+                async { 8usize },
+            )
+        });
+        assert_eq!(alloc_counter::snapshot().since(before).allocs, 0);
+        assert_eq!(out.7, 8);
+    }
+}
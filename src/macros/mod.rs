@@ -9,11 +9,14 @@ mod pin;
 #[macro_use]
 mod ready;
 
-
 #[macro_use]
 mod join;
 
+#[macro_use]
+mod buf_try;
 
+#[doc(hidden)]
+pub mod support;
 
 #[macro_use]
 mod debug;
@@ -24,7 +24,7 @@
 ///
 /// To make this work requires pinning:
 ///
-/// ```
+/// ```ignore
 /// use monoio::pin;
 ///
 /// async fn my_async_fn() {
@@ -68,7 +68,7 @@
 /// Because assigning to a variable followed by pinning is common, there is also
 /// a variant of the macro that supports doing both in one go.
 ///
-/// ```
+/// ```ignore
 /// use monoio::{pin, select};
 ///
 /// async fn my_async_fn() {
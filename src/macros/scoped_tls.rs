@@ -62,12 +62,20 @@ impl<T> ScopedKey<T> {
     /// Inserts a value into this scoped thread local storage slot for a
     /// duration of a closure.
     ///
-    /// While `cb` is running, the value `t` will be returned by `get` unless
-    /// this function is called recursively inside of `cb`.
+    /// While `f` is running, the value `t` will be returned by `with` unless
+    /// this function is called recursively inside of `f`.
     ///
-    /// Upon return, this function will restore the previous value, if any
-    /// was available.
+    /// Nesting is supported: upon return, also by unwinding, this function
+    /// restores the previous value, if any was available.
     pub fn set<F, R>(&'static self, t: &T, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.replace_during(Some(t), f)
+    }
+
+    /// Like [`set`](Self::set), but `None` leaves the slot empty while `f` runs.
+    pub fn replace_during<F, R>(&'static self, t: Option<&T>, f: F) -> R
     where
         F: FnOnce() -> R,
     {
@@ -80,11 +88,8 @@ impl<T> ScopedKey<T> {
                 self.key.with(|c| c.set(self.val));
             }
         }
-        let prev = self.inner.with(|c| {
-            let prev = c.get();
-            c.set(t as *const T as *const ());
-            prev
-        });
+        let val = t.map_or(std::ptr::null(), |t| t as *const T as *const ());
+        let prev = self.inner.with(|c| c.replace(val));
         let _reset = Reset {
             key: self.inner,
             val: prev,
@@ -108,16 +113,17 @@ impl<T> ScopedKey<T> {
         unsafe { f(&*(val as *const T)) }
     }
 
-    /// Gets a value out of this scoped variable.
-    pub fn try_with<F, R>(&'static self, f: F) -> R
+    /// Like [`with`](Self::with), but returns `None` instead of panicking
+    /// when the variable is not set.
+    pub fn try_with<F, R>(&'static self, f: F) -> Option<R>
     where
-        F: FnOnce(Option<&T>) -> R,
+        F: FnOnce(&T) -> R,
     {
         let val = self.inner.with(|c| c.get());
         if val.is_null() {
-            f(None)
+            None
         } else {
-            unsafe { f(Some(&*(val as *const T))) }
+            unsafe { Some(f(&*(val as *const T))) }
         }
     }
 
@@ -127,3 +133,42 @@ impl<T> ScopedKey<T> {
         self.inner.with(|c| !c.get().is_null())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    crate::scoped_thread_local!(static KEY: u32);
+
+    #[test]
+    fn try_with_unset() {
+        assert!(!KEY.is_set());
+        assert_eq!(KEY.try_with(|v| *v), None);
+        assert_eq!(KEY.set(&1, || KEY.try_with(|v| *v)), Some(1));
+    }
+
+    #[test]
+    fn nested_set_restores() {
+        KEY.set(&1, || {
+            KEY.set(&2, || {
+                assert_eq!(KEY.with(|v| *v), 2);
+                KEY.replace_during(None, || assert!(!KEY.is_set()));
+                assert_eq!(KEY.with(|v| *v), 2);
+            });
+            assert_eq!(KEY.with(|v| *v), 1);
+        });
+        assert!(!KEY.is_set());
+    }
+
+    #[test]
+    fn restores_on_unwind() {
+        KEY.set(&1, || {
+            let res = catch_unwind(AssertUnwindSafe(|| {
+                KEY.set(&2, || panic!("boom"));
+            }));
+            assert!(res.is_err());
+            assert_eq!(KEY.with(|v| *v), 1);
+        });
+        assert!(!KEY.is_set());
+    }
+}
@@ -0,0 +1,13 @@
+//! Items the exported macros expand to.
+
+pub use crate::future::maybe_done;
+pub use std::future::{poll_fn, Future};
+pub use std::pin::Pin;
+pub use std::task::Poll;
+
+/// Map the buffer returned by `buf_try!`, a call so a closure infers its
+/// argument.
+#[inline]
+pub fn map_buf<B, R>(buf: B, map: impl FnOnce(B) -> R) -> R {
+    map(buf)
+}
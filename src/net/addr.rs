@@ -0,0 +1,120 @@
+//! Socket addresses in the layout the kernel takes and returns.
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+/// An address of any family with its length, as the kernel takes it.
+pub(crate) type RawAddr = (libc::sockaddr_storage, libc::socklen_t);
+
+/// `addr` as a `sockaddr_in` or `sockaddr_in6`, with its length.
+pub(crate) fn to_raw(addr: &SocketAddr) -> RawAddr {
+    // Zeroed is a valid sockaddr_storage, the unused bytes stay zero.
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let raw = libc::sockaddr_in {
+                sin_family: libc::AF_INET as libc::sa_family_t,
+                sin_port: addr.port().to_be(),
+                sin_addr: libc::in_addr {
+                    s_addr: u32::from_ne_bytes(addr.ip().octets()),
+                },
+                sin_zero: [0; 8],
+            };
+            unsafe {
+                (&mut storage as *mut libc::sockaddr_storage)
+                    .cast::<libc::sockaddr_in>()
+                    .write(raw)
+            };
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(addr) => {
+            let raw = libc::sockaddr_in6 {
+                sin6_family: libc::AF_INET6 as libc::sa_family_t,
+                sin6_port: addr.port().to_be(),
+                sin6_flowinfo: addr.flowinfo(),
+                sin6_addr: libc::in6_addr {
+                    s6_addr: addr.ip().octets(),
+                },
+                sin6_scope_id: addr.scope_id(),
+            };
+            unsafe {
+                (&mut storage as *mut libc::sockaddr_storage)
+                    .cast::<libc::sockaddr_in6>()
+                    .write(raw)
+            };
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// The address the kernel wrote into `storage`, `len` bytes of it.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] for other families and for
+/// lengths too short for the family.
+pub(crate) fn from_raw(
+    storage: &libc::sockaddr_storage,
+    len: libc::socklen_t,
+) -> io::Result<SocketAddr> {
+    let len = len as usize;
+    let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+    if len < mem::size_of::<libc::sa_family_t>() {
+        return Err(invalid(format!("socket address of {len} bytes")));
+    }
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET if len >= mem::size_of::<libc::sockaddr_in>() => {
+            // The family says it is one, and it is long enough.
+            let raw =
+                unsafe { &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in>() };
+            let ip = Ipv4Addr::from(raw.sin_addr.s_addr.to_ne_bytes());
+            Ok(SocketAddr::V4(SocketAddrV4::new(
+                ip,
+                u16::from_be(raw.sin_port),
+            )))
+        }
+        libc::AF_INET6 if len >= mem::size_of::<libc::sockaddr_in6>() => {
+            let raw = unsafe {
+                &*(storage as *const libc::sockaddr_storage).cast::<libc::sockaddr_in6>()
+            };
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(raw.sin6_addr.s6_addr),
+                u16::from_be(raw.sin6_port),
+                raw.sin6_flowinfo,
+                raw.sin6_scope_id,
+            )))
+        }
+        family @ (libc::AF_INET | libc::AF_INET6) => Err(invalid(format!(
+            "socket address of family {family} with only {len} bytes"
+        ))),
+        family => Err(invalid(format!(
+            "socket address of unsupported family {family}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for addr in ["127.0.0.1:8080", "[::1]:443", "[fe80::1%2]:53"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            let (storage, len) = to_raw(&addr);
+            assert_eq!(from_raw(&storage, len).unwrap(), addr);
+        }
+        let (storage, len) = to_raw(&"[::1]:1".parse().unwrap());
+        assert_eq!(len as usize, mem::size_of::<libc::sockaddr_in6>());
+        let err = from_raw(&storage, len - 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(from_raw(&storage, 0).is_err());
+
+        // A unix address is no socket address of std.
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        storage.ss_family = libc::AF_UNIX as libc::sa_family_t;
+        let len = mem::size_of::<libc::sockaddr_un>() as libc::socklen_t;
+        let err = from_raw(&storage, len).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,291 @@
+//! Control messages, the ancillary data of `sendmsg` and `recvmsg`.
+//!
+//! The data is a run of `cmsghdr`s, each padded to the alignment of
+//! `size_t`. Offsets are counted from the start of the buffer and headers
+//! are read and written unaligned, so any `Vec<u8>` can hold them.
+
+use super::unix::UCred;
+use std::mem;
+use std::net::Ipv4Addr;
+use std::os::fd::{AsRawFd, BorrowedFd, RawFd};
+
+const HEADER: usize = mem::size_of::<libc::cmsghdr>();
+
+// The CMSG_ALIGN of the kernel headers.
+const fn align(len: usize) -> usize {
+    let to = mem::size_of::<usize>();
+    (len + to - 1) & !(to - 1)
+}
+
+/// Room for a control message carrying `len` bytes, `CMSG_SPACE`. Add them
+/// up for the capacity of a receive buffer.
+pub const fn space(len: usize) -> usize {
+    align(HEADER) + align(len)
+}
+
+/// Builds the control data of a message to send.
+#[derive(Clone, Debug, Default)]
+pub struct CmsgBuilder {
+    buf: Vec<u8>,
+}
+
+impl CmsgBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a message of `level` and `ty` carrying `data`.
+    pub fn push(mut self, level: i32, ty: i32, data: &[u8]) -> Self {
+        let start = self.buf.len();
+        self.buf.resize(start + space(data.len()), 0);
+        let header = libc::cmsghdr {
+            // CMSG_LEN, the padding after the data is not counted.
+            cmsg_len: align(HEADER) + data.len(),
+            cmsg_level: level,
+            cmsg_type: ty,
+        };
+        // In bounds, the buffer just grew by the space of the message.
+        unsafe {
+            let at = self.buf.as_mut_ptr().add(start);
+            at.cast::<libc::cmsghdr>().write_unaligned(header);
+        }
+        let data_start = start + align(HEADER);
+        self.buf[data_start..data_start + data.len()].copy_from_slice(data);
+        self
+    }
+
+    /// Pass `fds` to the receiver, `SCM_RIGHTS`. It gets new descriptors of
+    /// the same files, these stay open here.
+    pub fn rights(self, fds: &[BorrowedFd<'_>]) -> Self {
+        let data: Vec<u8> = fds
+            .iter()
+            .flat_map(|fd| fd.as_raw_fd().to_ne_bytes())
+            .collect();
+        self.push(libc::SOL_SOCKET, libc::SCM_RIGHTS, &data)
+    }
+
+    /// Send from the interface `ifindex` (0 for any) with source address
+    /// `source` (unspecified for the default), `IP_PKTINFO`.
+    pub fn pktinfo(self, ifindex: u32, source: Ipv4Addr) -> Self {
+        let info = libc::in_pktinfo {
+            ipi_ifindex: ifindex as libc::c_int,
+            ipi_spec_dst: libc::in_addr {
+                s_addr: u32::from_ne_bytes(source.octets()),
+            },
+            ipi_addr: libc::in_addr { s_addr: 0 },
+        };
+        // A plain struct of integers, every byte is initialized.
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                (&info as *const libc::in_pktinfo).cast::<u8>(),
+                mem::size_of::<libc::in_pktinfo>(),
+            )
+        };
+        self.push(libc::IPPROTO_IP, libc::IP_PKTINFO, data)
+    }
+
+    /// Send as the process `cred`, `SCM_CREDENTIALS`. Anything but
+    /// [`UCred::current`] takes privileges. The receiver sees them with
+    /// `SO_PASSCRED` set.
+    pub fn credentials(self, cred: UCred) -> Self {
+        let raw = cred.to_raw();
+        // A plain struct of integers, every byte is initialized.
+        let data = unsafe {
+            std::slice::from_raw_parts(
+                (&raw as *const libc::ucred).cast::<u8>(),
+                mem::size_of::<libc::ucred>(),
+            )
+        };
+        self.push(libc::SOL_SOCKET, libc::SCM_CREDENTIALS, data)
+    }
+
+    /// The control data.
+    pub fn build(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// One control message, from [`Cmsgs`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cmsg<'a> {
+    pub level: i32,
+    pub ty: i32,
+    pub data: &'a [u8],
+}
+
+/// Where a datagram was received, from `IP_PKTINFO`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PktInfo {
+    /// The interface it came in on.
+    pub ifindex: u32,
+    /// The local address it was routed to.
+    pub local: Ipv4Addr,
+    /// The destination address in its header.
+    pub dst: Ipv4Addr,
+}
+
+impl Cmsg<'_> {
+    /// The descriptors of an `SCM_RIGHTS` message. They were opened for the
+    /// receiver, who owns them now: wrap each in an `OwnedFd` exactly once,
+    /// or they leak.
+    pub fn rights(&self) -> Option<Vec<RawFd>> {
+        if (self.level, self.ty) != (libc::SOL_SOCKET, libc::SCM_RIGHTS) {
+            return None;
+        }
+        let fds = self.data.chunks_exact(mem::size_of::<RawFd>());
+        Some(
+            fds.map(|fd| RawFd::from_ne_bytes(fd.try_into().unwrap()))
+                .collect(),
+        )
+    }
+
+    /// The sender of an `SCM_CREDENTIALS` message.
+    pub fn credentials(&self) -> Option<UCred> {
+        if (self.level, self.ty) != (libc::SOL_SOCKET, libc::SCM_CREDENTIALS) {
+            return None;
+        }
+        if self.data.len() < mem::size_of::<libc::ucred>() {
+            return None;
+        }
+        // Long enough, read unaligned.
+        let raw = unsafe { self.data.as_ptr().cast::<libc::ucred>().read_unaligned() };
+        Some(UCred::from_raw(raw))
+    }
+
+    /// The addresses of an `IP_PKTINFO` message.
+    pub fn pktinfo(&self) -> Option<PktInfo> {
+        if (self.level, self.ty) != (libc::IPPROTO_IP, libc::IP_PKTINFO) {
+            return None;
+        }
+        if self.data.len() < mem::size_of::<libc::in_pktinfo>() {
+            return None;
+        }
+        // Long enough, read unaligned.
+        let info = unsafe {
+            self.data
+                .as_ptr()
+                .cast::<libc::in_pktinfo>()
+                .read_unaligned()
+        };
+        Some(PktInfo {
+            ifindex: info.ipi_ifindex as u32,
+            local: Ipv4Addr::from(info.ipi_spec_dst.s_addr.to_ne_bytes()),
+            dst: Ipv4Addr::from(info.ipi_addr.s_addr.to_ne_bytes()),
+        })
+    }
+}
+
+/// The control messages of received control data, in order.
+///
+/// A header that claims more bytes than there are, or fewer than itself,
+/// ends the iteration, as does a message cut off by a short buffer
+/// (`MSG_CTRUNC`).
+#[derive(Clone, Debug)]
+pub struct Cmsgs<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Cmsgs<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Cmsgs { buf }
+    }
+}
+
+impl<'a> Iterator for Cmsgs<'a> {
+    type Item = Cmsg<'a>;
+
+    fn next(&mut self) -> Option<Cmsg<'a>> {
+        if self.buf.len() < HEADER {
+            return None;
+        }
+        // At least a header is left, read unaligned.
+        let header = unsafe { self.buf.as_ptr().cast::<libc::cmsghdr>().read_unaligned() };
+        let len = header.cmsg_len;
+        if len < align(HEADER) || len > self.buf.len() {
+            self.buf = &[];
+            return None;
+        }
+        let data = &self.buf[align(HEADER)..len];
+        // The last message may go without its padding.
+        self.buf = self.buf.get(align(len)..).unwrap_or(&[]);
+        Some(Cmsg {
+            level: header.cmsg_level,
+            ty: header.cmsg_type,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::fd::AsFd;
+
+    #[test]
+    fn build_and_parse() {
+        let stdin = std::io::stdin();
+        let buf = CmsgBuilder::new()
+            .rights(&[stdin.as_fd(), stdin.as_fd()])
+            .push(1, 2, b"odd")
+            .pktinfo(3, Ipv4Addr::LOCALHOST)
+            .credentials(UCred::current())
+            .build();
+        assert_eq!(
+            buf.len(),
+            space(8) + space(3) + space(mem::size_of::<libc::in_pktinfo>()) + space(12)
+        );
+        assert_eq!(space(8), unsafe { libc::CMSG_SPACE(8) } as usize);
+
+        let all: Vec<_> = Cmsgs::new(&buf).collect();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].rights().unwrap(), [0, 0]);
+        assert_eq!(all[0].pktinfo(), None);
+        assert_eq!((all[1].level, all[1].ty, all[1].data), (1, 2, &b"odd"[..]));
+        assert_eq!(all[1].rights(), None);
+        let info = all[2].pktinfo().unwrap();
+        assert_eq!((info.ifindex, info.local), (3, Ipv4Addr::LOCALHOST));
+        assert_eq!(all[3].credentials(), Some(UCred::current()));
+        assert_eq!(all[2].credentials(), None);
+    }
+
+    #[test]
+    fn malformed_lengths() {
+        let valid = CmsgBuilder::new()
+            .push(1, 1, b"first")
+            .push(2, 2, &[0; 20])
+            .build();
+        // Every cut ends with the whole messages before it.
+        for cut in 0..=valid.len() {
+            let n = Cmsgs::new(&valid[..cut]).count();
+            let whole = match cut {
+                c if c >= space(5) + align(HEADER) + 20 => 2,
+                c if c >= align(HEADER) + 5 => 1,
+                _ => 0,
+            };
+            assert_eq!(n, whole, "cut at {cut}");
+        }
+
+        // Noise never panics nor reads out of the buffer.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..10_000 {
+            let len = (next() % 96) as usize;
+            let mut buf: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            // Lengths near the real ones reach the deeper checks.
+            if len >= HEADER && next() % 2 == 0 {
+                let claimed = (next() % (len as u64 + 8)) as usize;
+                buf[..mem::size_of::<usize>()].copy_from_slice(&claimed.to_ne_bytes());
+            }
+            for cmsg in Cmsgs::new(&buf) {
+                let start = cmsg.data.as_ptr() as usize - buf.as_ptr() as usize;
+                assert!(start + cmsg.data.len() <= buf.len());
+                let _ = (cmsg.rights(), cmsg.pktinfo(), cmsg.credentials());
+            }
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use super::socket;
+use super::{lookup, TcpStream, ToSocketAddrs};
+use crate::driver::net::accept::{Accept, AcceptMulti as Multishot};
+use crate::driver::op::{Mappable, Op};
+use crate::fs::File;
+use crate::io::{Canceller, Registration};
+use crate::utils::error_ctx::ResultExt;
+use std::cell::{Ref, RefCell};
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// How [`TcpListener::bind_with_config`] sets the socket up.
+#[derive(Clone, Debug)]
+pub struct ListenerConfig {
+    backlog: u32,
+    reuse_addr: bool,
+    reuse_port: bool,
+    v6_only: Option<bool>,
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        ListenerConfig {
+            backlog: 1024,
+            reuse_addr: true,
+            reuse_port: false,
+            v6_only: None,
+        }
+    }
+}
+
+impl ListenerConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most connections waiting to be accepted, 1024 by default. The
+    /// kernel caps it at `net.core.somaxconn`.
+    pub fn backlog(mut self, backlog: u32) -> Self {
+        self.backlog = backlog;
+        self
+    }
+
+    /// `SO_REUSEADDR`, on by default: a restarted server binds again while
+    /// connections of the old one linger.
+    pub fn reuse_addr(mut self, reuse: bool) -> Self {
+        self.reuse_addr = reuse;
+        self
+    }
+
+    /// `SO_REUSEPORT`, off by default: listeners that all set it share the
+    /// port, the kernel spreads the connections over them. For one runtime
+    /// per core.
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.reuse_port = reuse;
+        self
+    }
+
+    /// `IPV6_V6ONLY` for IPv6 addresses: whether `[::]` takes IPv6
+    /// connections only, or IPv4 ones too. The system default
+    /// (`net.ipv6.bindv6only`) when not set, ignored for IPv4.
+    pub fn v6_only(mut self, v6_only: bool) -> Self {
+        self.v6_only = Some(v6_only);
+        self
+    }
+}
+
+/// A listening TCP socket.
+///
+/// Dropping the listener closes it in the background, see
+/// [`close`](TcpListener::close) for waiting on it.
+///
+/// It has no `AsFd`, a [`shutdown`](TcpListener::shutdown) closes the
+/// descriptor while the listener is still borrowed.
+pub struct TcpListener {
+    // Taken and closed by a shutdown.
+    fd: RefCell<Option<File>>,
+    // Every accept registers with it, shutdown cancels them.
+    canceller: Canceller,
+}
+
+impl TcpListener {
+    /// Listen on `addr`, with the default [`ListenerConfig`].
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<TcpListener> {
+        Self::bind_with_config(addr, &ListenerConfig::default())
+    }
+
+    /// Listen on the first address of `addr` that can be bound. Fails with
+    /// the error of the last one tried. Takes numeric addresses only, a host
+    /// name fails with [`io::ErrorKind::InvalidInput`], look it up with
+    /// [`lookup_host`](super::lookup_host) first.
+    pub fn bind_with_config(
+        addr: impl ToSocketAddrs,
+        config: &ListenerConfig,
+    ) -> io::Result<TcpListener> {
+        let mut last = None;
+        for addr in lookup::resolve_numeric(addr)? {
+            match Self::bind_addr(addr, config) {
+                Ok(listener) => return Ok(listener),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to bind to")
+        }))
+    }
+
+    fn bind_addr(addr: SocketAddr, config: &ListenerConfig) -> io::Result<TcpListener> {
+        let fd = socket::new(&addr, libc::SOCK_STREAM)?;
+        if config.reuse_addr {
+            socket::set_int(&fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+        }
+        if config.reuse_port {
+            socket::set_int(&fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, 1)?;
+        }
+        if let (Some(v6_only), SocketAddr::V6(_)) = (config.v6_only, addr) {
+            let v6_only = v6_only as libc::c_int;
+            socket::set_int(&fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, v6_only)?;
+        }
+        socket::bind(&fd, &addr)?;
+        socket::listen(&fd, config.backlog).op_addr("listen", addr)?;
+        Ok(TcpListener::from_file(fd))
+    }
+
+    fn from_file(fd: File) -> TcpListener {
+        TcpListener {
+            fd: RefCell::new(Some(fd)),
+            canceller: Canceller::new(),
+        }
+    }
+
+    /// Wait for a connection, returning it with the address of the peer.
+    /// Fails with [`io::ErrorKind::Other`] once the listener is
+    /// [shut down](Self::shutdown).
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        self.check_open()?;
+        let fd = self.as_raw_fd();
+        let op = Op::accept(fd).op_fd("accept", fd)?;
+        let _registered = self.register(&op);
+        let res = op.result().await;
+        // A connection the kernel handed over after the shutdown is closed
+        // with its fd.
+        self.check_open()?;
+        let (stream, peer) = res.op_fd("accept", fd)?;
+        Ok((TcpStream::from(stream), peer))
+    }
+
+    /// Stop accepting, for a graceful shutdown: connections accepted before
+    /// are left to be served. The accepts in flight are cancelled on the
+    /// ring, they and every one after fail with [`io::ErrorKind::Other`]
+    /// ("listener closed"). A connection the kernel accepted before the
+    /// cancel reached it is closed. Shutting down again does nothing.
+    ///
+    /// The socket stops listening right away, connections waiting in the
+    /// backlog are reset. Then it is closed on the ring, it is gone once
+    /// this returns and the methods that use it fail like the accepts.
+    pub async fn shutdown(&self) -> io::Result<()> {
+        if self.canceller.is_canceled() {
+            return Ok(());
+        }
+        self.canceller.cancel();
+        let Some(file) = self.fd.borrow_mut().take() else {
+            return Ok(());
+        };
+        // Linux takes a listening socket out of listening on a shutdown of
+        // its read side, new connections are refused even while cancelled
+        // accepts still hold the socket in the kernel.
+        let fd = file.as_raw_fd();
+        crate::syscall!(shutdown@RAW(fd, libc::SHUT_RD)).op_fd("shutdown", fd)?;
+        file.close().await
+    }
+
+    /// Close the socket and wait for the result.
+    pub async fn close(self) -> io::Result<()> {
+        match self.fd.into_inner() {
+            Some(file) => file.close().await,
+            // Closed by the shutdown.
+            None => Ok(()),
+        }
+    }
+
+    fn check_open(&self) -> io::Result<()> {
+        match self.canceller.is_canceled() {
+            true => Err(io::Error::other("listener closed")),
+            false => Ok(()),
+        }
+    }
+
+    // The socket, unless a shutdown closed it.
+    fn fd(&self) -> io::Result<Ref<'_, File>> {
+        Ref::filter_map(self.fd.borrow(), Option::as_ref)
+            .map_err(|_| io::Error::other("listener closed"))
+    }
+
+    // Cancel `op` with a shutdown.
+    fn register<T: Mappable>(&self, op: &Op<T>) -> Registration {
+        self.canceller.handle().register(op)
+    }
+
+    /// Accept connections with a single multishot entry (5.19+) instead of
+    /// one per connection. Older kernels get one accept after the other
+    /// behind the same interface.
+    pub fn accept_multi(&self) -> AcceptMulti<'_> {
+        AcceptMulti {
+            listener: self,
+            multishot: Op::<Multishot>::is_accept_multi_supported(),
+            op: Pending::Idle,
+            registered: None,
+        }
+    }
+
+    /// The connections of the listener as a stream, for combinators and
+    /// `select`. The same as [`accept_multi`](Self::accept_multi): one
+    /// multishot accept where the kernel has it, single accepts before.
+    /// Dropping the stream cancels the accept in flight.
+    pub fn incoming(&self) -> AcceptMulti<'_> {
+        self.accept_multi()
+    }
+
+    /// The address the listener is bound to, with the port picked by the
+    /// kernel when binding to port 0.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(&*self.fd()?)
+    }
+
+    /// Send small writes right away instead of batching them (Nagle), for
+    /// `TCP_NODELAY`. Accepted streams inherit it.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        socket::set_nodelay(&*self.fd()?, nodelay)
+    }
+
+    /// Whether `TCP_NODELAY` is set.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        socket::nodelay(&*self.fd()?)
+    }
+
+    /// Set the time to live of outgoing IPv4 packets, `IP_TTL`.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        socket::set_ttl(&*self.fd()?, ttl)
+    }
+
+    /// The time to live of outgoing IPv4 packets.
+    pub fn ttl(&self) -> io::Result<u32> {
+        socket::ttl(&*self.fd()?)
+    }
+
+    /// Ask for a receive buffer of `size` bytes, `SO_RCVBUF`. The kernel
+    /// doubles it for its own bookkeeping and caps it at
+    /// `net.core.rmem_max`.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&*self.fd()?, libc::SO_RCVBUF, size)
+    }
+
+    /// The size of the receive buffer, as the kernel reports it.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        socket::buffer_size(&*self.fd()?, libc::SO_RCVBUF)
+    }
+
+    /// Ask for a send buffer of `size` bytes, `SO_SNDBUF`. The kernel
+    /// doubles it for its own bookkeeping and caps it at
+    /// `net.core.wmem_max`.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&*self.fd()?, libc::SO_SNDBUF, size)
+    }
+
+    /// The size of the send buffer, as the kernel reports it.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        socket::buffer_size(&*self.fd()?, libc::SO_SNDBUF)
+    }
+}
+
+/// Connections of a listener as they come, from
+/// [`TcpListener::accept_multi`].
+///
+/// The kernel keeps accepting between calls to [`next`](AcceptMulti::next),
+/// the connections wait here in order. Dropping it cancels the accept and
+/// closes the connections not taken.
+pub struct AcceptMulti<'a> {
+    listener: &'a TcpListener,
+    multishot: bool,
+    op: Pending,
+    // Of the op in flight, with the listener.
+    registered: Option<Registration>,
+}
+
+enum Pending {
+    Idle,
+    Multishot(Op<Multishot>),
+    Single(Op<Accept>),
+}
+
+impl AcceptMulti<'_> {
+    /// The next connection. The accept ends with an error, the call after
+    /// it starts a new one, unless the listener was shut down.
+    pub async fn next(&mut self) -> io::Result<TcpStream> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<TcpStream>> {
+        self.listener.check_open()?;
+        let fd = self.listener.as_raw_fd();
+        if let Pending::Idle = self.op {
+            let (registered, op) = match self.multishot {
+                true => {
+                    let op = Op::accept_multi(fd).op_fd("accept", fd)?;
+                    (self.listener.register(&op), Pending::Multishot(op))
+                }
+                false => {
+                    let op = Op::accept(fd).op_fd("accept", fd)?;
+                    (self.listener.register(&op), Pending::Single(op))
+                }
+            };
+            (self.registered, self.op) = (Some(registered), op);
+        }
+        let res = match &mut self.op {
+            Pending::Multishot(op) => {
+                let meta = ready!(op.poll_next(cx));
+                if op.is_terminated() {
+                    (self.op, self.registered) = (Pending::Idle, None);
+                }
+                meta.result
+            }
+            Pending::Single(op) => {
+                let completion = ready!(Pin::new(op).poll(cx));
+                (self.op, self.registered) = (Pending::Idle, None);
+                completion.meta.result
+            }
+            Pending::Idle => unreachable!("an accept is pending"),
+        };
+        self.listener.check_open()?;
+        let fd = res.op_fd("accept", fd)?;
+        let stream = fd.into_owned().expect("accept returns an fd");
+        Poll::Ready(Ok(TcpStream::from(stream)))
+    }
+}
+
+impl futures_core::Stream for AcceptMulti<'_> {
+    type Item = io::Result<TcpStream>;
+
+    /// Never ends, see [`AcceptMulti::next`].
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_accept(cx).map(Some)
+    }
+}
+
+impl From<OwnedFd> for TcpListener {
+    fn from(fd: OwnedFd) -> Self {
+        TcpListener::from_file(File::from(fd))
+    }
+}
+
+impl From<TcpListener> for OwnedFd {
+    /// Panics if the listener was shut down.
+    fn from(listener: TcpListener) -> Self {
+        let file = listener.fd.into_inner();
+        OwnedFd::from(file.expect("listener is shut down"))
+    }
+}
+
+impl AsRawFd for TcpListener {
+    /// -1 once the listener is shut down.
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.borrow().as_ref().map_or(-1, AsRawFd::as_raw_fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::io::AsyncWriteRentExt;
+    use crate::runtime::RuntimeBuilder;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn accept_echo() {
+        let config = ListenerConfig::new().backlog(16);
+        let listener = TcpListener::bind_with_config("127.0.0.1:0", &config).unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        let mut client = std::net::TcpStream::connect(addr).unwrap();
+        client.write_all(b"ping").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (stream, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer, client.local_addr().unwrap());
+            let mut stream = File::from(OwnedFd::from(stream));
+            let (n, buf) = stream.read(Vec::with_capacity(16)).await;
+            assert_eq!(n.unwrap(), 4);
+            stream.write_all(buf).await.0.unwrap();
+        });
+        let mut echoed = [0; 4];
+        client.read_exact(&mut echoed).unwrap();
+        assert_eq!(&echoed, b"ping");
+
+        // Taken by the first listener without SO_REUSEPORT.
+        let err = TcpListener::bind(addr).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert!(err.to_string().starts_with(&format!("bind {addr}: ")));
+    }
+
+    #[test]
+    fn reuse_port_and_v6_only() {
+        let shared = ListenerConfig::new().reuse_port(true);
+        let first = TcpListener::bind_with_config("127.0.0.1:0", &shared).unwrap();
+        let addr = first.local_addr().unwrap();
+        let second = TcpListener::bind_with_config(addr, &shared).unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+        // Every listener on the port has to ask for it.
+        let err = TcpListener::bind(addr).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        // An IPv6 only wildcard leaves the IPv4 port free.
+        let config = ListenerConfig::new().v6_only(true);
+        let v6 = TcpListener::bind_with_config("[::]:0", &config).unwrap();
+        let port = v6.local_addr().unwrap().port();
+        TcpListener::bind(("0.0.0.0", port)).unwrap();
+        let config = ListenerConfig::new().v6_only(false);
+        let dual = TcpListener::bind_with_config("[::]:0", &config).unwrap();
+        let port = dual.local_addr().unwrap().port();
+        let err = TcpListener::bind(("0.0.0.0", port)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+    }
+
+    #[test]
+    fn accept_multi_queues_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut incoming = listener.accept_multi();
+            let mut clients: Vec<_> = (0..4)
+                .map(|_| std::net::TcpStream::connect(addr).unwrap())
+                .collect();
+            for (i, client) in clients.iter_mut().enumerate() {
+                client.write_all(&[i as u8]).unwrap();
+            }
+            let mut seen = Vec::new();
+            for _ in 0..4 {
+                let stream = File::from(OwnedFd::from(incoming.next().await.unwrap()));
+                let (n, buf) = stream.read(Vec::with_capacity(1)).await;
+                assert_eq!(n.unwrap(), 1);
+                seen.push(buf[0]);
+            }
+            seen.sort();
+            assert_eq!(seen, [0, 1, 2, 3]);
+
+            // Accepted while nobody asks, then closed with the accept.
+            let mut late = std::net::TcpStream::connect(addr).unwrap();
+            let mut wait = Op::timeout(std::time::Duration::from_millis(20)).unwrap();
+            poll_fn(|cx| wait.poll_expired(cx)).await.unwrap();
+            drop(incoming);
+            let mut buf = [0; 1];
+            assert_eq!(late.read(&mut buf).unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn incoming_as_a_stream() {
+        use futures_util::StreamExt;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut incoming = listener.incoming();
+            let clients: Vec<_> = (0..3)
+                .map(|_| std::net::TcpStream::connect(addr).unwrap())
+                .collect();
+            let mut peers = Vec::new();
+            for _ in 0..3 {
+                let stream = StreamExt::next(&mut incoming).await.unwrap().unwrap();
+                peers.push(stream.peer_addr().unwrap());
+            }
+            let mut expected: Vec<_> = clients.iter().map(|c| c.local_addr().unwrap()).collect();
+            peers.sort();
+            expected.sort();
+            assert_eq!(peers, expected);
+
+            // The accept in flight goes with the stream, once the cancel
+            // landed the next connection waits for the next accept.
+            drop(incoming);
+            let mut wait = Op::timeout(std::time::Duration::from_millis(50)).unwrap();
+            poll_fn(|cx| wait.poll_expired(cx)).await.unwrap();
+            let client = std::net::TcpStream::connect(addr).unwrap();
+            let (_, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer, client.local_addr().unwrap());
+        });
+    }
+
+    #[test]
+    fn shutdown_ends_pending_accepts() {
+        use std::os::unix::fs::MetadataExt;
+        use std::rc::Rc;
+
+        let open_count = |ino: u64| {
+            let link = format!("socket:[{ino}]");
+            std::fs::read_dir("/proc/self/fd")
+                .unwrap()
+                .filter_map(|e| std::fs::read_link(e.ok()?.path()).ok())
+                .filter(|target| target.to_str() == Some(link.as_str()))
+                .count()
+        };
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let mut inodes = Vec::new();
+        rt.block_on(async {
+            for i in 0..100 {
+                let listener = Rc::new(TcpListener::bind("127.0.0.1:0").unwrap());
+                let addr = listener.local_addr().unwrap();
+                let std_fd = listener.fd().unwrap().try_clone().unwrap().into_std();
+                inodes.push(std_fd.metadata().unwrap().ino());
+                drop(std_fd);
+                let accepting = listener.clone();
+                let accept = crate::spawn(async move { accepting.accept().await.map(drop) });
+                let mut wait = Op::timeout(std::time::Duration::from_millis(1)).unwrap();
+                poll_fn(|cx| wait.poll_expired(cx)).await.unwrap();
+
+                // Every other round a connection is accepted into the op first,
+                // and closed once the shutdown is seen.
+                let client = (i % 2 == 0).then(|| std::net::TcpStream::connect(addr).unwrap());
+                listener.shutdown().await.unwrap();
+                // Closed on the ring already, not only once the listener goes.
+                assert_eq!(open_count(inodes[i]), 0);
+                assert_eq!(listener.as_raw_fd(), -1);
+                let err = listener.local_addr().unwrap_err();
+                assert_eq!(err.to_string(), "listener closed");
+                let err = accept.await.unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::Other);
+                assert_eq!(err.to_string(), "listener closed");
+                if let Some(mut client) = client {
+                    let mut buf = [0; 1];
+                    assert!(!matches!(client.read(&mut buf), Ok(1..)));
+                }
+
+                let err = listener.accept().await.map(drop).unwrap_err();
+                assert_eq!(err.to_string(), "listener closed");
+                let mut incoming = listener.incoming();
+                assert_eq!(
+                    incoming.next().await.map(drop).unwrap_err().kind(),
+                    io::ErrorKind::Other
+                );
+                drop(incoming);
+                listener.shutdown().await.unwrap();
+                let err = std::net::TcpStream::connect(addr).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+                match i % 4 {
+                    0 => Rc::into_inner(listener).unwrap().close().await.unwrap(),
+                    _ => drop(listener),
+                }
+            }
+        });
+        drop(rt);
+        for ino in inodes {
+            assert_eq!(open_count(ino), 0);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Name resolution off the runtime thread.
+//!
+//! `getaddrinfo` blocks, so names are looked up on the shared blocking pool
+//! and the answer comes back through the ring. Addresses that are already
+//! numeric never leave the calling thread.
+
+use super::addr;
+use crate::runtime::unblock;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::vec;
+
+/// Look up the addresses of `host`, a `name:port` like `example.com:443`.
+///
+/// A numeric address like `127.0.0.1:80` or `[::1]:80` is parsed in place.
+/// A name is resolved with `getaddrinfo` on the blocking pool, the runtime
+/// keeps running meanwhile. Fails with [`io::ErrorKind::NotFound`], carrying
+/// the resolver's message, when the name does not resolve.
+pub async fn lookup_host(host: &str) -> io::Result<impl Iterator<Item = SocketAddr>> {
+    resolve(host).await
+}
+
+/// Values that name socket addresses, numeric ones or host names to look
+/// up, for the constructors of the socket types.
+///
+/// Like [`std::net::ToSocketAddrs`], but names are resolved off the runtime
+/// thread by the async constructors, see [`lookup_host`]. The constructors
+/// that are not async take numeric addresses only.
+pub trait ToSocketAddrs: sealed::Sealed {}
+
+mod sealed {
+    use std::net::SocketAddr;
+    use std::vec;
+
+    pub trait Sealed {
+        fn to_addrs(&self) -> std::io::Result<super::Addrs>;
+    }
+
+    /// Addresses as given, or a name to look up first.
+    pub enum Addrs {
+        Ready(vec::IntoIter<SocketAddr>),
+        Lookup(String, u16),
+    }
+}
+
+use sealed::{Addrs, Sealed};
+
+/// The addresses of `addr`, a name looked up on the blocking pool.
+pub(crate) async fn resolve(addr: impl ToSocketAddrs) -> io::Result<vec::IntoIter<SocketAddr>> {
+    match addr.to_addrs()? {
+        Addrs::Ready(addrs) => Ok(addrs),
+        Addrs::Lookup(host, port) => unblock(move || getaddrinfo(&host, port)).await?,
+    }
+}
+
+/// The addresses of `addr`, for the constructors that are not async. They
+/// would block the runtime on `getaddrinfo`, so a name fails with
+/// [`io::ErrorKind::InvalidInput`] instead.
+pub(crate) fn resolve_numeric(addr: impl ToSocketAddrs) -> io::Result<vec::IntoIter<SocketAddr>> {
+    match addr.to_addrs()? {
+        Addrs::Ready(addrs) => Ok(addrs),
+        Addrs::Lookup(host, _) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{host} is a host name, look it up with lookup_host first"),
+        )),
+    }
+}
+
+fn ready(addr: SocketAddr) -> io::Result<Addrs> {
+    Ok(Addrs::Ready(vec![addr].into_iter()))
+}
+
+fn host_port(host: &str, port: u16) -> io::Result<Addrs> {
+    match host.parse::<IpAddr>() {
+        Ok(ip) => ready(SocketAddr::new(ip, port)),
+        Err(_) => Ok(Addrs::Lookup(host.to_owned(), port)),
+    }
+}
+
+impl ToSocketAddrs for SocketAddr {}
+impl Sealed for SocketAddr {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(*self)
+    }
+}
+
+impl ToSocketAddrs for SocketAddrV4 {}
+impl Sealed for SocketAddrV4 {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(SocketAddr::V4(*self))
+    }
+}
+
+impl ToSocketAddrs for SocketAddrV6 {}
+impl Sealed for SocketAddrV6 {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(SocketAddr::V6(*self))
+    }
+}
+
+impl ToSocketAddrs for (IpAddr, u16) {}
+impl Sealed for (IpAddr, u16) {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(SocketAddr::new(self.0, self.1))
+    }
+}
+
+impl ToSocketAddrs for (Ipv4Addr, u16) {}
+impl Sealed for (Ipv4Addr, u16) {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(SocketAddr::new(self.0.into(), self.1))
+    }
+}
+
+impl ToSocketAddrs for (Ipv6Addr, u16) {}
+impl Sealed for (Ipv6Addr, u16) {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        ready(SocketAddr::new(self.0.into(), self.1))
+    }
+}
+
+impl ToSocketAddrs for (&str, u16) {}
+impl Sealed for (&str, u16) {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        host_port(self.0, self.1)
+    }
+}
+
+impl ToSocketAddrs for (String, u16) {}
+impl Sealed for (String, u16) {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        host_port(&self.0, self.1)
+    }
+}
+
+impl ToSocketAddrs for str {}
+impl Sealed for str {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        if let Ok(addr) = self.parse() {
+            return ready(addr);
+        }
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        let (host, port) = self
+            .rsplit_once(':')
+            .ok_or_else(|| invalid("invalid socket address"))?;
+        let port = port.parse().map_err(|_| invalid("invalid port value"))?;
+        host_port(host, port)
+    }
+}
+
+impl ToSocketAddrs for String {}
+impl Sealed for String {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        self.as_str().to_addrs()
+    }
+}
+
+impl ToSocketAddrs for [SocketAddr] {}
+impl Sealed for [SocketAddr] {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        Ok(Addrs::Ready(Vec::from(self).into_iter()))
+    }
+}
+
+impl<T: ToSocketAddrs + ?Sized> ToSocketAddrs for &T {}
+impl<T: Sealed + ?Sized> Sealed for &T {
+    fn to_addrs(&self) -> io::Result<Addrs> {
+        (**self).to_addrs()
+    }
+}
+
+// The stream addresses of `host`, with `port`. Blocks.
+fn getaddrinfo(host: &str, port: u16) -> io::Result<vec::IntoIter<SocketAddr>> {
+    let c_host = CString::new(host)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "host name has a nul byte"))?;
+    // Zeroed hints ask for any family, one entry per address.
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_socktype = libc::SOCK_STREAM;
+    let mut res = std::ptr::null_mut();
+    let code = unsafe { libc::getaddrinfo(c_host.as_ptr(), std::ptr::null(), &hints, &mut res) };
+    if code != 0 {
+        return Err(gai_error(code, host));
+    }
+    let mut addrs = Vec::new();
+    let mut cur = res;
+    while !cur.is_null() {
+        // A list the resolver allocated, valid until freed below.
+        let info = unsafe { &*cur };
+        let len = (info.ai_addrlen as usize).min(std::mem::size_of::<libc::sockaddr_storage>());
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                info.ai_addr.cast::<u8>(),
+                (&mut storage as *mut libc::sockaddr_storage).cast::<u8>(),
+                len,
+            )
+        };
+        // Families other than IP are skipped.
+        if let Ok(mut addr) = addr::from_raw(&storage, len as libc::socklen_t) {
+            addr.set_port(port);
+            addrs.push(addr);
+        }
+        cur = info.ai_next;
+    }
+    unsafe { libc::freeaddrinfo(res) };
+    Ok(addrs.into_iter())
+}
+
+fn gai_error(code: libc::c_int, host: &str) -> io::Error {
+    if code == libc::EAI_SYSTEM {
+        return io::Error::last_os_error();
+    }
+    // A static string for every code.
+    let msg = unsafe { CStr::from_ptr(libc::gai_strerror(code)) };
+    let kind = match code {
+        libc::EAI_MEMORY => io::ErrorKind::OutOfMemory,
+        _ => io::ErrorKind::NotFound,
+    };
+    let msg = format!("failed to look up {host}: {}", msg.to_string_lossy());
+    io::Error::new(kind, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::net::{TcpListener, TcpStream};
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn numeric_and_localhost() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // Numeric ones resolve in place, without the pool.
+            let addrs: Vec<_> = resolve_numeric("[::1]:80").unwrap().collect();
+            assert_eq!(addrs, ["[::1]:80".parse::<SocketAddr>().unwrap()]);
+            let addrs: Vec<_> = resolve(("10.0.0.1", 7)).await.unwrap().collect();
+            assert_eq!(addrs, ["10.0.0.1:7".parse::<SocketAddr>().unwrap()]);
+
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let addrs: Vec<_> = lookup_host(&format!("localhost:{port}"))
+                .await
+                .unwrap()
+                .collect();
+            assert!(addrs.iter().any(|a| a.ip().is_loopback()));
+            assert!(addrs.iter().all(|a| a.port() == port));
+
+            // Tried in order until the one the listener is on.
+            let stream = TcpStream::connect(format!("localhost:{port}"))
+                .await
+                .unwrap();
+            let (_, peer) = listener.accept().await.unwrap();
+            assert_eq!(peer, stream.local_addr().unwrap());
+        });
+    }
+
+    #[test]
+    fn failures() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let Err(err) = lookup_host("no-such-host.invalid:80").await else {
+                panic!("resolved")
+            };
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err.to_string().contains("no-such-host.invalid"), "{err}");
+
+            for bad in ["localhost", "localhost:port", "localhost:70000"] {
+                let Err(err) = lookup_host(bad).await else {
+                    panic!("{bad} resolved")
+                };
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{bad}");
+            }
+
+            // Binding would block on the resolver, names are refused.
+            let Err(err) = TcpListener::bind("localhost:0") else {
+                panic!("bound a host name")
+            };
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert!(err.to_string().contains("lookup_host"), "{err}");
+        });
+    }
+}
@@ -0,0 +1,23 @@
+//! TCP, UDP and unix sockets on the ring.
+//!
+//! Setting a socket up is a few cheap syscalls made in place, accepting,
+//! reading and writing go through the ring of the current thread.
+
+pub(crate) mod addr;
+pub mod cmsg;
+mod listener;
+mod lookup;
+mod socket;
+mod stream;
+mod tcp_socket;
+mod udp;
+pub mod unix;
+
+pub use cmsg::{Cmsg, CmsgBuilder, Cmsgs, PktInfo};
+pub use listener::{AcceptMulti, ListenerConfig, TcpListener};
+pub use lookup::{lookup_host, ToSocketAddrs};
+pub use socket::KeepAlive;
+pub use stream::{RecvMulti, TcpStream};
+pub use tcp_socket::TcpSocket;
+pub use udp::{RecvFromMulti, UdpSocket};
+pub use unix::{UnixDatagram, UnixListener, UnixStream};
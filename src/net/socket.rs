@@ -0,0 +1,267 @@
+//! The setup syscalls shared by the socket types, made in place.
+
+use super::addr::{self, RawAddr};
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::mem;
+use std::net::SocketAddr;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+/// A new non-blocking, close-on-exec socket of type `ty` for the family of
+/// `addr`.
+pub(crate) fn new(addr: &SocketAddr, ty: libc::c_int) -> io::Result<File> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    open(domain, ty).op_addr("socket", *addr)
+}
+
+/// A new non-blocking, close-on-exec socket of `domain` and type `ty`.
+pub(crate) fn open(domain: libc::c_int, ty: libc::c_int) -> io::Result<File> {
+    let flags = ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+    let fd = crate::syscall!(socket@FD(domain, flags, 0))?;
+    Ok(File::from(fd.into_owned().expect("socket returns an fd")))
+}
+
+/// A pair of connected non-blocking, close-on-exec unix sockets of type
+/// `ty`.
+pub(crate) fn pair(ty: libc::c_int) -> io::Result<(File, File)> {
+    let flags = ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC;
+    let mut fds = [0; 2];
+    crate::syscall!(socketpair@RAW(libc::AF_UNIX, flags, 0, fds.as_mut_ptr()))?;
+    // Both were just opened for us.
+    let (a, b) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    Ok((File::from(a), File::from(b)))
+}
+
+/// Bind `fd` to `addr`.
+pub(crate) fn bind(fd: &File, addr: &SocketAddr) -> io::Result<()> {
+    bind_raw(fd, &addr::to_raw(addr)).op_addr("bind", *addr)
+}
+
+/// Bind `fd` to an address of its family.
+pub(crate) fn bind_raw(fd: &File, (storage, len): &RawAddr) -> io::Result<()> {
+    crate::syscall!(bind@RAW(
+        fd.as_raw_fd(),
+        (storage as *const libc::sockaddr_storage).cast(),
+        *len
+    ))?;
+    Ok(())
+}
+
+/// Connect `fd` to `addr` in place, for datagram sockets that only record
+/// the peer.
+pub(crate) fn connect(fd: &File, addr: &SocketAddr) -> io::Result<()> {
+    connect_raw(fd, &addr::to_raw(addr)).op_addr("connect", *addr)
+}
+
+/// Connect `fd` in place to an address of its family.
+pub(crate) fn connect_raw(fd: &File, (storage, len): &RawAddr) -> io::Result<()> {
+    crate::syscall!(connect@RAW(
+        fd.as_raw_fd(),
+        (storage as *const libc::sockaddr_storage).cast(),
+        *len
+    ))?;
+    Ok(())
+}
+
+/// Start listening on `fd`, with room for `backlog` connections not
+/// accepted yet.
+pub(crate) fn listen(fd: &File, backlog: u32) -> io::Result<()> {
+    let backlog = backlog.min(libc::c_int::MAX as u32) as libc::c_int;
+    crate::syscall!(listen@RAW(fd.as_raw_fd(), backlog))?;
+    Ok(())
+}
+
+/// Set an option that takes a `c_int`.
+pub(crate) fn set_int(
+    fd: &File,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> io::Result<()> {
+    let raw = fd.as_raw_fd();
+    crate::syscall!(setsockopt@RAW(
+        raw,
+        level,
+        name,
+        (&value as *const libc::c_int).cast(),
+        mem::size_of::<libc::c_int>() as libc::socklen_t,
+    ))
+    .op_fd("setsockopt", raw)?;
+    Ok(())
+}
+
+/// Set an option that takes bytes.
+pub(crate) fn set_bytes(
+    fd: &File,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &[u8],
+) -> io::Result<()> {
+    let raw = fd.as_raw_fd();
+    crate::syscall!(setsockopt@RAW(
+        raw,
+        level,
+        name,
+        value.as_ptr().cast(),
+        value.len() as libc::socklen_t,
+    ))
+    .op_fd("setsockopt", raw)?;
+    Ok(())
+}
+
+/// Set an option that takes a C struct, like `ip_mreq`.
+pub(crate) fn set_struct<T: Copy>(
+    fd: &File,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &T,
+) -> io::Result<()> {
+    // Plain C structs, read as the bytes they are made of.
+    let bytes = unsafe {
+        std::slice::from_raw_parts((value as *const T).cast::<u8>(), mem::size_of::<T>())
+    };
+    set_bytes(fd, level, name, bytes)
+}
+
+/// Read an option that is a `c_int`.
+pub(crate) fn get_int(fd: &File, level: libc::c_int, name: libc::c_int) -> io::Result<libc::c_int> {
+    let raw = fd.as_raw_fd();
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    crate::syscall!(getsockopt@RAW(
+        raw,
+        level,
+        name,
+        (&mut value as *mut libc::c_int).cast(),
+        &mut len,
+    ))
+    .op_fd("getsockopt", raw)?;
+    Ok(value)
+}
+
+/// The local address of `fd`.
+pub(crate) fn local_addr(fd: &File) -> io::Result<SocketAddr> {
+    let (storage, len) = local_raw(fd)?;
+    addr::from_raw(&storage, len).op_fd("getsockname", fd.as_raw_fd())
+}
+
+/// The address of the peer `fd` is connected to.
+pub(crate) fn peer_addr(fd: &File) -> io::Result<SocketAddr> {
+    let (storage, len) = peer_raw(fd)?;
+    addr::from_raw(&storage, len).op_fd("getpeername", fd.as_raw_fd())
+}
+
+/// The local address of `fd`, of whatever family it is.
+pub(crate) fn local_raw(fd: &File) -> io::Result<RawAddr> {
+    let raw = fd.as_raw_fd();
+    let (mut storage, mut len) = empty_addr();
+    crate::syscall!(getsockname@RAW(
+        raw,
+        (&mut storage as *mut libc::sockaddr_storage).cast(),
+        &mut len
+    ))
+    .op_fd("getsockname", raw)?;
+    Ok((storage, len))
+}
+
+/// The address of the peer of `fd`, of whatever family it is.
+pub(crate) fn peer_raw(fd: &File) -> io::Result<RawAddr> {
+    let raw = fd.as_raw_fd();
+    let (mut storage, mut len) = empty_addr();
+    crate::syscall!(getpeername@RAW(
+        raw,
+        (&mut storage as *mut libc::sockaddr_storage).cast(),
+        &mut len
+    ))
+    .op_fd("getpeername", raw)?;
+    Ok((storage, len))
+}
+
+// Room for any address, for the kernel to fill.
+fn empty_addr() -> RawAddr {
+    // Zeroed is a valid sockaddr_storage.
+    let storage = unsafe { mem::zeroed() };
+    (
+        storage,
+        mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t,
+    )
+}
+
+pub(crate) fn set_nodelay(fd: &File, nodelay: bool) -> io::Result<()> {
+    set_int(
+        fd,
+        libc::IPPROTO_TCP,
+        libc::TCP_NODELAY,
+        nodelay as libc::c_int,
+    )
+}
+
+pub(crate) fn nodelay(fd: &File) -> io::Result<bool> {
+    Ok(get_int(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY)? != 0)
+}
+
+/// How an idle TCP connection is probed, to notice a peer that went away
+/// and to keep NAT mappings alive. The kernel counts in whole seconds,
+/// rounded up here; each is between 1 and 32767, `retries` up to 127.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// Idle time before the first probe, `TCP_KEEPIDLE`.
+    pub time: Duration,
+    /// Time between unanswered probes, `TCP_KEEPINTVL`.
+    pub interval: Duration,
+    /// Unanswered probes before the connection is dropped, `TCP_KEEPCNT`.
+    pub retries: u32,
+}
+
+fn secs(d: Duration) -> libc::c_int {
+    let secs = d.as_secs() + u64::from(d.subsec_nanos() > 0);
+    secs.min(libc::c_int::MAX as u64) as libc::c_int
+}
+
+pub(crate) fn set_keepalive(fd: &File, keepalive: Option<KeepAlive>) -> io::Result<()> {
+    if let Some(k) = keepalive {
+        // Invalid values fail before probing is switched on.
+        set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs(k.time))?;
+        set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, secs(k.interval))?;
+        let retries = k.retries.min(libc::c_int::MAX as u32) as libc::c_int;
+        set_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, retries)?;
+    }
+    let on = keepalive.is_some() as libc::c_int;
+    set_int(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, on)
+}
+
+pub(crate) fn keepalive(fd: &File) -> io::Result<Option<KeepAlive>> {
+    if get_int(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE)? == 0 {
+        return Ok(None);
+    }
+    let time = get_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE)?;
+    let interval = get_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL)?;
+    let retries = get_int(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT)?;
+    Ok(Some(KeepAlive {
+        time: Duration::from_secs(time as u64),
+        interval: Duration::from_secs(interval as u64),
+        retries: retries as u32,
+    }))
+}
+
+pub(crate) fn set_ttl(fd: &File, ttl: u32) -> io::Result<()> {
+    set_int(fd, libc::IPPROTO_IP, libc::IP_TTL, ttl as libc::c_int)
+}
+
+pub(crate) fn ttl(fd: &File) -> io::Result<u32> {
+    Ok(get_int(fd, libc::IPPROTO_IP, libc::IP_TTL)? as u32)
+}
+
+pub(crate) fn set_buffer_size(fd: &File, name: libc::c_int, size: usize) -> io::Result<()> {
+    let size = size.min(libc::c_int::MAX as usize) as libc::c_int;
+    set_int(fd, libc::SOL_SOCKET, name, size)
+}
+
+pub(crate) fn buffer_size(fd: &File, name: libc::c_int) -> io::Result<usize> {
+    Ok(get_int(fd, libc::SOL_SOCKET, name)? as usize)
+}
@@ -0,0 +1,893 @@
+use super::{addr, lookup, socket, KeepAlive, ToSocketAddrs};
+use crate::buf::{BorrowedBuf, BufResult, BufRing, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use crate::driver::buf_group::Exhausted;
+use crate::driver::net::recv::{RecvFromGroup, RecvMulti as Multishot};
+use crate::driver::op::Op;
+use crate::fs::{pipe, File};
+use crate::io::{AsyncReadRent, AsyncWriteRent, Split};
+use crate::utils::error_ctx::ResultExt;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::pin::{pin, Pin};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+// How long an attempt of `connect_all` runs alone before the next address
+// is tried too, the connection attempt delay of RFC 8305.
+const STAGGER: Duration = Duration::from_millis(250);
+
+/// A connected TCP socket.
+///
+/// Dropping the stream closes it in the background.
+pub struct TcpStream {
+    fd: File,
+}
+
+impl TcpStream {
+    /// Connect to the addresses of `addr` in order, until one accepts. Fails
+    /// with the error of the last one tried. A host name is looked up off
+    /// the runtime thread first, see [`lookup_host`](super::lookup_host).
+    pub async fn connect(addr: impl ToSocketAddrs) -> io::Result<TcpStream> {
+        let mut last = None;
+        for addr in lookup::resolve(addr).await? {
+            match Self::connect_addr(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        }))
+    }
+
+    /// Connect to `addr`, failing with [`io::ErrorKind::TimedOut`] after
+    /// `timeout` instead of the minutes the kernel retries for. The
+    /// connect is cancelled then, its socket closed once the kernel gave it
+    /// back.
+    pub async fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+        let addr = *addr;
+        let mut connect = pin!(Self::connect_addr(addr));
+        let mut timer = Op::timeout(timeout).op_addr("connect", addr)?;
+        poll_fn(|cx| {
+            if let Poll::Ready(res) = connect.as_mut().poll(cx) {
+                return Poll::Ready(res);
+            }
+            if let Err(e) = std::task::ready!(timer.poll_expired(cx)) {
+                return Poll::Ready(Err(e).op_addr("connect", addr));
+            }
+            let err = io::Error::new(io::ErrorKind::TimedOut, "connect timed out");
+            Poll::Ready(Err(err).op_addr("connect", addr))
+        })
+        .await
+    }
+
+    /// Connect to the addresses of `addr` racing each other, for hosts with
+    /// both IPv6 and IPv4 addresses. Each one starts 250ms after the one
+    /// before it, or as soon as an attempt fails. The first to connect wins,
+    /// the others are cancelled and their sockets closed. Fails with the
+    /// error of the last one when all do.
+    pub async fn connect_all(addr: impl ToSocketAddrs) -> io::Result<TcpStream> {
+        type Attempt = Pin<Box<dyn Future<Output = io::Result<TcpStream>>>>;
+        let mut addrs = lookup::resolve(addr).await?.peekable();
+        let mut attempts: Vec<Attempt> = Vec::new();
+        let mut stagger: Option<Op<_>> = None;
+        let mut start_next = true;
+        let mut last = None;
+        poll_fn(|cx| loop {
+            if stagger
+                .as_mut()
+                .is_some_and(|timer| timer.poll_expired(cx).is_ready())
+            {
+                start_next = true;
+            }
+            if std::mem::take(&mut start_next) {
+                stagger = None;
+                match addrs.next() {
+                    Some(addr) => {
+                        attempts.push(Box::pin(Self::connect_addr(addr)));
+                        if addrs.peek().is_some() {
+                            stagger = Some(Op::timeout(STAGGER).op_addr("connect", addr)?);
+                        }
+                        // Polled first thing, for its waker.
+                        continue;
+                    }
+                    None if attempts.is_empty() => {
+                        return Poll::Ready(Err(last.take().unwrap_or_else(|| {
+                            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+                        })));
+                    }
+                    None => {}
+                }
+            }
+            let mut i = 0;
+            while i < attempts.len() {
+                match attempts[i].as_mut().poll(cx) {
+                    Poll::Ready(Ok(stream)) => return Poll::Ready(Ok(stream)),
+                    Poll::Ready(Err(e)) => {
+                        last = Some(e);
+                        drop(attempts.swap_remove(i));
+                        // The next one does not wait for the timer.
+                        start_next = true;
+                    }
+                    Poll::Pending => i += 1,
+                }
+            }
+            if !start_next && !attempts.is_empty() {
+                return Poll::Pending;
+            }
+            start_next = true;
+        })
+        .await
+    }
+
+    async fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
+        let fd = socket::new(&addr, libc::SOCK_STREAM)?;
+        Self::connect_with(fd, addr).await
+    }
+
+    /// Connect the socket `fd`, set up by the caller, to `addr`.
+    pub(crate) async fn connect_with(fd: File, addr: SocketAddr) -> io::Result<TcpStream> {
+        let op = Op::connect(fd, addr::to_raw(&addr)).op_addr("connect", addr)?;
+        let fd = op.result().await.op_addr("connect", addr)?;
+        // A failure the kernel noticed after the connect completed.
+        match socket::get_int(&fd, libc::SOL_SOCKET, libc::SO_ERROR)? {
+            0 => Ok(TcpStream { fd }),
+            errno => Err(io::Error::from_raw_os_error(errno)).op_addr("connect", addr),
+        }
+    }
+
+    /// The local address of the stream.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(&self.fd)
+    }
+
+    /// The address of the peer. Fails with
+    /// [`io::ErrorKind::NotConnected`] once the connection is gone.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        socket::peer_addr(&self.fd)
+    }
+
+    /// Receive into `buf`, leaving the bytes queued for the next read. To
+    /// sniff a protocol. 0 once the peer shut down its write side.
+    pub async fn peek<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.recv(buf, libc::MSG_PEEK).await
+    }
+
+    /// Receive into the buffers of `ring` with a single multishot entry
+    /// (6.0+) instead of one receive per read. Older kernels get one receive
+    /// after the other behind the same interface.
+    pub fn recv_multi(&self, ring: &BufRing) -> RecvMulti<'_> {
+        RecvMulti {
+            stream: self,
+            ring: ring.clone(),
+            multishot: Op::<Multishot>::is_recv_multi_supported(),
+            op: Receiving::Idle,
+        }
+    }
+
+    async fn recv<T: IoBufMut>(&self, buf: T, flags: i32) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv(fd, buf, flags) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("recv", fd), buf)
+    }
+
+    /// Send `buf` with zero copy (6.0+), a plain send on older kernels.
+    /// Returns once the kernel no longer reads from `buf`, with the number of
+    /// bytes sent. Worth it for large buffers only.
+    pub async fn send_zc<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send_zc(fd, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("send_zc", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("send_zc", fd), buf)
+    }
+
+    /// Send `len` bytes of `file` from `offset`, like `sendfile(2)`, leaving
+    /// its position alone. Returns the number of bytes sent, fewer when the
+    /// file ends first.
+    ///
+    /// Regular files are spliced through a pipe kept per thread, the bytes
+    /// are not copied through userspace. Other sources, like pipes or
+    /// character devices, are read and sent from their position, `offset`
+    /// must be 0 for them.
+    pub async fn send_file(&self, file: &File, offset: u64, len: u64) -> io::Result<u64> {
+        let src = file.as_raw_fd();
+        let sock = self.fd.as_raw_fd();
+        if !file.metadata().await?.is_file() {
+            if offset != 0 {
+                let err = io::Error::new(io::ErrorKind::InvalidInput, "offset into a stream");
+                return Err(err).op_fd("sendfile", src);
+            }
+            return self.copy_file(file, None, len).await;
+        }
+        match pipe::splice_through_pool(src, Some(offset), sock, len).await {
+            Ok(moved) => Ok(moved),
+            // A file system without splice support fails the first splice.
+            // Once bytes went out the error is a real one, copying again
+            // would send them twice.
+            Err((e, 0)) if e.kind() == io::ErrorKind::InvalidInput => {
+                self.copy_file(file, Some(offset), len).await
+            }
+            Err((e, _)) => Err(e),
+        }
+    }
+
+    // Read `file` and send it, from `offset` or its position.
+    async fn copy_file(&self, file: &File, offset: Option<u64>, len: u64) -> io::Result<u64> {
+        let fd = self.fd.as_raw_fd();
+        let mut buf = Vec::with_capacity(pipe::CHUNK);
+        let mut sent = 0;
+        while sent < len {
+            buf.clear();
+            let want = usize::try_from(len - sent)
+                .unwrap_or(usize::MAX)
+                .min(pipe::CHUNK);
+            let (n, read) = match offset {
+                Some(offset) => file.read_at(buf.slice_mut(..want), offset + sent).await,
+                None => file.read(buf.slice_mut(..want)).await,
+            };
+            buf = read.into_inner();
+            if n? == 0 {
+                break;
+            }
+            let mut written = 0;
+            while written < buf.len() {
+                let op = Op::send(fd, buf.slice(written..)).map_err(|(e, _)| e);
+                let (n, rest) = op.op_fd("send", fd)?.result().await;
+                buf = rest.into_inner();
+                match n.op_fd("send", fd)? {
+                    0 => {
+                        let err = io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        );
+                        return Err(err).op_fd("send", fd);
+                    }
+                    n => written += n,
+                }
+            }
+            sent += written as u64;
+        }
+        Ok(sent)
+    }
+
+    /// Send small writes right away instead of batching them (Nagle), for
+    /// `TCP_NODELAY`.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        socket::set_nodelay(&self.fd, nodelay)
+    }
+
+    /// Whether `TCP_NODELAY` is set.
+    pub fn nodelay(&self) -> io::Result<bool> {
+        socket::nodelay(&self.fd)
+    }
+
+    /// Probe the idle connection as `keepalive` says, or not for `None`,
+    /// `SO_KEEPALIVE`.
+    pub fn set_keepalive(&self, keepalive: Option<KeepAlive>) -> io::Result<()> {
+        socket::set_keepalive(&self.fd, keepalive)
+    }
+
+    /// How the idle connection is probed, `None` if it is not.
+    pub fn keepalive(&self) -> io::Result<Option<KeepAlive>> {
+        socket::keepalive(&self.fd)
+    }
+
+    /// Set the time to live of outgoing IPv4 packets, `IP_TTL`.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        socket::set_ttl(&self.fd, ttl)
+    }
+
+    /// The time to live of outgoing IPv4 packets.
+    pub fn ttl(&self) -> io::Result<u32> {
+        socket::ttl(&self.fd)
+    }
+
+    /// Ask for a receive buffer of `size` bytes, `SO_RCVBUF`. The kernel
+    /// doubles it for its own bookkeeping and caps it at
+    /// `net.core.rmem_max`.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&self.fd, libc::SO_RCVBUF, size)
+    }
+
+    /// The size of the receive buffer, as the kernel reports it.
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        socket::buffer_size(&self.fd, libc::SO_RCVBUF)
+    }
+
+    /// Ask for a send buffer of `size` bytes, `SO_SNDBUF`. The kernel
+    /// doubles it for its own bookkeeping and caps it at
+    /// `net.core.wmem_max`.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&self.fd, libc::SO_SNDBUF, size)
+    }
+
+    /// The size of the send buffer, as the kernel reports it.
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        socket::buffer_size(&self.fd, libc::SO_SNDBUF)
+    }
+}
+
+// Offset -1, a socket has no position.
+const NO_OFFSET: u64 = u64::MAX;
+
+impl AsyncReadRent for TcpStream {
+    /// Receive into `buf`. 0 once the peer shut down its write side.
+    fn read<T: IoBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.read_shared(buf)
+    }
+
+    fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.readv_shared(buf)
+    }
+}
+
+impl AsyncWriteRent for TcpStream {
+    fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.write_shared(buf)
+    }
+
+    fn writev<T: IoVecBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.writev_shared(buf)
+    }
+
+    /// Sends are not buffered in userspace, this does nothing.
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Shut down the write side, the peer reads the end of the stream.
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.fd.shutdown().await
+    }
+}
+
+// Receives and sends are separate ops on the socket.
+impl Split for TcpStream {
+    async fn read_shared<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        self.recv(buf, 0).await
+    }
+
+    async fn readv_shared<T: IoVecBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::readv(fd, NO_OFFSET, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("readv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("readv", fd), buf)
+    }
+
+    async fn write_shared<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send(fd, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("send", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("send", fd), buf)
+    }
+
+    async fn writev_shared<T: IoVecBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::writev(fd, NO_OFFSET, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("writev", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("writev", fd), buf)
+    }
+
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.fd.shutdown_write()
+    }
+}
+
+/// Bytes received into the buffers of a ring, from
+/// [`TcpStream::recv_multi`].
+///
+/// The kernel keeps receiving between calls to [`next`](RecvMulti::next),
+/// the buffers wait here in order. With every buffer of the ring borrowed
+/// the receive stops, and starts again once one is dropped. Dropping it
+/// cancels the receive and gives the buffers not taken back to the ring,
+/// bytes in them are lost. So are bytes that arrive before the cancel
+/// reached the kernel.
+pub struct RecvMulti<'a> {
+    stream: &'a TcpStream,
+    ring: BufRing,
+    multishot: bool,
+    op: Receiving,
+}
+
+enum Receiving {
+    Idle,
+    // The ring ran dry, waiting for a buffer to come back.
+    Starved,
+    Multishot(Op<Multishot>),
+    Single(Op<RecvFromGroup>),
+    // The peer shut down its write side.
+    Done,
+}
+
+impl RecvMulti<'_> {
+    /// The next buffer with the number of bytes in it. `None` once the peer
+    /// shut down its write side. The receive ends with an error, the call
+    /// after it starts a new one.
+    pub async fn next(&mut self) -> Option<io::Result<(BorrowedBuf, usize)>> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<io::Result<(BorrowedBuf, usize)>>> {
+        let fd = self.stream.as_raw_fd();
+        let group = self.ring.group();
+        loop {
+            let meta = match &mut self.op {
+                Receiving::Done => return Poll::Ready(None),
+                Receiving::Starved => {
+                    ready!(group.poll_available(cx));
+                    self.op = Receiving::Idle;
+                    continue;
+                }
+                Receiving::Idle => {
+                    let op = match self.multishot {
+                        true => Op::recv_multi(fd, group).map(Receiving::Multishot),
+                        false => Op::recv_from_group(fd, group).map(Receiving::Single),
+                    };
+                    self.op = op.op_fd("recv", fd)?;
+                    continue;
+                }
+                Receiving::Multishot(op) => {
+                    let meta = ready!(op.poll_next(cx));
+                    if op.is_terminated() {
+                        self.op = Receiving::Idle;
+                    }
+                    meta
+                }
+                Receiving::Single(op) => {
+                    let completion = ready!(Pin::new(op).poll(cx));
+                    self.op = Receiving::Idle;
+                    completion.meta
+                }
+            };
+            match group.take(meta) {
+                // Ends a multishot receive, it is started again after.
+                Err(e) if Exhausted::from_io_error(&e).is_some() => self.op = Receiving::Starved,
+                Err(e) => return Poll::Ready(Some(Err(e).op_fd("recv", fd))),
+                Ok(buf) if buf.is_empty() => {
+                    self.op = Receiving::Done;
+                    return Poll::Ready(None);
+                }
+                Ok(buf) => {
+                    let n = buf.len();
+                    return Poll::Ready(Some(Ok((buf, n))));
+                }
+            }
+        }
+    }
+}
+
+impl futures_core::Stream for RecvMulti<'_> {
+    type Item = io::Result<(BorrowedBuf, usize)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl From<OwnedFd> for TcpStream {
+    fn from(fd: OwnedFd) -> Self {
+        TcpStream { fd: File::from(fd) }
+    }
+}
+
+impl From<TcpStream> for OwnedFd {
+    fn from(stream: TcpStream) -> Self {
+        OwnedFd::from(stream.fd)
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::runtime::RuntimeBuilder;
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+
+    fn set_int(fd: &impl AsRawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int) {
+        let len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let value = (&value as *const libc::c_int).cast();
+        assert_eq!(
+            unsafe { libc::setsockopt(fd.as_raw_fd(), level, name, value, len) },
+            0
+        );
+    }
+
+    #[test]
+    fn connect_v4_v6_and_refused() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for local in ["127.0.0.1:0", "[::1]:0"] {
+                let listener = std::net::TcpListener::bind(local).unwrap();
+                let addr = listener.local_addr().unwrap();
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let (mut peer, _) = listener.accept().unwrap();
+                // Closing the stream ends the stream of the peer.
+                drop(OwnedFd::from(stream));
+                assert_eq!(peer.read(&mut [0; 1]).unwrap(), 0);
+            }
+
+            // Nothing listens on the port of a dropped listener, the later
+            // address is tried after the refused one.
+            let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let refused = closed.local_addr().unwrap();
+            drop(closed);
+            let err = TcpStream::connect(refused).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+            assert!(err.to_string().starts_with(&format!("connect {refused}: ")));
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let open = listener.local_addr().unwrap();
+            TcpStream::connect(&[refused, open][..]).await.unwrap();
+        });
+    }
+
+    #[test]
+    fn short_writes_and_peer_close() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        // Inherited by the accepted socket, keeps little in flight.
+        set_int(&listener, libc::SOL_SOCKET, libc::SO_RCVBUF, 4096);
+        let addr = listener.local_addr().unwrap();
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| (i % 251) as u8).collect();
+        let expected = data.clone();
+        let reader = std::thread::spawn(move || {
+            let (mut peer, _) = listener.accept().unwrap();
+            let mut got = Vec::new();
+            let mut chunk = [0; 1000];
+            while got.len() < expected.len() {
+                std::thread::sleep(std::time::Duration::from_micros(50));
+                let n = peer.read(&mut chunk).unwrap();
+                got.extend_from_slice(&chunk[..n]);
+            }
+            assert!(got == expected);
+            peer.write_all(b"done").unwrap();
+        });
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.set_send_buffer_size(4096).unwrap();
+            let (n, data) = stream.write(data).await;
+            let n = n.unwrap();
+            assert!(n < data.len(), "sent {n} at once");
+            stream.write_all(data.slice(n..)).await.0.unwrap();
+            let (n, buf) = stream.read_exact(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"done");
+            // The peer closed, the end of the stream is no error.
+            reader.join().unwrap();
+            let (n, _) = stream.read(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn reset_is_surfaced() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let (peer, _) = listener.accept().unwrap();
+            // Closing with a zero linger resets the connection.
+            let linger = libc::linger {
+                l_onoff: 1,
+                l_linger: 0,
+            };
+            let len = std::mem::size_of::<libc::linger>() as libc::socklen_t;
+            let ptr = (&linger as *const libc::linger).cast();
+            let res = unsafe {
+                libc::setsockopt(
+                    peer.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_LINGER,
+                    ptr,
+                    len,
+                )
+            };
+            assert_eq!(res, 0);
+            drop(peer);
+            let (n, _) = stream.read(Vec::with_capacity(4)).await;
+            assert_eq!(n.unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        });
+    }
+
+    #[test]
+    fn socket_options() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let listener = crate::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.set_nodelay(true).unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (accepted, _) = listener.accept().await.unwrap();
+            assert!(accepted.nodelay().unwrap());
+
+            assert!(!stream.nodelay().unwrap());
+            stream.set_nodelay(true).unwrap();
+            assert!(stream.nodelay().unwrap());
+            stream.set_ttl(42).unwrap();
+            assert_eq!(stream.ttl().unwrap(), 42);
+            let err = stream.set_ttl(0).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+            // Doubled by the kernel, below the default caps.
+            stream.set_recv_buffer_size(8192).unwrap();
+            assert_eq!(stream.recv_buffer_size().unwrap(), 2 * 8192);
+            stream.set_send_buffer_size(8192).unwrap();
+            assert_eq!(stream.send_buffer_size().unwrap(), 2 * 8192);
+        });
+    }
+
+    #[test]
+    fn keepalive_on_and_off() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            assert_eq!(stream.keepalive().unwrap(), None);
+            let keepalive = KeepAlive {
+                time: Duration::from_secs(60),
+                interval: Duration::from_secs(10),
+                retries: 5,
+            };
+            stream.set_keepalive(Some(keepalive)).unwrap();
+            assert_eq!(stream.keepalive().unwrap(), Some(keepalive));
+            let shorter = KeepAlive {
+                time: Duration::from_secs(20),
+                ..keepalive
+            };
+            stream.set_keepalive(Some(shorter)).unwrap();
+            assert_eq!(stream.keepalive().unwrap(), Some(shorter));
+            stream.set_keepalive(None).unwrap();
+            assert_eq!(stream.keepalive().unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn both_sides_agree_on_addresses() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for local in ["127.0.0.1:0", "[::1]:0"] {
+                let listener = crate::net::TcpListener::bind(local).unwrap();
+                let addr = listener.local_addr().unwrap();
+                let client = TcpStream::connect(addr).await.unwrap();
+                let (server, peer) = listener.accept().await.unwrap();
+                assert_eq!(client.peer_addr().unwrap(), addr);
+                assert_eq!(server.local_addr().unwrap(), addr);
+                assert_eq!(server.peer_addr().unwrap(), client.local_addr().unwrap());
+                assert_eq!(peer, client.local_addr().unwrap());
+                assert_eq!(peer.is_ipv6(), addr.is_ipv6());
+            }
+        });
+    }
+
+    #[test]
+    fn send_file_over_loopback() {
+        // FNV-1a, over everything the client got.
+        fn checksum(data: &[u8]) -> u64 {
+            data.iter().fold(0xcbf2_9ce4_8422_2325, |h, &b| {
+                (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+            })
+        }
+        let path = std::env::temp_dir().join(format!("loop-send-file-{}", std::process::id()));
+        let data: Vec<u8> = (0..3_000_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = std::thread::spawn(move || {
+            let (mut peer, _) = listener.accept().unwrap();
+            let mut got = Vec::new();
+            peer.read_to_end(&mut got).unwrap();
+            (got.len(), checksum(&got))
+        });
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let expected = rt.block_on(async {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let file = File::open(&path).await.unwrap();
+            // From an offset, in pieces, the file position stays put.
+            let sent = stream.send_file(&file, 1000, 2_000_000).await.unwrap();
+            assert_eq!(sent, 2_000_000);
+            // The end comes before `len`.
+            let sent = stream.send_file(&file, 2_001_000, u64::MAX).await.unwrap();
+            assert_eq!(sent, 999_000);
+            let (n, head) = file.read(Vec::with_capacity(4)).await;
+            assert_eq!((n.unwrap(), &head[..]), (4, &data[..4]));
+
+            // A pipe is read and sent, it has no offsets.
+            let pipe = crate::fs::Pipe::new().unwrap();
+            let (n, _) = pipe.writer().write(b"tail".to_vec()).await;
+            assert_eq!(n.unwrap(), 4);
+            let err = stream.send_file(pipe.reader(), 1, 4).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            assert_eq!(stream.send_file(pipe.reader(), 0, 4).await.unwrap(), 4);
+
+            let mut expected = data[1000..].to_vec();
+            expected.extend_from_slice(b"tail");
+            (expected.len(), checksum(&expected))
+        });
+        // Closes the stream, the client reads the end.
+        drop(rt);
+        assert_eq!(client.join().unwrap(), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn peek_leaves_bytes_queued() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let (mut peer, _) = listener.accept().unwrap();
+            peer.write_all(b"\x16\x03\x01 hello").unwrap();
+
+            let (n, head) = stream.peek(Vec::with_capacity(5)).await;
+            assert_eq!(n.unwrap(), 5);
+            assert_eq!(head, b"\x16\x03\x01 h");
+            let (n, all) = stream.read_exact(Vec::with_capacity(9)).await;
+            assert_eq!(n.unwrap(), 9);
+            assert_eq!(all, b"\x16\x03\x01 hello");
+
+            drop(peer);
+            let (n, _) = stream.peek(Vec::with_capacity(1)).await;
+            assert_eq!(n.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn connect_timeout_and_racing() {
+        // A backlog of 0 takes one connection, the handshakes after it are
+        // left unanswered.
+        let config = crate::net::ListenerConfig::new().backlog(0);
+        let full = crate::net::TcpListener::bind_with_config("127.0.0.1:0", &config).unwrap();
+        let blackhole = full.local_addr().unwrap();
+        let _queued = std::net::TcpStream::connect(blackhole).unwrap();
+        let v6 = std::net::TcpListener::bind("[::1]:0").unwrap();
+        let open = v6.local_addr().unwrap();
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let timeout = Duration::from_millis(50);
+            let err = TcpStream::connect_timeout(&blackhole, timeout)
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(err
+                .to_string()
+                .starts_with(&format!("connect {blackhole}: ")));
+            let stream = TcpStream::connect_timeout(&open, timeout).await.unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), open);
+
+            // The hanging address is raced by the next one after a while.
+            let start = std::time::Instant::now();
+            let stream = TcpStream::connect_all(&[blackhole, open][..])
+                .await
+                .unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), open);
+            assert!(start.elapsed() >= STAGGER);
+
+            // A failure starts the next one right away.
+            let refused = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let refused_addr = refused.local_addr().unwrap();
+            drop(refused);
+            let start = std::time::Instant::now();
+            let stream = TcpStream::connect_all(&[refused_addr, open][..])
+                .await
+                .unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), open);
+            assert!(start.elapsed() < STAGGER / 5, "{:?}", start.elapsed());
+            // Also while an earlier one still hangs.
+            let start = std::time::Instant::now();
+            let stream = TcpStream::connect_all(&[blackhole, refused_addr, open][..])
+                .await
+                .unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), open);
+            let elapsed = start.elapsed();
+            assert!(elapsed >= STAGGER, "{elapsed:?}");
+            assert!(elapsed < STAGGER + STAGGER / 5, "{elapsed:?}");
+            let err = TcpStream::connect_all(refused_addr).await.err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        });
+    }
+
+    #[test]
+    fn recv_multi_with_ring() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let stream = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (mut peer, _) = listener.accept().unwrap();
+            let ring = BufRing::new(7, 4, 16).unwrap();
+            let mut recv = stream.recv_multi(&ring);
+
+            // Twice the ring: it runs dry while every buffer is held, and
+            // goes on once they are back.
+            let data: Vec<u8> = (0..128).collect();
+            peer.write_all(&data).unwrap();
+            let mut got = Vec::new();
+            let mut held = Vec::new();
+            while got.len() < data.len() {
+                let (buf, n) = recv.next().await.unwrap().unwrap();
+                assert_eq!(n, buf.len());
+                got.extend_from_slice(&buf);
+                held.push(buf);
+                if held.len() == 4 {
+                    assert_eq!(ring.outstanding(), 4);
+                    held.clear();
+                }
+            }
+            assert_eq!(got, data);
+
+            // Dropped with a buffer queued, it goes back to the ring.
+            peer.write_all(&data[..16]).unwrap();
+            let (first, _) = recv.next().await.unwrap().unwrap();
+            peer.write_all(&data[16..32]).unwrap();
+            let sleep = || async {
+                let mut timer = Op::timeout(Duration::from_millis(50)).unwrap();
+                poll_fn(|cx| timer.poll_expired(cx)).await.unwrap();
+            };
+            sleep().await;
+            drop(recv);
+            assert_eq!(ring.outstanding(), 1);
+            drop(first);
+            // Until the cancel lands the old receive takes what arrives.
+            sleep().await;
+            let mut recv = stream.recv_multi(&ring);
+            // Every buffer is there to be filled again.
+            peer.write_all(&data[..64]).unwrap();
+            let mut held = Vec::new();
+            while held.len() < 4 {
+                held.push(recv.next().await.unwrap().unwrap().0);
+            }
+            assert_eq!(ring.outstanding(), 4);
+            drop(held);
+
+            drop(peer);
+            while let Some(res) = recv.next().await {
+                res.unwrap();
+            }
+            assert!(recv.next().await.is_none());
+        });
+    }
+}
@@ -0,0 +1,263 @@
+use super::{socket, KeepAlive, TcpListener, TcpStream};
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+
+/// A TCP socket not connected or listening yet, for options that only take
+/// effect before.
+///
+/// ```no_run
+/// # use Loop::net::TcpSocket;
+/// # async fn dial() -> std::io::Result<()> {
+/// let socket = TcpSocket::new_v4()?;
+/// socket.set_reuseaddr(true)?;
+/// socket.bind("10.0.0.2:4000".parse().unwrap())?;
+/// let stream = socket.connect("10.0.0.1:80".parse().unwrap()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TcpSocket {
+    fd: File,
+}
+
+impl TcpSocket {
+    /// A new IPv4 socket.
+    pub fn new_v4() -> io::Result<TcpSocket> {
+        Self::new(libc::AF_INET)
+    }
+
+    /// A new IPv6 socket.
+    pub fn new_v6() -> io::Result<TcpSocket> {
+        Self::new(libc::AF_INET6)
+    }
+
+    fn new(domain: libc::c_int) -> io::Result<TcpSocket> {
+        let fd = socket::open(domain, libc::SOCK_STREAM)?;
+        Ok(TcpSocket { fd })
+    }
+
+    /// Allow binding to an address still in `TIME_WAIT`, `SO_REUSEADDR`.
+    pub fn set_reuseaddr(&self, reuse: bool) -> io::Result<()> {
+        let reuse = reuse as libc::c_int;
+        socket::set_int(&self.fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, reuse)
+    }
+
+    /// Let sockets that all set it bind the same address, `SO_REUSEPORT`.
+    /// The kernel spreads the connections over the listeners among them.
+    pub fn set_reuseport(&self, reuse: bool) -> io::Result<()> {
+        let reuse = reuse as libc::c_int;
+        socket::set_int(&self.fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, reuse)
+    }
+
+    /// Send small writes right away instead of batching them (Nagle),
+    /// `TCP_NODELAY`. Accepted streams inherit it.
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        socket::set_nodelay(&self.fd, nodelay)
+    }
+
+    /// Probe idle connections as `keepalive` says, or not for `None`,
+    /// `SO_KEEPALIVE`. Connections accepted by a listener inherit it.
+    pub fn set_keepalive(&self, keepalive: Option<KeepAlive>) -> io::Result<()> {
+        socket::set_keepalive(&self.fd, keepalive)
+    }
+
+    /// How idle connections are probed, `None` if they are not.
+    pub fn keepalive(&self) -> io::Result<Option<KeepAlive>> {
+        socket::keepalive(&self.fd)
+    }
+
+    /// Ask for a receive buffer of `size` bytes, `SO_RCVBUF`. Set before
+    /// connecting it sizes the window the handshake offers.
+    pub fn set_recv_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&self.fd, libc::SO_RCVBUF, size)
+    }
+
+    /// Ask for a send buffer of `size` bytes, `SO_SNDBUF`.
+    pub fn set_send_buffer_size(&self, size: usize) -> io::Result<()> {
+        socket::set_buffer_size(&self.fd, libc::SO_SNDBUF, size)
+    }
+
+    /// Only send and receive through the interface named `interface`, or any
+    /// again for `None`, `SO_BINDTODEVICE`. Takes `CAP_NET_RAW` unless the
+    /// socket is bound to none yet.
+    pub fn bind_device(&self, interface: Option<&[u8]>) -> io::Result<()> {
+        let name = interface.unwrap_or(&[]);
+        socket::set_bytes(&self.fd, libc::SOL_SOCKET, libc::SO_BINDTODEVICE, name)
+    }
+
+    /// Bind to `addr`, to pick the source address and port of a connect.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<()> {
+        socket::bind(&self.fd, &addr)
+    }
+
+    /// The address the socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(&self.fd)
+    }
+
+    /// Connect to `addr`.
+    pub async fn connect(self, addr: SocketAddr) -> io::Result<TcpStream> {
+        TcpStream::connect_with(self.fd, addr).await
+    }
+
+    /// Listen for connections, with room for `backlog` not accepted yet.
+    /// Binds to a port the kernel picks unless bound before.
+    pub fn listen(self, backlog: u32) -> io::Result<TcpListener> {
+        let raw = self.fd.as_raw_fd();
+        socket::listen(&self.fd, backlog).op_fd("listen", raw)?;
+        Ok(TcpListener::from(OwnedFd::from(self.fd)))
+    }
+}
+
+impl From<OwnedFd> for TcpSocket {
+    fn from(fd: OwnedFd) -> Self {
+        TcpSocket { fd: File::from(fd) }
+    }
+}
+
+impl From<TcpSocket> for OwnedFd {
+    fn from(socket: TcpSocket) -> Self {
+        OwnedFd::from(socket.fd)
+    }
+}
+
+/// The socket as it is set up. Ops on the ring work with blocking sockets
+/// too, at the cost of a kernel worker thread each.
+#[cfg(feature = "socket2")]
+impl From<socket2::Socket> for TcpSocket {
+    fn from(socket: socket2::Socket) -> Self {
+        TcpSocket::from(OwnedFd::from(socket))
+    }
+}
+
+#[cfg(feature = "socket2")]
+impl From<TcpSocket> for socket2::Socket {
+    fn from(socket: TcpSocket) -> Self {
+        socket2::Socket::from(OwnedFd::from(socket))
+    }
+}
+
+impl AsRawFd for TcpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::time::Duration;
+
+    #[test]
+    fn bind_before_connect() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_reuseaddr(true).unwrap();
+            socket.set_nodelay(true).unwrap();
+            socket.set_recv_buffer_size(1 << 16).unwrap();
+            socket.set_send_buffer_size(1 << 16).unwrap();
+            socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            let source = socket.local_addr().unwrap();
+            assert_ne!(source.port(), 0);
+
+            let stream = socket
+                .connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(stream.local_addr().unwrap(), source);
+            assert!(stream.nodelay().unwrap());
+            let (_, peer) = listener.accept().unwrap();
+            assert_eq!(peer, source);
+        });
+    }
+
+    #[test]
+    fn keepalive_for_dialed_and_accepted() {
+        let keepalive = KeepAlive {
+            time: Duration::from_secs(30),
+            interval: Duration::from_millis(2500),
+            retries: 4,
+        };
+        // Sub-second parts round up.
+        let expected = KeepAlive {
+            interval: Duration::from_secs(3),
+            ..keepalive
+        };
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_keepalive(Some(keepalive)).unwrap();
+            socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            let addr = socket.local_addr().unwrap();
+            let listener = socket.listen(16).unwrap();
+
+            let socket = TcpSocket::new_v4().unwrap();
+            assert_eq!(socket.keepalive().unwrap(), None);
+            socket.set_keepalive(Some(keepalive)).unwrap();
+            let dialed = socket.connect(addr).await.unwrap();
+            assert_eq!(dialed.keepalive().unwrap(), Some(expected));
+            let (accepted, _) = listener.accept().await.unwrap();
+            assert_eq!(accepted.keepalive().unwrap(), Some(expected));
+
+            let err = TcpSocket::new_v4()
+                .unwrap()
+                .set_keepalive(Some(KeepAlive {
+                    time: Duration::ZERO,
+                    ..keepalive
+                }))
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        });
+    }
+
+    #[test]
+    fn reuseport_listener_pair() {
+        let listen = |addr: SocketAddr| {
+            let socket = TcpSocket::new_v4().unwrap();
+            socket.set_reuseport(true).unwrap();
+            socket.bind(addr)?;
+            socket.listen(16)
+        };
+        let first = listen("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = first.local_addr().unwrap();
+        let second = listen(addr).unwrap();
+        assert_eq!(second.local_addr().unwrap(), addr);
+
+        // Taken by the pair for a socket without it.
+        let socket = TcpSocket::new_v4().unwrap();
+        let err = socket.bind(addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        // Each connection goes to one of the two.
+        let listeners: Vec<std::net::TcpListener> = [first, second]
+            .into_iter()
+            .map(|l| OwnedFd::from(l).into())
+            .collect();
+        for listener in &listeners {
+            listener.set_nonblocking(true).unwrap();
+        }
+        let clients: Vec<_> = (0..8)
+            .map(|_| std::net::TcpStream::connect(addr).unwrap())
+            .collect();
+        let mut accepted = 0;
+        while accepted < clients.len() {
+            for listener in &listeners {
+                if listener.accept().is_ok() {
+                    accepted += 1;
+                }
+            }
+        }
+    }
+}
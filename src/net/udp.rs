@@ -0,0 +1,570 @@
+use super::{addr, lookup, socket, ToSocketAddrs};
+use crate::buf::{BorrowedBuf, BufResult, BufRing, IoBuf, IoBufMut};
+use crate::driver::buf_group::Exhausted;
+use crate::driver::net::msg::{self, RecvMsgMulti};
+use crate::driver::net::recv::RecvMulti;
+use crate::driver::op::Op;
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::future::poll_fn;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// A UDP socket.
+///
+/// Receives report the length of the whole datagram: a count over the size
+/// of the buffer means the datagram was truncated to fit. Dropping the
+/// socket closes it in the background.
+pub struct UdpSocket {
+    fd: File,
+}
+
+impl UdpSocket {
+    /// Bind to the first address of `addr` that can be bound. Fails with the
+    /// error of the last one tried. Takes numeric addresses only, a host
+    /// name fails with [`io::ErrorKind::InvalidInput`], look it up with
+    /// [`lookup_host`](super::lookup_host) first.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<UdpSocket> {
+        let mut last = None;
+        for addr in lookup::resolve_numeric(addr)? {
+            let bound = socket::new(&addr, libc::SOCK_DGRAM)
+                .and_then(|fd| socket::bind(&fd, &addr).map(|()| fd));
+            match bound {
+                Ok(fd) => return Ok(UdpSocket { fd }),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to bind to")
+        }))
+    }
+
+    /// Set the peer for [`send`](UdpSocket::send) and
+    /// [`recv`](UdpSocket::recv), datagrams from other addresses are
+    /// dropped. Takes the first address of `addr` that works, numeric ones
+    /// only like [`bind`](UdpSocket::bind).
+    pub fn connect(&self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        let mut last = None;
+        for addr in lookup::resolve_numeric(addr)? {
+            match socket::connect(&self.fd, &addr) {
+                Ok(()) => return Ok(()),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        }))
+    }
+
+    /// Send `buf` as one datagram to `addr`.
+    pub async fn send_to<T: IoBuf>(&self, buf: T, addr: SocketAddr) -> BufResult<usize, T> {
+        let (n, (buf, _)) = self.send_msg(buf, Vec::new(), Some(addr)).await;
+        (n, buf)
+    }
+
+    /// Receive a datagram into `buf`, returning its length and where it came
+    /// from.
+    pub async fn recv_from<T: IoBufMut>(&self, buf: T) -> BufResult<(usize, SocketAddr), T> {
+        let (res, (buf, _)) = self.recv_msg(buf, Vec::new()).await;
+        (res, buf)
+    }
+
+    /// Send `buf` as one datagram with the control messages `ctrl`, see
+    /// [`CmsgBuilder`](super::CmsgBuilder). To `addr`, or the peer set by
+    /// [`connect`](UdpSocket::connect) when `None`.
+    pub async fn send_msg<T: IoBuf>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+        addr: Option<SocketAddr>,
+    ) -> BufResult<usize, (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send_msg(fd, buf, addr.as_ref().map(addr::to_raw), ctrl) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("sendmsg", fd), (data.buf, data.ctrl)),
+        };
+        let (n, bufs) = op.result().await;
+        match addr {
+            Some(addr) => (n.op_addr("sendmsg", addr), bufs),
+            None => (n.op_fd("sendmsg", fd), bufs),
+        }
+    }
+
+    /// Receive a datagram into `buf` and its control messages into the
+    /// capacity of `ctrl`, see [`Cmsgs`](super::Cmsgs). Size `ctrl` with
+    /// [`cmsg::space`](super::cmsg::space), messages that do not fit are
+    /// dropped.
+    pub async fn recv_msg<T: IoBufMut>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+    ) -> BufResult<(usize, SocketAddr), (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv_msg(fd, buf, ctrl, libc::MSG_TRUNC) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recvmsg", fd), (data.buf, data.ctrl)),
+        };
+        let (received, bufs) = op.result().await;
+        let res = received.and_then(|received| Ok((received.len, received.source()?)));
+        (res.op_fd("recvmsg", fd), bufs)
+    }
+
+    /// Send `buf` as one datagram to the peer set by
+    /// [`connect`](UdpSocket::connect).
+    pub async fn send<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send(fd, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("send", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("send", fd), buf)
+    }
+
+    /// Receive a datagram from the peer set by
+    /// [`connect`](UdpSocket::connect), returning its length.
+    pub async fn recv<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv(fd, buf, libc::MSG_TRUNC) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("recv", fd), buf)
+    }
+
+    /// Receive datagrams into the buffers of `ring` with a single multishot
+    /// entry. Each buffer holds a header of 44 bytes before the datagram,
+    /// size them for it. Fails with [`io::ErrorKind::Unsupported`] on kernels
+    /// before 6.0.
+    pub fn recv_multi(&self, ring: &BufRing) -> io::Result<RecvFromMulti<'_>> {
+        if !Op::<RecvMulti>::is_recv_multi_supported() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "multishot receive is not supported by the kernel(6.0+)",
+            ));
+        }
+        Ok(RecvFromMulti {
+            socket: self,
+            ring: ring.clone(),
+            op: Receiving::Idle,
+        })
+    }
+
+    /// The local address of the socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket::local_addr(&self.fd)
+    }
+
+    /// The peer set by [`connect`](UdpSocket::connect).
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        socket::peer_addr(&self.fd)
+    }
+
+    /// Allow sending to broadcast addresses, `SO_BROADCAST`.
+    pub fn set_broadcast(&self, on: bool) -> io::Result<()> {
+        socket::set_int(
+            &self.fd,
+            libc::SOL_SOCKET,
+            libc::SO_BROADCAST,
+            on as libc::c_int,
+        )
+    }
+
+    /// Whether `SO_BROADCAST` is set.
+    pub fn broadcast(&self) -> io::Result<bool> {
+        Ok(socket::get_int(&self.fd, libc::SOL_SOCKET, libc::SO_BROADCAST)? != 0)
+    }
+
+    /// Set the time to live of outgoing unicast IPv4 packets, `IP_TTL`.
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        socket::set_ttl(&self.fd, ttl)
+    }
+
+    /// The time to live of outgoing unicast IPv4 packets.
+    pub fn ttl(&self) -> io::Result<u32> {
+        socket::ttl(&self.fd)
+    }
+
+    /// Set the type of service byte of outgoing IPv4 packets, `IP_TOS`, the
+    /// DSCP in its upper six bits and ECN in the lower two.
+    pub fn set_tos(&self, tos: u8) -> io::Result<()> {
+        socket::set_int(&self.fd, libc::IPPROTO_IP, libc::IP_TOS, tos as libc::c_int)
+    }
+
+    /// The type of service byte of outgoing IPv4 packets.
+    pub fn tos(&self) -> io::Result<u8> {
+        Ok(socket::get_int(&self.fd, libc::IPPROTO_IP, libc::IP_TOS)? as u8)
+    }
+
+    /// Receive the datagrams sent to the group `multiaddr`, on the interface
+    /// with the local address `interface`, or one the kernel picks by route
+    /// for [`Ipv4Addr::UNSPECIFIED`]. The socket must be bound to the port
+    /// they are sent to.
+    pub fn join_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = mreq_v4(multiaddr, interface);
+        socket::set_struct(&self.fd, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, &mreq)
+    }
+
+    /// Leave a group joined with [`join_multicast_v4`](Self::join_multicast_v4).
+    pub fn leave_multicast_v4(&self, multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> io::Result<()> {
+        let mreq = mreq_v4(multiaddr, interface);
+        socket::set_struct(&self.fd, libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP, &mreq)
+    }
+
+    /// Receive the datagrams sent to the group `multiaddr`, on the interface
+    /// with the index `interface`, or one the kernel picks by route for 0.
+    pub fn join_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = mreq_v6(multiaddr, interface);
+        socket::set_struct(
+            &self.fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_ADD_MEMBERSHIP,
+            &mreq,
+        )
+    }
+
+    /// Leave a group joined with [`join_multicast_v6`](Self::join_multicast_v6).
+    pub fn leave_multicast_v6(&self, multiaddr: &Ipv6Addr, interface: u32) -> io::Result<()> {
+        let mreq = mreq_v6(multiaddr, interface);
+        socket::set_struct(
+            &self.fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_DROP_MEMBERSHIP,
+            &mreq,
+        )
+    }
+
+    /// Send outgoing IPv4 multicast from the interface with the local
+    /// address `interface`, `IP_MULTICAST_IF`. [`Ipv4Addr::UNSPECIFIED`]
+    /// goes back to picking one by route.
+    pub fn set_multicast_if_v4(&self, interface: &Ipv4Addr) -> io::Result<()> {
+        let addr = libc::in_addr {
+            s_addr: u32::from_ne_bytes(interface.octets()),
+        };
+        socket::set_struct(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_IF, &addr)
+    }
+
+    /// The interface outgoing IPv4 multicast is sent from.
+    pub fn multicast_if_v4(&self) -> io::Result<Ipv4Addr> {
+        let addr = socket::get_int(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_IF)?;
+        Ok(Ipv4Addr::from(addr.to_ne_bytes()))
+    }
+
+    /// Deliver outgoing IPv4 multicast to the groups joined on this host
+    /// too, `IP_MULTICAST_LOOP`. On by default.
+    pub fn set_multicast_loop_v4(&self, on: bool) -> io::Result<()> {
+        let on = on as libc::c_int;
+        socket::set_int(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, on)
+    }
+
+    /// Whether `IP_MULTICAST_LOOP` is set.
+    pub fn multicast_loop_v4(&self) -> io::Result<bool> {
+        Ok(socket::get_int(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP)? != 0)
+    }
+
+    /// Set the time to live of outgoing IPv4 multicast, `IP_MULTICAST_TTL`.
+    /// 1 by default, which keeps it on the local network.
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> io::Result<()> {
+        let ttl = ttl.min(libc::c_int::MAX as u32) as libc::c_int;
+        socket::set_int(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, ttl)
+    }
+
+    /// The time to live of outgoing IPv4 multicast.
+    pub fn multicast_ttl_v4(&self) -> io::Result<u32> {
+        Ok(socket::get_int(&self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)? as u32)
+    }
+}
+
+fn mreq_v4(multiaddr: &Ipv4Addr, interface: &Ipv4Addr) -> libc::ip_mreq {
+    libc::ip_mreq {
+        imr_multiaddr: libc::in_addr {
+            s_addr: u32::from_ne_bytes(multiaddr.octets()),
+        },
+        imr_interface: libc::in_addr {
+            s_addr: u32::from_ne_bytes(interface.octets()),
+        },
+    }
+}
+
+fn mreq_v6(multiaddr: &Ipv6Addr, interface: u32) -> libc::ipv6_mreq {
+    libc::ipv6_mreq {
+        ipv6mr_multiaddr: libc::in6_addr {
+            s6_addr: multiaddr.octets(),
+        },
+        ipv6mr_interface: interface,
+    }
+}
+
+/// Datagrams received into the buffers of a ring, from
+/// [`UdpSocket::recv_multi`].
+///
+/// The kernel keeps receiving between calls to
+/// [`next`](RecvFromMulti::next), the datagrams wait here in order. With
+/// every buffer of the ring borrowed the receive stops, and starts again
+/// once one is dropped. Dropping it cancels the receive and gives the
+/// buffers not taken back to the ring, dropping the datagrams in them.
+pub struct RecvFromMulti<'a> {
+    socket: &'a UdpSocket,
+    ring: BufRing,
+    op: Receiving,
+}
+
+enum Receiving {
+    Idle,
+    // The ring ran dry, waiting for a buffer to come back.
+    Starved,
+    Multishot(Op<RecvMsgMulti>),
+}
+
+impl RecvFromMulti<'_> {
+    /// The next datagram, with its whole length and where it came from. A
+    /// length over the size of the buffer means it was truncated to fit.
+    /// The receive ends with an error, the call after it starts a new one.
+    pub async fn next(&mut self) -> io::Result<(BorrowedBuf, usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<(BorrowedBuf, usize, SocketAddr)>> {
+        let fd = self.socket.as_raw_fd();
+        let group = self.ring.group();
+        loop {
+            let meta = match &mut self.op {
+                Receiving::Starved => {
+                    ready!(group.poll_available(cx));
+                    self.op = Receiving::Idle;
+                    continue;
+                }
+                Receiving::Idle => {
+                    let op = Op::recv_msg_multi(fd, group).op_fd("recvmsg", fd)?;
+                    self.op = Receiving::Multishot(op);
+                    continue;
+                }
+                Receiving::Multishot(op) => {
+                    let meta = ready!(op.poll_next(cx));
+                    if op.is_terminated() {
+                        self.op = Receiving::Idle;
+                    }
+                    meta
+                }
+            };
+            let buf = match group.take(meta) {
+                Err(e) if Exhausted::from_io_error(&e).is_some() => {
+                    self.op = Receiving::Starved;
+                    continue;
+                }
+                res => res.op_fd("recvmsg", fd)?,
+            };
+            let (buf, received) = msg::parse_msg_out(buf).op_fd("recvmsg", fd)?;
+            let source = received.source().op_fd("recvmsg", fd)?;
+            return Poll::Ready(Ok((buf, received.len, source)));
+        }
+    }
+}
+
+impl futures_core::Stream for RecvFromMulti<'_> {
+    type Item = io::Result<(BorrowedBuf, usize, SocketAddr)>;
+
+    /// Never ends, see [`RecvFromMulti::next`].
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx).map(Some)
+    }
+}
+
+impl From<OwnedFd> for UdpSocket {
+    fn from(fd: OwnedFd) -> Self {
+        UdpSocket { fd: File::from(fd) }
+    }
+}
+
+impl From<UdpSocket> for OwnedFd {
+    fn from(socket: UdpSocket) -> Self {
+        OwnedFd::from(socket.fd)
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for UdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    // The largest payload of an IPv4 datagram.
+    const MAX_V4: usize = 65_507;
+
+    #[test]
+    fn multicast_over_loopback() {
+        let group = Ipv4Addr::new(224, 0, 0, 251);
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let receiver = UdpSocket::bind("0.0.0.0:0").unwrap();
+            let port = receiver.local_addr().unwrap().port();
+            receiver
+                .join_multicast_v4(&group, &Ipv4Addr::LOCALHOST)
+                .unwrap();
+            // Joining twice is refused.
+            let err = receiver
+                .join_multicast_v4(&group, &Ipv4Addr::LOCALHOST)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+            let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+            sender.set_multicast_if_v4(&Ipv4Addr::LOCALHOST).unwrap();
+            assert_eq!(sender.multicast_if_v4().unwrap(), Ipv4Addr::LOCALHOST);
+            assert!(sender.multicast_loop_v4().unwrap());
+            sender.set_multicast_loop_v4(true).unwrap();
+            sender.set_multicast_ttl_v4(4).unwrap();
+            assert_eq!(sender.multicast_ttl_v4().unwrap(), 4);
+            sender.set_tos(0xb8).unwrap();
+            assert_eq!(sender.tos().unwrap(), 0xb8);
+            sender.set_ttl(9).unwrap();
+            assert_eq!(sender.ttl().unwrap(), 9);
+
+            let to = SocketAddr::from((group, port));
+            let (n, _) = sender.send_to(b"mdns".to_vec(), to).await;
+            assert_eq!(n.unwrap(), 4);
+            let (res, buf) = receiver.recv_from(Vec::with_capacity(16)).await;
+            let (n, from) = res.unwrap();
+            assert_eq!(
+                (&buf[..n], from),
+                (&b"mdns"[..], sender.local_addr().unwrap())
+            );
+
+            receiver
+                .leave_multicast_v4(&group, &Ipv4Addr::LOCALHOST)
+                .unwrap();
+            let err = receiver
+                .leave_multicast_v4(&group, &Ipv4Addr::LOCALHOST)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AddrNotAvailable);
+
+            // On the loopback interface, by its index.
+            let v6 = UdpSocket::bind("[::]:0").unwrap();
+            let group = "ff02::fb".parse().unwrap();
+            v6.join_multicast_v6(&group, 1).unwrap();
+            v6.leave_multicast_v6(&group, 1).unwrap();
+            let err = v6.leave_multicast_v6(&group, 1).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::AddrNotAvailable);
+
+            assert!(!sender.broadcast().unwrap());
+            sender.set_broadcast(true).unwrap();
+            assert!(sender.broadcast().unwrap());
+        });
+    }
+
+    #[test]
+    fn ping_pong() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let (a_addr, b_addr) = (a.local_addr().unwrap(), b.local_addr().unwrap());
+
+            for len in [MAX_V4, 1] {
+                let ping: Vec<u8> = (0..len).map(|i| i as u8).collect();
+                let (n, _) = a.send_to(ping.clone(), b_addr).await;
+                assert_eq!(n.unwrap(), len);
+                let (res, buf) = b.recv_from(Vec::with_capacity(MAX_V4)).await;
+                assert_eq!(res.unwrap(), (len, a_addr));
+                assert!(buf == ping);
+                let (n, _) = b.send_to(buf, a_addr).await;
+                assert_eq!(n.unwrap(), len);
+                let (res, buf) = a.recv_from(Vec::with_capacity(MAX_V4)).await;
+                assert_eq!(res.unwrap(), (len, b_addr));
+                assert!(buf == ping);
+            }
+
+            // Truncated to the buffer, the length is the whole.
+            a.send_to(vec![7; 100], b_addr).await.0.unwrap();
+            let (res, buf) = b.recv_from(Vec::with_capacity(10)).await;
+            assert_eq!(res.unwrap().0, 100);
+            assert_eq!(buf, [7; 10]);
+
+            // Connected, over v6.
+            let a = UdpSocket::bind("[::1]:0").unwrap();
+            let b = UdpSocket::bind("[::1]:0").unwrap();
+            a.connect(b.local_addr().unwrap()).unwrap();
+            b.connect(a.local_addr().unwrap()).unwrap();
+            assert_eq!(a.peer_addr().unwrap(), b.local_addr().unwrap());
+            a.send(b"ping".to_vec()).await.0.unwrap();
+            let (n, buf) = b.recv(Vec::with_capacity(2)).await;
+            assert_eq!((n.unwrap(), &buf[..]), (4, &b"pi"[..]));
+        });
+    }
+
+    #[test]
+    fn pktinfo_both_ways() {
+        use crate::net::{CmsgBuilder, Cmsgs};
+        use std::net::Ipv4Addr;
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let b = UdpSocket::bind("0.0.0.0:0").unwrap();
+            socket::set_int(&b.fd, libc::IPPROTO_IP, libc::IP_PKTINFO, 1).unwrap();
+            let port = b.local_addr().unwrap().port();
+
+            let ctrl = CmsgBuilder::new().pktinfo(0, Ipv4Addr::LOCALHOST).build();
+            let to = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+            let (n, _) = a.send_msg(b"hi".to_vec(), ctrl, Some(to)).await;
+            assert_eq!(n.unwrap(), 2);
+
+            let ctrl = Vec::with_capacity(crate::net::cmsg::space(64));
+            let (res, (buf, ctrl)) = b.recv_msg(Vec::with_capacity(8), ctrl).await;
+            assert_eq!(res.unwrap(), (2, a.local_addr().unwrap()));
+            assert_eq!(buf, b"hi");
+            let info = Cmsgs::new(&ctrl).find_map(|cmsg| cmsg.pktinfo()).unwrap();
+            assert_eq!(info.dst, Ipv4Addr::LOCALHOST);
+        });
+    }
+
+    #[test]
+    fn recv_multi_with_ring() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+            let b = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+            let from = b.local_addr().unwrap();
+            // 44 bytes of header, room for 20 of the datagram.
+            let ring = BufRing::new(9, 2, 64).unwrap();
+            let mut recv = a.recv_multi(&ring).unwrap();
+
+            for i in 0..5u8 {
+                b.send_to(&[i; 8], a.local_addr().unwrap()).unwrap();
+            }
+            b.send_to(&[9; 30], a.local_addr().unwrap()).unwrap();
+            // Two buffers at a time, the rest waits in the socket.
+            let mut held = Vec::new();
+            for i in 0..5u8 {
+                let (buf, n, source) = recv.next().await.unwrap();
+                assert_eq!((&buf[..], n, source), (&[i; 8][..], 8, from));
+                held.push(buf);
+                if held.len() == 2 {
+                    held.clear();
+                }
+            }
+            let (buf, n, _) = recv.next().await.unwrap();
+            assert_eq!((&buf[..], n), (&[9; 20][..], 30));
+            drop((held, buf));
+            assert_eq!(ring.outstanding(), 0);
+        });
+    }
+}
@@ -0,0 +1,226 @@
+use crate::net::addr::RawAddr;
+use crate::utils::error_ctx::ResultExt;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+const PATH_OFFSET: usize = mem::offset_of!(libc::sockaddr_un, sun_path);
+const PATH_LEN: usize = mem::size_of::<[libc::c_char; 108]>();
+
+/// The address of a unix socket: a path in the file system, a name in the
+/// abstract namespace, or none for a socket that was never bound.
+#[derive(Clone, Copy)]
+pub struct SocketAddr {
+    addr: libc::sockaddr_un,
+    len: libc::socklen_t,
+}
+
+enum Kind<'a> {
+    Unnamed,
+    Pathname(&'a Path),
+    Abstract(&'a [u8]),
+}
+
+impl SocketAddr {
+    /// The address of the socket file at `path`. Fails with
+    /// [`io::ErrorKind::InvalidInput`] for paths with a nul byte or of more
+    /// than 107 bytes.
+    pub fn from_pathname(path: impl AsRef<Path>) -> io::Result<SocketAddr> {
+        let bytes = path.as_ref().as_os_str().as_bytes();
+        if bytes.contains(&0) {
+            return Err(invalid("unix socket path with a nul byte"));
+        }
+        if bytes.is_empty() || bytes.len() >= PATH_LEN {
+            return Err(invalid("unix socket path of 1 to 107 bytes"));
+        }
+        // Counted with its nul.
+        Ok(Self::with_path(bytes, bytes.len() + 1))
+    }
+
+    /// The name `name` in the abstract namespace of Linux, with no file
+    /// behind it. It is gone with the last socket bound to it. Fails with
+    /// [`io::ErrorKind::InvalidInput`] for names of more than 107 bytes.
+    pub fn from_abstract_name(name: impl AsRef<[u8]>) -> io::Result<SocketAddr> {
+        let name = name.as_ref();
+        if name.len() >= PATH_LEN {
+            return Err(invalid("abstract unix socket name of up to 107 bytes"));
+        }
+        // A leading nul, then the name. Not nul terminated.
+        let mut path = [0; PATH_LEN];
+        path[1..=name.len()].copy_from_slice(name);
+        Ok(Self::with_path(&path[..=name.len()], name.len() + 1))
+    }
+
+    fn with_path(bytes: &[u8], len: usize) -> SocketAddr {
+        // Zeroed is a valid sockaddr_un, the nul after the path included.
+        let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        for (dst, src) in addr.sun_path.iter_mut().zip(bytes) {
+            *dst = *src as libc::c_char;
+        }
+        SocketAddr {
+            addr,
+            len: (PATH_OFFSET + len) as libc::socklen_t,
+        }
+    }
+
+    /// Whether the socket was never bound.
+    pub fn is_unnamed(&self) -> bool {
+        matches!(self.kind(), Kind::Unnamed)
+    }
+
+    /// The path of the socket file, for one bound to a path.
+    pub fn as_pathname(&self) -> Option<&Path> {
+        match self.kind() {
+            Kind::Pathname(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The name in the abstract namespace, for one bound to a name.
+    pub fn as_abstract_name(&self) -> Option<&[u8]> {
+        match self.kind() {
+            Kind::Abstract(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> Kind<'_> {
+        let len = (self.len as usize)
+            .saturating_sub(PATH_OFFSET)
+            .min(PATH_LEN);
+        // c_char and u8 have the same layout.
+        let path: &[u8] =
+            unsafe { std::slice::from_raw_parts(self.addr.sun_path.as_ptr().cast(), len) };
+        match path.split_first() {
+            None => Kind::Unnamed,
+            Some((0, name)) => Kind::Abstract(name),
+            Some(_) => {
+                // Up to the nul, when the kernel counted it.
+                let end = path.iter().position(|&b| b == 0).unwrap_or(len);
+                Kind::Pathname(Path::new(OsStr::from_bytes(&path[..end])))
+            }
+        }
+    }
+
+    /// The address in the layout the kernel takes.
+    pub(crate) fn to_raw(self) -> RawAddr {
+        // Zeroed is a valid sockaddr_storage, and a sockaddr_un fits in it.
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        unsafe {
+            (&mut storage as *mut libc::sockaddr_storage)
+                .cast::<libc::sockaddr_un>()
+                .write(self.addr)
+        };
+        (storage, self.len)
+    }
+
+    /// The address the kernel wrote, `len` bytes of it. Fails with
+    /// [`io::ErrorKind::InvalidData`] for other families. Shorter than a
+    /// family, as for the peer of a socketpair, is unnamed.
+    pub(crate) fn from_raw((storage, len): &RawAddr) -> io::Result<SocketAddr> {
+        let len = (*len as usize).min(mem::size_of::<libc::sockaddr_un>());
+        if len >= mem::size_of::<libc::sa_family_t>()
+            && storage.ss_family != libc::AF_UNIX as libc::sa_family_t
+        {
+            let family = storage.ss_family;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unix socket address of family {family}"),
+            ));
+        }
+        // A sockaddr_un is in the first bytes of the storage.
+        let addr = unsafe {
+            (storage as *const libc::sockaddr_storage)
+                .cast::<libc::sockaddr_un>()
+                .read()
+        };
+        Ok(SocketAddr {
+            addr,
+            len: len.max(PATH_OFFSET) as libc::socklen_t,
+        })
+    }
+
+    /// Add the path to an error of `op`, or `fd` for other addresses.
+    pub(crate) fn context<T>(
+        &self,
+        res: io::Result<T>,
+        op: &'static str,
+        fd: RawFd,
+    ) -> io::Result<T> {
+        match self.as_pathname() {
+            Some(path) => res.op_path(op, path),
+            None => res.op_fd(op, fd),
+        }
+    }
+}
+
+fn invalid(what: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, what)
+}
+
+impl fmt::Debug for SocketAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind() {
+            Kind::Unnamed => f.write_str("(unnamed)"),
+            Kind::Pathname(path) => write!(f, "{path:?} (pathname)"),
+            Kind::Abstract(name) => write!(f, "\"{}\" (abstract)", name.escape_ascii()),
+        }
+    }
+}
+
+impl PartialEq for SocketAddr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.kind(), other.kind()) {
+            (Kind::Unnamed, Kind::Unnamed) => true,
+            (Kind::Pathname(a), Kind::Pathname(b)) => a == b,
+            (Kind::Abstract(a), Kind::Abstract(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SocketAddr {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds_and_raw_round_trip() {
+        let path = SocketAddr::from_pathname("/tmp/loop.sock").unwrap();
+        assert_eq!(path.as_pathname(), Some(Path::new("/tmp/loop.sock")));
+        assert_eq!(format!("{path:?}"), "\"/tmp/loop.sock\" (pathname)");
+
+        let name = SocketAddr::from_abstract_name(b"loop\0x").unwrap();
+        assert_eq!(name.as_abstract_name(), Some(&b"loop\0x"[..]));
+        assert_eq!(name.as_pathname(), None);
+        assert_eq!(format!("{name:?}"), "\"loop\\x00x\" (abstract)");
+
+        for addr in [path, name] {
+            assert_eq!(SocketAddr::from_raw(&addr.to_raw()).unwrap(), addr);
+        }
+        let (storage, _) = path.to_raw();
+        for len in [0, 2] {
+            assert!(SocketAddr::from_raw(&(storage, len)).unwrap().is_unnamed());
+        }
+
+        let long = "x".repeat(PATH_LEN);
+        for bad in [
+            SocketAddr::from_pathname(&long),
+            SocketAddr::from_pathname("a\0b"),
+        ] {
+            assert_eq!(bad.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(SocketAddr::from_pathname(&long[1..]).is_ok());
+        assert!(SocketAddr::from_abstract_name(&long[1..]).is_ok());
+
+        let (ip, len) = crate::net::addr::to_raw(&"127.0.0.1:1".parse().unwrap());
+        let err = SocketAddr::from_raw(&(ip, len)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
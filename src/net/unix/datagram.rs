@@ -0,0 +1,232 @@
+use super::{ucred, SocketAddr, UCred};
+use crate::buf::{BufResult, IoBuf, IoBufMut};
+use crate::driver::op::Op;
+use crate::fs::File;
+use crate::net::socket;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::path::Path;
+
+/// A unix datagram socket.
+///
+/// Receives report the length of the whole datagram: a count over the size
+/// of the buffer means the datagram was truncated to fit. Dropping the
+/// socket closes it in the background.
+pub struct UnixDatagram {
+    fd: File,
+}
+
+impl UnixDatagram {
+    /// Bind to the socket file at `path`, which must not exist yet.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixDatagram> {
+        Self::bind_addr(&SocketAddr::from_pathname(path)?)
+    }
+
+    /// Bind to `addr`, a path or a name in the abstract namespace.
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixDatagram> {
+        let socket = Self::unbound()?;
+        let fd = socket.fd.as_raw_fd();
+        addr.context(socket::bind_raw(&socket.fd, &addr.to_raw()), "bind", fd)?;
+        Ok(socket)
+    }
+
+    /// A socket with no address, which can send but not be sent to.
+    pub fn unbound() -> io::Result<UnixDatagram> {
+        let fd = socket::open(libc::AF_UNIX, libc::SOCK_DGRAM)?;
+        Ok(UnixDatagram { fd })
+    }
+
+    /// Two sockets connected to each other, with no address.
+    pub fn pair() -> io::Result<(UnixDatagram, UnixDatagram)> {
+        let (a, b) = socket::pair(libc::SOCK_DGRAM)?;
+        Ok((UnixDatagram { fd: a }, UnixDatagram { fd: b }))
+    }
+
+    /// Set the socket file at `path` as the peer for
+    /// [`send`](UnixDatagram::send) and [`recv`](UnixDatagram::recv).
+    pub fn connect(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.connect_addr(&SocketAddr::from_pathname(path)?)
+    }
+
+    /// Set `addr` as the peer for [`send`](UnixDatagram::send) and
+    /// [`recv`](UnixDatagram::recv).
+    pub fn connect_addr(&self, addr: &SocketAddr) -> io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        addr.context(socket::connect_raw(&self.fd, &addr.to_raw()), "connect", fd)
+    }
+
+    /// Send `buf` as one datagram to the socket file at `path`.
+    pub async fn send_to<T: IoBuf>(&self, buf: T, path: impl AsRef<Path>) -> BufResult<usize, T> {
+        match SocketAddr::from_pathname(path) {
+            Ok(addr) => self.send_to_addr(buf, &addr).await,
+            Err(e) => (Err(e), buf),
+        }
+    }
+
+    /// Send `buf` as one datagram to `addr`.
+    pub async fn send_to_addr<T: IoBuf>(&self, buf: T, addr: &SocketAddr) -> BufResult<usize, T> {
+        let (n, (buf, _)) = self.send_msg(buf, Vec::new(), Some(addr)).await;
+        (n, buf)
+    }
+
+    /// Receive a datagram into `buf`, returning its length and where it came
+    /// from, unnamed for a sender that is not bound.
+    pub async fn recv_from<T: IoBufMut>(&self, buf: T) -> BufResult<(usize, SocketAddr), T> {
+        let (res, (buf, _)) = self.recv_msg(buf, Vec::new()).await;
+        (res, buf)
+    }
+
+    /// Send `buf` as one datagram with the control messages `ctrl`, see
+    /// [`CmsgBuilder`](crate::net::CmsgBuilder). To `addr`, or the peer when
+    /// `None`.
+    pub async fn send_msg<T: IoBuf>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+        addr: Option<&SocketAddr>,
+    ) -> BufResult<usize, (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let ctx = |res, addr: Option<&SocketAddr>| match addr {
+            Some(addr) => addr.context(res, "sendmsg", fd),
+            None => res.op_fd("sendmsg", fd),
+        };
+        let op = match Op::send_msg(fd, buf, addr.map(|addr| addr.to_raw()), ctrl) {
+            Ok(op) => op,
+            Err((e, data)) => return (ctx(Err(e), addr), (data.buf, data.ctrl)),
+        };
+        let (n, bufs) = op.result().await;
+        (ctx(n, addr), bufs)
+    }
+
+    /// Receive a datagram into `buf` and its control messages into the
+    /// capacity of `ctrl`, see [`Cmsgs`](crate::net::Cmsgs).
+    pub async fn recv_msg<T: IoBufMut>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+    ) -> BufResult<(usize, SocketAddr), (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv_msg(fd, buf, ctrl, libc::MSG_TRUNC) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recvmsg", fd), (data.buf, data.ctrl)),
+        };
+        let (received, bufs) = op.result().await;
+        let res =
+            received.and_then(|received| Ok((received.len, SocketAddr::from_raw(&received.addr)?)));
+        (res.op_fd("recvmsg", fd), bufs)
+    }
+
+    /// Send `buf` as one datagram to the peer.
+    pub async fn send<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send(fd, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("send", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("send", fd), buf)
+    }
+
+    /// Receive a datagram from the peer, returning its length.
+    pub async fn recv<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv(fd, buf, libc::MSG_TRUNC) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("recv", fd), buf)
+    }
+
+    /// The local address of the socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let fd = self.fd.as_raw_fd();
+        SocketAddr::from_raw(&socket::local_raw(&self.fd)?).op_fd("getsockname", fd)
+    }
+
+    /// Have the kernel attach the credentials of the sender to every
+    /// datagram received, `SO_PASSCRED`. Read them with
+    /// [`Cmsg::credentials`](crate::net::Cmsg::credentials).
+    pub fn set_passcred(&self, passcred: bool) -> io::Result<()> {
+        let passcred = passcred as libc::c_int;
+        socket::set_int(&self.fd, libc::SOL_SOCKET, libc::SO_PASSCRED, passcred)
+    }
+
+    /// The credentials of the peer, as they were when it connected or the
+    /// pair was made.
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        ucred::peer_cred(&self.fd)
+    }
+
+    /// The peer set by [`connect`](UnixDatagram::connect).
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        let fd = self.fd.as_raw_fd();
+        SocketAddr::from_raw(&socket::peer_raw(&self.fd)?).op_fd("getpeername", fd)
+    }
+}
+
+impl From<OwnedFd> for UnixDatagram {
+    fn from(fd: OwnedFd) -> Self {
+        UnixDatagram { fd: File::from(fd) }
+    }
+}
+
+impl From<UnixDatagram> for OwnedFd {
+    fn from(socket: UnixDatagram) -> Self {
+        OwnedFd::from(socket.fd)
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixDatagram {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn addressed_and_paired() {
+        let dir = std::env::temp_dir().join(format!("loop-unix-dgram-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a_path, b_path) = (dir.join("a.sock"), dir.join("b.sock"));
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let a = UnixDatagram::bind(&a_path).unwrap();
+            let b = UnixDatagram::bind(&b_path).unwrap();
+            let (n, _) = a.send_to(b"to b".to_vec(), &b_path).await;
+            assert_eq!(n.unwrap(), 4);
+            let (res, buf) = b.recv_from(Vec::with_capacity(2)).await;
+            // The length of the whole datagram, cut to the buffer.
+            let (n, from) = res.unwrap();
+            assert_eq!((n, &buf[..]), (4, &b"to"[..]));
+            assert_eq!(from.as_pathname(), Some(&*a_path));
+
+            let unbound = UnixDatagram::unbound().unwrap();
+            let (n, _) = unbound.send_to(b"anon".to_vec(), &b_path).await;
+            assert_eq!(n.unwrap(), 4);
+            let (res, _) = b.recv_from(Vec::with_capacity(8)).await;
+            assert!(res.unwrap().1.is_unnamed());
+
+            let (x, y) = UnixDatagram::pair().unwrap();
+            let (n, _) = x.send(b"paired".to_vec()).await;
+            assert_eq!(n.unwrap(), 6);
+            let (n, buf) = y.recv(Vec::with_capacity(8)).await;
+            assert_eq!(n.unwrap(), 6);
+            assert_eq!(buf, b"paired");
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
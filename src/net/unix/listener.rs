@@ -0,0 +1,154 @@
+use super::{SocketAddr, UnixStream};
+use crate::driver::op::Op;
+use crate::fs::File;
+use crate::net::socket;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+// As for TCP listeners by default.
+const BACKLOG: u32 = 1024;
+
+/// A unix socket listening for connections.
+///
+/// Dropping the listener closes it in the background. The socket file of a
+/// path stays behind, unless bound with
+/// [`bind_with_cleanup`](UnixListener::bind_with_cleanup).
+pub struct UnixListener {
+    fd: File,
+    cleanup: Option<PathBuf>,
+}
+
+impl UnixListener {
+    /// Bind to the socket file at `path`, which must not exist yet.
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+        Self::bind_addr(&SocketAddr::from_pathname(path)?)
+    }
+
+    /// Bind to `path` like [`bind`](UnixListener::bind), and remove the socket
+    /// file again when the listener is dropped.
+    pub fn bind_with_cleanup(path: impl AsRef<Path>) -> io::Result<UnixListener> {
+        let mut listener = Self::bind(path.as_ref())?;
+        listener.cleanup = Some(path.as_ref().to_path_buf());
+        Ok(listener)
+    }
+
+    /// Bind to `addr`, a path or a name in the abstract namespace.
+    pub fn bind_addr(addr: &SocketAddr) -> io::Result<UnixListener> {
+        let fd = socket::open(libc::AF_UNIX, libc::SOCK_STREAM)?;
+        let raw = fd.as_raw_fd();
+        addr.context(socket::bind_raw(&fd, &addr.to_raw()), "bind", raw)?;
+        addr.context(socket::listen(&fd, BACKLOG), "listen", raw)?;
+        Ok(UnixListener { fd, cleanup: None })
+    }
+
+    /// Wait for a connection, returning it with the address of the peer,
+    /// unnamed unless the peer bound its socket.
+    pub async fn accept(&self) -> io::Result<(UnixStream, SocketAddr)> {
+        let fd = self.fd.as_raw_fd();
+        let op = Op::accept(fd).op_fd("accept", fd)?;
+        let (stream, peer) = op.result_raw().await.op_fd("accept", fd)?;
+        let peer = SocketAddr::from_raw(&peer).op_fd("accept", fd)?;
+        Ok((UnixStream::from(stream), peer))
+    }
+
+    /// The address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let fd = self.fd.as_raw_fd();
+        SocketAddr::from_raw(&socket::local_raw(&self.fd)?).op_fd("getsockname", fd)
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        // A new socket may have taken the path since, nothing to do then.
+        if let Some(path) = self.cleanup.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::net::unix::UCred;
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn pathname_echo() {
+        let dir = std::env::temp_dir().join(format!("loop-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("echo.sock");
+        let _ = std::fs::remove_file(&path);
+
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let listener = UnixListener::bind_with_cleanup(&path).unwrap();
+            assert_eq!(listener.local_addr().unwrap().as_pathname(), Some(&*path));
+            // Taken, until the listener is gone.
+            let err = UnixListener::bind(&path).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+            assert!(err.to_string().contains("echo.sock"));
+
+            let client = crate::spawn({
+                let path = path.clone();
+                async move {
+                    let mut stream = UnixStream::connect(&path).await.unwrap();
+                    assert!(stream.local_addr().unwrap().is_unnamed());
+                    assert_eq!(stream.peer_addr().unwrap().as_pathname(), Some(&*path));
+                    assert_eq!(stream.peer_cred().unwrap(), UCred::current());
+                    let (res, _) = stream.write_all(b"ping".to_vec()).await;
+                    res.unwrap();
+                    let (res, buf) = stream.read_exact(vec![0; 4]).await;
+                    res.unwrap();
+                    buf
+                }
+            });
+            let (mut stream, peer) = listener.accept().await.unwrap();
+            assert!(peer.is_unnamed());
+            assert_eq!(stream.peer_cred().unwrap(), UCred::current());
+            let (res, buf) = stream.read_exact(vec![0; 4]).await;
+            res.unwrap();
+            let (res, _) = stream.write_all(buf).await;
+            res.unwrap();
+            assert_eq!(client.await, b"ping");
+
+            drop(listener);
+            assert!(!path.exists());
+        });
+        std::fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn abstract_namespace() {
+        let name = format!("loop-abstract-{}", std::process::id());
+        let addr = SocketAddr::from_abstract_name(&name).unwrap();
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let listener = UnixListener::bind_addr(&addr).unwrap();
+            assert_eq!(listener.local_addr().unwrap(), addr);
+            let mut client = UnixStream::connect_addr(&addr).await.unwrap();
+            let (mut server, _) = listener.accept().await.unwrap();
+            let (res, _) = client.write_all(b"abstract".to_vec()).await;
+            res.unwrap();
+            let (res, buf) = server.read_exact(vec![0; 8]).await;
+            res.unwrap();
+            assert_eq!(buf, b"abstract");
+        });
+    }
+}
@@ -0,0 +1,16 @@
+//! Unix domain sockets, for talking to other processes on the same host.
+//!
+//! The API follows the TCP and UDP sockets, with [`SocketAddr`] for
+//! addresses of the unix family.
+
+mod addr;
+mod datagram;
+mod listener;
+mod stream;
+mod ucred;
+
+pub use addr::SocketAddr;
+pub use datagram::UnixDatagram;
+pub use listener::UnixListener;
+pub use stream::UnixStream;
+pub use ucred::UCred;
@@ -0,0 +1,278 @@
+use super::{ucred, SocketAddr, UCred};
+use crate::buf::{BufResult, IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use crate::driver::op::Op;
+use crate::fs::File;
+use crate::io::{AsyncReadRent, AsyncWriteRent, Split};
+use crate::net::socket;
+use crate::utils::error_ctx::ResultExt;
+use std::future::Future;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::path::Path;
+
+/// A connected unix stream socket.
+///
+/// Dropping the stream closes it in the background.
+pub struct UnixStream {
+    fd: File,
+}
+
+impl UnixStream {
+    /// Connect to the socket file at `path`.
+    pub async fn connect(path: impl AsRef<Path>) -> io::Result<UnixStream> {
+        Self::connect_addr(&SocketAddr::from_pathname(path)?).await
+    }
+
+    /// Connect to `addr`, a path or a name in the abstract namespace.
+    pub async fn connect_addr(addr: &SocketAddr) -> io::Result<UnixStream> {
+        let fd = socket::open(libc::AF_UNIX, libc::SOCK_STREAM)?;
+        let raw = fd.as_raw_fd();
+        let op = addr.context(Op::connect(fd, addr.to_raw()), "connect", raw)?;
+        let fd = addr.context(op.result().await, "connect", raw)?;
+        Ok(UnixStream { fd })
+    }
+
+    /// Two streams connected to each other, with no address.
+    pub fn pair() -> io::Result<(UnixStream, UnixStream)> {
+        let (a, b) = socket::pair(libc::SOCK_STREAM)?;
+        Ok((UnixStream { fd: a }, UnixStream { fd: b }))
+    }
+
+    /// The local address of the stream, unnamed for the connecting side.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        let fd = self.fd.as_raw_fd();
+        SocketAddr::from_raw(&socket::local_raw(&self.fd)?).op_fd("getsockname", fd)
+    }
+
+    /// The address of the peer, unnamed unless it bound its socket.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        let fd = self.fd.as_raw_fd();
+        SocketAddr::from_raw(&socket::peer_raw(&self.fd)?).op_fd("getpeername", fd)
+    }
+
+    /// The credentials of the peer, `SO_PEERCRED`, as they were when it
+    /// connected or the pair was made.
+    pub fn peer_cred(&self) -> io::Result<UCred> {
+        ucred::peer_cred(&self.fd)
+    }
+
+    /// Have the kernel attach the credentials of the sender to the bytes
+    /// received, `SO_PASSCRED`. Read them with
+    /// [`Cmsg::credentials`](crate::net::Cmsg::credentials).
+    pub fn set_passcred(&self, passcred: bool) -> io::Result<()> {
+        let passcred = passcred as libc::c_int;
+        socket::set_int(&self.fd, libc::SOL_SOCKET, libc::SO_PASSCRED, passcred)
+    }
+
+    /// Send `buf` with the control messages `ctrl`, see
+    /// [`CmsgBuilder`](crate::net::CmsgBuilder). The messages go with the
+    /// first byte of `buf`.
+    pub async fn send_msg<T: IoBuf>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+    ) -> BufResult<usize, (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send_msg(fd, buf, None, ctrl) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("sendmsg", fd), (data.buf, data.ctrl)),
+        };
+        let (n, bufs) = op.result().await;
+        (n.op_fd("sendmsg", fd), bufs)
+    }
+
+    /// Receive into `buf` and control messages into the capacity of `ctrl`,
+    /// see [`Cmsgs`](crate::net::Cmsgs). A receive stops at bytes sent with
+    /// other control messages.
+    pub async fn recv_msg<T: IoBufMut>(
+        &self,
+        buf: T,
+        ctrl: Vec<u8>,
+    ) -> BufResult<usize, (T, Vec<u8>)> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv_msg(fd, buf, ctrl, 0) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recvmsg", fd), (data.buf, data.ctrl)),
+        };
+        let (received, bufs) = op.result().await;
+        (
+            received.map(|received| received.len).op_fd("recvmsg", fd),
+            bufs,
+        )
+    }
+}
+
+// Offset -1, a socket has no position.
+const NO_OFFSET: u64 = u64::MAX;
+
+impl AsyncReadRent for UnixStream {
+    /// Receive into `buf`. 0 once the peer shut down its write side.
+    fn read<T: IoBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.read_shared(buf)
+    }
+
+    fn readv<T: IoVecBufMut>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.readv_shared(buf)
+    }
+}
+
+impl AsyncWriteRent for UnixStream {
+    fn write<T: IoBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.write_shared(buf)
+    }
+
+    fn writev<T: IoVecBuf>(&mut self, buf: T) -> impl Future<Output = BufResult<usize, T>> {
+        self.writev_shared(buf)
+    }
+
+    /// Sends are not buffered in userspace, this does nothing.
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Shut down the write side, the peer reads the end of the stream.
+    async fn shutdown(&mut self) -> io::Result<()> {
+        self.fd.shutdown().await
+    }
+}
+
+// Receives and sends are separate ops on the socket.
+impl Split for UnixStream {
+    async fn read_shared<T: IoBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::recv(fd, buf, 0) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("recv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("recv", fd), buf)
+    }
+
+    async fn readv_shared<T: IoVecBufMut>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::readv(fd, NO_OFFSET, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("readv", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("readv", fd), buf)
+    }
+
+    async fn write_shared<T: IoBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::send(fd, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("send", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("send", fd), buf)
+    }
+
+    async fn writev_shared<T: IoVecBuf>(&self, buf: T) -> BufResult<usize, T> {
+        let fd = self.fd.as_raw_fd();
+        let op = match Op::writev(fd, NO_OFFSET, buf) {
+            Ok(op) => op,
+            Err((e, data)) => return (Err(e).op_fd("writev", fd), data.buf),
+        };
+        let (n, buf) = op.result().await;
+        (n.op_fd("writev", fd), buf)
+    }
+
+    fn shutdown_write(&self) -> io::Result<()> {
+        self.fd.shutdown_write()
+    }
+}
+
+impl From<OwnedFd> for UnixStream {
+    fn from(fd: OwnedFd) -> Self {
+        UnixStream { fd: File::from(fd) }
+    }
+}
+
+impl From<UnixStream> for OwnedFd {
+    fn from(stream: UnixStream) -> Self {
+        OwnedFd::from(stream.fd)
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::io::{AsyncReadRentExt, AsyncWriteRentExt};
+    use crate::net::{CmsgBuilder, Cmsgs};
+    use crate::runtime::RuntimeBuilder;
+
+    #[test]
+    fn pair_round_trip() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (mut a, mut b) = UnixStream::pair().unwrap();
+            assert!(a.local_addr().unwrap().is_unnamed());
+            assert!(b.peer_addr().unwrap().is_unnamed());
+
+            let (res, _) = a.write_all(b"there".to_vec()).await;
+            res.unwrap();
+            let (res, buf) = b.read_exact(vec![0; 5]).await;
+            res.unwrap();
+            assert_eq!(buf, b"there");
+            let (res, _) = b.write_all(b"back".to_vec()).await;
+            res.unwrap();
+            let (res, buf) = a.read_exact(vec![0; 4]).await;
+            res.unwrap();
+            assert_eq!(buf, b"back");
+
+            // Both ends are this process.
+            for stream in [&a, &b] {
+                let cred = stream.peer_cred().unwrap();
+                assert_eq!(cred, UCred::current());
+            }
+
+            a.shutdown().await.unwrap();
+            let (n, _) = b.read(vec![0; 1]).await;
+            assert_eq!(n.unwrap(), 0);
+        });
+    }
+
+    #[test]
+    fn credentials_both_ways() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let (a, b) = UnixStream::pair().unwrap();
+            b.set_passcred(true).unwrap();
+            let ctrl = CmsgBuilder::new().credentials(UCred::current()).build();
+            let (n, _) = a.send_msg(b"cred".to_vec(), ctrl).await;
+            assert_eq!(n.unwrap(), 4);
+            // Without any from the sender, the kernel attaches its own.
+            let (n, _) = a.send_msg(b"auto".to_vec(), Vec::new()).await;
+            assert_eq!(n.unwrap(), 4);
+
+            for expected in [b"cred", b"auto"] {
+                let ctrl = Vec::with_capacity(crate::net::cmsg::space(12));
+                let (n, (buf, ctrl)) = b.recv_msg(Vec::with_capacity(4), ctrl).await;
+                assert_eq!(n.unwrap(), 4);
+                assert_eq!(&buf, expected);
+                let cred = Cmsgs::new(&ctrl).find_map(|cmsg| cmsg.credentials());
+                assert_eq!(cred, Some(UCred::current()));
+            }
+        });
+
+        // A socket with no peer has no credentials.
+        let fd = crate::net::socket::open(libc::AF_UNIX, libc::SOCK_STREAM).unwrap();
+        let lonely = UnixStream::from(OwnedFd::from(fd));
+        let err = lonely.peer_cred().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::fs::File;
+use crate::utils::error_ctx::ResultExt;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+
+/// The credentials of a process at the other end of a unix socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UCred {
+    pub pid: libc::pid_t,
+    pub uid: libc::uid_t,
+    pub gid: libc::gid_t,
+}
+
+impl UCred {
+    /// The credentials of this process, what the kernel accepts in an
+    /// `SCM_CREDENTIALS` message without privileges.
+    pub fn current() -> UCred {
+        // These never fail.
+        unsafe {
+            UCred {
+                pid: libc::getpid(),
+                uid: libc::getuid(),
+                gid: libc::getgid(),
+            }
+        }
+    }
+
+    pub(crate) fn to_raw(self) -> libc::ucred {
+        libc::ucred {
+            pid: self.pid,
+            uid: self.uid,
+            gid: self.gid,
+        }
+    }
+
+    pub(crate) fn from_raw(raw: libc::ucred) -> UCred {
+        UCred {
+            pid: raw.pid,
+            uid: raw.uid,
+            gid: raw.gid,
+        }
+    }
+}
+
+/// The credentials of the peer of `fd`. Fails with
+/// [`io::ErrorKind::NotConnected`] when it never had one, the kernel reports
+/// pid 0 then.
+pub(crate) fn peer_cred(fd: &File) -> io::Result<UCred> {
+    let raw = fd.as_raw_fd();
+    // Zeroed is a valid ucred.
+    let mut cred: libc::ucred = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::ucred>() as libc::socklen_t;
+    crate::syscall!(getsockopt@RAW(
+        raw,
+        libc::SOL_SOCKET,
+        libc::SO_PEERCRED,
+        (&mut cred as *mut libc::ucred).cast(),
+        &mut len,
+    ))
+    .op_fd("getsockopt", raw)?;
+    if cred.pid == 0 {
+        let err = io::Error::new(io::ErrorKind::NotConnected, "no peer credentials");
+        return Err(err).op_fd("getsockopt", raw);
+    }
+    Ok(UCred::from_raw(cred))
+}
@@ -0,0 +1,16 @@
+//! The items most programs need, `use Loop::prelude::*;` to get them.
+
+pub use crate::buf::{BufResult, IoBuf, IoBufMut};
+pub use crate::driver::{Driver, IoUringDriver};
+pub use crate::fs::{DirectFile, File, OpenOptions};
+pub use crate::future::join_all;
+pub use crate::io::{
+    AsyncBufReadRent, AsyncBufReadRentExt, AsyncReadRent, AsyncReadRentExt, AsyncWriteRent,
+    AsyncWriteRentExt,
+};
+pub use crate::join;
+pub use crate::net::{
+    TcpListener, TcpSocket, TcpStream, UdpSocket, UnixDatagram, UnixListener, UnixStream,
+};
+pub use crate::runtime::{spawn, Runtime, RuntimeBuilder};
+pub use crate::task::JoinHandle;
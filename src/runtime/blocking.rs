@@ -1,132 +1,98 @@
 //! Blocking tasks related.
 
-use std::{future::Future, task::Poll};
+use crate::driver::op::Op;
+use crate::runtime::spawn;
+use crate::task::JoinHandle;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::{Arc, Mutex, OnceLock};
 use threadpool::{Builder as ThreadPoolBuilder, ThreadPool as ThreadPoolImpl};
 
-/// Users may implement a ThreadPool and attach it to runtime.
-/// We also provide an implementation based on threadpool crate, you can use DefaultThreadPool.
-pub trait ThreadPool {
-    /// Monoio runtime will call `schedule_task` on `spawn_blocking`.
-    /// ThreadPool impl must execute it now or later.
-    fn schedule_task(&self, task: BlockingTask);
-}
-
-/// Error on waiting blocking task.
-#[derive(Debug, Clone, Copy)]
-pub enum JoinError {
-    /// Task is canceled.
-    Canceled,
-}
-
-/// BlockingTask is contrusted by monoio, ThreadPool impl
-/// will execute it with `.run()`.
-pub struct BlockingTask {
-    task: Option<crate::task::Task<NoopScheduler>>,
-    blocking_vtable: &'static BlockingTaskVtable,
-}
-
-unsafe impl Send for BlockingTask {}
-
-struct BlockingTaskVtable {
-    pub(crate) drop: unsafe fn(&mut crate::task::Task<NoopScheduler>),
+/// Run the blocking `f` on a thread of a pool shared by the runtimes, the
+/// tasks of this one keep running meanwhile. The handle gives what `f`
+/// returns, an error if it panicked. Dropping the handle leaves `f` running.
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<io::Result<R>>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    spawn(unblock(f))
 }
 
+// Threads shared by every runtime for `unblock`.
+const UNBLOCK_THREADS: usize = 4;
 
+/// Run `f` on a thread of a pool shared by the runtimes. Wakers do not cross
+/// threads, so the pool signals an eventfd the ring is reading instead.
+/// Dropping the future leaves `f` running, its result is dropped there.
+pub(crate) async fn unblock<F, R>(f: F) -> io::Result<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    static POOL: OnceLock<Mutex<ThreadPoolImpl>> = OnceLock::new();
 
+    // Signals when it drops, after a panic of `f` too.
+    struct Done(Arc<OwnedFd>);
 
-
-impl Drop for BlockingTask {
-    fn drop(&mut self) {
-        if let Some(task) = self.task.as_mut() {
-            unsafe { (self.blocking_vtable.drop)(task) };
+    impl Drop for Done {
+        fn drop(&mut self) {
+            let one = 1u64.to_ne_bytes();
+            unsafe { libc::write(self.0.as_raw_fd(), one.as_ptr().cast(), one.len()) };
         }
     }
-}
 
-impl BlockingTask {
-    /// Run task.
-    #[inline]
-    pub fn run(mut self) {
-        let task = self.task.take().unwrap();
-        task.run();
+    // Blocking, the ring polls it rather than handing the read to a worker.
+    let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
     }
-}
-
-/// BlockingStrategy can be set if there is no ThreadPool attached.
-/// It controls how to handle `spawn_blocking` without thread pool.
-#[derive(Clone, Copy, Debug)]
-pub enum BlockingStrategy {
-    /// Panic when `spawn_blocking`.
-    Panic,
-    /// Execute with current thread when `spawn_blocking`.
-    ExecuteLocal,
-}
-
-/// DefaultThreadPool is a simple wrapped `threadpool::ThreadPool` that implement
-/// `monoio::blocking::ThreadPool`. You may use this implementation, or you can use your own thread
-/// pool implementation.
-#[derive(Clone)]
-pub struct DefaultThreadPool {
-    pool: ThreadPoolImpl,
-}
-
-impl DefaultThreadPool {
-    /// Create a new DefaultThreadPool.
-    pub fn new(num_threads: usize) -> Self {
-        let pool = ThreadPoolBuilder::default()
-            .num_threads(num_threads)
+    let fd = Arc::new(unsafe { OwnedFd::from_raw_fd(fd) });
+    let op = Op::read_at(fd.as_raw_fd(), 0, Vec::with_capacity(8)).map_err(|(e, _)| e)?;
+    let slot = Arc::new(Mutex::new(None));
+    let (done, out) = (Done(fd.clone()), slot.clone());
+    let pool = POOL.get_or_init(|| {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(UNBLOCK_THREADS)
+            .thread_name("loop-blocking".into())
             .build();
-        Self { pool }
-    }
-}
-
-impl ThreadPool for DefaultThreadPool {
-    #[inline]
-    fn schedule_task(&self, task: BlockingTask) {
-        self.pool.execute(move || task.run());
-    }
-}
-
-pub(crate) struct NoopScheduler;
-
-impl crate::task::Schedule for NoopScheduler {
-    fn schedule(&self, _task: crate::task::Task<Self>) {
-        unreachable!()
-    }
-
-    fn yield_now(&self, _task: crate::task::Task<Self>) {
-        unreachable!()
-    }
-}
-
-pub(crate) enum BlockingHandle {
-    Attached(Box<dyn ThreadPool + Send + 'static>),
-    Empty(BlockingStrategy),
-}
-
-impl From<BlockingStrategy> for BlockingHandle {
-    fn from(value: BlockingStrategy) -> Self {
-        Self::Empty(value)
-    }
+        Mutex::new(pool)
+    });
+    pool.lock().unwrap().execute(move || {
+        let _done = done;
+        let res = f();
+        *out.lock().unwrap() = Some(res);
+    });
+    op.result().await.0?;
+    let res = slot.lock().unwrap().take();
+    res.ok_or_else(|| io::Error::other("blocking task panicked"))
 }
 
-struct BlockingFuture<F>(Option<F>);
-
-impl<T> Unpin for BlockingFuture<T> {}
-
-impl<F, R> Future for BlockingFuture<F>
-where
-    F: FnOnce() -> R + Send + 'static,
-    R: Send + 'static,
-{
-    type Output = Result<R, JoinError>;
-
-    fn poll(
-        mut self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context<'_>,
-    ) -> Poll<Self::Output> {
-        let me = &mut *self;
-        let func = me.0.take().expect("blocking task ran twice.");
-        Poll::Ready(Ok(func()))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn spawn_blocking_runs_off_the_runtime() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let here = thread::current().id();
+            let (tx, rx) = mpsc::channel();
+            // Blocks until the task below ran, on the runtime thread it never
+            // would.
+            let blocking = spawn_blocking(move || {
+                rx.recv().unwrap();
+                thread::current().id()
+            });
+            spawn(async move { tx.send(()).unwrap() }).await;
+            assert_ne!(blocking.await.unwrap(), here);
+
+            let panicked = spawn_blocking(|| panic!("in the pool")).await;
+            assert_eq!(panicked.unwrap_err().to_string(), "blocking task panicked");
+        });
     }
 }
@@ -1,7 +1,11 @@
-use crate::driver::IoUringDriver;
+use crate::driver::fixed_files::SlotPolicy;
+use crate::driver::{Driver, IoUringDriver, UringBuilder};
 use crate::runtime::runtime::Runtime;
 use crate::scoped_thread_local;
+use crate::time::wheel::Wheel;
 use crate::utils::thread_id::gen_id;
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::{io, marker::PhantomData};
 
 // ===== basic builder structure definition =====
@@ -10,8 +14,34 @@ use std::{io, marker::PhantomData};
 pub struct RuntimeBuilder<D> {
     // io_uring entries
     entries: Option<u32>,
+    // size the ring from RLIMIT_MEMLOCK and the concurrency hint
+    auto_entries: bool,
+    expected_concurrency: Option<usize>,
 
-    urb: io_uring::Builder,
+    urb: UringBuilder,
+    // whether `urb` was supplied by the user
+    custom_urb: bool,
+    // entry sizes asked for with setup_sqe128/setup_cqe32
+    sqe128: Option<bool>,
+    cqe32: Option<bool>,
+
+    // io-wq worker limits, (bounded, unbounded)
+    io_workers: Option<(u32, u32)>,
+    // fail the build if the limits can not be applied
+    io_workers_required: bool,
+
+    // registered file table size and what to do when it is full
+    fixed_files: Option<u32>,
+    slot_policy: SlotPolicy,
+
+    // buffer sizes to register, and how many of each
+    registered_bufs: Option<(Vec<usize>, usize)>,
+
+    // attach the op and its target to io errors
+    error_context: bool,
+
+    // keep sleeps on a timer wheel instead of kernel timers
+    timer: bool,
 
     // driver mark
     _mark: PhantomData<D>,
@@ -20,7 +50,6 @@ pub struct RuntimeBuilder<D> {
 scoped_thread_local!(pub(crate) static BUILD_THREAD_ID: usize);
 
 impl<T> Default for RuntimeBuilder<T> {
-    #[must_use]
     fn default() -> Self {
         RuntimeBuilder::<T>::new()
     }
@@ -31,8 +60,25 @@ impl<T> RuntimeBuilder<T> {
     pub fn new() -> Self {
         Self {
             entries: None,
+            auto_entries: false,
+            expected_concurrency: None,
+
+            urb: UringBuilder::new(false, false),
+            custom_urb: false,
+            sqe128: None,
+            cqe32: None,
 
-            urb: io_uring::IoUring::builder(),
+            io_workers: None,
+            io_workers_required: false,
+
+            fixed_files: None,
+            slot_policy: SlotPolicy::default(),
+
+            registered_bufs: None,
+
+            error_context: true,
+
+            timer: false,
 
             _mark: PhantomData,
         }
@@ -42,7 +88,7 @@ impl<T> RuntimeBuilder<T> {
 // ===== buildable trait and forward methods =====
 
 /// Buildable trait.
-pub trait Buildable: Sized {
+pub trait Buildable: Driver + Sized {
     /// Build the runtime.
     fn build(this: RuntimeBuilder<Self>) -> io::Result<Runtime<Self>>;
 }
@@ -64,15 +110,37 @@ direct_build!(IoUringDriver);
 // ===== builder impl =====
 
 impl Buildable for IoUringDriver {
-    fn build(this: RuntimeBuilder<Self>) -> io::Result<Runtime<IoUringDriver>> {
+    fn build(mut this: RuntimeBuilder<Self>) -> io::Result<Runtime<IoUringDriver>> {
         let thread_id = gen_id();
 
         BUILD_THREAD_ID.set(&thread_id, || {
-            let driver = match this.entries {
-                Some(entries) => IoUringDriver::new_with_entries(&this.urb, entries)?,
-                None => IoUringDriver::new(&this.urb)?,
+            let entries = this.entries;
+            let auto = (this.auto_entries)
+                .then(|| auto_entries(memlock_budget(), this.expected_concurrency));
+            let urb = this.uring_builder_for_build()?;
+            let driver = match (entries, auto) {
+                (Some(entries), _) => IoUringDriver::new_with_entries(urb, entries)?,
+                (None, Some(entries)) => IoUringDriver::new_sized(urb, entries)?,
+                (None, None) => IoUringDriver::new(urb)?,
             };
-            let context = crate::runtime::runtime::Context::new();
+            if let Some((bounded, unbounded)) = this.io_workers {
+                match driver.set_max_io_workers(bounded, unbounded) {
+                    Ok(()) => {}
+                    Err(e) if this.io_workers_required => return Err(e),
+                    Err(e) => log::warn!("io-wq worker limits are not applied: {e}"),
+                }
+            }
+            if let Some(slots) = this.fixed_files {
+                driver.register_files(slots, this.slot_policy)?;
+            }
+            if let Some((sizes, count)) = &this.registered_bufs {
+                driver.register_buffers(sizes, *count)?;
+            }
+            let mut context = crate::runtime::runtime::Context::new();
+            context.error_context = this.error_context;
+            if this.timer {
+                context.timer = Some(Rc::new(RefCell::new(Wheel::new())));
+            }
             Ok(Runtime::new(context, driver))
         })
     }
@@ -93,14 +161,338 @@ impl<D> RuntimeBuilder<D> {
         self
     }
 
+    /// Size the ring from `RLIMIT_MEMLOCK`, on kernels that still charge
+    /// rings against it(before 5.12), and the
+    /// [`expected_concurrency`](Self::expected_concurrency) hint. The size is a
+    /// power of two between 256 and 32768, see
+    /// [`Runtime::ring_entries`](crate::runtime::runtime::Runtime::ring_entries)
+    /// for the one chosen. [`with_entries`](Self::with_entries) takes
+    /// precedence.
+    #[must_use]
+    pub fn auto_entries(mut self) -> Self {
+        self.auto_entries = true;
+        self
+    }
+
+    /// Hint how many operations are expected to be in flight at once, used by
+    /// [`auto_entries`](Self::auto_entries).
+    #[must_use]
+    pub fn expected_concurrency(mut self, ops: usize) -> Self {
+        self.expected_concurrency = Some(ops);
+        self
+    }
+
+    /// Limit the io-wq worker threads the kernel spawns for ops that can not
+    /// complete inline, per NUMA node. `bounded` covers regular file and block
+    /// device io, `unbounded` covers io that may never complete, like sockets.
+    /// Zero leaves a limit at its default.
+    ///
+    /// If the kernel does not support the limits, the build only logs a
+    /// warning, unless [`io_workers_required`](Self::io_workers_required) is set.
+    #[must_use]
+    pub fn max_io_workers(mut self, bounded: u32, unbounded: u32) -> Self {
+        self.io_workers = Some((bounded, unbounded));
+        self
+    }
+
+    /// Fail the build with [`io::ErrorKind::Unsupported`] if the limits set by
+    /// [`max_io_workers`](Self::max_io_workers) can not be applied.
+    #[must_use]
+    pub fn io_workers_required(mut self, required: bool) -> Self {
+        self.io_workers_required = required;
+        self
+    }
+
+    /// Register a file table of `slots` entries, which direct descriptors are
+    /// opened into. Needs a 5.19+ kernel.
+    #[must_use]
+    pub fn fixed_files(mut self, slots: u32) -> Self {
+        self.fixed_files = Some(slots);
+        self
+    }
+
+    /// Choose whether opening a direct descriptor waits or fails when every
+    /// slot of the file table is taken. Fails by default.
+    #[must_use]
+    pub fn fixed_file_policy(mut self, policy: SlotPolicy) -> Self {
+        self.slot_policy = policy;
+        self
+    }
+
+    /// Register `count` buffers of each of `sizes` bytes with the ring, for
+    /// [`FixedBuf::checkout`](crate::buf::FixedBuf::checkout). Sizes are
+    /// rounded up to the page size, at most 16384 buffers can be registered.
+    ///
+    /// The buffers stay pinned for the life of the runtime and count against
+    /// `RLIMIT_MEMLOCK`, the build fails if it is too low.
+    #[must_use]
+    pub fn with_registered_buffers(mut self, sizes: &[usize], count: usize) -> Self {
+        self.registered_bufs = Some((sizes.to_vec(), count));
+        self
+    }
+
+    /// Name the failed operation, and the path or file where cheap, in io
+    /// errors. On by default, it costs an allocation per error.
+    ///
+    /// The error keeps its [`kind`](io::Error::kind), the original error with
+    /// its `raw_os_error` is the [`source`](std::error::Error::source) of
+    /// [`get_ref`](io::Error::get_ref).
+    #[must_use]
+    pub fn error_context(mut self, enable: bool) -> Self {
+        self.error_context = enable;
+        self
+    }
+
+    /// Keep [`sleep`](crate::time::sleep)s and timeouts on a timer wheel of
+    /// the runtime, with millisecond resolution. The runtime wakes the ring
+    /// for the nearest one only, instead of arming a kernel timer for each,
+    /// which pays off with many timers. Off by default.
+    #[must_use]
+    pub fn enable_timer(mut self) -> Self {
+        self.timer = true;
+        self
+    }
+
+    /// Use 128-byte submission entries, needed by passthrough commands like
+    /// NVMe `uring_cmd`.
+    #[must_use]
+    pub fn setup_sqe128(mut self, enable: bool) -> Self {
+        self.sqe128 = Some(enable);
+        self
+    }
+
+    /// Use 32-byte completion entries, which carry extra data for
+    /// passthrough commands.
+    #[must_use]
+    pub fn setup_cqe32(mut self, enable: bool) -> Self {
+        self.cqe32 = Some(enable);
+        self
+    }
+
     /// Replaces the default [`io_uring::Builder`], which controls the settings for the
     /// inner `io_uring` API.
     ///
     /// Refer to the [`io_uring::Builder`] documentation for all the supported methods.
     #[must_use]
-    pub fn uring_builder(mut self, urb: io_uring::Builder) -> Self {
-        self.urb = urb;
+    pub fn uring_builder(self, urb: io_uring::Builder) -> Self {
+        self.sized_uring_builder(urb)
+    }
+
+    /// Like [`uring_builder`](Self::uring_builder), for builders of any entry
+    /// size. The entry types pick the entry sizes of the ring, they must agree
+    /// with [`setup_sqe128`](Self::setup_sqe128) and
+    /// [`setup_cqe32`](Self::setup_cqe32) if those are set.
+    #[must_use]
+    pub fn sized_uring_builder<S, C>(mut self, urb: io_uring::Builder<S, C>) -> Self
+    where
+        S: io_uring::squeue::EntryMarker + 'static,
+        C: io_uring::cqueue::EntryMarker + 'static,
+    {
+        self.urb = UringBuilder::from_builder(urb);
+        self.custom_urb = true;
         self
     }
+
+    fn uring_builder_for_build(&mut self) -> io::Result<&UringBuilder> {
+        let (sqe128, cqe32) = self.urb.entry_sizes();
+        let wanted = (self.sqe128.unwrap_or(sqe128), self.cqe32.unwrap_or(cqe32));
+        if wanted != (sqe128, cqe32) {
+            if self.custom_urb {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "entry sizes of uring_builder do not match setup_sqe128/setup_cqe32",
+                ));
+            }
+            self.urb = UringBuilder::new(wanted.0, wanted.1);
+        }
+        Ok(&self.urb)
+    }
+}
+
+const AUTO_MIN_ENTRIES: u32 = 256;
+const AUTO_MAX_ENTRIES: u32 = 32768;
+// Locked memory of a ring per entry: a 64-byte SQE, its 4-byte index and two
+// 16-byte CQEs, rounded up for the ring headers and page rounding.
+const LOCKED_BYTES_PER_ENTRY: u64 = 128;
+
+/// Pick a ring size for `concurrency` in-flight ops, using at most half of
+/// `memlock` bytes of locked memory when it is limited.
+fn auto_entries(memlock: Option<u64>, concurrency: Option<usize>) -> u32 {
+    let wanted = concurrency.map_or(1024, |ops| ops.min(AUTO_MAX_ENTRIES as usize) as u32);
+    let affordable = memlock.map_or(u32::MAX, |bytes| {
+        (bytes / 2 / LOCKED_BYTES_PER_ENTRY).min(u32::MAX as u64) as u32
+    });
+    let entries = wanted.next_power_of_two().min(affordable.max(1));
+    // Round down, a ring never gets more than was affordable.
+    let entries = 1 << (u32::BITS - 1 - entries.leading_zeros());
+    entries.clamp(AUTO_MIN_ENTRIES, AUTO_MAX_ENTRIES)
+}
+
+/// The `RLIMIT_MEMLOCK` budget rings are charged against, `None` if there is
+/// no limit or the kernel accounts rings to the memory cgroup instead(5.12+).
+fn memlock_budget() -> Option<u64> {
+    if !rings_charge_memlock() {
+        return None;
+    }
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } {
+        0 if limit.rlim_cur != libc::RLIM_INFINITY => Some(limit.rlim_cur),
+        _ => None,
+    }
+}
+
+fn rings_charge_memlock() -> bool {
+    let mut uts = unsafe { std::mem::zeroed::<libc::utsname>() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return true;
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) };
+    let mut version = release
+        .to_str()
+        .unwrap_or_default()
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    let major = version.next().unwrap_or(0);
+    let minor = version.next().unwrap_or(0);
+    (major, minor) < (5, 12)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::supported;
+
+    #[test]
+    fn max_io_workers() {
+        let builder = RuntimeBuilder::<IoUringDriver>::new()
+            .max_io_workers(3, 5)
+            .io_workers_required(true);
+        let Some(rt) = supported(builder.build()) else {
+            return;
+        };
+        assert_eq!(rt.max_io_workers().unwrap(), (3, 5));
+    }
+
+    #[test]
+    fn max_io_workers_zero_keeps_default() {
+        let default = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        let Ok((bounded, _)) = default.max_io_workers() else {
+            return;
+        };
+        let rt = RuntimeBuilder::<IoUringDriver>::new()
+            .max_io_workers(0, 2)
+            .build()
+            .unwrap();
+        assert_eq!(rt.max_io_workers().unwrap(), (bounded, 2));
+    }
+
+    fn with_max_ring_entries<R>(max: u32, f: impl FnOnce() -> R) -> R {
+        crate::driver::MAX_RING_ENTRIES.with(|m| m.set(max));
+        let r = f();
+        crate::driver::MAX_RING_ENTRIES.with(|m| m.set(u32::MAX));
+        r
+    }
+
+    #[test]
+    fn default_entries_fall_back_on_memory_pressure() {
+        let rt = with_max_ring_entries(300, || RuntimeBuilder::<IoUringDriver>::new().build());
+        assert_eq!(rt.unwrap().ring_entries(), 256);
+
+        let rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        assert_eq!(rt.ring_entries(), 1024);
+    }
+
+    #[test]
+    fn pinned_entries_fail_fast() {
+        let err = with_max_ring_entries(300, || {
+            RuntimeBuilder::<IoUringDriver>::new()
+                .with_entries(512)
+                .build()
+        })
+        .err()
+        .unwrap();
+        assert_eq!(err.raw_os_error(), None);
+        assert_eq!(err.kind(), io::ErrorKind::OutOfMemory);
+        assert!(err.to_string().contains("RLIMIT_MEMLOCK"));
+    }
+
+    #[test]
+    fn fallback_exhausted() {
+        let err = with_max_ring_entries(32, || RuntimeBuilder::<IoUringDriver>::new().build())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("64 entries"));
+    }
+
+    #[test]
+    fn entry_sizes_follow_uring_builder() {
+        let rt = RuntimeBuilder::<IoUringDriver>::new()
+            .sized_uring_builder(io_uring::IoUring::<io_uring::squeue::Entry128>::builder())
+            .build()
+            .unwrap();
+        assert!(rt.driver.is_sqe128());
+        assert!(!rt.driver.is_cqe32());
+
+        let err = RuntimeBuilder::<IoUringDriver>::new()
+            .uring_builder(io_uring::IoUring::builder())
+            .setup_cqe32(true)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn auto_entries_sizing() {
+        const MIB: u64 = 1 << 20;
+        let table: &[(Option<u64>, Option<usize>, u32)] = &[
+            // No limit, no hint: the default.
+            (None, None, 1024),
+            // The hint rounds up to a power of two within the bounds.
+            (None, Some(1), 256),
+            (None, Some(1000), 1024),
+            (None, Some(1025), 2048),
+            (None, Some(50_000), 32768),
+            // A tight limit shrinks the ring down to the minimum.
+            (Some(64 << 10), None, 256),
+            (Some(256 << 10), None, 1024),
+            (Some(256 << 10), Some(50_000), 1024),
+            (Some(MIB), Some(50_000), 4096),
+            (Some(3 * MIB), Some(50_000), 8192),
+            // A generous limit leaves the hint alone.
+            (Some(64 * MIB), Some(3000), 4096),
+            (Some(0), Some(50_000), 256),
+        ];
+        for &(memlock, concurrency, entries) in table {
+            assert_eq!(
+                auto_entries(memlock, concurrency),
+                entries,
+                "memlock {memlock:?}, concurrency {concurrency:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn explicit_entries_beat_auto() {
+        let rt = RuntimeBuilder::<IoUringDriver>::new()
+            .auto_entries()
+            .expected_concurrency(5000)
+            .with_entries(512)
+            .build()
+            .unwrap();
+        assert_eq!(rt.ring_entries(), 512);
+
+        let rt = RuntimeBuilder::<IoUringDriver>::new()
+            .auto_entries()
+            .expected_concurrency(5000)
+            .build()
+            .unwrap();
+        assert_eq!(
+            rt.ring_entries(),
+            auto_entries(memlock_budget(), Some(5000))
+        );
+    }
+}
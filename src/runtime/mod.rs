@@ -1,5 +1,12 @@
+//! The runtime and its builder.
 
+mod blocking;
+pub(crate) mod builder;
+#[allow(clippy::module_inception)]
 pub(crate) mod runtime;
 mod scheduler;
-mod blocking;
-pub(crate) mod builder;
\ No newline at end of file
+
+pub use blocking::spawn_blocking;
+pub(crate) use blocking::unblock;
+pub use builder::{Buildable, RuntimeBuilder};
+pub use runtime::{spawn, Runtime};
@@ -1,15 +1,24 @@
-use crate::driver::Driver;
+use crate::driver::{Driver, IoUringDriver};
 use crate::runtime::scheduler::{LocalScheduler, TaskQueue};
 use crate::scoped_thread_local;
 use crate::task::waker_fn::{dummy_waker, set_poll, should_poll};
 use crate::task::{new_task, JoinHandle};
+use crate::time::wheel::Wheel;
+use std::cell::RefCell;
 use std::future::Future;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 scoped_thread_local!(pub(crate) static CURRENT: Context);
 
 pub(crate) struct Context {
-    pub tasks : TaskQueue,
+    pub tasks: TaskQueue,
     pub thread_id: usize,
+    // Whether io errors get the op and its target attached.
+    pub error_context: bool,
+    // Where sleeps wait when the timer is enabled, kernel timers otherwise.
+    pub timer: Option<Rc<RefCell<Wheel>>>,
 }
 
 impl Context {
@@ -19,27 +28,44 @@ impl Context {
         Self {
             thread_id,
             tasks: TaskQueue::default(),
+            error_context: true,
+            timer: None,
         }
     }
 
-}
+    // How long the driver may wait for the next timer.
+    fn park_timeout(&self) -> Option<Duration> {
+        let wheel = self.timer.as_ref()?.borrow();
+        wheel.next_timeout(Instant::now())
+    }
 
+    fn fire_timers(&self) {
+        let Some(wheel) = &self.timer else {
+            return;
+        };
+        // Woken with the wheel released, a task may run and poll its sleep.
+        let wakers = wheel.borrow_mut().process(Instant::now());
+        wakers.into_iter().for_each(|w| w.wake());
+    }
+}
 
-pub struct Runtime<D> {
+pub struct Runtime<D: Driver> {
     pub(crate) context: Context,
     pub(crate) driver: D,
 }
 
-impl<D> Runtime<D> {
+impl<D: Driver> Runtime<D> {
     pub(crate) fn new(context: Context, driver: D) -> Self {
         Self { context, driver }
     }
 
-    /// Block on
+    /// Run `future` to completion, along with the spawned tasks.
+    ///
+    /// If `future` panics, the ops in flight are cancelled and the queued
+    /// tasks dropped before the panic goes on, the runtime stays usable.
     pub fn block_on<F>(&mut self, future: F) -> F::Output
     where
         F: Future,
-        D: Driver,
     {
         assert!(
             !CURRENT.is_set(),
@@ -49,6 +75,19 @@ impl<D> Runtime<D> {
         let waker = dummy_waker();
         let cx = &mut std::task::Context::from_waker(&waker);
 
+        let res = catch_unwind(AssertUnwindSafe(|| self.run_until(future, cx)));
+        match res {
+            Ok(output) => output,
+            Err(panic) => {
+                // The scoped TLS was restored by the unwind. Leave the runtime
+                // as if the future had never run, so it can block_on again.
+                self.quiesce();
+                resume_unwind(panic)
+            }
+        }
+    }
+
+    fn run_until<F: Future>(&self, future: F, cx: &mut std::task::Context<'_>) -> F::Output {
         self.driver.with(|| {
             CURRENT.set(&self.context, || {
                 let join = future;
@@ -93,14 +132,66 @@ impl<D> Runtime<D> {
                         }
                         // Cold path
                         let _ = self.driver.submit();
+                        self.context.fire_timers();
                     }
                     // Wait and Process CQ(the error is ignored for not debug mode)
-                    let _ = self.driver.park();
+                    let _ = match self.context.park_timeout() {
+                        Some(timeout) => self.driver.park_timeout(timeout),
+                        None => self.driver.park(),
+                    };
+                    self.context.fire_timers();
+                }
+            })
+        })
+    }
+
+    // Tasks waiting on io are only referenced by the wakers stored in the
+    // driver. Cancel their ops inside the runtime context so the wakeups land
+    // in our queue, then drop the tasks with the buffers they own.
+    fn quiesce(&self) {
+        self.driver.with(|| {
+            CURRENT.set(&self.context, || {
+                let _ = self.driver.cancel_all();
+                // Tasks parked on timers go with their wakers, which have to
+                // be dropped with the wheel released.
+                let wakers = self
+                    .context
+                    .timer
+                    .as_ref()
+                    .map(|w| w.borrow_mut().take_wakers());
+                drop(wakers);
+                while let Some(task) = self.context.tasks.pop() {
+                    drop(task);
                 }
             })
         })
     }
 }
+impl Runtime<IoUringDriver> {
+    /// Get the io-wq worker limits of the ring as `(bounded, unbounded)`.
+    pub fn max_io_workers(&self) -> std::io::Result<(u32, u32)> {
+        self.driver.max_io_workers()
+    }
+
+    /// Get the number of entries the ring was created with. It may be smaller
+    /// than requested when the default size hit memory limits.
+    pub fn ring_entries(&self) -> u32 {
+        self.driver.ring_entries()
+    }
+
+    /// Release memory the driver kept from past bursts of operations. Call it
+    /// when the runtime is quiet, memory of operations still in flight stays.
+    pub fn compact(&self) {
+        self.driver.compact()
+    }
+}
+
+impl<D: Driver> Drop for Runtime<D> {
+    fn drop(&mut self) {
+        self.quiesce();
+    }
+}
+
 pub fn spawn<T>(future: T) -> JoinHandle<T::Output>
 where
     T: Future + 'static,
@@ -116,4 +207,78 @@ where
         ctx.tasks.push(task);
     });
     join
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buf::IoBufMut;
+    use crate::driver::op::Op;
+    use crate::runtime::RuntimeBuilder;
+    use std::cell::Cell;
+    use std::os::fd::AsRawFd;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    struct TrackedBuf {
+        buf: Vec<u8>,
+        dropped: Rc<Cell<usize>>,
+    }
+
+    impl Drop for TrackedBuf {
+        fn drop(&mut self) {
+            self.dropped.set(self.dropped.get() + 1);
+        }
+    }
+
+    unsafe impl IoBufMut for TrackedBuf {
+        fn write_ptr(&mut self) -> *mut u8 {
+            self.buf.write_ptr()
+        }
+
+        fn bytes_total(&mut self) -> usize {
+            self.buf.bytes_total()
+        }
+
+        unsafe fn set_init(&mut self, pos: usize) {
+            self.buf.set_init(pos)
+        }
+    }
+
+    #[test]
+    fn main_future_panic_leaves_runtime_usable() {
+        let (rx, tx) = std::io::pipe().unwrap();
+        let dropped = Rc::new(Cell::new(0));
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            rt.block_on(async {
+                let buf = TrackedBuf {
+                    buf: Vec::with_capacity(16 << 20),
+                    dropped: dropped.clone(),
+                };
+                spawn(async move {
+                    // Nothing is written before the panic.
+                    let op = Op::read_at(rx.as_raw_fd(), 0, buf).ok().unwrap();
+                    let _ = op.result().await;
+                    drop(rx);
+                });
+                // Let the task submit its read.
+                let _ = Op::timeout(Duration::from_millis(1)).unwrap().await;
+                assert_eq!(dropped.get(), 0);
+                panic!("main future");
+            })
+        }));
+        assert!(res.is_err());
+        assert!(!CURRENT.is_set());
+        assert_eq!(dropped.get(), 1);
+
+        // Everything still works after the panic.
+        let out = rt.block_on(async {
+            let task = spawn(async { 1 });
+            task.await + 1
+        });
+        assert_eq!(out, 2);
+        drop(tx);
+    }
+}
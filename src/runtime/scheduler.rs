@@ -1,6 +1,6 @@
-use std::{cell::UnsafeCell, collections::VecDeque, marker::PhantomData};
 use crate::runtime::runtime::CURRENT;
 use crate::task::{Schedule, Task};
+use std::{cell::UnsafeCell, collections::VecDeque, marker::PhantomData};
 
 pub(crate) struct LocalScheduler;
 
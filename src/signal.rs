@@ -0,0 +1,338 @@
+//! Unix signals as futures.
+//!
+//! Signals are read from a `signalfd` with a ring read, no handler gets
+//! installed. To keep the kernel from running the default action instead,
+//! [`Signal::new`] blocks the signal in the calling thread with
+//! `pthread_sigmask`. Threads spawned afterwards inherit the mask, threads
+//! that already run do not: a signal sent to the whole process only reaches
+//! the runtime if every thread blocks it.
+
+use crate::driver::op::Op;
+use crate::runtime::spawn;
+use std::cell::{Cell, RefCell};
+use std::future::poll_fn;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+/// A kind of signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SignalKind(libc::c_int);
+
+impl SignalKind {
+    /// A signal by its number.
+    pub const fn from_raw(signum: libc::c_int) -> Self {
+        SignalKind(signum)
+    }
+
+    /// The signal number.
+    pub const fn as_raw_value(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// `SIGINT`, sent by the terminal on ctrl-c.
+    pub const fn interrupt() -> Self {
+        SignalKind(libc::SIGINT)
+    }
+
+    /// `SIGTERM`, the polite request to exit.
+    pub const fn terminate() -> Self {
+        SignalKind(libc::SIGTERM)
+    }
+
+    /// `SIGHUP`, the terminal went away.
+    pub const fn hangup() -> Self {
+        SignalKind(libc::SIGHUP)
+    }
+
+    /// `SIGQUIT`.
+    pub const fn quit() -> Self {
+        SignalKind(libc::SIGQUIT)
+    }
+
+    /// `SIGCHLD`, a child process changed state.
+    pub const fn child() -> Self {
+        SignalKind(libc::SIGCHLD)
+    }
+
+    /// `SIGUSR1`.
+    pub const fn user_defined1() -> Self {
+        SignalKind(libc::SIGUSR1)
+    }
+
+    /// `SIGUSR2`.
+    pub const fn user_defined2() -> Self {
+        SignalKind(libc::SIGUSR2)
+    }
+}
+
+/// A listener for one kind of signal.
+///
+/// Each listener sees every delivery made after it was created, however many
+/// listen for the same signal. Deliveries between two calls to
+/// [`recv`](Signal::recv) are coalesced into one.
+pub struct Signal {
+    signum: libc::c_int,
+    // Deliveries already returned by `recv`.
+    seen: u64,
+    dispatch: Rc<Dispatch>,
+}
+
+impl Signal {
+    /// Listen for `kind` on the current runtime, blocking it in this thread.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] for signals that cannot or
+    /// should not be blocked, like `SIGKILL` or `SIGSEGV`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a runtime.
+    pub fn new(kind: SignalKind) -> io::Result<Signal> {
+        let signum = kind.0;
+        if !(1..=libc::SIGRTMAX()).contains(&signum) || FORBIDDEN.contains(&signum) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("signal {signum} cannot be listened for"),
+            ));
+        }
+        let dispatch = Dispatch::current()?;
+        dispatch.add(signum)?;
+        let seen = dispatch.slots.borrow()[signum as usize].deliveries;
+        Ok(Signal {
+            signum,
+            seen,
+            dispatch,
+        })
+    }
+
+    /// Wait for the next delivery of the signal.
+    ///
+    /// Returns `None` once signals can no longer be received, when reading
+    /// the signalfd failed or the runtime that read it is gone.
+    pub async fn recv(&mut self) -> Option<()> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Poll for the next delivery of the signal, see [`recv`](Signal::recv).
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        let mut slots = self.dispatch.slots.borrow_mut();
+        let slot = &mut slots[self.signum as usize];
+        if slot.deliveries > self.seen {
+            self.seen = slot.deliveries;
+            return Poll::Ready(Some(()));
+        }
+        if !self.dispatch.running.get() {
+            return Poll::Ready(None);
+        }
+        if !slot.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            slot.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Wait for ctrl-c, a shorthand for receiving [`SignalKind::interrupt`].
+pub async fn ctrl_c() -> io::Result<()> {
+    let mut signal = Signal::new(SignalKind::interrupt())?;
+    signal
+        .recv()
+        .await
+        .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "signals are no longer received"))
+}
+
+// Can't be blocked, or are raised by faults and must not be blocked.
+const FORBIDDEN: [libc::c_int; 6] = [
+    libc::SIGKILL,
+    libc::SIGSTOP,
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+];
+
+// Room for the standard and realtime signals.
+const SLOTS: usize = 65;
+
+// Read a few deliveries at once.
+const READ_BATCH: usize = 16;
+
+thread_local! {
+    static DISPATCH: RefCell<Option<Rc<Dispatch>>> = const { RefCell::new(None) };
+}
+
+/// One signalfd per thread, read by a task that fans deliveries out to every
+/// listener.
+struct Dispatch {
+    fd: OwnedFd,
+    mask: Cell<libc::sigset_t>,
+    slots: RefCell<Vec<Slot>>,
+    // Whether the reading task is alive.
+    running: Cell<bool>,
+}
+
+#[derive(Default)]
+struct Slot {
+    deliveries: u64,
+    wakers: Vec<Waker>,
+}
+
+impl Dispatch {
+    fn current() -> io::Result<Rc<Dispatch>> {
+        let dispatch = DISPATCH.with(|cell| -> io::Result<_> {
+            let mut cell = cell.borrow_mut();
+            if let Some(dispatch) = &*cell {
+                return Ok(dispatch.clone());
+            }
+            let mask = unsafe {
+                let mut mask = mem::zeroed();
+                libc::sigemptyset(&mut mask);
+                mask
+            };
+            let fd = unsafe { libc::signalfd(-1, &mask, libc::SFD_NONBLOCK | libc::SFD_CLOEXEC) };
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let dispatch = Rc::new(Dispatch {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                mask: Cell::new(mask),
+                slots: RefCell::new((0..SLOTS).map(|_| Slot::default()).collect()),
+                running: Cell::new(false),
+            });
+            *cell = Some(dispatch.clone());
+            Ok(dispatch)
+        })?;
+        // The task of a runtime that was dropped is gone, start one on this one.
+        if !dispatch.running.get() {
+            dispatch.running.set(true);
+            spawn(dispatch.clone().run());
+        }
+        Ok(dispatch)
+    }
+
+    fn add(&self, signum: libc::c_int) -> io::Result<()> {
+        let mut mask = self.mask.get();
+        if unsafe { libc::sigismember(&mask, signum) } == 1 {
+            return Ok(());
+        }
+        unsafe {
+            let mut only = mem::zeroed();
+            libc::sigemptyset(&mut only);
+            libc::sigaddset(&mut only, signum);
+            let res = libc::pthread_sigmask(libc::SIG_BLOCK, &only, std::ptr::null_mut());
+            if res != 0 {
+                return Err(io::Error::from_raw_os_error(res));
+            }
+            libc::sigaddset(&mut mask, signum);
+            if libc::signalfd(self.fd.as_raw_fd(), &mask, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        self.mask.set(mask);
+        Ok(())
+    }
+
+    async fn run(self: Rc<Self>) {
+        // Listeners learn the task is gone however it ends.
+        struct Stopped<'a>(&'a Dispatch);
+
+        impl Drop for Stopped<'_> {
+            fn drop(&mut self) {
+                self.0.running.set(false);
+                for slot in self.0.slots.borrow_mut().iter_mut() {
+                    slot.wakers.drain(..).for_each(Waker::wake);
+                }
+            }
+        }
+
+        let _stopped = Stopped(&self);
+        let info_len = mem::size_of::<libc::signalfd_siginfo>();
+        let mut buf = Vec::with_capacity(info_len * READ_BATCH);
+        loop {
+            buf.clear();
+            let op = match Op::read_at(self.fd.as_raw_fd(), 0, buf) {
+                Ok(op) => op,
+                Err(_) => return,
+            };
+            let (res, b) = op.result().await;
+            buf = b;
+            match res {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(_) => return,
+            }
+            for info in buf.chunks_exact(info_len) {
+                // `ssi_signo` leads the struct.
+                let signum = u32::from_ne_bytes(info[..4].try_into().unwrap());
+                self.deliver(signum as usize);
+            }
+        }
+    }
+
+    fn deliver(&self, signum: usize) {
+        let mut slots = self.slots.borrow_mut();
+        if let Some(slot) = slots.get_mut(signum) {
+            slot.deliveries += 1;
+            slot.wakers.drain(..).for_each(Waker::wake);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+
+    // Sent to the runtime thread only: the other test threads do not block
+    // the signal, a process wide one could kill the test binary.
+    fn raise_from_thread(signum: libc::c_int) -> std::thread::JoinHandle<()> {
+        let pid = unsafe { libc::getpid() };
+        let tid = unsafe { libc::gettid() };
+        std::thread::spawn(move || {
+            let res = unsafe { libc::syscall(libc::SYS_tgkill, pid, tid, signum) };
+            assert_eq!(res, 0, "{}", io::Error::last_os_error());
+        })
+    }
+
+    #[test]
+    fn receives_from_another_thread() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut first = Signal::new(SignalKind::user_defined2()).unwrap();
+            let mut second = Signal::new(SignalKind::user_defined2()).unwrap();
+
+            raise_from_thread(libc::SIGUSR2).join().unwrap();
+            assert_eq!(first.recv().await, Some(()));
+            // Every listener is told.
+            assert_eq!(second.recv().await, Some(()));
+
+            let waiter = spawn(async move { first.recv().await });
+            raise_from_thread(libc::SIGUSR2).join().unwrap();
+            assert_eq!(waiter.await, Some(()));
+            assert_eq!(second.recv().await, Some(()));
+        });
+        drop(rt);
+
+        // A runtime built later on the thread reads the signals again.
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut signal = Signal::new(SignalKind::user_defined2()).unwrap();
+            raise_from_thread(libc::SIGUSR2).join().unwrap();
+            assert_eq!(signal.recv().await, Some(()));
+        });
+    }
+
+    #[test]
+    fn rejects_unblockable_signals() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            for signum in [libc::SIGKILL, libc::SIGSEGV, 0, 1000] {
+                let err = Signal::new(SignalKind::from_raw(signum)).err().unwrap();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+            }
+        });
+    }
+}
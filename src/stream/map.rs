@@ -0,0 +1,80 @@
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Stream returned by [`StreamExt::map`](super::StreamExt::map).
+#[derive(Debug)]
+pub struct Map<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> Map<S, F> {
+    pub(super) fn new(stream: S, f: F) -> Self {
+        Map { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for Map<S, F> {}
+
+impl<S, F, T> Stream for Map<S, F>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> T,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        // Only the stream is pinned, the closure may move.
+        let this = unsafe { self.get_unchecked_mut() };
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        Poll::Ready(ready!(stream.poll_next(cx)).map(&mut this.f))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+/// Stream returned by [`StreamExt::filter_map`](super::StreamExt::filter_map).
+#[derive(Debug)]
+pub struct FilterMap<S, F> {
+    stream: S,
+    f: F,
+}
+
+impl<S, F> FilterMap<S, F> {
+    pub(super) fn new(stream: S, f: F) -> Self {
+        FilterMap { stream, f }
+    }
+}
+
+impl<S: Unpin, F> Unpin for FilterMap<S, F> {}
+
+impl<S, F, T> Stream for FilterMap<S, F>
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Option<T>,
+{
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        // Only the stream is pinned, the closure may move.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        loop {
+            match ready!(stream.as_mut().poll_next(cx)) {
+                Some(item) => {
+                    if let Some(item) = (this.f)(item) {
+                        return Poll::Ready(Some(item));
+                    }
+                }
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.stream.size_hint().1)
+    }
+}
@@ -0,0 +1,83 @@
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Stream returned by [`StreamExt::merge`](super::StreamExt::merge).
+#[derive(Debug)]
+pub struct Merge<A, B> {
+    a: A,
+    b: B,
+    a_done: bool,
+    b_done: bool,
+    // Which stream the next poll starts with.
+    a_first: bool,
+}
+
+impl<A, B> Merge<A, B> {
+    pub(super) fn new(a: A, b: B) -> Self {
+        Merge {
+            a,
+            b,
+            a_done: false,
+            b_done: false,
+            a_first: true,
+        }
+    }
+}
+
+impl<A: Unpin, B: Unpin> Unpin for Merge<A, B> {}
+
+impl<A, B> Stream for Merge<A, B>
+where
+    A: Stream,
+    B: Stream<Item = A::Item>,
+{
+    type Item = A::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<A::Item>> {
+        // Both streams are pinned, the flags are not.
+        let this = unsafe { self.get_unchecked_mut() };
+        let a_first = this.a_first;
+        this.a_first = !a_first;
+        let mut a = unsafe { Pin::new_unchecked(&mut this.a) };
+        let mut b = unsafe { Pin::new_unchecked(&mut this.b) };
+        for poll_a in [a_first, !a_first] {
+            let (stream, done): (Pin<&mut dyn Stream<Item = A::Item>>, _) = if poll_a {
+                (a.as_mut(), &mut this.a_done)
+            } else {
+                (b.as_mut(), &mut this.b_done)
+            };
+            if *done {
+                continue;
+            }
+            match stream.poll_next(cx) {
+                Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                Poll::Ready(None) => *done = true,
+                Poll::Pending => {}
+            }
+        }
+        if this.a_done && this.b_done {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a_lower, a_upper) = if self.a_done {
+            (0, Some(0))
+        } else {
+            self.a.size_hint()
+        };
+        let (b_lower, b_upper) = if self.b_done {
+            (0, Some(0))
+        } else {
+            self.b.size_hint()
+        };
+        let upper = match (a_upper, b_upper) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        (a_lower.saturating_add(b_lower), upper)
+    }
+}
@@ -0,0 +1,324 @@
+//! Utilities for streams.
+//!
+//! Everything works over [`futures_core::Stream`], so streams from other
+//! crates compose with the ones of this crate.
+
+mod iter;
+mod map;
+mod merge;
+mod next;
+mod take;
+mod timeout;
+
+pub use futures_core::Stream;
+pub use iter::{iter, Iter};
+pub use map::{FilterMap, Map};
+pub use merge::Merge;
+pub use next::Next;
+pub use take::Take;
+pub use timeout::{Elapsed, TimeoutPerItem};
+
+use std::time::Duration;
+
+/// Combinators for [`Stream`]s.
+pub trait StreamExt: Stream {
+    /// The next item of the stream, `None` once it ended.
+    ///
+    /// Dropping the future before it completes loses no item, so it can be
+    /// raced against other futures.
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next::new(self)
+    }
+
+    /// Map every item with `f`.
+    fn map<T, F>(self, f: F) -> Map<Self, F>
+    where
+        F: FnMut(Self::Item) -> T,
+        Self: Sized,
+    {
+        Map::new(self, f)
+    }
+
+    /// Map every item with `f`, skipping those it returns `None` for.
+    fn filter_map<T, F>(self, f: F) -> FilterMap<Self, F>
+    where
+        F: FnMut(Self::Item) -> Option<T>,
+        Self: Sized,
+    {
+        FilterMap::new(self, f)
+    }
+
+    /// End the stream after `n` items.
+    fn take(self, n: usize) -> Take<Self>
+    where
+        Self: Sized,
+    {
+        Take::new(self, n)
+    }
+
+    /// Yield the items of both streams as they come, until both ended.
+    ///
+    /// The streams take turns being polled first, so a stream that is always
+    /// ready does not starve the other.
+    fn merge<S>(self, other: S) -> Merge<Self, S>
+    where
+        S: Stream<Item = Self::Item>,
+        Self: Sized,
+    {
+        Merge::new(self, other)
+    }
+
+    /// Yield [`Elapsed`] whenever the stream takes longer than `duration` to
+    /// produce an item, then keep waiting for it. The deadline starts over
+    /// with each item and each `Elapsed`, an item that is ready wins over a
+    /// deadline that passed.
+    ///
+    /// One [`Sleep`](crate::time::Sleep) of the current runtime is reset
+    /// from wait to wait.
+    fn timeout_per_item(self, duration: Duration) -> TimeoutPerItem<Self>
+    where
+        Self: Sized,
+    {
+        TimeoutPerItem::new(self, duration)
+    }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::{spawn, RuntimeBuilder};
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+    use std::time::Instant;
+
+    // A queue fed by hand, pending while empty.
+    struct Queue<T> {
+        shared: Rc<RefCell<QueueState<T>>>,
+    }
+
+    struct QueueState<T> {
+        items: VecDeque<T>,
+        waker: Option<Waker>,
+        closed: bool,
+    }
+
+    impl<T> Clone for Queue<T> {
+        fn clone(&self) -> Self {
+            Queue {
+                shared: self.shared.clone(),
+            }
+        }
+    }
+
+    impl<T> Queue<T> {
+        fn new() -> Self {
+            Queue {
+                shared: Rc::new(RefCell::new(QueueState {
+                    items: VecDeque::new(),
+                    waker: None,
+                    closed: false,
+                })),
+            }
+        }
+
+        fn push(&self, item: T) {
+            let mut shared = self.shared.borrow_mut();
+            shared.items.push_back(item);
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+
+        fn close(&self) {
+            let mut shared = self.shared.borrow_mut();
+            shared.closed = true;
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    impl<T> Stream for Queue<T> {
+        type Item = T;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            let mut shared = self.shared.borrow_mut();
+            match shared.items.pop_front() {
+                Some(item) => Poll::Ready(Some(item)),
+                None if shared.closed => Poll::Ready(None),
+                None => {
+                    shared.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    async fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+        let mut items = Vec::new();
+        while let Some(item) = stream.next().await {
+            items.push(item);
+        }
+        items
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(future)
+    }
+
+    #[test]
+    fn combinators() {
+        let items = block_on(collect(
+            iter(0..)
+                .map(|n| n * 3)
+                .filter_map(|n| (n % 2 == 0).then_some(n))
+                .take(4),
+        ));
+        assert_eq!(items, [0, 6, 12, 18]);
+        assert_eq!(iter(0..10).take(3).size_hint(), (3, Some(3)));
+    }
+
+    #[test]
+    fn merge_alternates() {
+        // Both sides are always ready, neither gets two turns in a row.
+        let merged = iter(std::iter::repeat('a')).merge(iter(std::iter::repeat('b')));
+        let items = block_on(collect(merged.take(8)));
+        assert_eq!(items.iter().collect::<String>(), "abababab");
+    }
+
+    #[test]
+    fn merge_outlives_either_side() {
+        let items = block_on(collect(iter([1, 2]).merge(iter([10, 20, 30, 40]))));
+        assert_eq!(items, [1, 10, 2, 20, 30, 40]);
+
+        let items = block_on(collect(iter(Vec::<i32>::new()).merge(iter([5]))));
+        assert_eq!(items, [5]);
+    }
+
+    #[test]
+    fn next_is_cancel_safe() {
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let queue = Queue::new();
+        let mut stream = queue.clone();
+
+        // Give up on a pending next, like the losing branch of a select.
+        {
+            let mut next = stream.next();
+            assert!(Pin::new(&mut next).poll(&mut cx).is_pending());
+        }
+
+        queue.push(1);
+        queue.push(2);
+        {
+            let mut next = stream.next();
+            assert_eq!(Pin::new(&mut next).poll(&mut cx), Poll::Ready(Some(1)));
+        }
+        // Creating and dropping a next without polling takes nothing.
+        {
+            let _next = stream.next();
+        }
+        let mut next = stream.next();
+        assert_eq!(Pin::new(&mut next).poll(&mut cx), Poll::Ready(Some(2)));
+    }
+
+    #[test]
+    fn timeout_per_item() {
+        block_on(async {
+            let queue = Queue::new();
+            let mut stream = queue.clone().timeout_per_item(Duration::from_millis(20));
+
+            let start = Instant::now();
+            assert_eq!(stream.next().await, Some(Err(Elapsed(()))));
+            assert!(start.elapsed() >= Duration::from_millis(20));
+
+            // Ready items pass through, the wait starts over after each.
+            queue.push(1);
+            assert_eq!(stream.next().await, Some(Ok(1)));
+            let start = Instant::now();
+            assert_eq!(stream.next().await, Some(Err(Elapsed(()))));
+            assert!(start.elapsed() >= Duration::from_millis(20));
+
+            queue.close();
+            assert_eq!(stream.next().await, None);
+        });
+        let err: std::io::Error = Elapsed(()).into();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn timeout_per_item_ordering() {
+        block_on(async {
+            // Ticks every 30ms from the start, a deadline of 20ms after each.
+            let ticks = crate::time::interval(Duration::from_millis(30));
+            let stream = ticks.timeout_per_item(Duration::from_millis(20)).take(5);
+            let events: Vec<_> = collect(stream).await.iter().map(Result::is_ok).collect();
+            assert_eq!(events, [true, false, true, false, true]);
+
+            // Ready together with a deadline that passed, the item comes first.
+            let queue = Queue::new();
+            let mut stream = queue.clone().timeout_per_item(Duration::from_millis(10));
+            let mut cx = Context::from_waker(Waker::noop());
+            assert!(Pin::new(&mut stream).poll_next(&mut cx).is_pending());
+            std::thread::sleep(Duration::from_millis(20));
+            queue.push(1);
+            assert_eq!(stream.next().await, Some(Ok(1)));
+            assert_eq!(stream.next().await, Some(Err(Elapsed(()))));
+            queue.close();
+            assert_eq!(stream.next().await, None);
+            assert_eq!(stream.next().await, None);
+        });
+    }
+
+    #[test]
+    fn accept_loop_with_shutdown() {
+        #[derive(Debug, PartialEq)]
+        enum Event {
+            Conn(u32),
+            Shutdown,
+        }
+
+        let served = block_on(async {
+            let incoming = Queue::new();
+            let shutdown = Queue::new();
+            let mut events = incoming
+                .clone()
+                .map(Event::Conn)
+                .merge(shutdown.clone().map(|()| Event::Shutdown));
+
+            let acks = Queue::new();
+            let mut served_acks = acks.clone();
+            spawn(async move {
+                for conn in 0..3 {
+                    incoming.push(conn);
+                    served_acks.next().await;
+                }
+                shutdown.push(());
+            });
+
+            let mut served = Vec::new();
+            while let Some(event) = events.next().await {
+                match event {
+                    Event::Conn(conn) => {
+                        served.push(conn);
+                        acks.push(());
+                    }
+                    Event::Shutdown => break,
+                }
+            }
+            served
+        });
+        assert_eq!(served, [0, 1, 2]);
+    }
+}
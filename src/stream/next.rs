@@ -0,0 +1,26 @@
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future returned by [`StreamExt::next`](super::StreamExt::next).
+#[derive(Debug)]
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<'a, S: ?Sized> Next<'a, S> {
+    pub(super) fn new(stream: &'a mut S) -> Self {
+        Next { stream }
+    }
+}
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Items only leave the stream when this returns them, nothing is held
+        // across polls.
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
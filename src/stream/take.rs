@@ -0,0 +1,53 @@
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+/// Stream returned by [`StreamExt::take`](super::StreamExt::take).
+#[derive(Debug)]
+pub struct Take<S> {
+    stream: S,
+    remaining: usize,
+}
+
+impl<S> Take<S> {
+    pub(super) fn new(stream: S, n: usize) -> Self {
+        Take {
+            stream,
+            remaining: n,
+        }
+    }
+}
+
+impl<S: Unpin> Unpin for Take<S> {}
+
+impl<S: Stream> Stream for Take<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<S::Item>> {
+        if self.remaining == 0 {
+            // Done, the stream is not polled again.
+            return Poll::Ready(None);
+        }
+        // Only the stream is pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        let item = ready!(stream.poll_next(cx));
+        match item {
+            Some(_) => this.remaining -= 1,
+            None => this.remaining = 0,
+        }
+        Poll::Ready(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.remaining == 0 {
+            return (0, Some(0));
+        }
+        let (lower, upper) = self.stream.size_hint();
+        let upper = match upper {
+            Some(upper) => upper.min(self.remaining),
+            None => self.remaining,
+        };
+        (lower.min(self.remaining), Some(upper))
+    }
+}
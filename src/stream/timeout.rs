@@ -0,0 +1,89 @@
+use crate::time::{sleep, Sleep};
+use futures_core::Stream;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Stream returned by
+/// [`StreamExt::timeout_per_item`](super::StreamExt::timeout_per_item).
+pub struct TimeoutPerItem<S> {
+    stream: S,
+    duration: Duration,
+    // Made for the first wait, reset for the ones after.
+    timer: Option<Sleep>,
+    // Whether the timer runs for the current wait.
+    armed: bool,
+}
+
+impl<S> TimeoutPerItem<S> {
+    pub(super) fn new(stream: S, duration: Duration) -> Self {
+        TimeoutPerItem {
+            stream,
+            duration,
+            timer: None,
+            armed: false,
+        }
+    }
+}
+
+impl<S: Unpin> Unpin for TimeoutPerItem<S> {}
+
+impl<S: Stream> Stream for TimeoutPerItem<S> {
+    type Item = Result<S::Item, Elapsed>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Only the stream is pinned, the timer is Unpin.
+        let this = unsafe { self.get_unchecked_mut() };
+        let stream = unsafe { Pin::new_unchecked(&mut this.stream) };
+        // An item that is ready wins over a deadline that passed meanwhile.
+        if let Poll::Ready(item) = stream.poll_next(cx) {
+            // The next wait starts over, an ended stream needs no timer.
+            this.armed = false;
+            if item.is_none() {
+                this.timer = None;
+            }
+            return Poll::Ready(item.map(Ok));
+        }
+        let timer = match (&mut this.timer, this.armed) {
+            (Some(timer), true) => timer,
+            (Some(timer), false) => {
+                timer.reset_after(this.duration);
+                timer
+            }
+            (None, _) => this.timer.insert(sleep(this.duration)),
+        };
+        this.armed = true;
+        if Pin::new(timer).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        this.armed = false;
+        Poll::Ready(Some(Err(Elapsed(()))))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every wait may add an `Elapsed`.
+        (self.stream.size_hint().0, None)
+    }
+}
+
+/// The deadline passed first, see [`time::timeout`](crate::time::timeout)
+/// and [`StreamExt::timeout_per_item`](super::StreamExt::timeout_per_item).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elapsed(pub(crate) ());
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+impl From<Elapsed> for io::Error {
+    fn from(e: Elapsed) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, e)
+    }
+}
@@ -1,10 +1,3 @@
-use std::{
-    future::Future,
-    panic,
-    ptr::NonNull,
-    task::{Context, Poll, Waker},
-};
-use log::trace;
 use super::utils::UnsafeCellExt;
 use crate::{
     task::{
@@ -15,6 +8,13 @@ use crate::{
     },
     utils::thread_id::{try_get_current_thread_id, DEFAULT_THREAD_ID},
 };
+use log::trace;
+use std::{
+    future::Future,
+    panic,
+    ptr::NonNull,
+    task::{Context, Poll, Waker},
+};
 
 pub(crate) struct Harness<T: Future, S: 'static> {
     cell: NonNull<Cell<T, S>>,
@@ -103,8 +103,6 @@ where
         }
     }
 
-
-
     // ===== join handle =====
 
     /// Read the task output into `dst`.
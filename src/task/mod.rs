@@ -46,8 +46,6 @@ impl<S: 'static> Task<S> {
     pub(crate) fn run(self) {
         self.raw.poll();
     }
-
-
 }
 
 impl<S: 'static> Drop for Task<S> {
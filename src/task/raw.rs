@@ -29,9 +29,6 @@ pub(crate) struct Vtable {
 
     /// The join handle has been dropped
     pub(crate) drop_join_handle_slow: unsafe fn(NonNull<Header>),
-
-
-
 }
 
 /// Get the vtable for the requested `T` and `S` generics.
@@ -41,7 +38,6 @@ pub(super) fn vtable<T: Future, S: Schedule>() -> &'static Vtable {
         dealloc: dealloc::<T, S>,
         try_read_output: try_read_output::<T, S>,
         drop_join_handle_slow: drop_join_handle_slow::<T, S>,
-
     }
 }
 
@@ -89,8 +85,6 @@ impl RawTask {
         let vtable = self.header().vtable;
         unsafe { (vtable.drop_join_handle_slow)(self.ptr) }
     }
-
-
 }
 
 unsafe fn poll<T: Future, S: Schedule>(ptr: NonNull<Header>) {
@@ -103,8 +97,6 @@ unsafe fn dealloc<T: Future, S: Schedule>(ptr: NonNull<Header>) {
     harness.dealloc();
 }
 
-
-
 unsafe fn try_read_output<T: Future, S: Schedule>(
     ptr: NonNull<Header>,
     dst: *mut (),
@@ -1,3 +1,4 @@
+use log::trace;
 use std::{
     fmt,
     sync::atomic::{
@@ -5,7 +6,6 @@ use std::{
         Ordering::{AcqRel, Acquire},
     },
 };
-use log::trace;
 
 pub(crate) struct State(AtomicUsize);
 
@@ -1,3 +1,5 @@
+use super::{core::Header, harness::Harness, Schedule};
+use log::trace;
 use std::{
     future::Future,
     marker::PhantomData,
@@ -6,8 +8,6 @@ use std::{
     ptr::NonNull,
     task::{RawWaker, RawWakerVTable, Waker},
 };
-use log::trace;
-use super::{core::Header, harness::Harness, Schedule};
 
 pub(super) struct WakerRef<'a, S: 'static> {
     waker: ManuallyDrop<Waker>,
@@ -27,7 +27,6 @@ pub(crate) fn dummy_waker() -> Waker {
     unsafe { Waker::from_raw(raw_waker()) }
 }
 
-
 thread_local! {
     static SHOULD_POLL: Cell<bool> = const { Cell::new(true) };
 }
@@ -0,0 +1,174 @@
+use super::sleep::{sleep_until, Sleep};
+use futures_core::Stream;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+/// What an [`Interval`] does with the ticks it missed, because the task
+/// took longer than a period between two of them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissedTickBehavior {
+    /// Fire the missed ticks right away, one after the other, to catch up
+    /// with the schedule. The default.
+    #[default]
+    Burst,
+    /// Fire once right away, then a period after that: the schedule moves
+    /// by the time lost.
+    Delay,
+    /// Fire once right away, then on the next tick of the schedule,
+    /// dropping the ones missed.
+    Skip,
+}
+
+/// Tick every `period`, the first tick right away.
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    interval_at(Instant::now(), period)
+}
+
+/// Tick every `period`, the first tick at `start`.
+///
+/// Panics if `period` is zero.
+pub fn interval_at(start: Instant, period: Duration) -> Interval {
+    assert!(!period.is_zero(), "interval period must be non-zero");
+    Interval {
+        sleep: sleep_until(start),
+        period,
+        missed: MissedTickBehavior::default(),
+    }
+}
+
+/// Ticks on a schedule, from [`interval`] or [`interval_at`].
+///
+/// Ticks are deadlines on the schedule, each a whole number of periods after
+/// the start, so a late wakeup does not push the next ones back. One timer
+/// is reset from tick to tick.
+///
+/// As a [`Stream`] it yields the ticks and never ends.
+#[derive(Debug)]
+pub struct Interval {
+    // Until the next tick.
+    sleep: Sleep,
+    period: Duration,
+    missed: MissedTickBehavior,
+}
+
+impl Interval {
+    /// Wait for the next tick, returning the time it was due.
+    ///
+    /// Dropping the future before it completes loses no tick.
+    pub async fn tick(&mut self) -> Instant {
+        poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Poll for the next tick, see [`tick`](Self::tick).
+    pub fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        ready!(Pin::new(&mut self.sleep).poll(cx));
+        let tick = self.sleep.deadline();
+        let now = Instant::now();
+        let mut next = tick + self.period;
+        // Missed when the next one is due already.
+        if now >= next {
+            next = match self.missed {
+                MissedTickBehavior::Burst => next,
+                MissedTickBehavior::Delay => now + self.period,
+                MissedTickBehavior::Skip => {
+                    let behind = (now - tick).as_nanos() / self.period.as_nanos();
+                    tick + self.period * (behind.min(u32::MAX as u128 - 1) as u32 + 1)
+                }
+            };
+        }
+        self.sleep.reset(next);
+        Poll::Ready(tick)
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    pub fn missed_tick_behavior(&self) -> MissedTickBehavior {
+        self.missed
+    }
+
+    pub fn set_missed_tick_behavior(&mut self, behavior: MissedTickBehavior) {
+        self.missed = behavior;
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        self.get_mut().poll_tick(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::RuntimeBuilder;
+    use crate::time::sleep;
+
+    const PERIOD: Duration = Duration::from_millis(20);
+
+    // The three ticks after a slow consumer missed some, as offsets from the
+    // first one.
+    fn slow_consumer(behavior: MissedTickBehavior) -> Vec<Duration> {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let mut interval = interval(PERIOD);
+            interval.set_missed_tick_behavior(behavior);
+            let start = interval.tick().await;
+            assert!(start.elapsed() < PERIOD);
+            sleep(PERIOD * 5 / 2).await;
+            let mut ticks = Vec::new();
+            for _ in 0..3 {
+                ticks.push(interval.tick().await - start);
+            }
+            ticks
+        })
+    }
+
+    #[test]
+    fn burst_catches_up() {
+        let ticks = slow_consumer(MissedTickBehavior::Burst);
+        assert_eq!(ticks, [PERIOD, PERIOD * 2, PERIOD * 3]);
+    }
+
+    #[test]
+    fn delay_moves_the_schedule() {
+        let ticks = slow_consumer(MissedTickBehavior::Delay);
+        // The late tick fires at 50ms, the next ones a period after it.
+        assert_eq!(ticks[0], PERIOD);
+        assert!(ticks[1] >= PERIOD * 7 / 2, "{ticks:?}");
+        assert_eq!(ticks[2], ticks[1] + PERIOD);
+    }
+
+    #[test]
+    fn skip_keeps_the_schedule() {
+        let ticks = slow_consumer(MissedTickBehavior::Skip);
+        // The tick at 20ms fires late at 50ms, the one at 40ms is skipped and
+        // the schedule goes on at 60 and 80ms.
+        assert_eq!(ticks, [PERIOD, PERIOD * 3, PERIOD * 4]);
+    }
+
+    #[test]
+    fn no_drift() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let start = Instant::now() + PERIOD;
+            let mut interval = interval_at(start, Duration::from_millis(5));
+            for i in 0..20 {
+                let tick = interval.tick().await;
+                assert_eq!(tick, start + Duration::from_millis(5) * i);
+                assert!(Instant::now() >= tick);
+            }
+            // Late wakeups did not add up.
+            let elapsed = start.elapsed();
+            assert!(elapsed < Duration::from_millis(95 + 40), "{elapsed:?}");
+        });
+    }
+}
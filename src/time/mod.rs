@@ -0,0 +1,18 @@
+//! Waiting for time, on timers of the ring or of the runtime.
+//!
+//! By default every timer is a `Timeout` operation, the kernel completes it
+//! when the time is up and the runtime sleeps in between. A runtime built
+//! with [`enable_timer`](crate::RuntimeBuilder::enable_timer) keeps them on
+//! a timer wheel instead, with millisecond resolution, and only bounds its
+//! wait on the ring by the nearest one. Which one a timer uses is decided
+//! when it is first polled, by the runtime it runs on.
+
+mod interval;
+mod sleep;
+mod timeout;
+pub(crate) mod wheel;
+
+pub use crate::stream::Elapsed;
+pub use interval::{interval, interval_at, Interval, MissedTickBehavior};
+pub use sleep::{sleep, Sleep};
+pub use timeout::{timeout, timeout_at, Timeout, TimeoutExt};
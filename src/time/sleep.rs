@@ -0,0 +1,392 @@
+use super::wheel::Wheel;
+use crate::driver::op::Op;
+use crate::driver::timeout::Timeout;
+use crate::runtime::runtime::CURRENT;
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{ready, Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Wait until `duration` has passed, counted from the call.
+///
+/// The timer is armed on the first poll, on the timer wheel of the runtime
+/// when it is [enabled](crate::RuntimeBuilder::enable_timer), as a kernel
+/// timer otherwise. A sleep of zero still yields to the other tasks once.
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+pub(crate) fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        timer: Timer::Idle,
+        waker: None,
+    }
+}
+
+/// Future returned by [`sleep`].
+///
+/// Dropping it cancels the timer, an idle ring is not woken for it. It can
+/// be [reset](Sleep::reset) to another deadline instead of making a new one,
+/// like an idle timeout pushed back on every read.
+pub struct Sleep {
+    deadline: Instant,
+    timer: Timer,
+    // The task waiting on a kernel timer, woken when a reset replaces it.
+    waker: Option<Waker>,
+}
+
+// Armed on the first poll, and again after a reset once it completed.
+enum Timer {
+    Idle,
+    // A kernel timer, armed for the deadline it has.
+    Ring(Op<Timeout>, Instant),
+    // An entry of the wheel of the runtime.
+    Wheel(Rc<RefCell<Wheel>>, usize),
+}
+
+impl Sleep {
+    /// The time the sleep ends.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// End the sleep at `deadline` instead, also while it is waited on or
+    /// after it completed. A deadline that passed wakes the task right away.
+    ///
+    /// On the wheel the entry only moves. A kernel timer moved later is
+    /// updated in place (5.11+), it does not wake the task at the old
+    /// deadline; older kernels do, and the timer is armed again for what is
+    /// left. One moved earlier is replaced right away.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.deadline = deadline;
+        match &mut self.timer {
+            Timer::Wheel(wheel, key) => wheel.borrow_mut().reset(*key, deadline),
+            Timer::Ring(timer, armed) if *armed <= deadline => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if timer.update(left).is_ok() {
+                    *armed = deadline;
+                }
+            }
+            Timer::Ring(..) => {
+                self.timer = Timer::Idle;
+                if let Some(waker) = self.waker.take() {
+                    waker.wake();
+                }
+            }
+            Timer::Idle => {}
+        }
+    }
+
+    /// End the sleep `duration` from now instead, see [`reset`](Self::reset).
+    pub fn reset_after(&mut self, duration: Duration) {
+        self.reset(Instant::now() + duration);
+    }
+
+    fn arm_ring(&mut self) {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        let timer =
+            Op::timeout(left).unwrap_or_else(|e| panic!("failed to arm the sleep timer: {e}"));
+        self.timer = Timer::Ring(timer, self.deadline);
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if let Timer::Idle = this.timer {
+            match CURRENT.try_with(|ctx| ctx.timer.clone()).flatten() {
+                Some(wheel) => {
+                    let key = wheel.borrow_mut().insert(this.deadline);
+                    this.timer = Timer::Wheel(wheel, key);
+                }
+                None => this.arm_ring(),
+            }
+        }
+        loop {
+            match &mut this.timer {
+                Timer::Ring(timer, _) => {
+                    match &this.waker {
+                        Some(waker) if waker.will_wake(cx.waker()) => {}
+                        _ => this.waker = Some(cx.waker().clone()),
+                    }
+                    match ready!(timer.poll_expired(cx)) {
+                        // Fired at a deadline from before a reset, the update
+                        // came too late or the kernel has none.
+                        Ok(()) if Instant::now() < this.deadline => {
+                            this.arm_ring();
+                            continue;
+                        }
+                        Ok(()) => {}
+                        // Cancelled under the sleep, it keeps waiting.
+                        Err(e) if e.raw_os_error() == Some(libc::ECANCELED) => {
+                            this.arm_ring();
+                            continue;
+                        }
+                        Err(e) => panic!("sleep timer failed: {e}"),
+                    }
+                    this.timer = Timer::Idle;
+                    this.waker = None;
+                    return Poll::Ready(());
+                }
+                Timer::Wheel(wheel, key) => {
+                    return match wheel.borrow_mut().poll(*key, cx.waker()) {
+                        true => Poll::Ready(()),
+                        false => Poll::Pending,
+                    }
+                }
+                Timer::Idle => unreachable!("the timer is armed"),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep")
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Timer::Wheel(wheel, key) = &self.timer {
+            wheel.borrow_mut().remove(*key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::runtime::{Runtime, RuntimeBuilder};
+    use std::cell::Cell;
+
+    // On kernel timers, then on the wheel.
+    fn runtimes() -> [Runtime<IoUringDriver>; 2] {
+        let builder = RuntimeBuilder::<IoUringDriver>::new;
+        [
+            builder().build().unwrap(),
+            builder().enable_timer().build().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn sleeps_for_the_duration() {
+        for mut rt in runtimes() {
+            rt.block_on(async {
+                for ms in [10, 200] {
+                    let duration = Duration::from_millis(ms);
+                    let start = Instant::now();
+                    sleep(duration).await;
+                    let elapsed = start.elapsed();
+                    assert!(elapsed >= duration, "{elapsed:?}");
+                    assert!(
+                        elapsed < duration + Duration::from_millis(50),
+                        "{elapsed:?}"
+                    );
+                }
+
+                // Counted from the call, not from the first poll.
+                let timer = sleep(Duration::from_millis(10));
+                std::thread::sleep(Duration::from_millis(20));
+                let start = Instant::now();
+                timer.await;
+                assert!(start.elapsed() < Duration::from_millis(10));
+            });
+        }
+    }
+
+    #[test]
+    fn zero_yields_once() {
+        for mut rt in runtimes() {
+            rt.block_on(async {
+                let ran = Rc::new(Cell::new(false));
+                let flag = ran.clone();
+                let task = crate::spawn(async move { flag.set(true) });
+                sleep(Duration::ZERO).await;
+                assert!(ran.get());
+                task.await;
+            });
+        }
+    }
+
+    #[test]
+    fn dropped_sleep_is_cancelled() {
+        for mut rt in runtimes() {
+            let start = Instant::now();
+            rt.block_on(async {
+                let mut timer = sleep(Duration::from_millis(500));
+                let waker = std::task::Waker::noop();
+                assert!(Pin::new(&mut timer)
+                    .poll(&mut Context::from_waker(waker))
+                    .is_pending());
+                let wheel = CURRENT.with(|ctx| ctx.timer.clone());
+                assert_eq!(
+                    wheel.as_ref().map(|w| w.borrow().len()),
+                    wheel.as_ref().map(|_| 1)
+                );
+                drop(timer);
+                // Unlinked from the wheel right away.
+                assert_eq!(wheel.map(|w| w.borrow().len()).unwrap_or(0), 0);
+                sleep(Duration::from_millis(20)).await;
+            });
+            // The cancel completed the kernel timer long before it was due.
+            assert_eq!(rt.driver.metrics().ops, 0);
+            assert!(start.elapsed() < Duration::from_millis(500));
+
+            // Left sleeping in a task, dropped with the runtime.
+            rt.block_on(async {
+                crate::spawn(sleep(Duration::from_secs(60)));
+            });
+        }
+    }
+
+    #[test]
+    fn many_sleeps_on_the_wheel() {
+        const SLEEPS: u64 = 100_000;
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new()
+            .enable_timer()
+            .build()
+            .unwrap();
+        let cqes = rt.driver.metrics().cqes;
+        let fired = Rc::new(RefCell::new(Vec::with_capacity(SLEEPS as usize)));
+        // Far enough out for every task to register before the first is due.
+        let start = Instant::now() + Duration::from_millis(500);
+        rt.block_on(async {
+            let tasks: Vec<_> = (0..SLEEPS)
+                .map(|i| {
+                    let fired = fired.clone();
+                    // Spread over 200ms, in no particular order.
+                    let deadline = start + Duration::from_micros(i * 7919 % 200_000);
+                    crate::spawn(async move {
+                        sleep_until(deadline).await;
+                        fired.borrow_mut().push((deadline, Instant::now()));
+                    })
+                })
+                .collect();
+            for task in tasks {
+                task.await;
+            }
+        });
+        let fired = fired.borrow();
+        assert_eq!(fired.len(), SLEEPS as usize);
+        for (deadline, at) in fired.iter() {
+            assert!(at >= deadline);
+        }
+        // In order of the deadlines, down to the millisecond of resolution.
+        for pair in fired.windows(2) {
+            let (a, b) = (pair[0].0 - start, pair[1].0 - start);
+            assert!(a <= b + Duration::from_millis(1), "{a:?} before {b:?}");
+        }
+        // The ring was only woken for the nearest deadline.
+        let cqes = rt.driver.metrics().cqes - cqes;
+        assert!(cqes < 1000, "{cqes} completions");
+    }
+
+    // Whether `timer` is still pending after `wait`, polling it meanwhile.
+    async fn pending_for(timer: &mut Sleep, wait: Duration) -> bool {
+        let mut wait = sleep(wait);
+        std::future::poll_fn(|cx| {
+            if Pin::new(&mut *timer).poll(cx).is_ready() {
+                return Poll::Ready(false);
+            }
+            Pin::new(&mut wait).poll(cx).map(|()| true)
+        })
+        .await
+    }
+
+    #[test]
+    fn reset_while_pending() {
+        for mut rt in runtimes() {
+            rt.block_on(async {
+                let start = Instant::now();
+                let mut timer = sleep(Duration::from_millis(50));
+                for _ in 0..5 {
+                    assert!(pending_for(&mut timer, Duration::from_millis(20)).await);
+                    timer.reset_after(Duration::from_millis(50));
+                }
+                let last = timer.deadline() - Duration::from_millis(50);
+                (&mut timer).await;
+                let elapsed = start.elapsed();
+                assert!(Instant::now() >= timer.deadline());
+                assert!(
+                    last.elapsed() < Duration::from_millis(100),
+                    "{:?}",
+                    last.elapsed()
+                );
+                assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+
+                // Completed, a reset arms it again.
+                timer.reset_after(Duration::from_millis(10));
+                let start = Instant::now();
+                (&mut timer).await;
+                assert!(start.elapsed() >= Duration::from_millis(10));
+            });
+        }
+    }
+
+    #[test]
+    fn reset_later_skips_the_old_deadline() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::task::Wake;
+
+        struct Count(AtomicUsize);
+        impl Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        for mut rt in runtimes() {
+            rt.block_on(async {
+                let woken = Arc::new(Count(AtomicUsize::new(0)));
+                let waker = Waker::from(woken.clone());
+                let mut cx = Context::from_waker(&waker);
+                let mut timer = sleep(Duration::from_millis(20));
+                assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+                timer.reset_after(Duration::from_millis(100));
+
+                // Nothing wakes it where it would have ended.
+                sleep(Duration::from_millis(60)).await;
+                assert_eq!(woken.0.load(Ordering::SeqCst), 0);
+                assert!(Pin::new(&mut timer).poll(&mut cx).is_pending());
+
+                sleep(Duration::from_millis(60)).await;
+                assert_eq!(woken.0.load(Ordering::SeqCst), 1);
+                assert!(Pin::new(&mut timer).poll(&mut cx).is_ready());
+            });
+        }
+    }
+
+    #[test]
+    fn reset_earlier() {
+        for mut rt in runtimes() {
+            rt.block_on(async {
+                let start = Instant::now();
+                let mut timer = sleep(Duration::from_secs(5));
+                assert!(pending_for(&mut timer, Duration::from_millis(5)).await);
+                timer.reset_after(Duration::from_millis(20));
+                (&mut timer).await;
+                let elapsed = start.elapsed();
+                assert!(elapsed < Duration::from_millis(200), "{elapsed:?}");
+
+                // Already passed, woken without waiting.
+                let mut timer = sleep(Duration::from_secs(5));
+                assert!(pending_for(&mut timer, Duration::from_millis(5)).await);
+                timer.reset(Instant::now() - Duration::from_millis(1));
+                let start = Instant::now();
+                (&mut timer).await;
+                assert!(start.elapsed() < Duration::from_millis(20));
+            });
+        }
+    }
+}
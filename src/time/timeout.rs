@@ -0,0 +1,169 @@
+use super::sleep::{sleep_until, Sleep};
+use crate::stream::Elapsed;
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Run `future` for at most `duration`, failing with [`Elapsed`] when the
+/// time is up first.
+///
+/// On a timeout the future is dropped, and with it whatever it owns. For the
+/// io of this crate that is the buffer: a read that times out cancels its
+/// operation and the buffer is gone. To get it back, start the read with
+/// [`read_cancelable`](crate::fs::File::read_cancelable) and cancel it when
+/// a [`sleep`](super::sleep) wins instead.
+pub fn timeout<F: IntoFuture>(duration: Duration, future: F) -> Timeout<F::IntoFuture> {
+    timeout_at(Instant::now() + duration, future)
+}
+
+/// Like [`timeout`], up to `deadline`.
+pub fn timeout_at<F: IntoFuture>(deadline: Instant, future: F) -> Timeout<F::IntoFuture> {
+    Timeout {
+        future: future.into_future(),
+        sleep: sleep_until(deadline),
+    }
+}
+
+/// [`timeout`] and [`timeout_at`] as methods of any future, like
+/// `stream.next().timeout(idle)`. For a deadline per item of a stream see
+/// [`StreamExt::timeout_per_item`](crate::stream::StreamExt::timeout_per_item).
+pub trait TimeoutExt: IntoFuture + Sized {
+    /// Run for at most `duration`, see [`timeout`].
+    fn timeout(self, duration: Duration) -> Timeout<Self::IntoFuture> {
+        timeout(duration, self)
+    }
+
+    /// Run up to `deadline`, see [`timeout_at`].
+    fn timeout_at(self, deadline: Instant) -> Timeout<Self::IntoFuture> {
+        timeout_at(deadline, self)
+    }
+}
+
+impl<F: IntoFuture> TimeoutExt for F {}
+
+/// Future returned by [`timeout`] and [`timeout_at`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F> Timeout<F> {
+    pub fn get_ref(&self) -> &F {
+        &self.future
+    }
+
+    pub fn into_inner(self) -> F {
+        self.future
+    }
+}
+
+impl<F: Unpin> Unpin for Timeout<F> {}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Only the future is pinned, the sleep is Unpin.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        // A future that is ready wins over a deadline that passed meanwhile.
+        if let Poll::Ready(out) = future.poll(cx) {
+            return Poll::Ready(Ok(out));
+        }
+        Pin::new(&mut this.sleep)
+            .poll(cx)
+            .map(|()| Err(Elapsed(())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::driver::IoUringDriver;
+    use crate::fs::Pipe;
+    use crate::io::Canceller;
+    use crate::runtime::RuntimeBuilder;
+    use crate::time::sleep;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn in_time_and_late() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            assert_eq!(timeout(ms(200), async { 7 }).await, Ok(7));
+            assert_eq!(timeout(ms(200), sleep(ms(10))).await, Ok(()));
+
+            let start = Instant::now();
+            assert_eq!(timeout(ms(20), sleep(ms(500))).await, Err(Elapsed(())));
+            let elapsed = start.elapsed();
+            assert!(elapsed >= ms(20) && elapsed < ms(200), "{elapsed:?}");
+            let deadline = Instant::now() + ms(10);
+            assert!(timeout_at(deadline, sleep(ms(500))).await.is_err());
+            assert!(Instant::now() >= deadline);
+        });
+    }
+
+    #[test]
+    fn as_methods() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            assert_eq!(sleep(ms(10)).timeout(ms(200)).await, Ok(()));
+            let deadline = Instant::now() + ms(10);
+            let out = sleep(ms(500)).timeout_at(deadline).await;
+            assert_eq!(out, Err(Elapsed(())));
+            let out = async { sleep(ms(500)).timeout(ms(200)).await }
+                .timeout(ms(10))
+                .await;
+            assert_eq!(out, Err(Elapsed(())));
+        });
+    }
+
+    #[test]
+    fn nested() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            // The inner deadline comes first, the outer one never fires.
+            let out = timeout(ms(200), timeout(ms(10), sleep(ms(500)))).await;
+            assert_eq!(out, Ok(Err(Elapsed(()))));
+            let out = timeout(ms(10), timeout(ms(200), sleep(ms(500)))).await;
+            assert_eq!(out, Err(Elapsed(())));
+            let out = timeout(ms(200), timeout(ms(100), sleep(ms(10)))).await;
+            assert_eq!(out, Ok(Ok(())));
+        });
+    }
+
+    #[test]
+    fn buffers_travel_with_the_future() {
+        let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+        rt.block_on(async {
+            let pipe = Pipe::new().unwrap();
+            // The read is cancelled with its future, the buffer goes too.
+            let read = pipe.reader().read(Vec::with_capacity(16));
+            assert!(timeout(ms(10), read).await.is_err());
+
+            // Racing a cancelable read against a sleep hands it back.
+            let canceller = Canceller::new();
+            let handle = canceller.handle();
+            let read = pipe
+                .reader()
+                .read_cancelable(Vec::with_capacity(16), &handle);
+            let mut read = std::pin::pin!(read);
+            assert!(timeout(ms(10), read.as_mut()).await.is_err());
+            canceller.cancel();
+            let (n, buf) = read.await;
+            assert!(n.is_err());
+            assert_eq!(buf.capacity(), 16);
+
+            // Neither took anything from the pipe.
+            let (n, _) = pipe.writer().write("data").await;
+            assert_eq!(n.unwrap(), 4);
+            let (n, buf) = pipe.reader().read(Vec::with_capacity(16)).await;
+            assert_eq!(n.unwrap(), 4);
+            assert_eq!(buf, b"data");
+        });
+    }
+}
@@ -0,0 +1,353 @@
+//! A hierarchical timer wheel, for many timers without a kernel timer each.
+//! Part of the design forked from tokio.
+//!
+//! Time counts in milliseconds from the creation of the wheel. Level `n`
+//! has 64 slots of `64^n` ms each, a timer sits in the lowest level where
+//! its deadline and the current time differ, and moves down a level each
+//! time its slot comes up, until it fires from level 0.
+
+use crate::utils::slab::Slab;
+use std::task::Waker;
+use std::time::{Duration, Instant};
+
+const LEVELS: usize = 6;
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+// Deadlines further out are clamped, over two years.
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+pub(crate) struct Wheel {
+    start: Instant,
+    // Ticks processed so far.
+    elapsed: u64,
+    entries: Slab<Entry>,
+    // Entries linked into a slot.
+    pending: usize,
+    levels: [Level; LEVELS],
+    // Entries added when they were due already, fired with the next
+    // processing.
+    due: Vec<usize>,
+}
+
+struct Entry {
+    when: u64,
+    waker: Option<Waker>,
+    state: State,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    // In a slot, by level and slot.
+    Linked(usize, usize),
+    Due,
+    Fired,
+}
+
+struct Level {
+    // Bit n is set when slot n has entries.
+    occupied: u64,
+    heads: [Option<usize>; SLOTS],
+}
+
+impl Wheel {
+    pub(crate) fn new() -> Wheel {
+        Wheel {
+            start: Instant::now(),
+            elapsed: 0,
+            entries: Slab::new(),
+            pending: 0,
+            due: Vec::new(),
+            levels: std::array::from_fn(|_| Level {
+                occupied: 0,
+                heads: [None; SLOTS],
+            }),
+        }
+    }
+
+    /// Add a timer for `deadline`, returning its key. One that is due
+    /// already fires with the next processing, not right away.
+    pub(crate) fn insert(&mut self, deadline: Instant) -> usize {
+        let when = self.ticks(deadline);
+        let key = self.entries.insert(Entry {
+            when,
+            waker: None,
+            state: State::Due,
+            prev: None,
+            next: None,
+        });
+        self.schedule(key, when);
+        key
+    }
+
+    /// Move the timer at `key` to `deadline`, fired or not. It keeps its
+    /// waker.
+    pub(crate) fn reset(&mut self, key: usize, deadline: Instant) {
+        self.unlink(key);
+        let when = self.ticks(deadline);
+        self.entries.get(key).expect("timer is registered").when = when;
+        self.schedule(key, when);
+    }
+
+    fn schedule(&mut self, key: usize, when: u64) {
+        match when > self.elapsed {
+            true => self.link(key, when),
+            false => {
+                self.entries.get(key).expect("timer is registered").state = State::Due;
+                self.due.push(key);
+            }
+        }
+    }
+
+    /// Whether the timer at `key` fired, storing `waker` otherwise.
+    pub(crate) fn poll(&mut self, key: usize, waker: &Waker) -> bool {
+        let mut entry = self.entries.get(key).expect("timer is registered");
+        if entry.state == State::Fired {
+            return true;
+        }
+        match &mut entry.waker {
+            Some(old) if old.will_wake(waker) => {}
+            slot => *slot = Some(waker.clone()),
+        }
+        false
+    }
+
+    /// Take the timer at `key` out of the wheel, fired or not.
+    pub(crate) fn remove(&mut self, key: usize) {
+        self.unlink(key);
+        self.entries.remove(key);
+    }
+
+    /// Timers not fired yet.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.pending + self.due.len()
+    }
+
+    /// How long until the next slot comes up, `None` without timers. Zero
+    /// when it is due.
+    pub(crate) fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        if !self.due.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let at = self.next_expiration()?.2;
+        let at = self.start + Duration::from_millis(at);
+        Some(at.saturating_duration_since(now))
+    }
+
+    /// Fire the timers due at `now`, returning their wakers.
+    pub(crate) fn process(&mut self, now: Instant) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        for key in std::mem::take(&mut self.due) {
+            let mut entry = self.entries.get(key).expect("due timer");
+            entry.state = State::Fired;
+            wakers.extend(entry.waker.take());
+        }
+        if self.pending == 0 {
+            self.elapsed = self.elapsed.max(self.ticks_down(now));
+            return wakers;
+        }
+        let now = self.ticks_down(now);
+        while let Some((level, slot, at)) = self.next_expiration() {
+            if at > now {
+                break;
+            }
+            self.elapsed = at;
+            let mut cur = self.take_slot(level, slot);
+            while let Some(key) = cur {
+                let mut entry = self.entries.get(key).expect("linked timer");
+                cur = entry.next;
+                entry.state = State::Fired;
+                entry.prev = None;
+                entry.next = None;
+                let when = entry.when;
+                let waker = entry.waker.take();
+                self.pending -= 1;
+                if when <= self.elapsed {
+                    wakers.extend(waker);
+                } else {
+                    // Due in this slot, at a lower level.
+                    self.entries.get(key).expect("linked timer").waker = waker;
+                    self.link(key, when);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+        wakers
+    }
+
+    /// Drop the stored wakers, for a runtime shutting down.
+    pub(crate) fn take_wakers(&mut self) -> Vec<Waker> {
+        let keys: Vec<_> = self.entries.iter().map(|(key, _)| key).collect();
+        keys.into_iter()
+            .filter_map(|key| self.entries.get(key)?.waker.take())
+            .collect()
+    }
+
+    // Ticks from the start to `deadline`, rounded up so a timer never fires
+    // early.
+    fn ticks(&self, deadline: Instant) -> u64 {
+        let d = deadline.saturating_duration_since(self.start);
+        let ms = d.as_millis() + u128::from(!d.subsec_nanos().is_multiple_of(1_000_000));
+        (ms.min(MAX_TICKS as u128) as u64).min(self.elapsed + MAX_TICKS)
+    }
+
+    fn ticks_down(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_millis() as u64
+    }
+
+    fn level_for(&self, when: u64) -> usize {
+        let masked = (self.elapsed ^ when) | (SLOTS as u64 - 1);
+        let significant = 63 - masked.leading_zeros() as usize;
+        (significant / SLOT_BITS as usize).min(LEVELS - 1)
+    }
+
+    fn slot_for(level: usize, when: u64) -> usize {
+        ((when >> (level as u32 * SLOT_BITS)) as usize) & (SLOTS - 1)
+    }
+
+    fn link(&mut self, key: usize, when: u64) {
+        let level = self.level_for(when);
+        let slot = Self::slot_for(level, when);
+        let head = self.levels[level].heads[slot].replace(key);
+        if let Some(head) = head {
+            self.entries.get(head).expect("linked timer").prev = Some(key);
+        }
+        let mut entry = self.entries.get(key).expect("timer is registered");
+        entry.state = State::Linked(level, slot);
+        entry.prev = None;
+        entry.next = head;
+        self.levels[level].occupied |= 1 << slot;
+        self.pending += 1;
+    }
+
+    fn unlink(&mut self, key: usize) {
+        let Some(mut entry) = self.entries.get(key) else {
+            return;
+        };
+        let state = std::mem::replace(&mut entry.state, State::Fired);
+        let (level, slot) = match state {
+            State::Linked(level, slot) => (level, slot),
+            State::Due => {
+                self.due.retain(|&due| due != key);
+                return;
+            }
+            State::Fired => return,
+        };
+        let (prev, next) = (entry.prev.take(), entry.next.take());
+        if let Some(next) = next {
+            self.entries.get(next).expect("linked timer").prev = prev;
+        }
+        match prev {
+            Some(prev) => self.entries.get(prev).expect("linked timer").next = next,
+            None => {
+                self.levels[level].heads[slot] = next;
+                if next.is_none() {
+                    self.levels[level].occupied &= !(1 << slot);
+                }
+            }
+        }
+        self.pending -= 1;
+    }
+
+    fn take_slot(&mut self, level: usize, slot: usize) -> Option<usize> {
+        self.levels[level].occupied &= !(1 << slot);
+        self.levels[level].heads[slot].take()
+    }
+
+    // The first slot with timers, by level, and the tick it starts at.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        self.levels.iter().enumerate().find_map(|(level, l)| {
+            if l.occupied == 0 {
+                return None;
+            }
+            let shift = level as u32 * SLOT_BITS;
+            let slot_range = 1u64 << shift;
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = ((self.elapsed >> shift) as usize) & (SLOTS - 1);
+            let dist = l.occupied.rotate_right(now_slot as u32).trailing_zeros() as usize;
+            let slot = (now_slot + dist) & (SLOTS - 1);
+            let level_start = self.elapsed & !(level_range - 1);
+            let mut at = level_start + slot as u64 * slot_range;
+            if at + slot_range <= self.elapsed {
+                at += level_range;
+            }
+            Some((level, slot, at.max(self.elapsed)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn fires_in_order_across_levels() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let deadlines = [1, 5, 63, 64, 65, 300, 4095, 4096, 70_000, 5_000_000];
+        let keys: Vec<_> = deadlines
+            .iter()
+            .map(|&d| wheel.insert(start + ms(d)))
+            .collect();
+        assert_eq!(wheel.len(), deadlines.len());
+
+        let waker = Waker::noop();
+        let mut fired = Vec::new();
+        while let Some(timeout) = wheel.next_timeout(start + ms(wheel.elapsed)) {
+            // Never ahead of the earliest timer left.
+            let now = start + ms(wheel.elapsed) + timeout;
+            let pending: Vec<_> = keys
+                .iter()
+                .zip(deadlines)
+                .filter(|(&key, _)| !wheel.poll(key, waker))
+                .map(|(_, d)| d)
+                .collect();
+            assert!(pending.iter().all(|&d| start + ms(d) >= now));
+            wheel.process(now);
+            for (&key, d) in keys.iter().zip(deadlines) {
+                if wheel.poll(key, waker) && !fired.contains(&d) {
+                    assert!(wheel.elapsed >= d);
+                    fired.push(d);
+                }
+            }
+        }
+        assert_eq!(fired, deadlines);
+        assert_eq!(wheel.len(), 0);
+    }
+
+    #[test]
+    fn remove_unlinks() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let a = wheel.insert(start + ms(10));
+        let b = wheel.insert(start + ms(10));
+        let c = wheel.insert(start + ms(10));
+        // From the middle and the head of the slot.
+        wheel.remove(b);
+        wheel.remove(c);
+        assert_eq!(wheel.len(), 1);
+        assert_eq!(wheel.next_timeout(start), Some(ms(10)));
+        wheel.remove(a);
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_timeout(start), None);
+
+        // Due already, fired by the next processing.
+        wheel.process(start + ms(20));
+        let key = wheel.insert(start + ms(5));
+        assert!(!wheel.poll(key, Waker::noop()));
+        assert_eq!(wheel.next_timeout(start + ms(20)), Some(Duration::ZERO));
+        wheel.process(start + ms(20));
+        assert!(wheel.poll(key, Waker::noop()));
+        assert_eq!(wheel.len(), 0);
+        wheel.remove(key);
+        let key = wheel.insert(start + ms(5));
+        wheel.remove(key);
+        assert_eq!(wheel.next_timeout(start + ms(20)), None);
+    }
+}
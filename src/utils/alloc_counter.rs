@@ -0,0 +1,80 @@
+//! Allocator for tests that counts what the current thread allocates.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct Counting;
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+thread_local! {
+    static COUNTS: Cell<Counts> = const { Cell::new(Counts::ZERO) };
+}
+
+/// Allocations made by a thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Counts {
+    pub(crate) allocs: usize,
+    pub(crate) bytes: usize,
+    /// Bytes handed out zeroed.
+    pub(crate) zeroed_bytes: usize,
+}
+
+impl Counts {
+    const ZERO: Counts = Counts {
+        allocs: 0,
+        bytes: 0,
+        zeroed_bytes: 0,
+    };
+}
+
+fn record(size: usize, zeroed: bool) {
+    // Fails only while the thread is torn down.
+    let _ = COUNTS.try_with(|c| {
+        let mut counts = c.get();
+        counts.allocs += 1;
+        counts.bytes += size;
+        if zeroed {
+            counts.zeroed_bytes += size;
+        }
+        c.set(counts);
+    });
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record(layout.size(), false);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record(layout.size(), true);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record(new_size, false);
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// What this thread allocated so far.
+pub(crate) fn snapshot() -> Counts {
+    COUNTS.with(Cell::get)
+}
+
+impl Counts {
+    /// What was allocated after `before` was taken.
+    pub(crate) fn since(self, before: Counts) -> Counts {
+        Counts {
+            allocs: self.allocs - before.allocs,
+            bytes: self.bytes - before.bytes,
+            zeroed_bytes: self.zeroed_bytes - before.zeroed_bytes,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Name the operation, and the path or descriptor, in io errors.
+
+use crate::runtime::runtime::CURRENT;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::os::fd::RawFd;
+use std::path::{Path, PathBuf};
+
+/// Payload of an io error with context, the original error is its source.
+///
+/// The error keeps the kind of the original, its `raw_os_error` is reached
+/// through [`Error::source`].
+#[derive(Debug)]
+pub(crate) struct ErrorCtx {
+    op: &'static str,
+    target: Target,
+    source: io::Error,
+}
+
+#[derive(Debug)]
+enum Target {
+    Path(PathBuf),
+    Fd(RawFd),
+    Slot(u32),
+    Addr(SocketAddr),
+}
+
+impl fmt::Display for ErrorCtx {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.target {
+            Target::Path(path) => write!(f, "{} {}: {}", self.op, path.display(), self.source),
+            Target::Fd(fd) => write!(f, "{} fd {}: {}", self.op, fd, self.source),
+            Target::Slot(slot) => write!(f, "{} fixed file {}: {}", self.op, slot, self.source),
+            Target::Addr(addr) => write!(f, "{} {}: {}", self.op, addr, self.source),
+        }
+    }
+}
+
+impl Error for ErrorCtx {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Attach context to the error of an io result, unless the runtime was
+/// built without [`error_context`](crate::runtime::RuntimeBuilder::error_context).
+pub(crate) trait ResultExt<T> {
+    fn op_path(self, op: &'static str, path: &Path) -> io::Result<T>;
+    fn op_fd(self, op: &'static str, fd: RawFd) -> io::Result<T>;
+    fn op_slot(self, op: &'static str, slot: u32) -> io::Result<T>;
+    fn op_addr(self, op: &'static str, addr: SocketAddr) -> io::Result<T>;
+}
+
+impl<T> ResultExt<T> for io::Result<T> {
+    fn op_path(self, op: &'static str, path: &Path) -> io::Result<T> {
+        self.map_err(|e| wrap(e, op, || Target::Path(path.to_owned())))
+    }
+
+    fn op_fd(self, op: &'static str, fd: RawFd) -> io::Result<T> {
+        self.map_err(|e| wrap(e, op, || Target::Fd(fd)))
+    }
+
+    fn op_slot(self, op: &'static str, slot: u32) -> io::Result<T> {
+        self.map_err(|e| wrap(e, op, || Target::Slot(slot)))
+    }
+
+    fn op_addr(self, op: &'static str, addr: SocketAddr) -> io::Result<T> {
+        self.map_err(|e| wrap(e, op, || Target::Addr(addr)))
+    }
+}
+
+fn wrap(source: io::Error, op: &'static str, target: impl FnOnce() -> Target) -> io::Error {
+    let enabled = CURRENT.try_with(|cx| cx.error_context).unwrap_or(true);
+    // Context of a deeper layer is kept as is.
+    let wrapped = source.get_ref().is_some_and(|inner| inner.is::<ErrorCtx>());
+    if !enabled || wrapped {
+        return source;
+    }
+    let ctx = ErrorCtx {
+        op,
+        target: target(),
+        source,
+    };
+    io::Error::new(ctx.source.kind(), ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_os_error(err: &io::Error) -> Option<i32> {
+        let source = err.get_ref()?.source()?;
+        source.downcast_ref::<io::Error>()?.raw_os_error()
+    }
+
+    #[test]
+    fn keeps_kind_and_errno() {
+        let err = Err::<(), _>(io::Error::from_raw_os_error(libc::EBADF))
+            .op_slot("read", 7)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::Error::from_raw_os_error(libc::EBADF).kind());
+        assert_eq!(raw_os_error(&err), Some(libc::EBADF));
+        assert!(err.to_string().starts_with("read fixed file 7: "), "{err}");
+
+        // An outer layer does not bury the inner context.
+        let err = Err::<(), _>(err)
+            .op_path("openat", Path::new("/"))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("read fixed file 7: "), "{err}");
+    }
+
+    #[test]
+    fn display() {
+        let err = || io::Error::from_raw_os_error(libc::ENOENT);
+        let path = Err::<(), _>(err())
+            .op_path("openat", Path::new("/a/b"))
+            .unwrap_err();
+        assert_eq!(path.to_string(), format!("openat /a/b: {}", err()));
+        let slot = Err::<(), _>(err()).op_slot("write", 3).unwrap_err();
+        assert_eq!(slot.to_string(), format!("write fixed file 3: {}", err()));
+        let fd = Err::<(), _>(err()).op_fd("read", 5).unwrap_err();
+        assert_eq!(fd.to_string(), format!("read fd 5: {}", err()));
+        let addr = Err::<(), _>(err())
+            .op_addr("bind", "127.0.0.1:80".parse().unwrap())
+            .unwrap_err();
+        assert_eq!(addr.to_string(), format!("bind 127.0.0.1:80: {}", err()));
+    }
+}
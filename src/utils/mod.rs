@@ -1,11 +1,11 @@
 //! Common utils
 
+#[cfg(test)]
+pub(crate) mod alloc_counter;
+pub(crate) mod error_ctx;
 #[allow(dead_code)]
 pub(crate) mod slab;
+#[cfg(test)]
+pub(crate) mod testing;
 #[allow(dead_code)]
 pub(crate) mod thread_id;
-
-
-
-
-
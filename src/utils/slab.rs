@@ -7,14 +7,23 @@ use std::{
 };
 
 /// Pre-allocated storage for a uniform data type
+///
+/// Keys are reused: an insert takes a free slot of the lowest page that has
+/// one, so keys stay below the capacity needed at the peak number of live
+/// entries. Pages double in size, the largest key is below
+/// `PAGE_INITIAL_SIZE << NUM_PAGES`, far from the user_data values the driver
+/// reserves.
 #[derive(Default)]
 pub(crate) struct Slab<T> {
     // pages of continued memory
     pages: [Option<Page<T>>; NUM_PAGES],
-    // cached write page id
+    // cached write page id, every page before it is full
     w_page_id: usize,
     // current generation
     generation: u32,
+    // generation the slots of a released page start again from, above any
+    // they had so a stale key never names a new entry
+    epochs: [u32; NUM_PAGES],
 }
 
 const NUM_PAGES: usize = 26;
@@ -31,11 +40,11 @@ impl<T> Slab<T> {
             ],
             w_page_id: 0,
             generation: 0,
+            epochs: [0; NUM_PAGES],
         }
     }
 
     /// Get slab len.
-    #[allow(unused)]
     pub(crate) fn len(&self) -> usize {
         self.pages.iter().fold(0, |acc, page| match page {
             Some(page) => acc + page.used,
@@ -43,6 +52,54 @@ impl<T> Slab<T> {
         })
     }
 
+    /// Generation of the slot at `key`. It goes up by one each time the entry
+    /// there is removed, so a key together with its generation names one
+    /// entry even after the slot is reused.
+    pub(crate) fn generation(&self, key: usize) -> u32 {
+        let page_id = get_page_id(key);
+        match unsafe { self.pages.get_unchecked(page_id) } {
+            Some(page) => page.generation(key - page.prev_len),
+            None => self.epochs[page_id],
+        }
+    }
+
+    /// Number of slots in allocated pages.
+    pub(crate) fn capacity(&self) -> usize {
+        self.pages
+            .iter()
+            .flatten()
+            .map(|page| page.slots.len())
+            .sum()
+    }
+
+    /// Release the empty pages at the end, keeping the first one. Keys of
+    /// live entries stay valid.
+    pub(crate) fn compact(&mut self) {
+        for id in (1..NUM_PAGES).rev() {
+            match &self.pages[id] {
+                Some(p) if p.is_empty() => self.release(id),
+                Some(_) => break,
+                None => {}
+            }
+        }
+    }
+
+    // Drop the empty page `id`, a new one there picks up after the highest
+    // generation of its slots.
+    fn release(&mut self, id: usize) {
+        if let Some(page) = self.pages[id].take() {
+            self.epochs[id] = page.generations.iter().copied().max().unwrap_or(0);
+        }
+    }
+
+    /// Iterate over occupied slots and their keys.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.pages.iter().flatten().flat_map(|page| {
+            (0..page.initialized)
+                .filter_map(move |slot| page.get(slot).map(|val| (page.prev_len + slot, val)))
+        })
+    }
+
     pub(crate) fn get(&mut self, key: usize) -> Option<Ref<'_, T>> {
         let page_id = get_page_id(key);
         // here we make 2 mut ref so we must make it safe.
@@ -73,6 +130,7 @@ impl<T> Slab<T> {
                         let page = Page::new(
                             PAGE_INITIAL_SIZE << i,
                             (PAGE_INITIAL_SIZE << i) - PAGE_INITIAL_SIZE,
+                            self.epochs[i],
                         );
                         let r = self.pages.get_unchecked_mut(i);
                         *r = Some(page);
@@ -98,14 +156,18 @@ impl<T> Slab<T> {
             None => return None,
         };
         let val = page.remove(key - page.prev_len);
-        self.mark_remove();
+        if val.is_some() {
+            self.mark_remove(page_id);
+        }
         val
     }
 
-    pub(crate) fn mark_remove(&mut self) {
+    fn mark_remove(&mut self, page_id: usize) {
+        // the page has a free slot now
+        self.w_page_id = self.w_page_id.min(page_id);
         // compact
         self.generation = self.generation.wrapping_add(1);
-        if self.generation % COMPACT_INTERVAL == 0 {
+        if self.generation.is_multiple_of(COMPACT_INTERVAL) {
             // reset write page index
             self.w_page_id = 0;
             // find the last allocated page and try to drop
@@ -117,9 +179,7 @@ impl<T> Slab<T> {
                 .find_map(|(id, p)| p.as_mut().map(|p| (id, p)))
             {
                 if last_page.is_empty() && id > 0 {
-                    unsafe {
-                        *self.pages.get_unchecked_mut(id) = None;
-                    }
+                    self.release(id);
                 }
             }
         }
@@ -148,7 +208,7 @@ impl<T> Ref<'_, T> {
         // # Safety
         // We make sure the index is valid.
         let val = unsafe { self.page.remove(self.index).unwrap_unchecked() };
-        self.slab.mark_remove();
+        self.slab.mark_remove(get_page_id(self.page.prev_len));
         val
     }
 }
@@ -218,6 +278,8 @@ impl<T> Entry<T> {
 struct Page<T> {
     // continued buffer of fixed size
     slots: Box<[MaybeUninit<Entry<T>>]>,
+    // removals per slot
+    generations: Box<[u32]>,
     // number of occupied slots
     used: usize,
     // number of initialized slots
@@ -229,12 +291,13 @@ struct Page<T> {
 }
 
 impl<T> Page<T> {
-    fn new(size: usize, prev_len: usize) -> Self {
+    fn new(size: usize, prev_len: usize, epoch: u32) -> Self {
         let mut buffer = Vec::with_capacity(size);
         unsafe { buffer.set_len(size) };
         let slots = buffer.into_boxed_slice();
         Self {
             slots,
+            generations: vec![epoch; size].into_boxed_slice(),
             used: 0,
             initialized: 0,
             next: 0,
@@ -286,6 +349,10 @@ impl<T> Page<T> {
         *slot = MaybeUninit::new(Entry::Occupied(val));
     }
 
+    fn generation(&self, slot: usize) -> u32 {
+        self.generations.get(slot).copied().unwrap_or(0)
+    }
+
     fn get(&self, slot: usize) -> Option<&T> {
         if slot >= self.initialized {
             return None;
@@ -319,6 +386,8 @@ impl<T> Page<T> {
             let val = std::mem::replace(slot_mut, Entry::Vacant(self.next));
             self.next = slot;
             self.used -= 1;
+            let generation = self.generations.get_unchecked_mut(slot);
+            *generation = generation.wrapping_add(1);
 
             Some(val.unwrap_unchecked())
         }
@@ -387,6 +456,27 @@ mod tests {
         assert!(slab.remove(usize::MAX).is_none());
     }
 
+    #[test]
+    fn iter_occupied() {
+        let mut slab = Slab::new();
+        let keys = (0..200).map(|i| slab.insert(i)).collect::<Vec<_>>();
+        for key in keys.iter().step_by(2) {
+            slab.remove(*key);
+        }
+        let left = slab
+            .iter()
+            .map(|(key, val)| (key, *val))
+            .collect::<Vec<_>>();
+        let expected = keys
+            .iter()
+            .zip(0..200)
+            .skip(1)
+            .step_by(2)
+            .map(|(key, val)| (*key, val))
+            .collect::<Vec<_>>();
+        assert_eq!(left, expected);
+    }
+
     #[test]
     fn insert_remove_big() {
         let mut slab = Slab::default();
@@ -399,4 +489,105 @@ mod tests {
         });
         assert_eq!(slab.len(), 0);
     }
+
+    #[test]
+    fn reuse_low_keys() {
+        let mut slab = Slab::new();
+        let keys = (0..300).map(|i| slab.insert(i)).collect::<Vec<_>>();
+        // Free slots in the first and in a later page.
+        slab.remove(keys[5]);
+        slab.remove(keys[200]);
+        slab.remove(keys[3]);
+        assert_eq!(slab.insert(0), keys[3]);
+        assert_eq!(slab.insert(0), keys[5]);
+        assert_eq!(slab.insert(0), keys[200]);
+        assert_eq!(slab.insert(0), 300);
+
+        // Interleaved insert and remove never go past the peak.
+        let mut slab = Slab::new();
+        let mut live = Vec::new();
+        for round in 0..10_000 {
+            if round % 3 == 2 {
+                slab.remove(live.swap_remove(round % live.len()));
+            } else if live.len() < 100 {
+                live.push(slab.insert(round));
+            }
+            assert!(live.iter().all(|key| *key < 128));
+        }
+    }
+
+    #[test]
+    fn compact_releases_trailing_pages() {
+        let mut slab = Slab::new();
+        let keys = (0..1000).map(|i| slab.insert(i)).collect::<Vec<_>>();
+        let capacity = slab.capacity();
+        assert!(capacity >= 1000);
+
+        // A live entry in the second page keeps it and everything before.
+        for key in &keys[65..] {
+            slab.remove(*key);
+        }
+        slab.compact();
+        assert_eq!(slab.capacity(), 64 + 128);
+        assert_eq!(slab.get(keys[64]).unwrap().as_mut(), &64);
+
+        for key in &keys[..65] {
+            slab.remove(*key);
+        }
+        slab.compact();
+        assert_eq!(slab.capacity(), 64);
+        assert!(slab.insert(0) < 64);
+    }
+
+    #[test]
+    fn bursts_do_not_grow() {
+        let mut slab = Slab::new();
+        let mut peak = 0;
+        for burst in 0..5 {
+            let keys = (0..10_000).map(|i| slab.insert(i)).collect::<Vec<_>>();
+            if burst == 0 {
+                peak = slab.capacity();
+            }
+            assert!(slab.capacity() <= peak);
+            for key in keys {
+                slab.remove(key);
+            }
+        }
+        slab.compact();
+        assert_eq!(slab.capacity(), 64);
+    }
+
+    #[test]
+    fn generation_bumps_on_remove() {
+        let mut slab = Slab::new();
+        let key = slab.insert(1);
+        assert_eq!(slab.generation(key), 0);
+        slab.remove(key);
+        assert_eq!(slab.generation(key), 1);
+        // Removing a vacant slot changes nothing.
+        slab.remove(key);
+        assert_eq!(slab.generation(key), 1);
+
+        assert_eq!(slab.insert(2), key);
+        slab.get(key).unwrap().remove();
+        assert_eq!(slab.generation(key), 2);
+        assert_eq!(slab.generation(1 << 20), 0);
+    }
+
+    #[test]
+    fn generations_survive_compaction() {
+        let mut slab = Slab::new();
+        let keys = (0..100).map(|i| slab.insert(i)).collect::<Vec<_>>();
+        let key = keys[80];
+        for key in &keys {
+            slab.remove(*key);
+        }
+        slab.compact();
+        assert_eq!(slab.capacity(), 64);
+        // The page is gone, its slots keep counting where they stopped.
+        assert_eq!(slab.generation(key), 1);
+        let again = (0..100).map(|i| slab.insert(i)).collect::<Vec<_>>();
+        assert_eq!(again[80], key);
+        assert_eq!(slab.generation(key), 1);
+    }
 }
@@ -0,0 +1,24 @@
+//! Fixtures shared by the tests.
+
+use crate::driver::IoUringDriver;
+use crate::runtime::{Runtime, RuntimeBuilder};
+use std::io;
+
+/// The value of `res`, `None` when the kernel lacks the feature so the test
+/// can skip. Other errors fail the test.
+pub(crate) fn supported<T>(res: io::Result<T>) -> Option<T> {
+    match res {
+        Ok(value) => Some(value),
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => None,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// A runtime with `slots` fixed file slots, `None` without fixed files.
+pub(crate) fn fixed_runtime(slots: u32) -> Option<Runtime<IoUringDriver>> {
+    supported(
+        RuntimeBuilder::<IoUringDriver>::new()
+            .fixed_files(slots)
+            .build(),
+    )
+}
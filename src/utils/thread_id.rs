@@ -17,5 +17,5 @@ pub(crate) fn get_current_thread_id() -> usize {
 }
 
 pub(crate) fn try_get_current_thread_id() -> Option<usize> {
-    crate::runtime::runtime::CURRENT.try_with(|maybe_ctx| maybe_ctx.map(|ctx| ctx.thread_id))
+    crate::runtime::runtime::CURRENT.try_with(|ctx| ctx.thread_id)
 }
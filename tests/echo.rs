@@ -0,0 +1,124 @@
+//! Echoes over pipes and TCP, written against the public api only.
+
+use std::os::fd::AsRawFd;
+
+use Loop::prelude::*;
+
+async fn open(fd: &impl AsRawFd, write: bool) -> std::io::Result<DirectFile> {
+    OpenOptions::new()
+        .read(!write)
+        .write(write)
+        .open_direct(format!("/proc/self/fd/{}", fd.as_raw_fd()))
+        .await
+}
+
+#[test]
+fn echo() {
+    let mut rt = match RuntimeBuilder::<IoUringDriver>::new()
+        .fixed_files(4)
+        .build()
+    {
+        Ok(rt) => rt,
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return,
+        Err(e) => panic!("{e}"),
+    };
+    let (req_rx, req_tx) = std::io::pipe().unwrap();
+    let (resp_rx, resp_tx) = std::io::pipe().unwrap();
+
+    rt.block_on(async move {
+        let server_rx = open(&req_rx, false).await.unwrap();
+        let server_tx = open(&resp_tx, true).await.unwrap();
+        let client_tx = open(&req_tx, true).await.unwrap();
+        let client_rx = open(&resp_rx, false).await.unwrap();
+
+        let server: JoinHandle<usize> = spawn(async move {
+            let mut buf = Vec::with_capacity(64);
+            let mut echoed = 0;
+            loop {
+                buf.clear();
+                let (res, b) = server_rx.read_at(buf, 0).await;
+                buf = b;
+                match res.unwrap() {
+                    0 => break,
+                    n => echoed += n,
+                }
+                let (res, b) = server_tx.write_all_at(buf, 0).await;
+                res.unwrap();
+                buf = b;
+            }
+            echoed
+        });
+
+        let mut got = Vec::new();
+        for msg in ["hello", "from", "the other side"] {
+            let (res, _) = client_tx.write_all_at(msg.as_bytes().to_vec(), 0).await;
+            res.unwrap();
+            let (res, buf) = client_rx
+                .read_exact_at(Vec::with_capacity(msg.len()), 0)
+                .await;
+            res.unwrap();
+            got.extend_from_slice(&buf);
+        }
+        client_tx.close().await.unwrap();
+        drop(req_tx);
+
+        assert_eq!(got, b"hellofromthe other side");
+        assert_eq!(server.await, got.len());
+    });
+}
+
+#[test]
+fn tcp_echo() {
+    let mut rt = RuntimeBuilder::<IoUringDriver>::new().build().unwrap();
+    rt.block_on(async {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        const CLIENTS: usize = 8;
+
+        let server: JoinHandle<()> = spawn(async move {
+            let mut conns = Vec::new();
+            for _ in 0..CLIENTS {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                conns.push(spawn(async move {
+                    let mut buf = Vec::with_capacity(16);
+                    loop {
+                        buf.clear();
+                        let (res, b) = stream.read(buf).await;
+                        buf = b;
+                        if res.unwrap() == 0 {
+                            break;
+                        }
+                        let (res, b) = stream.write_all(buf).await;
+                        res.unwrap();
+                        buf = b;
+                    }
+                }));
+            }
+            for conn in conns {
+                conn.await;
+            }
+        });
+
+        let clients: Vec<JoinHandle<Vec<u8>>> = (0..CLIENTS)
+            .map(|i| {
+                spawn(async move {
+                    let mut stream = TcpStream::connect(addr).await.unwrap();
+                    let msg = format!("client {i} says hello, more than a buffer");
+                    let (res, _) = stream.write_all(msg.clone().into_bytes()).await;
+                    res.unwrap();
+                    stream.shutdown().await.unwrap();
+                    let (res, buf) = stream.read_exact(Vec::with_capacity(msg.len())).await;
+                    res.unwrap();
+                    assert_eq!(buf, msg.as_bytes());
+                    let (res, _) = stream.read(Vec::with_capacity(1)).await;
+                    assert_eq!(res.unwrap(), 0);
+                    buf
+                })
+            })
+            .collect();
+        for client in clients {
+            assert!(!client.await.is_empty());
+        }
+        server.await;
+    });
+}